@@ -0,0 +1,2 @@
+DROP INDEX IF EXISTS idx_player_game_player_id;
+DROP TABLE IF EXISTS player_games;
\ No newline at end of file
@@ -0,0 +1,16 @@
+CREATE TABLE IF NOT EXISTS player_games (
+	player_game_id SERIAL,
+	player_game_player_id BIGINT NOT NULL,
+	player_game_finished_at BIGINT NOT NULL,
+	player_game_n SMALLINT NOT NULL,
+	player_game_pattern SMALLINT NOT NULL,
+	player_game_pattern_size SMALLINT,
+	player_game_difficulty SMALLINT NOT NULL,
+	player_game_time BIGINT NOT NULL,
+	player_game_mistakes SMALLINT NOT NULL,
+	player_game_hints SMALLINT NOT NULL,
+	player_game_won BOOLEAN NOT NULL,
+	UNIQUE (player_game_player_id, player_game_finished_at),
+	PRIMARY KEY (player_game_id)
+);
+CREATE INDEX IF NOT EXISTS idx_player_game_player_id ON player_games(player_game_player_id);
@@ -1,10 +1,16 @@
 use crate::simple_sudoku::{Coords, Sudoku, SudokuDifficulty, SudokuError, SudokuGroups};
 
-use super::{CarpetLinks, CarpetPattern, CarpetSudoku};
+use super::{
+    CarpetDiff, CarpetLayout, CarpetLinks, CarpetPattern, CarpetSolveStep, CarpetSudoku,
+    CarpetTimelineStep,
+};
 use rand::{rng, seq::SliceRandom};
 use std::{
     collections::{HashMap, HashSet},
+    env::current_dir,
     hash::{DefaultHasher, Hash, Hasher},
+    sync::atomic::{AtomicBool, Ordering},
+    time::Duration,
 };
 
 impl CarpetSudoku {
@@ -44,10 +50,29 @@ impl CarpetSudoku {
         self.links.clone()
     }
 
+    pub fn get_layout(&self) -> &CarpetLayout {
+        &self.layout
+    }
+
     pub fn get_difficulty(&self) -> SudokuDifficulty {
         self.difficulty
     }
 
+    pub fn get_difficulty_score(&self) -> usize {
+        self.difficulty_score
+    }
+
+    pub fn get_filled_board_hash(&self) -> u64 {
+        self.filled_board_hash
+    }
+
+    pub fn get_sudoku_difficulties(&self) -> Vec<SudokuDifficulty> {
+        self.sudokus
+            .iter()
+            .map(|sudoku| sudoku.get_difficulty())
+            .collect()
+    }
+
     pub fn get_cell_value(&self, sudoku_id: usize, x: usize, y: usize) -> usize {
         self.sudokus[sudoku_id].get_cell_value(x, y)
     }
@@ -149,9 +174,10 @@ impl CarpetSudoku {
 			CarpetPattern::Custom(_) => panic!("Can't call CarpetSudoku::new() with a CarpetPattern::Custom pattern ! Try using CarpetSudoku::new_custom() instead."),
             pattern => pattern,
         };
+        let layout = pattern.get_layout(n);
         let n_sudokus = pattern.get_n_sudokus(n);
         let sudokus = (0..n_sudokus).map(|_| Sudoku::new(n)).collect();
-        let links: CarpetLinks = pattern.get_carpet_links(n);
+        let links: CarpetLinks = layout.get_carpet_links(n);
 
         Self {
             n,
@@ -161,12 +187,18 @@ impl CarpetSudoku {
             difficulty_score: 0,
             sudokus,
             links,
+            layout,
             filled_board_hash: 0,
             is_canonical: false,
         }
     }
 
-    pub fn new_custom(n: usize, sudokus: Vec<Sudoku>, links: CarpetLinks) -> Self {
+    pub fn new_custom(
+        n: usize,
+        sudokus: Vec<Sudoku>,
+        links: CarpetLinks,
+        layout: CarpetLayout,
+    ) -> Self {
         Self {
             n,
             n2: n * n,
@@ -175,11 +207,226 @@ impl CarpetSudoku {
             difficulty_score: 0,
             sudokus,
             links,
+            layout,
             filled_board_hash: 0,
             is_canonical: false,
         }
     }
 
+    /// Same as [`CarpetSudoku::new_custom`], but checks that the links describe a coherent carpet first:
+    /// there must be at least one sudoku, every sudoku and square must exist, links must go both ways, a square can only be linked to one square of each other sudoku,
+    /// twins of twins must be linked too, the bands (and stacks) of linked squares can only meet one band (or stack) of each sudoku,
+    /// and linked cells can't already hold different values
+    pub fn try_new_custom(
+        n: usize,
+        sudokus: Vec<Sudoku>,
+        links: CarpetLinks,
+        layout: CarpetLayout,
+    ) -> Result<Self, SudokuError> {
+        let n2 = n * n;
+        let n_sudokus = sudokus.len();
+
+        if n_sudokus == 0 {
+            return Err(SudokuError::WrongInput(
+                "CarpetSudoku::try_new_custom(): a carpet needs at least one sudoku".to_string(),
+            ));
+        }
+        if let Some(i) = sudokus.iter().position(|sudoku| sudoku.get_n() != n) {
+            return Err(SudokuError::WrongInput(format!(
+                "CarpetSudoku::try_new_custom(): sudoku {i} has n = {} instead of {n}",
+                sudokus[i].get_n()
+            )));
+        }
+        if !layout.get_positions().is_empty() && layout.get_n_sudokus() != n_sudokus {
+            return Err(SudokuError::WrongInput(format!(
+                "CarpetSudoku::try_new_custom(): layout has {} positions for {n_sudokus} sudokus",
+                layout.get_n_sudokus()
+            )));
+        }
+
+        let invalid =
+            |sudoku1: usize, square1: usize, sudoku2: usize, square2: usize, reason: &str| {
+                Err(SudokuError::InvalidLink((
+                    (sudoku1, square1),
+                    (sudoku2, square2),
+                    reason.to_string(),
+                )))
+            };
+
+        for (&sudoku1, sudoku_links) in links.iter() {
+            for &(square1, sudoku2, square2) in sudoku_links.iter() {
+                if sudoku1 >= n_sudokus || sudoku2 >= n_sudokus {
+                    return invalid(sudoku1, square1, sudoku2, square2, "sudoku out of range");
+                }
+                if square1 >= n2 || square2 >= n2 {
+                    return invalid(sudoku1, square1, sudoku2, square2, "square out of range");
+                }
+                if sudoku1 == sudoku2 {
+                    return invalid(
+                        sudoku1,
+                        square1,
+                        sudoku2,
+                        square2,
+                        "sudoku linked to itself",
+                    );
+                }
+                if !links
+                    .get(&sudoku2)
+                    .is_some_and(|links2| links2.contains(&(square2, sudoku1, square1)))
+                {
+                    return invalid(sudoku1, square1, sudoku2, square2, "missing reverse link");
+                }
+                if sudoku_links
+                    .iter()
+                    .any(|&(other_square1, other_sudoku2, other_square2)| {
+                        other_square1 == square1
+                            && other_sudoku2 == sudoku2
+                            && other_square2 != square2
+                    })
+                {
+                    return invalid(
+                        sudoku1,
+                        square1,
+                        sudoku2,
+                        square2,
+                        "square linked to several squares of the same sudoku",
+                    );
+                }
+                for &(twin_square, sudoku3, square3) in links[&sudoku2].iter() {
+                    if twin_square != square2 || sudoku3 == sudoku1 {
+                        continue;
+                    }
+                    if !sudoku_links.contains(&(square1, sudoku3, square3)) {
+                        return invalid(
+                            sudoku1,
+                            square1,
+                            sudoku3,
+                            square3,
+                            "missing link between twins of the same square",
+                        );
+                    }
+                }
+
+                let (x1, y1) = ((square1 % n) * n, (square1 / n) * n);
+                let (x2, y2) = ((square2 % n) * n, (square2 / n) * n);
+                for dy in 0..n {
+                    for dx in 0..n {
+                        let value1 = sudokus[sudoku1].get_cell_value(x1 + dx, y1 + dy);
+                        let value2 = sudokus[sudoku2].get_cell_value(x2 + dx, y2 + dy);
+                        if value1 != 0 && value2 != 0 && value1 != value2 {
+                            return invalid(
+                                sudoku1,
+                                square1,
+                                sudoku2,
+                                square2,
+                                "linked cells have different values",
+                            );
+                        }
+                    }
+                }
+            }
+        }
+
+        if let Some((sudoku1, square1, sudoku2, square2)) =
+            Self::find_inconsistent_link(n, &links, true)
+                .or_else(|| Self::find_inconsistent_link(n, &links, false))
+        {
+            return invalid(
+                sudoku1,
+                square1,
+                sudoku2,
+                square2,
+                "linked squares join two bands or two stacks of the same sudoku",
+            );
+        }
+
+        Ok(Self::new_custom(n, sudokus, links, layout))
+    }
+
+    /// The first link that joins two bands (or two stacks) of the same sudoku, directly or through twins.
+    /// The rows of a band are swapped like the rows of the bands it's linked to (see [`CarpetSudoku::randomize`]),
+    /// so a band can only be linked to one band of each other sudoku
+    fn find_inconsistent_link(
+        n: usize,
+        links: &CarpetLinks,
+        bands: bool,
+    ) -> Option<(usize, usize, usize, usize)> {
+        let get_line = |square: usize| if bands { square / n } else { square % n };
+        let mut visited = HashSet::new();
+        for (&sudoku0, sudoku_links) in links.iter() {
+            for &(square0, _, _) in sudoku_links.iter() {
+                let line0 = get_line(square0);
+                if !visited.insert((sudoku0, line0)) {
+                    continue;
+                }
+
+                // the line of each sudoku linked to this one
+                let mut linked_lines = HashMap::from([(sudoku0, line0)]);
+                let mut to_visit = vec![(sudoku0, line0)];
+                while let Some((sudoku1, line1)) = to_visit.pop() {
+                    for &(square1, sudoku2, square2) in links[&sudoku1].iter() {
+                        if get_line(square1) != line1 {
+                            continue;
+                        }
+                        let line2 = get_line(square2);
+                        if *linked_lines.entry(sudoku2).or_insert(line2) != line2 {
+                            return Some((sudoku1, square1, sudoku2, square2));
+                        }
+                        if visited.insert((sudoku2, line2)) {
+                            to_visit.push((sudoku2, line2));
+                        }
+                    }
+                }
+            }
+        }
+        None
+    }
+
+    pub fn new_from_layout(n: usize, layout: CarpetLayout) -> Self {
+        let sudokus = (0..layout.get_n_sudokus())
+            .map(|_| Sudoku::new(n))
+            .collect();
+        let links = layout.get_carpet_links(n);
+
+        Self {
+            n,
+            n2: n * n,
+            pattern: CarpetPattern::Custom(layout.get_n_sudokus()),
+            difficulty: SudokuDifficulty::Unknown,
+            difficulty_score: 0,
+            sudokus,
+            links,
+            layout,
+            filled_board_hash: 0,
+            is_canonical: false,
+        }
+    }
+
+    /// Parse a pattern file from res/carpet_patterns/ (see [`CarpetSudoku::parse_string`])
+    pub fn parse_file(file_name: &str) -> Result<Self, SudokuError> {
+        let mut file_path = current_dir().unwrap();
+        file_path.push("res/carpet_patterns/");
+        file_path.push(file_name);
+        let file_content = std::fs::read_to_string(&file_path).map_err(|error| {
+            SudokuError::ReadFile((
+                file_path.into_os_string().into_string().unwrap(),
+                error.to_string(),
+            ))
+        })?;
+        Self::parse_string(&file_content)
+    }
+
+    /// The first line is n, the following lines are the character map of the layout (see [`CarpetLayout::parse_string`])
+    pub fn parse_string(string: &str) -> Result<Self, SudokuError> {
+        let (first_line, map) = string.split_once('\n').unwrap_or((string, ""));
+        let n = first_line.trim().parse::<usize>().map_err(|error| {
+            SudokuError::ParseString((first_line.to_string(), error.to_string()))
+        })?;
+
+        let layout = CarpetLayout::parse_string(n, map)?;
+        Ok(Self::new_from_layout(n, layout))
+    }
+
     ////////////////////////////////////////////////////////////////////////////////////////////////////////////////////////////////
     ///////////////////////////////////////////////////////   MODIFICATION   ///////////////////////////////////////////////////////
     ////////////////////////////////////////////////////////////////////////////////////////////////////////////////////////////////
@@ -197,15 +444,23 @@ impl CarpetSudoku {
                     for dx in 0..self.n {
                         let value1 = self.sudokus[sudoku1].get_cell_value(x1 + dx, y1 + dy);
                         let value2 = self.sudokus[sudoku2].get_cell_value(x2 + dx, y2 + dy);
-                        if value1 != value2 {
-                            if value1 != 0 {
+                        match (value1, value2) {
+                            _ if value1 == value2 => (),
+                            (_, 0) => {
                                 self.sudokus[sudoku2].set_value(x2 + dx, y2 + dy, value1)?;
                                 continue;
-                            } else if value2 != 0 {
+                            }
+                            (0, _) => {
                                 self.sudokus[sudoku1].set_value(x1 + dx, y1 + dy, value2)?;
                                 continue;
-                            } else {
-                                panic!("ALORS LÀ J'AI PAS COMPRIS");
+                            }
+                            _ => {
+                                let ((x1, y1), (x2, y2)) = ((x1 + dx, y1 + dy), (x2 + dx, y2 + dy));
+                                return Err(SudokuError::InvalidLink((
+                                    (sudoku1, square1),
+                                    (sudoku2, square2),
+                                    format!("linked cells ({x1}, {y1}) and ({x2}, {y2}) hold {value1} and {value2}"),
+                                )));
                             }
                         }
 
@@ -266,19 +521,7 @@ impl CarpetSudoku {
         }
 
         if self.is_canonical && self.filled_board_hash == 0 && self.is_filled() {
-            self.filled_board_hash = {
-                let mut hasher = DefaultHasher::new();
-                for sudoku_i in 0..self.sudokus.len() {
-                    for y in 0..self.n2 {
-                        for x in 0..self.n2 {
-                            self.sudokus[sudoku_i]
-                                .get_cell_value(x, y)
-                                .hash(&mut hasher);
-                        }
-                    }
-                }
-                hasher.finish()
-            };
+            self.filled_board_hash = self.compute_filled_board_hash();
         }
 
         Ok(())
@@ -319,6 +562,84 @@ impl CarpetSudoku {
         Ok(value)
     }
 
+    /// Like [`CarpetSudoku::set_value`], but the value is set in every twin cell even when it breaks the rules,
+    /// returns the first error, if any (the player can show conflicts instead of being stopped by them)
+    pub fn set_conflicting_value(
+        &mut self,
+        sudoku_id: usize,
+        x: usize,
+        y: usize,
+        value: usize,
+    ) -> Result<(), SudokuError> {
+        let mut res = Ok(());
+        for (sudoku2, x2, y2) in self.get_twin_cells(sudoku_id, x, y) {
+            let twin_res = self.sudokus[sudoku2].set_value(x2, y2, value);
+            if res.is_ok() {
+                res = twin_res;
+            }
+        }
+
+        for (sudoku2, x2, y2) in self.get_global_cell_group(sudoku_id, x, y, SudokuGroups::All) {
+            for (sudoku3, x3, y3) in self.get_twin_cells(sudoku2, x2, y2) {
+                self.sudokus[sudoku3]
+                    .get_cell_possibilities_mut(x3, y3)
+                    .remove(&value);
+            }
+        }
+
+        res
+    }
+
+    /// See [`Sudoku::restore_cell`], the twin cells aren't restored with it
+    pub fn restore_cell(
+        &mut self,
+        sudoku_id: usize,
+        x: usize,
+        y: usize,
+        value: usize,
+        possibilities: HashSet<usize>,
+    ) {
+        self.sudokus[sudoku_id].restore_cell(x, y, value, possibilities);
+    }
+
+    /// The cells whose value or possibilities are different in `after`, only in its sudoku when `sudoku_id` is given
+    pub fn get_diff(&self, after: &Self, sudoku_id: Option<usize>) -> CarpetDiff {
+        let sudoku_ids = match sudoku_id {
+            Some(sudoku_id) => sudoku_id..sudoku_id + 1,
+            None => 0..self.sudokus.len(),
+        };
+        let mut diff = CarpetDiff::new();
+        for sudoku_id in sudoku_ids {
+            let (sudoku, sudoku_after) = (&self.sudokus[sudoku_id], &after.sudokus[sudoku_id]);
+            for y in 0..self.n2 {
+                for x in 0..self.n2 {
+                    let (value, value_after) = (
+                        sudoku.get_cell_value(x, y),
+                        sudoku_after.get_cell_value(x, y),
+                    );
+                    let possibilities = sudoku.get_cell_possibilities(x, y);
+                    let possibilities_after = sudoku_after.get_cell_possibilities(x, y);
+                    if value != value_after || possibilities != possibilities_after {
+                        diff.push((
+                            (sudoku_id, x, y),
+                            (value, possibilities.clone()),
+                            (value_after, possibilities_after.clone()),
+                        ));
+                    }
+                }
+            }
+        }
+        diff
+    }
+
+    /// Puts the cells of the diff as they are before (or after, with `undo` false) it
+    pub fn apply_diff(&mut self, diff: &CarpetDiff, undo: bool) {
+        for ((sudoku_id, x, y), before, after) in diff.iter() {
+            let (value, possibilities) = if undo { before } else { after };
+            self.restore_cell(*sudoku_id, *x, *y, *value, possibilities.clone());
+        }
+    }
+
     ////////////////////////////////////////////////////////////////////////////////////////////////////////////////////////////////
     /////////////////////////////////////////////////////////   SOLVING   //////////////////////////////////////////////////////////
     ////////////////////////////////////////////////////////////////////////////////////////////////////////////////////////////////
@@ -326,18 +647,28 @@ impl CarpetSudoku {
     pub fn rule_solve(
         &mut self,
         max_difficulty: Option<SudokuDifficulty>,
-    ) -> Result<(bool, bool, Vec<usize>), SudokuError> {
+    ) -> Result<(bool, bool, CarpetSolveStep), SudokuError> {
+        let max_difficulties = vec![max_difficulty; self.sudokus.len()];
+        self.rule_solve_per_sudoku(&max_difficulties)
+    }
+
+    /// Same as [`CarpetSudoku::rule_solve`] with a maximal difficulty for each sudoku,
+    /// the carpet rules being limited by the smallest one
+    pub fn rule_solve_per_sudoku(
+        &mut self,
+        max_difficulties: &[Option<SudokuDifficulty>],
+    ) -> Result<(bool, bool, CarpetSolveStep), SudokuError> {
         let mut modified_possibility = false;
         let mut modified_value = false;
         let mut rules_used = Vec::new();
-        for sudoku in self.sudokus.iter_mut() {
-            match sudoku.rule_solve(None, max_difficulty) {
+        for (sudoku_id, sudoku) in self.sudokus.iter_mut().enumerate() {
+            match sudoku.rule_solve(None, max_difficulties[sudoku_id]) {
                 Ok(Some(a)) => {
                     if a == 0 || a == 1 {
                         modified_value = true;
                     }
                     modified_possibility = true;
-                    rules_used.push(a);
+                    rules_used.push((Some(sudoku_id), a));
                 }
                 Ok(None) => (),
                 Err(err) => {
@@ -347,7 +678,28 @@ impl CarpetSudoku {
             }
             self.difficulty = self.difficulty.max(sudoku.get_difficulty());
         }
-        self.difficulty_score += rules_used.iter().fold(0, |acc, e| acc + *e);
+
+        // when every sudoku is stuck, try the rules working across the sudokus
+        if !modified_possibility {
+            let max_difficulty = max_difficulties.iter().flatten().min().copied();
+            for &(rule_id, difficulty, rule) in CarpetSudoku::CARPET_RULES.iter() {
+                let difficulty_filter = if let Some(max_difficulty) = max_difficulty {
+                    difficulty <= max_difficulty
+                } else {
+                    difficulty < SudokuDifficulty::Unimplemented
+                };
+                let filled_cells = self.get_filled_cells();
+                if !difficulty_filter || !rule(self).unwrap_or(false) {
+                    continue;
+                }
+                modified_possibility = true;
+                modified_value = self.get_filled_cells() != filled_cells;
+                rules_used.push((None, rule_id));
+                self.difficulty = self.difficulty.max(difficulty);
+                break;
+            }
+        }
+
         self.update_link()
             .map(|_| (modified_possibility, modified_value, rules_used))
     }
@@ -356,13 +708,25 @@ impl CarpetSudoku {
         &mut self,
         rule_solve_result: (bool, bool),
         max_difficulty: Option<SudokuDifficulty>,
-    ) -> (bool, Vec<Vec<usize>>) {
+    ) -> (bool, Vec<CarpetSolveStep>) {
+        let max_difficulties = vec![max_difficulty; self.sudokus.len()];
+        self.rule_solve_until_per_sudoku(rule_solve_result, &max_difficulties)
+    }
+
+    pub fn rule_solve_until_per_sudoku(
+        &mut self,
+        rule_solve_result: (bool, bool),
+        max_difficulties: &[Option<SudokuDifficulty>],
+    ) -> (bool, Vec<CarpetSolveStep>) {
         let mut used_rules = Vec::new();
         self.difficulty = SudokuDifficulty::Unknown;
         self.difficulty_score = 0;
+        for sudoku in self.sudokus.iter_mut() {
+            sudoku.set_difficulty(SudokuDifficulty::Unknown);
+        }
         let mut did_anything = false;
         while let Ok((modified_possibility, modified_value, rules)) =
-            self.rule_solve(max_difficulty)
+            self.rule_solve_per_sudoku(max_difficulties)
         {
             used_rules.push(rules);
             if (modified_possibility, modified_value) == (false, false) {
@@ -373,9 +737,107 @@ impl CarpetSudoku {
                 break;
             }
         }
+        self.difficulty_score = Self::get_solve_path_score(&used_rules);
         (did_anything, used_rules)
     }
 
+    /// Rates the carpet (and each of its sudokus) with the easiest rules that can solve a copy of it,
+    /// a carpet the rules can't finish is [`SudokuDifficulty::Unimplemented`]
+    pub fn rate_difficulty(&mut self) -> SudokuDifficulty {
+        for difficulty in SudokuDifficulty::iter() {
+            let mut solved_carpet = self.clone();
+            solved_carpet.rule_solve_until((false, false), Some(difficulty));
+            if !solved_carpet.is_filled() {
+                continue;
+            }
+            self.difficulty = solved_carpet.difficulty;
+            self.difficulty_score = solved_carpet.difficulty_score;
+            for (sudoku, solved_sudoku) in self.sudokus.iter_mut().zip(solved_carpet.sudokus) {
+                sudoku.set_difficulty(solved_sudoku.get_difficulty());
+            }
+            return self.difficulty;
+        }
+        self.difficulty = SudokuDifficulty::Unimplemented;
+        self.difficulty_score = 0;
+        self.difficulty
+    }
+
+    /// Every rule of the rule solve one after the other, the sudokus of a round being changed one at a time,
+    /// stops when no rule can be used anymore or on an error
+    pub fn get_solve_timeline(
+        &self,
+        max_difficulty: Option<SudokuDifficulty>,
+    ) -> Vec<CarpetTimelineStep> {
+        let mut timeline: Vec<CarpetTimelineStep> = Vec::new();
+        // the carpet as the steps of the timeline leave it
+        let mut state = self.clone();
+        let mut carpet = self.clone();
+        loop {
+            let Ok((true, _, rules)) = carpet.rule_solve(max_difficulty) else {
+                break;
+            };
+
+            for &(sudoku_id, rule) in rules.iter() {
+                // each sudoku is changed by one rule at most in a round, the state still holds it as before
+                let step = sudoku_id.and_then(|sudoku_id| {
+                    state.sudokus[sudoku_id]
+                        .find_step(max_difficulty)
+                        .filter(|step| step.rule == rule)
+                });
+                let diff = state.get_diff(&carpet, sudoku_id);
+                state.apply_diff(&diff, false);
+                timeline.push(CarpetTimelineStep {
+                    sudoku_id,
+                    rule,
+                    step,
+                    diff,
+                });
+            }
+            // the links spread the last changes to the twin cells
+            let diff = state.get_diff(&carpet, None);
+            state.apply_diff(&diff, false);
+            if let Some(last) = timeline.last_mut() {
+                last.diff.extend(diff);
+            }
+        }
+        timeline
+    }
+
+    /// Each step of the solve path weighs as much as the difficulty of its rule,
+    /// then each sudoku (or the carpet itself for the carpet rules) adds the weight of its hardest step again,
+    /// so hard steps spread over several sudokus are worth more than the same steps in a single one
+    pub fn get_solve_path_score(solve_path: &[CarpetSolveStep]) -> usize {
+        let mut score = 0;
+        let mut hardest_steps: HashMap<Option<usize>, SudokuDifficulty> = HashMap::new();
+        for &(sudoku_id, rule_id) in solve_path.iter().flatten() {
+            let difficulty = Self::get_rule_difficulty_by_id(rule_id);
+            score += difficulty.get_weight();
+            let hardest_step = hardest_steps.entry(sudoku_id).or_insert(difficulty);
+            *hardest_step = (*hardest_step).max(difficulty);
+        }
+        score
+            + hardest_steps
+                .values()
+                .map(|difficulty| difficulty.get_weight())
+                .sum::<usize>()
+    }
+
+    /// Score of a finished game: 100 points per sudoku of the pattern times the weight of the difficulty,
+    /// each mistake costs 10% and each hint 20% (75% at most),
+    /// and a game slower than its par time (5 minutes per sudoku and difficulty level) loses up to 75% more
+    pub fn get_game_score(&self, elapsed: Duration, mistakes: usize, hints: usize) -> usize {
+        let n_sudokus = self.sudokus.len();
+        let score = 100 * n_sudokus * self.difficulty.get_weight().max(1);
+        let score = score * (100 - (10 * mistakes + 20 * hints).min(75)) / 100;
+
+        let par_time = 300 * n_sudokus as u64 * (self.difficulty as u64).clamp(1, 5);
+        let elapsed = elapsed.as_secs().max(1);
+        if elapsed <= par_time {
+            return score;
+        }
+        (score as u64 * par_time / elapsed).max(score as u64 / 4) as usize
+    }
+
     pub fn backtrack_solve(&mut self) -> bool {
         self._backtrack_solve(
             (0..self.sudokus.len() * self.n2 * self.n2)
@@ -475,6 +937,20 @@ impl CarpetSudoku {
         }
 
         for (sudoku1, sudoku) in self.sudokus.iter_mut().enumerate().skip(1) {
+            // take the rows_swaps already set by another link (ex: the bottom sudoku of a Sohei)
+            for &(square1, sudoku2, square2) in self.links.get(&sudoku1).unwrap() {
+                let y1 = square1 - square1 % self.n;
+                let y2 = square2 - square2 % self.n;
+                if rows_swaps[sudoku1].contains_key(&y1) || !rows_swaps[sudoku2].contains_key(&y2) {
+                    continue;
+                }
+                for dy in 0..self.n {
+                    let (to_y2, from_y2) = rows_swaps[sudoku2][&(y2 + dy)];
+                    rows_swaps[sudoku1]
+                        .insert(y1 + dy, (y1 + to_y2 % self.n, y1 + from_y2 % self.n));
+                }
+            }
+
             // complete the rows_swap
             for y0 in (0..self.n2).step_by(self.n) {
                 if rows_swaps[sudoku1].contains_key(&y0) {
@@ -549,12 +1025,234 @@ impl CarpetSudoku {
         self.sudokus.iter().all(|sudoku| sudoku.is_filled())
     }
 
+    /// Hash of the board that doesn't change when the whole layout is rotated or mirrored, when the sudokus are re-indexed
+    /// (or shifted around a torus) or when the values are relabelled:
+    /// the layout is read in each of these orientations, the values being relabelled in order of appearance,
+    /// and only the smallest reading is hashed.
+    /// Carpets stored with a previous version of this hash are moved by `fill_database carpet rehash`
+    pub fn compute_filled_board_hash(&self) -> u64 {
+        let mut hasher = DefaultHasher::new();
+        let positions = self.layout.get_positions();
+
+        // without positions (ex: custom links without layout), only the values can be relabelled
+        if positions.is_empty() {
+            let mut relabels = HashMap::new();
+            for sudoku in self.sudokus.iter() {
+                for y in 0..self.n2 {
+                    for x in 0..self.n2 {
+                        let value = sudoku.get_cell_value(x, y);
+                        let relabels_len = relabels.len();
+                        relabels
+                            .entry(value)
+                            .or_insert(relabels_len + 1)
+                            .hash(&mut hasher);
+                    }
+                }
+            }
+            return hasher.finish();
+        }
+
+        let board = self.get_layout_board();
+        let mut smallest_reading: Option<(Coords, Vec<usize>)> = None;
+        for ((new_width, new_height), read_positions) in self.get_layout_readings() {
+            let mut new_board = vec![vec![0; new_width]; new_height];
+            for (y, row) in board.iter().enumerate() {
+                for (x, &value) in row.iter().enumerate() {
+                    let (new_x, new_y) = read_positions[y][x];
+                    new_board[new_y][new_x] = value;
+                }
+            }
+
+            let mut relabels = HashMap::new();
+            let mut reading = Vec::with_capacity(new_width * new_height);
+            for &value in new_board.iter().flatten() {
+                if value == 0 {
+                    reading.push(0);
+                    continue;
+                }
+                let relabels_len = relabels.len();
+                reading.push(*relabels.entry(value).or_insert(relabels_len + 1));
+            }
+
+            let reading = ((new_width, new_height), reading);
+            if smallest_reading
+                .as_ref()
+                .is_none_or(|smallest_reading| reading < *smallest_reading)
+            {
+                smallest_reading = Some(reading);
+            }
+        }
+
+        smallest_reading.hash(&mut hasher);
+        hasher.finish()
+    }
+
+    /// The values of all the sudokus placed on the layout (0 outside of the sudokus)
+    fn get_layout_board(&self) -> Vec<Vec<usize>> {
+        let (width, height) = self
+            .layout
+            .get_wrap()
+            .unwrap_or_else(|| self.layout.get_size(self.n));
+        let mut board = vec![vec![0; width]; height];
+        for (sudoku, &(x0, y0)) in self.sudokus.iter().zip(self.layout.get_positions().iter()) {
+            for y in 0..self.n2 {
+                for x in 0..self.n2 {
+                    board[(y0 + y) % height][(x0 + x) % width] = sudoku.get_cell_value(x, y);
+                }
+            }
+        }
+        board
+    }
+
+    /// Every way to read the layout board: mirrored and rotated, and around a torus starting at any sudoku.
+    /// Each reading gives the size of the read board, and where each cell of the layout board is read
+    fn get_layout_readings(&self) -> Vec<(Coords, Vec<Vec<Coords>>)> {
+        let positions = self.layout.get_positions();
+        let (width, height) = self
+            .layout
+            .get_wrap()
+            .unwrap_or_else(|| self.layout.get_size(self.n));
+
+        // mirror then rotate by a quarter turn (x, y) -> (height - 1 - y, x) as many times as needed
+        let transform = |symmetry: usize, (mut x, mut y): Coords| {
+            let (mut width, mut height) = (width, height);
+            if symmetry >= 4 {
+                x = width - 1 - x;
+            }
+            for _ in 0..symmetry % 4 {
+                (x, y) = (height - 1 - y, x);
+                (width, height) = (height, width);
+            }
+            ((x, y), (width, height))
+        };
+
+        let mut readings = Vec::new();
+        for symmetry in 0..8 {
+            let (_, (new_width, new_height)) = transform(symmetry, (0, 0));
+
+            // around a torus, the reading can start at any sudoku
+            let shifts = if self.layout.get_wrap().is_none() {
+                vec![(0, 0)]
+            } else {
+                positions
+                    .iter()
+                    .map(|&(x0, y0)| {
+                        // the new top left corner is the transformed corner followed by the other one
+                        let ((x1, y1), _) = transform(symmetry, (x0, y0));
+                        let ((x2, y2), _) = transform(
+                            symmetry,
+                            ((x0 + self.n2 - 1) % width, (y0 + self.n2 - 1) % height),
+                        );
+                        let x = if (x1 + self.n2 - 1) % new_width == x2 {
+                            x1
+                        } else {
+                            x2
+                        };
+                        let y = if (y1 + self.n2 - 1) % new_height == y2 {
+                            y1
+                        } else {
+                            y2
+                        };
+                        (x, y)
+                    })
+                    .collect()
+            };
+
+            for (shift_x, shift_y) in shifts {
+                let read_positions = (0..height)
+                    .map(|y| {
+                        (0..width)
+                            .map(|x| {
+                                let ((new_x, new_y), _) = transform(symmetry, (x, y));
+                                (
+                                    (new_x + new_width - shift_x) % new_width,
+                                    (new_y + new_height - shift_y) % new_height,
+                                )
+                            })
+                            .collect()
+                    })
+                    .collect();
+                readings.push(((new_width, new_height), read_positions));
+            }
+        }
+        readings
+    }
+
+    /// Moves the filled cells of a game of this carpet (one per cell of each sudoku, as stored in the database)
+    /// to the same cells of `other`, the same filled carpet read in another orientation.
+    /// None if `other` isn't one of the readings of this carpet
+    pub fn move_filled_cells_to(&self, other: &Self, filled_cells: &[u8]) -> Option<Vec<u8>> {
+        if self.pattern != other.pattern || self.layout.get_positions().is_empty() {
+            return None;
+        }
+        let board = self.get_layout_board();
+        let other_board = other.get_layout_board();
+        let (width, height) = (board[0].len(), board.len());
+        let positions = self.layout.get_positions();
+        let other_positions = other.layout.get_positions();
+
+        for ((new_width, new_height), read_positions) in self.get_layout_readings() {
+            if (new_width, new_height) != (other_board[0].len(), other_board.len()) {
+                continue;
+            }
+
+            // the values can be relabelled, but always the same way
+            let mut relabels = HashMap::new();
+            let is_same_carpet = (0..height).all(|y| {
+                (0..width).all(|x| {
+                    let (new_x, new_y) = read_positions[y][x];
+                    *relabels
+                        .entry(board[y][x])
+                        .or_insert(other_board[new_y][new_x])
+                        == other_board[new_y][new_x]
+                })
+            }) && relabels.values().collect::<HashSet<_>>().len()
+                == relabels.len();
+            if !is_same_carpet {
+                continue;
+            }
+
+            let mut filled_board = vec![vec![false; new_width]; new_height];
+            for (i, &is_filled) in filled_cells.iter().enumerate() {
+                let sudoku_id = i / (self.n2 * self.n2);
+                let cell_i = i - sudoku_id * self.n2 * self.n2;
+                let (x0, y0) = positions[sudoku_id];
+                let x = (x0 + cell_i % self.n2) % width;
+                let y = (y0 + cell_i / self.n2) % height;
+                let (new_x, new_y) = read_positions[y][x];
+                filled_board[new_y][new_x] |= is_filled != 0;
+            }
+            return Some(
+                (0..filled_cells.len())
+                    .map(|i| {
+                        let sudoku_id = i / (self.n2 * self.n2);
+                        let cell_i = i - sudoku_id * self.n2 * self.n2;
+                        let (x0, y0) = other_positions[sudoku_id];
+                        filled_board[(y0 + cell_i / self.n2) % new_height]
+                            [(x0 + cell_i % self.n2) % new_width] as u8
+                    })
+                    .collect(),
+            );
+        }
+        None
+    }
+
     pub fn is_unique(&self) -> bool {
         self.count_solutions(Some(2)) == 1
     }
 
     pub fn count_solutions(&self, max_solutions: Option<usize>) -> usize {
-        self.clone()._count_solutions(
+        self.count_solutions_cancellable(max_solutions, &AtomicBool::new(false))
+            .unwrap_or_default()
+    }
+
+    /// Same as [`CarpetSudoku::count_solutions`], gives up and returns None once `cancel` is set
+    pub fn count_solutions_cancellable(
+        &self,
+        max_solutions: Option<usize>,
+        cancel: &AtomicBool,
+    ) -> Option<usize> {
+        let solutions = self.clone()._count_solutions(
             (0..self.sudokus.len() * self.n2 * self.n2)
                 .filter_map(|i| {
                     let sudoku_id = i / (self.n2 * self.n2);
@@ -569,14 +1267,20 @@ impl CarpetSudoku {
                 })
                 .collect::<Vec<_>>(),
             max_solutions,
-        )
+            cancel,
+        );
+        (!cancel.load(Ordering::Relaxed)).then_some(solutions)
     }
 
     fn _count_solutions(
         &mut self,
         mut empty_cells: Vec<(usize, usize, usize)>,
         max_solutions: Option<usize>,
+        cancel: &AtomicBool,
     ) -> usize {
+        if cancel.load(Ordering::Relaxed) {
+            return 0;
+        }
         empty_cells
             .sort_by_key(|&(sudoku, x, y)| self.sudokus[sudoku].get_cell_possibilities(x, y).len());
 
@@ -614,7 +1318,7 @@ impl CarpetSudoku {
                 }
             }
 
-            sub_solutions += self._count_solutions(empty_cells.clone(), max_solutions);
+            sub_solutions += self._count_solutions(empty_cells.clone(), max_solutions, cancel);
             if let Some(max_solutions) = max_solutions {
                 if sub_solutions >= max_solutions {
                     return sub_solutions;
@@ -662,7 +1366,7 @@ impl CarpetSudoku {
             );
         }
 
-        let (pattern, pattern_size) = self.pattern.to_db();
+        let (pattern, pattern_size) = self.pattern.to_canonical_db()?;
         let db_carpet = DBCanonicalCarpet {
             carpet_filled_board_hash: self.filled_board_hash.wrapping_sub(u64::MAX / 2 + 1) as i64,
             carpet_n: self.n as i16,
@@ -725,7 +1429,7 @@ impl CarpetSudoku {
                 .wrapping_sub(u64::MAX / 2 + 1)
                 as i64,
             carpet_game_difficulty: self.difficulty as i16,
-            carpet_game_difficulty_score: self.difficulty_score as i16,
+            carpet_game_difficulty_score: self.difficulty_score.min(i16::MAX as usize) as i16,
             carpet_game_filled_cells: filled_cells,
             carpet_game_filled_cells_count: filled_cells_count,
         }
@@ -735,11 +1439,12 @@ impl CarpetSudoku {
         db_carpet: DBCanonicalCarpet,
         db_carpet_sudokus: Vec<DBCanonicalCarpetSudoku>,
         db_sudokus: Vec<DBCanonicalSudoku>,
-    ) -> Self {
-        let mut carpet = Self::new(
-            db_carpet.carpet_n as usize,
-            CarpetPattern::from_db(db_carpet.carpet_pattern, db_carpet.carpet_pattern_size),
-        );
+    ) -> Result<Self, SudokuError> {
+        let pattern = CarpetPattern::try_from_canonical_db(
+            db_carpet.carpet_pattern,
+            db_carpet.carpet_pattern_size,
+        )?;
+        let mut carpet = Self::new(db_carpet.carpet_n as usize, pattern);
         carpet.filled_board_hash =
             (db_carpet.carpet_filled_board_hash as u64).wrapping_add(u64::MAX / 2 + 1);
         carpet.is_canonical = true;
@@ -755,7 +1460,7 @@ impl CarpetSudoku {
                 Sudoku::db_from_filled(sudoku.clone());
         }
 
-        carpet
+        Ok(carpet)
     }
 
     pub fn db_from_game(
@@ -763,10 +1468,11 @@ impl CarpetSudoku {
         db_carpet: DBCanonicalCarpet,
         db_carpet_sudokus: Vec<DBCanonicalCarpetSudoku>,
         db_sudokus: Vec<DBCanonicalSudoku>,
-    ) -> Self {
+    ) -> Result<Self, SudokuError> {
         let game_info = game_info.into();
-        let mut carpet = Self::db_from_filled(db_carpet, db_carpet_sudokus, db_sudokus);
+        let mut carpet = Self::db_from_filled(db_carpet, db_carpet_sudokus, db_sudokus)?;
         carpet.difficulty = SudokuDifficulty::from(game_info.carpet_game_difficulty);
+        carpet.difficulty_score = game_info.carpet_game_difficulty_score as usize;
 
         for (i, is_filled) in game_info.carpet_game_filled_cells.into_iter().enumerate() {
             if is_filled != 0 {
@@ -780,7 +1486,7 @@ impl CarpetSudoku {
         }
 
         carpet.update_link().unwrap();
-        carpet
+        Ok(carpet)
     }
 
     pub fn load_filled_from_db(
@@ -789,7 +1495,8 @@ impl CarpetSudoku {
         pattern: CarpetPattern,
     ) -> Option<Self> {
         database
-            .get_random_canonical_carpet(n as i16, pattern.to_db())
+            .get_random_canonical_carpet(n as i16, pattern.to_canonical_db().ok()?)
+            .inspect_err(Self::log_db_error)
             .ok()
     }
 
@@ -800,7 +1507,12 @@ impl CarpetSudoku {
         difficulty: SudokuDifficulty,
     ) -> Option<Self> {
         database
-            .get_random_canonical_carpet_game(n as i16, pattern.to_db(), difficulty as i16)
+            .get_random_canonical_carpet_game(
+                n as i16,
+                pattern.to_canonical_db().ok()?,
+                difficulty as i16,
+            )
+            .inspect_err(Self::log_db_error)
             .ok()
     }
 
@@ -811,21 +1523,344 @@ impl CarpetSudoku {
         difficulty: SudokuDifficulty,
     ) -> Option<Self> {
         database
-            .get_random_minimal_canonical_carpet_game(n as i16, pattern.to_db(), difficulty as i16)
+            .get_random_minimal_canonical_carpet_game(
+                n as i16,
+                pattern.to_canonical_db().ok()?,
+                difficulty as i16,
+            )
+            .inspect_err(Self::log_db_error)
             .ok()
     }
 
-    // TODO:
-    // pub fn generate_filled_from_db(
-    //     database: &mut Database,
-    //     n: usize,
-    //     pattern: CarpetPattern,
-    // ) -> Self {
-    //     let (db_carpet, db_carpet_sudokus, db_sudokus) = database
-    //         .construct_canonical_carpet(n as i16, pattern)
-    //         .unwrap();
-    //     Self::db_from_filled(db_carpet, db_carpet_sudokus, db_sudokus)
-    // }
+    /// No carpet in the database is expected, any other error is shown
+    fn log_db_error(error: &diesel::result::Error) {
+        if !matches!(error, diesel::result::Error::NotFound) {
+            eprintln!("Couldn't load a carpet from the database: {error}");
+        }
+    }
+
+    const DB_ASSEMBLY_TRIES: usize = 100;
+    const DB_RANDOM_CANDIDATES: usize = 8;
+
+    /// Assembles a filled carpet from the filled sudokus stored in the database, without backtracking the whole carpet.
+    /// Each sudoku is a stored grid whose bands and stacks are swapped and whose values are relabelled
+    /// so that its linked squares match the ones of the sudokus placed before it.
+    /// A sudoku that none of a few random stored grids fits is backtracked around its linked squares.
+    pub fn generate_filled_from_db(
+        database: &mut Database,
+        n: usize,
+        pattern: CarpetPattern,
+    ) -> Self {
+        for _ in 0..Self::DB_ASSEMBLY_TRIES {
+            if let Some(carpet) = Self::_generate_filled_from_db(database, n, pattern) {
+                return carpet;
+            }
+        }
+        Self::generate_full(n, pattern)
+    }
+
+    fn _generate_filled_from_db(
+        database: &mut Database,
+        n: usize,
+        pattern: CarpetPattern,
+    ) -> Option<Self> {
+        let mut carpet = Self::new(n, pattern);
+        // the board of every placed sudoku
+        let mut boards: Vec<Vec<Vec<usize>>> = Vec::new();
+        for sudoku_id in 0..carpet.sudokus.len() {
+            // (square, values, sudoku it comes from) for every square shared with a placed sudoku
+            let mut constraints = carpet
+                .links
+                .get(&sudoku_id)
+                .into_iter()
+                .flatten()
+                .filter(|&&(_, sudoku2, _)| sudoku2 < sudoku_id)
+                .map(|&(square1, sudoku2, square2)| {
+                    (
+                        square1,
+                        Self::get_square_values(&boards[sudoku2], n, square2),
+                        sudoku2,
+                    )
+                })
+                .collect::<Vec<_>>();
+            constraints.sort();
+            constraints.dedup_by_key(|(square, _, _)| *square);
+
+            let (board, _) = Self::find_stored_grid(database, n, &constraints)
+                .or_else(|| Self::backtrack_grid(n, &constraints))?;
+            boards.push(board);
+        }
+
+        for sudoku in carpet.sudokus.iter_mut() {
+            sudoku.set_is_canonical(true);
+        }
+        carpet.is_canonical = true;
+        for (sudoku_id, board) in boards.iter().enumerate() {
+            for (y, row) in board.iter().enumerate() {
+                for (x, &value) in row.iter().enumerate() {
+                    if carpet.sudokus[sudoku_id].get_cell_value(x, y) == 0 {
+                        carpet.set_value(sudoku_id, x, y, value).ok()?;
+                    }
+                }
+            }
+        }
+        carpet.filled_board_hash = carpet.compute_filled_board_hash();
+
+        Some(carpet)
+    }
+
+    /// A random stored grid, permuted and relabelled to fit every constraint.
+    /// A lone square fits any grid once relabelled, so the square hashes of the database can't narrow the search:
+    /// only the grids sharing several squares can miss, and a few random grids are tried for them
+    fn find_stored_grid(
+        database: &mut Database,
+        n: usize,
+        constraints: &[(usize, Vec<usize>, usize)],
+    ) -> Option<(Vec<Vec<usize>>, Vec<usize>)> {
+        let n2 = n * n;
+        let mut rng = rng();
+
+        if constraints.is_empty() {
+            let stored = database.get_random_canonical_sudokus(n as u8).ok()?;
+            let mut board = stored.get_board().clone();
+            let mut squares = (0..n2).collect::<Vec<_>>();
+            squares.shuffle(&mut rng);
+            for (from, to) in squares.into_iter().enumerate() {
+                Self::move_square(&mut board, n, from, to);
+            }
+            let mut values = (1..=n2).collect::<Vec<_>>();
+            values.shuffle(&mut rng);
+            let to_stored = Self::relabel_board(&mut board, &(1..=n2).collect::<Vec<_>>(), &values);
+            return Some((board, to_stored));
+        }
+
+        let mut boards = Vec::new();
+        for _ in 0..Self::DB_RANDOM_CANDIDATES {
+            let stored = database.get_random_canonical_sudokus(n as u8).ok()?;
+            boards.push(stored.get_board().clone());
+        }
+
+        boards
+            .iter()
+            .find_map(|board| Self::fit_grid(board, n, constraints))
+    }
+}
+
+impl CarpetSudoku {
+    ////////////////////////////////////////////////////////////////////////////////////////////////////////////////////////////////
+    //////////////////////////////////////////////////////   GRID ASSEMBLY   ////////////////////////////////////////////////////////
+    ////////////////////////////////////////////////////////////////////////////////////////////////////////////////////////////////
+
+    /// Permutes the bands, the stacks, the rows inside each band and the columns inside each stack of a filled grid,
+    /// and relabels its values, so that the squares of the constraints `(square, values, _)` hold these values.
+    /// Returns the new grid with the matching from its values to the ones of the given grid, or None if the grid can't fit them
+    pub fn fit_grid(
+        board: &[Vec<usize>],
+        n: usize,
+        constraints: &[(usize, Vec<usize>, usize)],
+    ) -> Option<(Vec<Vec<usize>>, Vec<usize>)> {
+        let n2 = n * n;
+        let mut row_map = vec![None; n2];
+        let mut col_map = vec![None; n2];
+        let mut to_new = vec![0; n2 + 1];
+        let mut to_old = vec![0; n2 + 1];
+        if !Self::_fit_grid(
+            board,
+            n,
+            constraints,
+            (&mut row_map, &mut col_map),
+            (&mut to_new, &mut to_old),
+        ) {
+            return None;
+        }
+
+        // the bands and stacks without constraints keep their order
+        for map in [&mut row_map, &mut col_map] {
+            let mut free_lines = (0..n2)
+                .filter(|line| !map.contains(&Some(*line)))
+                .collect::<Vec<_>>()
+                .into_iter();
+            for line in map.iter_mut().filter(|line| line.is_none()) {
+                *line = free_lines.next();
+            }
+        }
+        // the values outside of the constraints take the labels left
+        let free_values = (1..=n2)
+            .filter(|&value| to_old[value] == 0)
+            .collect::<Vec<_>>();
+        let free_old_values = (1..=n2)
+            .filter(|&old_value| to_new[old_value] == 0)
+            .collect::<Vec<_>>();
+        for (old_value, new_value) in free_old_values.into_iter().zip(free_values) {
+            to_new[old_value] = new_value;
+            to_old[new_value] = old_value;
+        }
+
+        let new_board = (0..n2)
+            .map(|y| {
+                (0..n2)
+                    .map(|x| to_new[board[row_map[y].unwrap()][col_map[x].unwrap()]])
+                    .collect::<Vec<_>>()
+            })
+            .collect::<Vec<_>>();
+        Some((new_board, to_old))
+    }
+
+    fn _fit_grid(
+        board: &[Vec<usize>],
+        n: usize,
+        constraints: &[(usize, Vec<usize>, usize)],
+        (row_map, col_map): (&mut Vec<Option<usize>>, &mut Vec<Option<usize>>),
+        (to_new, to_old): (&mut Vec<usize>, &mut Vec<usize>),
+    ) -> bool {
+        let Some(((square, values, _), next_constraints)) = constraints.split_first() else {
+            return true;
+        };
+        let (band, stack) = (square / n, square % n);
+
+        for band_rows in Self::get_line_choices(row_map, n, band) {
+            let old_rows = row_map[band * n..(band + 1) * n].to_vec();
+            row_map[band * n..(band + 1) * n].copy_from_slice(&band_rows);
+            for stack_cols in Self::get_line_choices(col_map, n, stack) {
+                let old_cols = col_map[stack * n..(stack + 1) * n].to_vec();
+                col_map[stack * n..(stack + 1) * n].copy_from_slice(&stack_cols);
+
+                // the values of the square are compared through the relabelling built by the previous squares
+                let mut new_labels = Vec::new();
+                let fits = values.iter().enumerate().all(|(i, &value)| {
+                    let row = row_map[band * n + i / n].unwrap();
+                    let col = col_map[stack * n + i % n].unwrap();
+                    let old_value = board[row][col];
+                    if to_new[old_value] == 0 && to_old[value] == 0 {
+                        to_new[old_value] = value;
+                        to_old[value] = old_value;
+                        new_labels.push((old_value, value));
+                    }
+                    to_new[old_value] == value
+                });
+                if fits
+                    && Self::_fit_grid(
+                        board,
+                        n,
+                        next_constraints,
+                        (row_map, col_map),
+                        (to_new, to_old),
+                    )
+                {
+                    return true;
+                }
+
+                for (old_value, value) in new_labels {
+                    to_new[old_value] = 0;
+                    to_old[value] = 0;
+                }
+                col_map[stack * n..(stack + 1) * n].copy_from_slice(&old_cols);
+            }
+            row_map[band * n..(band + 1) * n].copy_from_slice(&old_rows);
+        }
+        false
+    }
+
+    /// The lines of the grid that can be put in the band (or stack) `block`:
+    /// the ones already chosen, or every order of the lines of a block that isn't used yet
+    fn get_line_choices(map: &[Option<usize>], n: usize, block: usize) -> Vec<Vec<Option<usize>>> {
+        if map[block * n].is_some() {
+            return vec![map[block * n..(block + 1) * n].to_vec()];
+        }
+        let used_blocks = map
+            .iter()
+            .flatten()
+            .map(|line| line / n)
+            .collect::<HashSet<_>>();
+        let mut orders = vec![Vec::new()];
+        for _ in 0..n {
+            orders = orders
+                .into_iter()
+                .flat_map(|order: Vec<usize>| {
+                    (0..n)
+                        .filter(|i| !order.contains(i))
+                        .map(|i| [order.clone(), vec![i]].concat())
+                        .collect::<Vec<_>>()
+                })
+                .collect();
+        }
+        (0..n)
+            .filter(|old_block| !used_blocks.contains(old_block))
+            .flat_map(|old_block| {
+                orders
+                    .iter()
+                    .map(|order| {
+                        order
+                            .iter()
+                            .map(|i| Some(old_block * n + i))
+                            .collect::<Vec<_>>()
+                    })
+                    .collect::<Vec<_>>()
+            })
+            .collect()
+    }
+
+    /// A grid filled by backtracking around the constraints, when no stored grid fits
+    pub fn backtrack_grid(
+        n: usize,
+        constraints: &[(usize, Vec<usize>, usize)],
+    ) -> Option<(Vec<Vec<usize>>, Vec<usize>)> {
+        let mut sudoku = Sudoku::new(n);
+        for (square, values, _) in constraints {
+            for (i, &value) in values.iter().enumerate() {
+                let x = (square % n) * n + i % n;
+                let y = (square / n) * n + i / n;
+                sudoku.set_value(x, y, value).ok()?;
+            }
+        }
+        if !sudoku.backtrack_solve(0, 0) {
+            return None;
+        }
+        // the grid isn't stored: its values are kept as they are
+        Some((sudoku.get_board().clone(), (0..=n * n).collect()))
+    }
+
+    pub fn get_square_values(board: &[Vec<usize>], n: usize, square: usize) -> Vec<usize> {
+        let (x0, y0) = ((square % n) * n, (square / n) * n);
+        (0..n * n).map(|i| board[y0 + i / n][x0 + i % n]).collect()
+    }
+
+    /// Swaps the bands and the stacks of the squares `from` and `to`, which keeps the board valid
+    pub fn move_square(board: &mut [Vec<usize>], n: usize, from: usize, to: usize) {
+        let (from_x, from_y) = (from % n, from / n);
+        let (to_x, to_y) = (to % n, to / n);
+        if from_y != to_y {
+            for dy in 0..n {
+                board.swap(from_y * n + dy, to_y * n + dy);
+            }
+        }
+        if from_x != to_x {
+            for row in board.iter_mut() {
+                for dx in 0..n {
+                    row.swap(from_x * n + dx, to_x * n + dx);
+                }
+            }
+        }
+    }
+
+    /// Replaces every old value by the new value at the same index,
+    /// and returns the matching from the new values to the old ones
+    pub fn relabel_board(
+        board: &mut [Vec<usize>],
+        old_values: &[usize],
+        new_values: &[usize],
+    ) -> Vec<usize> {
+        let mut to_new = vec![0; old_values.len() + 1];
+        let mut to_old = vec![0; old_values.len() + 1];
+        for (&old_value, &new_value) in old_values.iter().zip(new_values) {
+            to_new[old_value] = new_value;
+            to_old[new_value] = old_value;
+        }
+        for value in board.iter_mut().flatten() {
+            *value = to_new[*value];
+        }
+        to_old
+    }
 }
 
 ////////////////////////////////////////////////////////////////////////////////////////////////////////////////////////////////
@@ -859,6 +1894,7 @@ impl PartialEq for CarpetSudoku {
     fn eq(&self, other: &Self) -> bool {
         if self.n.ne(&other.n)
             || self.pattern.ne(&other.pattern)
+            || self.layout.ne(&other.layout)
             || self.difficulty.ne(&other.difficulty)
             || self.is_canonical.ne(&other.is_canonical)
             || self.filled_board_hash.ne(&other.filled_board_hash)
@@ -25,6 +25,68 @@ struct CarpetGenerationLogInfos {
     pub solvable_sub_carpet_counter: usize,
 }
 
+/// The difficulty aimed by a generation: either the difficulty of the whole carpet or the one of each sudoku
+#[derive(Clone)]
+enum CarpetDifficultyTarget {
+    Carpet(SudokuDifficulty),
+    Sudokus(Vec<SudokuDifficulty>),
+}
+
+impl CarpetDifficultyTarget {
+    fn get_max_difficulties(&self, n_sudokus: usize) -> Vec<Option<SudokuDifficulty>> {
+        match self {
+            CarpetDifficultyTarget::Carpet(difficulty) => vec![Some(*difficulty); n_sudokus],
+            CarpetDifficultyTarget::Sudokus(difficulties) => difficulties
+                .iter()
+                .map(|&difficulty| Some(difficulty))
+                .collect(),
+        }
+    }
+
+    /// Number of games missing the difficulty after which the generation gives up:
+    /// the difficulty of each sudoku is bound by the ones of its neighbours, so a per sudoku target may be unreachable
+    fn get_max_wrong_difficulties(&self) -> Option<usize> {
+        match self {
+            CarpetDifficultyTarget::Carpet(_) => None,
+            CarpetDifficultyTarget::Sudokus(_) => Some(CarpetSudoku::PER_SUDOKU_GENERATION_TRIES),
+        }
+    }
+
+    fn is_given_up(&self, log_infos: &CarpetGenerationLogInfos) -> bool {
+        self.get_max_wrong_difficulties()
+            .is_some_and(|max_tries| log_infos.wrong_difficulty_counter >= max_tries)
+    }
+
+    fn is_reached(&self, solved_carpet: &CarpetSudoku) -> bool {
+        match self {
+            CarpetDifficultyTarget::Carpet(difficulty) => solved_carpet.difficulty == *difficulty,
+            // each sudoku is limited by its own difficulty, and has to reach it
+            CarpetDifficultyTarget::Sudokus(difficulties) => solved_carpet
+                .sudokus
+                .iter()
+                .zip(difficulties)
+                .all(|(sudoku, difficulty)| sudoku.get_difficulty() == *difficulty),
+        }
+    }
+}
+
+impl std::fmt::Display for CarpetDifficultyTarget {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            CarpetDifficultyTarget::Carpet(difficulty) => write!(f, "{difficulty}"),
+            CarpetDifficultyTarget::Sudokus(difficulties) => write!(
+                f,
+                "{}",
+                difficulties
+                    .iter()
+                    .map(|difficulty| difficulty.to_string())
+                    .collect::<Vec<_>>()
+                    .join("/")
+            ),
+        }
+    }
+}
+
 impl std::fmt::Display for CarpetGenerationLogInfos {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         write!(
@@ -43,6 +105,8 @@ impl std::fmt::Display for CarpetGenerationLogInfos {
 }
 
 impl CarpetSudoku {
+    const PER_SUDOKU_GENERATION_TRIES: usize = 100;
+
     ////////////////////////////////////////////////////////////////////////////////////////////////////////////////////////////////
     //////////////////////////////////////////////////////////   FILLED   //////////////////////////////////////////////////////////
     ////////////////////////////////////////////////////////////////////////////////////////////////////////////////////////////////
@@ -59,7 +123,10 @@ impl CarpetSudoku {
         let mut tries = 0;
         loop {
             let mut carpet = self.clone();
-            if !carpet._generate_canonical_from(0, 0, 0) {
+            // Some patterns (like the Windmill) can't be completed once the first column of every
+            // sudoku is canonical too: after a failed try only the first rows are canonical
+            let first_column = tries == 0;
+            if !carpet._generate_canonical_from(0, 0, 0, first_column) {
                 panic!("pattern: {} juste pas possible en fait", carpet.pattern);
             }
 
@@ -85,6 +152,7 @@ impl CarpetSudoku {
         mut sudoku_id: usize,
         mut x: usize,
         mut y: usize,
+        first_column: bool,
     ) -> bool {
         let backup = self.clone();
 
@@ -98,6 +166,9 @@ impl CarpetSudoku {
                 }
 
                 if sudoku_id == self.sudokus.len() {
+                    if !first_column {
+                        return true;
+                    }
                     sudoku_id = 0;
                     y = 1;
                 }
@@ -124,7 +195,7 @@ impl CarpetSudoku {
 
         for value in possibilities {
             if self.set_value(sudoku_id, x, y, value).is_ok()
-                && self._generate_canonical_from(sudoku_id, x, y)
+                && self._generate_canonical_from(sudoku_id, x, y, first_column)
             {
                 return true;
             }
@@ -149,7 +220,41 @@ impl CarpetSudoku {
         self.clone().into_generate_from(aimed_difficulty)
     }
 
-    pub fn into_generate_from(mut self, aimed_difficulty: SudokuDifficulty) -> Option<Self> {
+    pub fn into_generate_from(self, aimed_difficulty: SudokuDifficulty) -> Option<Self> {
+        self._into_generate_from(CarpetDifficultyTarget::Carpet(aimed_difficulty))
+    }
+
+    /// Generate a game where each sudoku has its own maximal difficulty (like an easy center with hard corners for a samurai),
+    /// each sudoku reaches exactly its difficulty, as given back by [`CarpetSudoku::get_sudoku_difficulties`],
+    /// None if no such game was found after some tries
+    pub fn generate_new_per_sudoku(
+        n: usize,
+        pattern: CarpetPattern,
+        difficulties: Vec<SudokuDifficulty>,
+    ) -> Option<Self> {
+        Self::generate_full(n, pattern).into_generate_from_per_sudoku(difficulties)
+    }
+
+    pub fn generate_from_per_sudoku(
+        &self,
+        aimed_difficulties: Vec<SudokuDifficulty>,
+    ) -> Option<Self> {
+        self.clone()
+            .into_generate_from_per_sudoku(aimed_difficulties)
+    }
+
+    pub fn into_generate_from_per_sudoku(
+        self,
+        aimed_difficulties: Vec<SudokuDifficulty>,
+    ) -> Option<Self> {
+        if aimed_difficulties.len() != self.sudokus.len() {
+            return None;
+        }
+        self._into_generate_from(CarpetDifficultyTarget::Sudokus(aimed_difficulties))
+    }
+
+    fn _into_generate_from(mut self, aimed_difficulty: CarpetDifficultyTarget) -> Option<Self> {
+        let max_difficulties = aimed_difficulty.get_max_difficulties(self.sudokus.len());
         self.difficulty = SudokuDifficulty::Unknown;
         self.difficulty_score = 0;
 
@@ -240,6 +345,7 @@ impl CarpetSudoku {
             let already_explored_filled_cells = Arc::clone(&already_explored_filled_cells);
             let log_infos = Arc::clone(&log_infos);
             let carpet_tx = carpet_tx.clone();
+            let aimed_difficulty = aimed_difficulty.clone();
             let should_stop = Arc::new(Mutex::new(false));
 
             let thread_should_stop = Arc::clone(&should_stop);
@@ -269,7 +375,7 @@ impl CarpetSudoku {
                         }
 
                         starting_carpet._generate_from(
-                            aimed_difficulty,
+                            &aimed_difficulty,
                             &mut carpet_generation_input,
                             &thread_should_stop,
                             &already_explored_filled_cells,
@@ -305,9 +411,13 @@ impl CarpetSudoku {
 
             {
                 let mut solved_carpet = carpet.clone();
-                solved_carpet.rule_solve_until((false, false), Some(aimed_difficulty));
+                solved_carpet.rule_solve_until_per_sudoku((false, false), &max_difficulties);
                 carpet.difficulty = solved_carpet.difficulty;
                 carpet.difficulty_score = solved_carpet.difficulty_score;
+                for (sudoku, solved_sudoku) in carpet.sudokus.iter_mut().zip(solved_carpet.sudokus)
+                {
+                    sudoku.set_difficulty(solved_sudoku.get_difficulty());
+                }
             }
 
             println!(
@@ -325,16 +435,19 @@ impl CarpetSudoku {
 
     fn _generate_from(
         &mut self,
-        aimed_difficulty: SudokuDifficulty,
+        aimed_difficulty: &CarpetDifficultyTarget,
         carpet_generation_input: &mut CarpetGenerationThreadInput,
         thread_should_stop: &Arc<Mutex<bool>>,
         already_explored_filled_cells: &Arc<Mutex<HashSet<Vec<bool>>>>,
         log_infos: &Arc<Mutex<CarpetGenerationLogInfos>>,
     ) {
-        // stop if a solution was found by another thread
-        if *thread_should_stop.lock().unwrap() {
+        // stop if a solution was found by another thread, or if the aimed difficulty seems out of reach
+        if *thread_should_stop.lock().unwrap()
+            || aimed_difficulty.is_given_up(&log_infos.lock().unwrap())
+        {
             return;
         }
+        let max_difficulties = aimed_difficulty.get_max_difficulties(self.sudokus.len());
 
         // skip if we are below the minimal filled cells
         if carpet_generation_input.cells_to_remove.len() < (2 * self.n2 - 1) {
@@ -409,7 +522,7 @@ impl CarpetSudoku {
 
             // if we can still solve the carpet
             let mut carpet = self.clone();
-            carpet.rule_solve_until((false, false), Some(aimed_difficulty));
+            carpet.rule_solve_until_per_sudoku((false, false), &max_difficulties);
             if carpet.is_filled() {
                 can_remove_a_cell = true;
                 // recurcively try to remove more cells
@@ -453,8 +566,8 @@ impl CarpetSudoku {
 
         // if we can solve the carpet and its the right difficulty...
         let mut verify_carpet = self.clone();
-        verify_carpet.rule_solve_until((false, false), Some(aimed_difficulty));
-        if !verify_carpet.is_filled() || verify_carpet.difficulty != aimed_difficulty {
+        verify_carpet.rule_solve_until_per_sudoku((false, false), &max_difficulties);
+        if !verify_carpet.is_filled() || !aimed_difficulty.is_reached(&verify_carpet) {
             let mut log_infos = log_infos.lock().unwrap();
             log_infos.wrong_difficulty_counter += 1;
             print!(
@@ -469,15 +582,22 @@ impl CarpetSudoku {
         }
 
         // and if we can't solve any of the sub carpets...
-        for sub_links in self.pattern.get_sub_links(self.n) {
+        let all_sub_links = match self.pattern {
+            CarpetPattern::Custom(_) => {
+                CarpetPattern::get_custom_sub_links(self.sudokus.len(), &self.links)
+            }
+            pattern => pattern.get_sub_links(self.n),
+        };
+        for sub_links in all_sub_links {
             // stop if a solution was found by another thread
             if *thread_should_stop.lock().unwrap() {
                 return;
             }
 
             let sub_sudokus = self.sudokus.clone();
-            let mut sub_carpet = CarpetSudoku::new_custom(self.n, sub_sudokus, sub_links);
-            sub_carpet.rule_solve_until((false, false), Some(aimed_difficulty));
+            let mut sub_carpet =
+                CarpetSudoku::new_custom(self.n, sub_sudokus, sub_links, self.layout.clone());
+            sub_carpet.rule_solve_until_per_sudoku((false, false), &max_difficulties);
             if sub_carpet.is_filled() {
                 let mut log_infos = log_infos.lock().unwrap();
                 log_infos.solvable_sub_carpet_counter += 1;
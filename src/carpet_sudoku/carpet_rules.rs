@@ -0,0 +1,241 @@
+use std::collections::{HashMap, HashSet};
+
+use super::{CarpetRule, CarpetSudoku};
+use crate::debug_only;
+use crate::simple_sudoku::{
+    Sudoku,
+    SudokuDifficulty::{self, *},
+    SudokuError,
+    SudokuGroups::*,
+};
+
+/// A cell of the carpet, identified by its first twin (sudoku_id, x, y)
+type GlobalCell = (usize, usize, usize);
+
+impl CarpetSudoku {
+    // the ids follow the ones of Sudoku::RULES
+    pub const CARPET_RULES: &'static [(usize, SudokuDifficulty, CarpetRule)] = &[
+        (58, Easy, CarpetSudoku::carpet_hidden_single),
+        (56, Medium, CarpetSudoku::carpet_locked_candidates),
+        (57, Hard, CarpetSudoku::carpet_x_wing),
+    ];
+
+    pub const fn get_rule_name_by_id(id: usize) -> &'static str {
+        match id {
+            56 => "Carpet Locked Candidates",
+            57 => "Carpet X-Wing",
+            58 => "Carpet Hidden Single",
+            id => Sudoku::get_rule_name_by_id(id),
+        }
+    }
+
+    pub fn get_rule_difficulty_by_id(id: usize) -> SudokuDifficulty {
+        Sudoku::RULES
+            .iter()
+            .map(|&(rule_id, difficulty, _)| (rule_id, difficulty))
+            .chain(
+                CarpetSudoku::CARPET_RULES
+                    .iter()
+                    .map(|&(rule_id, difficulty, _)| (rule_id, difficulty)),
+            )
+            .find(|&(rule_id, _)| rule_id == id)
+            .map_or(Unknown, |(_, difficulty)| difficulty)
+    }
+
+    // GLOBAL UNITS
+
+    fn get_global_cell(&self, sudoku_id: usize, x: usize, y: usize) -> GlobalCell {
+        self.get_twin_cells(sudoku_id, x, y)
+            .into_iter()
+            .min()
+            .unwrap()
+    }
+
+    /// Every row, column and square of every sudoku, twin cells being merged into the same global cell
+    fn get_global_units(&self) -> Vec<Vec<GlobalCell>> {
+        let mut units = Vec::new();
+        let mut seen_units = HashSet::new();
+        for (sudoku_id, sudoku) in self.sudokus.iter().enumerate() {
+            for group in sudoku.get_group(All) {
+                let mut unit = group
+                    .into_iter()
+                    .map(|(x, y)| self.get_global_cell(sudoku_id, x, y))
+                    .collect::<Vec<_>>();
+                unit.sort();
+                // squares shared by several sudokus are the same unit
+                if seen_units.insert(unit.clone()) {
+                    units.push(unit);
+                }
+            }
+        }
+        units
+    }
+
+    /// Cells seeing the global cell in at least one sudoku, without the cell itself
+    fn get_global_peers(&self, (sudoku_id, x, y): GlobalCell) -> HashSet<GlobalCell> {
+        self.get_global_cell_group(sudoku_id, x, y, All)
+            .into_iter()
+            .map(|(sudoku2, x2, y2)| self.get_global_cell(sudoku2, x2, y2))
+            .filter(|&cell| cell != (sudoku_id, x, y))
+            .collect()
+    }
+
+    fn has_global_possibility(&self, (sudoku_id, x, y): GlobalCell, value: usize) -> bool {
+        self.sudokus[sudoku_id].get_cell_value(x, y) == 0
+            && self.sudokus[sudoku_id]
+                .get_cell_possibilities(x, y)
+                .contains(&value)
+    }
+
+    /// The value is possible in the global cell only if it's possible in every one of its twins
+    fn has_twins_possibility(&self, (sudoku_id, x, y): GlobalCell, value: usize) -> bool {
+        self.get_twin_cells(sudoku_id, x, y)
+            .into_iter()
+            .all(|twin| self.has_global_possibility(twin, value))
+    }
+
+    fn remove_global_possibility(&mut self, (sudoku_id, x, y): GlobalCell, value: usize) -> bool {
+        let mut modified = false;
+        for (sudoku2, x2, y2) in self.get_twin_cells(sudoku_id, x, y) {
+            if self.sudokus[sudoku2]
+                .get_cell_possibilities_mut(x2, y2)
+                .remove(&value)
+            {
+                modified = true;
+            }
+        }
+        if modified {
+            debug_only!("sudoku {sudoku_id} ({x}, {y}): possibilité {value} supprimée");
+        }
+        modified
+    }
+
+    // RULES SOLVING
+    // THESE RULES WORK ON THE UNITS OF ALL THE SUDOKUS AT ONCE
+    // A RULE RETURN TRUE IF IT CHANGED SOMETHING
+
+    // règle 58: if a value can only be in one cell of a unit, once each cell keeps only the possibilities left in all its twins,
+    // the value is in that cell
+    fn carpet_hidden_single(&mut self) -> Result<bool, SudokuError> {
+        for unit in self.get_global_units() {
+            for value in 1..=self.n2 {
+                if unit
+                    .iter()
+                    .any(|&(sudoku_id, x, y)| self.sudokus[sudoku_id].get_cell_value(x, y) == value)
+                {
+                    continue;
+                }
+                let occurences = unit
+                    .iter()
+                    .filter(|&&cell| self.has_twins_possibility(cell, value))
+                    .collect::<Vec<_>>();
+                if let [&(sudoku_id, x, y)] = occurences[..] {
+                    debug_only!("sudoku {sudoku_id} ({x}, {y}): valeur {value} trouvée");
+                    self.set_value(sudoku_id, x, y, value)?;
+                    return Ok(true);
+                }
+            }
+        }
+        Ok(false)
+    }
+
+    // règle 56: if all the possibilities of a value in a unit see a cell, through any sudoku, the value can't be in that cell
+    fn carpet_locked_candidates(&mut self) -> Result<bool, SudokuError> {
+        let mut modified = false;
+        let mut peers: HashMap<GlobalCell, HashSet<GlobalCell>> = HashMap::new();
+        for unit in self.get_global_units() {
+            for value in 1..=self.n2 {
+                let occurences = unit
+                    .iter()
+                    .filter(|&&cell| self.has_global_possibility(cell, value))
+                    .cloned()
+                    .collect::<Vec<_>>();
+                if occurences.len() < 2 {
+                    continue;
+                }
+
+                let mut common_peers: Option<HashSet<GlobalCell>> = None;
+                for &cell in occurences.iter() {
+                    let cell_peers = peers
+                        .entry(cell)
+                        .or_insert_with(|| self.get_global_peers(cell));
+                    common_peers = Some(match common_peers {
+                        Some(common_peers) => {
+                            common_peers.intersection(cell_peers).cloned().collect()
+                        }
+                        None => cell_peers.clone(),
+                    });
+                }
+
+                for cell in common_peers.unwrap_or_default() {
+                    if self.remove_global_possibility(cell, value) {
+                        modified = true;
+                    }
+                }
+            }
+        }
+        Ok(modified)
+    }
+
+    // règle 57: two units of any sudokus having a value in exactly two cells each,
+    // if each cell of the first unit sees a different cell of the second one, the value is in one of the two diagonals,
+    // so it can be removed from the cells seeing both cells of a side
+    fn carpet_x_wing(&mut self) -> Result<bool, SudokuError> {
+        let mut modified = false;
+        let units = self.get_global_units();
+        let mut peers: HashMap<GlobalCell, HashSet<GlobalCell>> = HashMap::new();
+        for value in 1..=self.n2 {
+            let strong_links = units
+                .iter()
+                .filter_map(|unit| {
+                    let occurences = unit
+                        .iter()
+                        .filter(|&&cell| self.has_global_possibility(cell, value))
+                        .cloned()
+                        .collect::<Vec<_>>();
+                    if occurences.len() == 2 {
+                        Some((occurences[0], occurences[1]))
+                    } else {
+                        None
+                    }
+                })
+                .collect::<HashSet<_>>()
+                .into_iter()
+                .collect::<Vec<_>>();
+
+            for &(cell1, cell2) in strong_links.iter() {
+                for cell in [cell1, cell2] {
+                    peers
+                        .entry(cell)
+                        .or_insert_with(|| self.get_global_peers(cell));
+                }
+            }
+
+            for (i, &(a1, a2)) in strong_links.iter().enumerate() {
+                for &(b1, b2) in strong_links.iter().skip(i + 1) {
+                    let cells = [a1, a2, b1, b2];
+                    if cells.iter().collect::<HashSet<_>>().len() != 4 {
+                        continue;
+                    }
+                    for (b1, b2) in [(b1, b2), (b2, b1)] {
+                        if !peers[&a1].contains(&b1) || !peers[&a2].contains(&b2) {
+                            continue;
+                        }
+                        let eliminations = peers[&a1]
+                            .intersection(&peers[&b1])
+                            .chain(peers[&a2].intersection(&peers[&b2]))
+                            .filter(|cell| !cells.contains(cell))
+                            .cloned()
+                            .collect::<Vec<_>>();
+                        for cell in eliminations {
+                            if self.remove_global_possibility(cell, value) {
+                                modified = true;
+                            }
+                        }
+                    }
+                }
+            }
+        }
+        Ok(modified)
+    }
+}
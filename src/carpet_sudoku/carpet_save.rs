@@ -0,0 +1,419 @@
+use std::{
+    collections::{HashMap, HashSet},
+    iter::Peekable,
+};
+
+use super::{CarpetLayout, CarpetLinks, CarpetPattern, CarpetSudoku};
+use crate::parse_value;
+use crate::simple_sudoku::{Coords, Sudoku, SudokuDifficulty, SudokuError};
+
+impl CarpetSudoku {
+    /// Text describing the whole state of the carpet, randomized or not (see [`CarpetSudoku::parse_save_string`]):
+    /// one `key: value` line per field, then a `sudoku: i` section per sudoku with its constraints, board and possibilities
+    pub fn to_save_string(&self) -> String {
+        let (pattern, pattern_size) = self.pattern.to_db();
+        let mut lines = vec![
+            format!("n: {}", self.n),
+            format!("pattern: {pattern} {}", Self::option_to_save(pattern_size)),
+        ];
+        if let CarpetPattern::Custom(_) = self.pattern {
+            lines.push(format!(
+                "positions: {}",
+                Self::coords_to_save(self.layout.get_positions())
+            ));
+            lines.push(format!(
+                "wrap: {}",
+                self.layout
+                    .get_wrap()
+                    .map_or("-".to_string(), |wrap| Self::coords_to_save(&[wrap]))
+            ));
+            // the links may not come from the layout, they are saved as they are
+            lines.push(format!("sudokus: {}", self.sudokus.len()));
+            let mut links = self
+                .links
+                .iter()
+                .flat_map(|(&sudoku1, sudoku_links)| {
+                    sudoku_links
+                        .iter()
+                        .map(move |&(square1, sudoku2, square2)| {
+                            (sudoku1, square1, sudoku2, square2)
+                        })
+                })
+                .collect::<Vec<_>>();
+            links.sort();
+            lines.push(format!(
+                "links: {}",
+                links
+                    .into_iter()
+                    .map(|(sudoku1, square1, sudoku2, square2)| {
+                        format!("{sudoku1},{square1},{sudoku2},{square2}")
+                    })
+                    .collect::<Vec<_>>()
+                    .join(" ")
+            ));
+        }
+        lines.push(format!("difficulty: {}", self.difficulty as i16));
+        lines.push(format!("difficulty_score: {}", self.difficulty_score));
+        lines.push(format!("is_canonical: {}", self.is_canonical));
+        lines.push(format!("filled_board_hash: {}", self.filled_board_hash));
+
+        for (sudoku_id, sudoku) in self.sudokus.iter().enumerate() {
+            lines.push(format!("sudoku: {sudoku_id}"));
+            lines.push(format!("difficulty: {}", sudoku.get_difficulty() as i16));
+            lines.push(format!("is_canonical: {}", sudoku.is_canonical()));
+            lines.push(format!(
+                "canonical_filled_board_hash: {}",
+                sudoku.get_canonical_filled_board_hash()
+            ));
+            lines.push(format!(
+                "rows_swap: {}",
+                Self::swap_to_save(&sudoku.get_rows_swap())
+            ));
+            lines.push(format!(
+                "values_swap: {}",
+                Self::swap_to_save(&sudoku.get_values_swap())
+            ));
+            for constraint in sudoku.constraints_to_string_lines() {
+                lines.push(format!("constraint: {constraint}"));
+            }
+            for line in sudoku.get_board() {
+                lines.push(format!(
+                    "board: {}",
+                    line.iter()
+                        .map(|value| value.to_string())
+                        .collect::<Vec<_>>()
+                        .join(" ")
+                ));
+            }
+            // the possibilities may have been reduced by the rules, so they are saved too
+            for (y, line) in sudoku.get_possibility_board().iter().enumerate() {
+                for (x, possibilities) in line.iter().enumerate() {
+                    if sudoku.get_cell_value(x, y) != 0 {
+                        continue;
+                    }
+                    let mut possibilities = possibilities.iter().collect::<Vec<_>>();
+                    possibilities.sort();
+                    lines.push(format!(
+                        "possibilities: {x},{y}:{}",
+                        possibilities
+                            .into_iter()
+                            .map(|value| value.to_string())
+                            .collect::<Vec<_>>()
+                            .join(",")
+                    ));
+                }
+            }
+        }
+
+        lines.join("\n")
+    }
+
+    /// Rebuilds a carpet from [`CarpetSudoku::to_save_string`]
+    pub fn parse_save_string(string: &str) -> Result<Self, SudokuError> {
+        let mut lines = string
+            .lines()
+            .map(|line| line.trim())
+            .filter(|line| !line.is_empty())
+            .map(|line| {
+                line.split_once(':')
+                    .map(|(key, value)| (key, value.trim()))
+                    .ok_or_else(|| {
+                        SudokuError::ParseString((
+                            line.to_string(),
+                            "expected \"key: value\"".to_string(),
+                        ))
+                    })
+            })
+            .collect::<Result<Vec<_>, _>>()?
+            .into_iter()
+            .peekable();
+        let n = Self::next_save_parsed::<usize>(&mut lines, "n")?;
+        let pattern = {
+            let value = Self::next_save_value(&mut lines, "pattern")?;
+            let (pattern, pattern_size) = value.split_once(' ').ok_or_else(|| {
+                SudokuError::ParseString((value.clone(), "expected \"pattern size\"".to_string()))
+            })?;
+            CarpetPattern::from_db(
+                parse_value(&value, pattern)?,
+                Self::option_from_save(pattern_size)?,
+            )
+        };
+        let mut carpet = if let CarpetPattern::Custom(_) = pattern {
+            let positions =
+                Self::coords_from_save(&Self::next_save_value(&mut lines, "positions")?)?;
+            let wrap = match Self::next_save_value(&mut lines, "wrap")?.as_str() {
+                "-" => None,
+                wrap => Self::coords_from_save(wrap)?.first().cloned(),
+            };
+            let n_sudokus = Self::next_save_parsed::<usize>(&mut lines, "sudokus")?;
+            let links = Self::links_from_save(&Self::next_save_value(&mut lines, "links")?)?;
+            let mut carpet = Self::try_new_custom(
+                n,
+                (0..n_sudokus).map(|_| Sudoku::new(n)).collect(),
+                links,
+                CarpetLayout::new(positions, wrap),
+            )?;
+            carpet.pattern = pattern;
+            carpet
+        } else {
+            Self::new(n, pattern)
+        };
+        let difficulty = SudokuDifficulty::from(Self::next_save_parsed(&mut lines, "difficulty")?);
+        let difficulty_score = Self::next_save_parsed(&mut lines, "difficulty_score")?;
+        let is_canonical = Self::next_save_parsed(&mut lines, "is_canonical")?;
+        let filled_board_hash = Self::next_save_parsed(&mut lines, "filled_board_hash")?;
+
+        let mut sudokus_state = Vec::new();
+        for sudoku_id in 0..carpet.sudokus.len() {
+            let read_id = Self::next_save_parsed::<usize>(&mut lines, "sudoku")?;
+            if read_id != sudoku_id {
+                return Err(SudokuError::ParseString((
+                    format!("sudoku: {read_id}"),
+                    format!("expected the sudoku {sudoku_id}"),
+                )));
+            }
+            let sudoku_difficulty =
+                SudokuDifficulty::from(Self::next_save_parsed(&mut lines, "difficulty")?);
+            let sudoku_is_canonical = Self::next_save_parsed::<bool>(&mut lines, "is_canonical")?;
+            let canonical_filled_board_hash =
+                Self::next_save_parsed::<u64>(&mut lines, "canonical_filled_board_hash")?;
+            let rows_swap = Self::swap_from_save(&Self::next_save_value(&mut lines, "rows_swap")?)?;
+            let values_swap =
+                Self::swap_from_save(&Self::next_save_value(&mut lines, "values_swap")?)?;
+            let mut constraints = Vec::new();
+            while let Some(constraint) = Self::next_save_optional_value(&mut lines, "constraint") {
+                constraints.push(constraint);
+            }
+
+            let mut board = Vec::new();
+            for _ in 0..carpet.n2 {
+                let board_line = Self::next_save_value(&mut lines, "board")?;
+                let line = board_line
+                    .split_whitespace()
+                    .map(|value| parse_value::<usize>(&board_line, value))
+                    .collect::<Result<Vec<_>, _>>()?;
+                if line.len() != carpet.n2 || line.iter().any(|&value| value > carpet.n2) {
+                    return Err(SudokuError::ParseString((
+                        format!("{line:?}"),
+                        format!("expected {} values", carpet.n2),
+                    )));
+                }
+                board.push(line);
+            }
+
+            let mut possibilities = Vec::new();
+            while let Some(value) = Self::next_save_optional_value(&mut lines, "possibilities") {
+                let (cell, values) = value.split_once(':').ok_or_else(|| {
+                    SudokuError::ParseString((value.clone(), "expected \"x,y:values\"".to_string()))
+                })?;
+                let (x, y) = match Self::coords_from_save(cell)?.as_slice() {
+                    [(x, y)] if *x < carpet.n2 && *y < carpet.n2 => (*x, *y),
+                    _ => {
+                        return Err(SudokuError::ParseString((
+                            cell.to_string(),
+                            "expected a cell of the sudoku".to_string(),
+                        )))
+                    }
+                };
+                let values = values
+                    .split(',')
+                    .filter(|possibility| !possibility.is_empty())
+                    .map(|possibility| parse_value::<usize>(&value, possibility))
+                    .collect::<Result<HashSet<_>, _>>()?;
+                possibilities.push((x, y, values));
+            }
+
+            sudokus_state.push((
+                board,
+                constraints,
+                possibilities,
+                sudoku_difficulty,
+                sudoku_is_canonical,
+                canonical_filled_board_hash,
+                rows_swap,
+                values_swap,
+            ));
+        }
+        if let Some((key, value)) = lines.next() {
+            return Err(SudokuError::ParseString((
+                format!("{key}: {value}"),
+                "unexpected line after the last sudoku".to_string(),
+            )));
+        }
+
+        // the constraints come first, so that the values are checked against them
+        for (sudoku, (_, constraints, ..)) in carpet.sudokus.iter_mut().zip(sudokus_state.iter()) {
+            for constraint in constraints {
+                sudoku.parse_constraint_line(constraint)?;
+            }
+            if !constraints.is_empty() {
+                sudoku.reset_possibility_board()?;
+            }
+        }
+        // the values are set through the carpet, so that the twin cells stay equal
+        for (sudoku_id, (board, ..)) in sudokus_state.iter().enumerate() {
+            for (y, line) in board.iter().enumerate() {
+                for (x, &value) in line.iter().enumerate() {
+                    if value != 0 && carpet.sudokus[sudoku_id].get_cell_value(x, y) == 0 {
+                        carpet.set_value(sudoku_id, x, y, value)?;
+                    }
+                }
+            }
+        }
+        // the possibilities are restored once every value is set
+        for (
+            sudoku,
+            (
+                _,
+                _,
+                possibilities,
+                sudoku_difficulty,
+                sudoku_is_canonical,
+                hash,
+                rows_swap,
+                values_swap,
+            ),
+        ) in carpet.sudokus.iter_mut().zip(sudokus_state)
+        {
+            for (x, y, values) in possibilities {
+                *sudoku.get_cell_possibilities_mut(x, y) = values;
+            }
+            sudoku.set_difficulty(sudoku_difficulty);
+            sudoku.set_is_canonical(sudoku_is_canonical);
+            sudoku.set_canonical_filled_board_hash(hash);
+            sudoku.set_swaps(rows_swap, values_swap);
+        }
+        carpet.difficulty = difficulty;
+        carpet.difficulty_score = difficulty_score;
+        carpet.is_canonical = is_canonical;
+        carpet.filled_board_hash = filled_board_hash;
+
+        Ok(carpet)
+    }
+
+    // SAVE FORMAT
+
+    fn next_save_value<'a>(
+        lines: &mut Peekable<impl Iterator<Item = (&'a str, &'a str)>>,
+        expected_key: &str,
+    ) -> Result<String, SudokuError> {
+        match lines.next() {
+            Some((key, value)) if key == expected_key => Ok(value.to_string()),
+            Some((key, value)) => Err(SudokuError::ParseString((
+                format!("{key}: {value}"),
+                format!("expected the key \"{expected_key}\""),
+            ))),
+            None => Err(SudokuError::ParseString((
+                String::new(),
+                format!("missing the key \"{expected_key}\""),
+            ))),
+        }
+    }
+
+    fn next_save_optional_value<'a>(
+        lines: &mut Peekable<impl Iterator<Item = (&'a str, &'a str)>>,
+        expected_key: &str,
+    ) -> Option<String> {
+        lines
+            .next_if(|(key, _)| *key == expected_key)
+            .map(|(_, value)| value.to_string())
+    }
+
+    fn next_save_parsed<'a, T: std::str::FromStr>(
+        lines: &mut Peekable<impl Iterator<Item = (&'a str, &'a str)>>,
+        expected_key: &str,
+    ) -> Result<T, SudokuError>
+    where
+        T::Err: std::fmt::Display,
+    {
+        let value = Self::next_save_value(lines, expected_key)?;
+        parse_value(&format!("{expected_key}: {value}"), &value)
+    }
+
+    fn option_to_save(option: Option<i16>) -> String {
+        option.map_or("-".to_string(), |value| value.to_string())
+    }
+
+    fn option_from_save(string: &str) -> Result<Option<i16>, SudokuError> {
+        match string.trim() {
+            "-" => Ok(None),
+            value => parse_value(string, value).map(Some),
+        }
+    }
+
+    fn coords_to_save(coords: &[Coords]) -> String {
+        coords
+            .iter()
+            .map(|(x, y)| format!("{x},{y}"))
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+
+    fn coords_from_save(string: &str) -> Result<Vec<Coords>, SudokuError> {
+        string
+            .split_whitespace()
+            .map(|coords| {
+                let (x, y) = coords.split_once(',').ok_or_else(|| {
+                    SudokuError::ParseString((coords.to_string(), "expected \"x,y\"".to_string()))
+                })?;
+                Ok((parse_value(coords, x)?, parse_value(coords, y)?))
+            })
+            .collect()
+    }
+
+    fn links_from_save(string: &str) -> Result<CarpetLinks, SudokuError> {
+        let mut links = CarpetLinks::new();
+        for link in string.split_whitespace() {
+            match link
+                .split(',')
+                .map(|value| parse_value::<usize>(link, value))
+                .collect::<Result<Vec<_>, _>>()?
+                .as_slice()
+            {
+                &[sudoku1, square1, sudoku2, square2] => {
+                    links
+                        .entry(sudoku1)
+                        .or_default()
+                        .insert((square1, sudoku2, square2));
+                }
+                _ => {
+                    return Err(SudokuError::ParseString((
+                        link.to_string(),
+                        "expected \"sudoku1,square1,sudoku2,square2\"".to_string(),
+                    )))
+                }
+            }
+        }
+        Ok(links)
+    }
+
+    fn swap_to_save(swap: &HashMap<usize, Coords>) -> String {
+        let mut swap = swap.iter().collect::<Vec<_>>();
+        swap.sort();
+        swap.into_iter()
+            .map(|(key, (to, from))| format!("{key}:{to},{from}"))
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+
+    fn swap_from_save(string: &str) -> Result<HashMap<usize, Coords>, SudokuError> {
+        string
+            .split_whitespace()
+            .map(|entry| {
+                let (key, coords) = entry.split_once(':').ok_or_else(|| {
+                    SudokuError::ParseString((
+                        entry.to_string(),
+                        "expected \"key:to,from\"".to_string(),
+                    ))
+                })?;
+                match Self::coords_from_save(coords)?.as_slice() {
+                    [coords] => Ok((parse_value(entry, key)?, *coords)),
+                    _ => Err(SudokuError::ParseString((
+                        entry.to_string(),
+                        "expected \"key:to,from\"".to_string(),
+                    ))),
+                }
+            })
+            .collect()
+    }
+}
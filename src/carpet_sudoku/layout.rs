@@ -0,0 +1,124 @@
+use super::{CarpetLayout, CarpetLinks, RawLink};
+use crate::simple_sudoku::{Coords, SudokuError};
+use std::collections::HashMap;
+
+impl CarpetLayout {
+    pub fn new(positions: Vec<Coords>, wrap: Option<Coords>) -> Self {
+        Self { positions, wrap }
+    }
+
+    pub fn get_positions(&self) -> &Vec<Coords> {
+        &self.positions
+    }
+
+    pub fn get_wrap(&self) -> Option<Coords> {
+        self.wrap
+    }
+
+    pub fn get_n_sudokus(&self) -> usize {
+        self.positions.len()
+    }
+
+    /// Width and height (in cells) of the layout once drawn (without wrapping)
+    pub fn get_size(&self, n: usize) -> Coords {
+        let n2 = n * n;
+        self.positions
+            .iter()
+            .fold((0, 0), |(width, height), &(x, y)| {
+                (width.max(x + n2), height.max(y + n2))
+            })
+    }
+
+    /// First sudoku containing the global cell (x, y), with the cell coordinates in this sudoku
+    pub fn get_sudoku_at(&self, n: usize, x: usize, y: usize) -> Option<(usize, usize, usize)> {
+        let n2 = n * n;
+        self.positions
+            .iter()
+            .enumerate()
+            .find(|(_, &(x0, y0))| x >= x0 && x < x0 + n2 && y >= y0 && y < y0 + n2)
+            .map(|(i, &(x0, y0))| (i, x - x0, y - y0))
+    }
+
+    /// Distance from start to global along one axis, going through the wrap if needed
+    fn get_offset(global: usize, start: usize, wrap: Option<usize>) -> Option<usize> {
+        match wrap {
+            Some(wrap) => Some((global % wrap + wrap - start % wrap) % wrap),
+            None => global.checked_sub(start),
+        }
+    }
+
+    /// Link every square shared by two sudokus (overlaps which aren't aligned on squares are ignored)
+    pub fn get_raw_links(&self, n: usize) -> Vec<RawLink> {
+        let n2 = n * n;
+        let (wrap_x, wrap_y) = match self.wrap {
+            Some((wrap_x, wrap_y)) => (Some(wrap_x), Some(wrap_y)),
+            None => (None, None),
+        };
+
+        let mut links = Vec::new();
+        for (sudoku1, &(x1, y1)) in self.positions.iter().enumerate() {
+            for (sudoku2, &(x2, y2)) in self.positions.iter().enumerate().skip(sudoku1 + 1) {
+                for square1 in 0..n2 {
+                    let global_x = x1 + (square1 % n) * n;
+                    let global_y = y1 + (square1 / n) * n;
+                    let (Some(dx), Some(dy)) = (
+                        Self::get_offset(global_x, x2, wrap_x),
+                        Self::get_offset(global_y, y2, wrap_y),
+                    ) else {
+                        continue;
+                    };
+                    if dx >= n2 || dy >= n2 || dx % n != 0 || dy % n != 0 {
+                        continue;
+                    }
+                    links.push(((sudoku1, square1), (sudoku2, (dy / n) * n + dx / n)));
+                }
+            }
+        }
+        links
+    }
+
+    pub fn get_carpet_links(&self, n: usize) -> CarpetLinks {
+        let mut links: CarpetLinks = HashMap::new();
+
+        for ((sudoku1, square1), (sudoku2, square2)) in self.get_raw_links(n) {
+            links
+                .entry(sudoku1)
+                .or_default()
+                .insert((square1, sudoku2, square2));
+            links
+                .entry(sudoku2)
+                .or_default()
+                .insert((square2, sudoku1, square1));
+        }
+
+        links
+    }
+
+    ////////////////////////////////////////////////////////////////////////////////////////////////////////////////////////////////
+    //////////////////////////////////////////////////////////   PARSING   /////////////////////////////////////////////////////////
+    ////////////////////////////////////////////////////////////////////////////////////////////////////////////////////////////////
+
+    /// Parse a character map where each character is a square of the lattice,
+    /// and each letter marks the top left square of a sudoku (sudokus are numbered in reading order)
+    pub fn parse_string(n: usize, string: &str) -> Result<Self, SudokuError> {
+        let positions = string
+            .lines()
+            .enumerate()
+            .flat_map(|(y, line)| {
+                line.chars()
+                    .enumerate()
+                    .filter(|(_, c)| c.is_ascii_alphabetic())
+                    .map(move |(x, _)| (x * n, y * n))
+            })
+            .collect::<Vec<_>>();
+
+        if positions.is_empty() {
+            return Err(SudokuError::ParseString((
+                string.to_string(),
+                "no letter marking a sudoku in this pattern".to_string(),
+            )));
+        }
+
+        Ok(Self::new(positions, None))
+    }
+}
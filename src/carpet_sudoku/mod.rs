@@ -1,12 +1,45 @@
-use crate::simple_sudoku::{Coords, Sudoku, SudokuDifficulty};
+use crate::simple_sudoku::{Coords, Sudoku, SudokuDifficulty, SudokuError, SudokuStep};
 use std::collections::{HashMap, HashSet};
 
 pub mod carpet;
 mod carpet_generation;
+mod carpet_rules;
+mod carpet_save;
+pub mod layout;
 pub mod pattern;
 
 pub type CarpetLinks = HashMap<usize, HashSet<(usize, usize, usize)>>;
 type RawLink = (Coords, Coords);
+pub type CarpetRule = fn(&mut CarpetSudoku) -> Result<bool, SudokuError>;
+/// (sudoku_id, rule_id) of the rules applied in one call to rule_solve, sudoku_id is None for the carpet rules
+pub type CarpetSolveStep = Vec<(Option<usize>, usize)>;
+/// The cells changed by a step, with their value and possibilities before and after it
+pub type CarpetDiff = Vec<(
+    (usize, usize, usize),
+    (usize, HashSet<usize>),
+    (usize, HashSet<usize>),
+)>;
+
+/// One rule of a whole rule solve, with the cells it changes in the carpet
+#[derive(Clone)]
+pub struct CarpetTimelineStep {
+    /// None for the carpet rules
+    pub sudoku_id: Option<usize>,
+    pub rule: usize,
+    /// What the rule changes in its sudoku, None for the carpet rules
+    pub step: Option<SudokuStep>,
+    /// The carpet before the step gives the carpet after it once applied, see [`CarpetSudoku::apply_diff`]
+    pub diff: CarpetDiff,
+}
+
+/// Position of the top left cell of every sudoku on a global cell lattice,
+/// the lattice wraps around after `wrap` cells (for the torus patterns)
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct CarpetLayout {
+    positions: Vec<Coords>,
+    wrap: Option<Coords>,
+}
+
 #[derive(Clone)]
 pub struct CarpetSudoku {
     n: usize,
@@ -14,6 +47,7 @@ pub struct CarpetSudoku {
     pattern: CarpetPattern,
     sudokus: Vec<Sudoku>,
     links: CarpetLinks,
+    layout: CarpetLayout,
 
     difficulty: SudokuDifficulty,
     difficulty_score: usize,
@@ -32,5 +66,10 @@ pub enum CarpetPattern {
     DenseCarpet(usize),
     Torus(usize),
     DenseTorus,
+    Sohei,
+    Butterfly,
+    Flower,
+    Windmill,
+    Gattai8,
     Custom(usize),
 }
@@ -1,5 +1,7 @@
+use crate::simple_sudoku::SudokuError;
+
 use super::{
-    CarpetLinks,
+    CarpetLayout, CarpetLinks,
     CarpetPattern::{self, *},
     RawLink,
 };
@@ -23,22 +25,68 @@ impl CarpetPattern {
             DenseCarpet(size) => (5, Some(size as i16)),
             Torus(size) => (6, Some(size as i16)),
             DenseTorus => (7, None),
-            Custom(_) => panic!("Custom pattern not supported in DB"),
+            Sohei => (8, None),
+            Butterfly => (9, None),
+            Flower => (10, None),
+            Windmill => (11, None),
+            Gattai8 => (12, None),
+            Custom(size) => (13, Some(size as i16)),
+        }
+    }
+
+    /// Same as [`CarpetPattern::to_db`], for the canonical carpet tables:
+    /// they only hold the known patterns, a custom layout can't be rebuilt from its id
+    pub fn to_canonical_db(&self) -> Result<(i16, Option<i16>), SudokuError> {
+        if let Custom(_) = self {
+            return Err(SudokuError::WrongInput(format!(
+                "pattern {self}: the canonical carpets can't hold a custom layout"
+            )));
         }
+        Ok(self.to_db())
+    }
+
+    /// Same as [`CarpetPattern::try_from_db`], refusing the custom layouts like [`CarpetPattern::to_canonical_db`]
+    pub fn try_from_canonical_db(
+        pattern: i16,
+        pattern_size: Option<i16>,
+    ) -> Result<Self, SudokuError> {
+        let pattern = Self::try_from_db(pattern, pattern_size)?;
+        pattern.to_canonical_db()?;
+        Ok(pattern)
     }
 
     pub fn from_db(pattern: i16, pattern_size: Option<i16>) -> Self {
-        match (pattern, pattern_size) {
+        Self::try_from_db(pattern, pattern_size).unwrap_or_else(|error| panic!("{error}"))
+    }
+
+    /// Same as [`CarpetPattern::from_db`], for ids read from a file or a table that may hold anything
+    pub fn try_from_db(pattern: i16, pattern_size: Option<i16>) -> Result<Self, SudokuError> {
+        let size = |size: i16| {
+            usize::try_from(size).map_err(|_| {
+                SudokuError::WrongInput(format!("pattern size {size} should be positive"))
+            })
+        };
+        Ok(match (pattern, pattern_size) {
             (0, None) => Simple,
             (1, None) => Samurai,
-            (2, Some(n)) => Diagonal(n as usize),
-            (3, Some(n)) => DenseDiagonal(n as usize),
-            (4, Some(n)) => Carpet(n as usize),
-            (5, Some(n)) => DenseCarpet(n as usize),
-            (6, Some(n)) => Torus(n as usize),
+            (2, Some(n)) => Diagonal(size(n)?),
+            (3, Some(n)) => DenseDiagonal(size(n)?),
+            (4, Some(n)) => Carpet(size(n)?),
+            (5, Some(n)) => DenseCarpet(size(n)?),
+            (6, Some(n)) => Torus(size(n)?),
             (7, None) => DenseTorus,
-            (a, b) => panic!("pattern:{a} & pattern_size:{:?} not recognized !", b),
-        }
+            (8, None) => Sohei,
+            (9, None) => Butterfly,
+            (10, None) => Flower,
+            (11, None) => Windmill,
+            (12, None) => Gattai8,
+            (13, Some(n)) => Custom(size(n)?),
+            (a, b) => {
+                return Err(SudokuError::WrongInput(format!(
+                    "pattern:{a} & pattern_size:{b:?} not recognized !"
+                )))
+            }
+        })
     }
 
     pub fn iter() -> impl Iterator<Item = CarpetPattern> {
@@ -51,6 +99,11 @@ impl CarpetPattern {
             DenseCarpet(2),
             Torus(2),
             DenseTorus,
+            Sohei,
+            Butterfly,
+            Flower,
+            Windmill,
+            Gattai8,
         ]
         .into_iter()
     }
@@ -58,7 +111,9 @@ impl CarpetPattern {
     pub fn get_n_sudokus(&self, n: usize) -> usize {
         match *self {
             Simple => 1,
-            Samurai => 5,
+            Sohei | Butterfly => 4,
+            Samurai | Flower | Windmill => 5,
+            Gattai8 => 8,
             DenseTorus => n * n,
             Diagonal(size) | DenseDiagonal(size) | Custom(size) => size,
             Carpet(size) | DenseCarpet(size) | Torus(size) => size * size,
@@ -68,7 +123,9 @@ impl CarpetPattern {
     pub fn get_size(&self, n: usize) -> usize {
         match *self {
             Simple => 1,
-            Samurai => 5,
+            Sohei | Butterfly => 4,
+            Samurai | Flower | Windmill => 5,
+            Gattai8 => 8,
             DenseTorus => n,
             Diagonal(size) | DenseDiagonal(size) | Carpet(size) | DenseCarpet(size)
             | Torus(size) | Custom(size) => size,
@@ -92,7 +149,7 @@ impl CarpetPattern {
                     *size = 1;
                 }
             }
-            Simple | Samurai | DenseTorus => (),
+            Simple | Samurai | DenseTorus | Sohei | Butterfly | Flower | Windmill | Gattai8 => (),
         }
     }
 
@@ -100,245 +157,90 @@ impl CarpetPattern {
         match self {
             Diagonal(size) | Carpet(size) | DenseDiagonal(size) | DenseCarpet(size)
             | Torus(size) | Custom(size) => *size += rhs,
-            Simple | Samurai | DenseTorus => (),
+            Simple | Samurai | DenseTorus | Sohei | Butterfly | Flower | Windmill | Gattai8 => (),
         }
     }
 
-    pub fn get_raw_links(&self, n: usize) -> Vec<RawLink> {
-        let up_left = 0;
-        let up_right = n - 1;
-        let bottom_left = n * (n - 1);
-        let bottom_right = n * n - 1;
+    pub fn get_layout(&self, n: usize) -> CarpetLayout {
+        let n2 = n * n;
+        let grid = |size: usize, step: usize| {
+            (0..size * size)
+                .map(|i| ((i % size) * step, (i / size) * step))
+                .collect::<Vec<_>>()
+        };
 
         match *self {
-            Simple => vec![],
-            Samurai => vec![
-                ((0, up_left), (1, bottom_right)),
-                ((0, up_right), (2, bottom_left)),
-                ((0, bottom_left), (3, up_right)),
-                ((0, bottom_right), (4, up_left)),
-            ],
-            Diagonal(size) => (1..size)
-                .map(|i| ((i - 1, up_right), (i, bottom_left)))
-                .collect(),
-            Carpet(size) => {
-                let mut links = Vec::new();
-                for y in 0..size {
-                    for x in 0..size {
-                        let sudoku_i = y * size + x;
-
-                        if y < size - 1 {
-                            let bottom_i = (y + 1) * size + x;
-                            links.extend(
-                                (0..n).map(|k| {
-                                    ((sudoku_i, bottom_left + k), (bottom_i, up_left + k))
-                                }),
-                            );
-                        }
-
-                        if x < size - 1 {
-                            let right_i = y * size + x + 1;
-                            links.extend((0..n).map(|k| {
-                                ((sudoku_i, n * k + up_right), (right_i, n * k + up_left))
-                            }));
-                        }
-
-                        if y < size - 1 && x < size - 1 {
-                            let corner_i = (y + 1) * size + x + 1;
-                            links.push(((sudoku_i, bottom_right), (corner_i, up_left)));
-                        }
-
-                        if y < size - 1 && x > 0 {
-                            let corner_i = (y + 1) * size + x - 1;
-                            links.push(((sudoku_i, bottom_left), (corner_i, up_right)));
-                        }
-                    }
-                }
-                links
-            }
+            Simple => CarpetLayout::new(vec![(0, 0)], None),
+            Samurai => CarpetLayout::new(
+                vec![
+                    (n2 - n, n2 - n),
+                    (0, 0),
+                    (2 * n2 - 2 * n, 0),
+                    (0, 2 * n2 - 2 * n),
+                    (2 * n2 - 2 * n, 2 * n2 - 2 * n),
+                ],
+                None,
+            ),
+            Diagonal(size) => CarpetLayout::new(
+                (0..size)
+                    .map(|i| (i * (n2 - n), (size - i - 1) * (n2 - n)))
+                    .collect(),
+                None,
+            ),
+            DenseDiagonal(size) => CarpetLayout::new(
+                (0..size).map(|i| (i * n, (size - i - 1) * n)).collect(),
+                None,
+            ),
+            Carpet(size) => CarpetLayout::new(grid(size, n2 - n), None),
+            DenseCarpet(size) => CarpetLayout::new(grid(size, n), None),
             Torus(size) => {
-                let mut links = Vec::new();
-                for y in 0..size {
-                    for x in 0..size {
-                        let sudoku_i = y * size + x;
-
-                        let bottom_i = ((y + 1) % size) * size + x;
-                        links.extend(
-                            (0..n).map(|k| ((sudoku_i, bottom_left + k), (bottom_i, up_left + k))),
-                        );
-
-                        let right_i = y * size + (x + 1) % size;
-                        links.extend(
-                            (0..n).map(|k| {
-                                ((sudoku_i, n * k + up_right), (right_i, n * k + up_left))
-                            }),
-                        );
-
-                        let corner_i = ((y + 1) % size) * size + (x + 1) % size;
-                        links.push(((sudoku_i, bottom_right), (corner_i, up_left)));
-
-                        let corner_i =
-                            ((y + 1) % size) * size + if x == 0 { size - 1 } else { x - 1 };
-                        links.push(((sudoku_i, bottom_left), (corner_i, up_right)));
-                    }
-                }
-                links
+                CarpetLayout::new(grid(size, n2 - n), Some((size * (n2 - n), size * (n2 - n))))
             }
-            DenseDiagonal(size) => {
-                let mut links = Vec::new();
-                for sudoku_i in 0..size - 1 {
-                    for j in 1..n {
-                        let sudoku_j = sudoku_i + j;
-                        if sudoku_j >= size {
-                            continue;
-                        }
-                        for y1 in 0..n - j {
-                            let y2 = y1 + j;
-                            for x1 in j..n {
-                                let x2 = x1 - j;
-                                links.push(((sudoku_i, (y1 * n) + x1), (sudoku_j, (y2 * n) + x2)));
-                            }
-                        }
-                    }
-                }
-                links
-            }
-            DenseCarpet(size) => {
-                let mut links = Vec::new();
-                for y in 0..size {
-                    for x in 0..size {
-                        let sudoku_i = y * size + x;
-
-                        for dx in 1..n {
-                            if x + dx >= size {
-                                continue;
-                            }
-                            let right_i = y * size + x + dx;
-                            for y1 in 0..n {
-                                let y2 = y1;
-                                for x1 in dx..n {
-                                    let x2 = x1 - dx;
-                                    links.push((
-                                        (sudoku_i, (y1 * n) + x1),
-                                        (right_i, (y2 * n) + x2),
-                                    ));
-                                }
-                            }
-                        }
-
-                        for dy in 1..n {
-                            if y + dy >= size {
-                                continue;
-                            }
-                            let bottom_i = (y + dy) * size + x;
-                            for y1 in dy..n {
-                                let y2 = y1 - dy;
-                                for x1 in 0..n {
-                                    let x2 = x1;
-                                    links.push((
-                                        (sudoku_i, (y1 * n) + x1),
-                                        (bottom_i, (y2 * n) + x2),
-                                    ));
-                                }
-                            }
-                        }
-
-                        for dy in 1..n {
-                            if y + dy >= size {
-                                continue;
-                            }
-                            for dx in 1..n {
-                                if x + dx >= size {
-                                    continue;
-                                }
-                                let corner_i = (y + dy) * size + x + dx;
-                                for y1 in dy..n {
-                                    let y2 = y1 - dy;
-                                    for x1 in dx..n {
-                                        let x2 = x1 - dx;
-                                        links.push((
-                                            (sudoku_i, (y1 * n) + x1),
-                                            (corner_i, (y2 * n) + x2),
-                                        ));
-                                    }
-                                }
-                            }
-                        }
-
-                        for dy in 1..n {
-                            if y + dy >= size {
-                                continue;
-                            }
-                            for dx in 1..n {
-                                if x < dx {
-                                    continue;
-                                }
-                                let corner_i = (y + dy) * size + x - dx;
-                                for y1 in dy..n {
-                                    let y2 = y1 - dy;
-                                    for x1 in 0..n - dx {
-                                        let x2 = x1 + dx;
-                                        links.push((
-                                            (sudoku_i, (y1 * n) + x1),
-                                            (corner_i, (y2 * n) + x2),
-                                        ));
-                                    }
-                                }
-                            }
-                        }
-                    }
-                }
-                links
-            }
-            DenseTorus => {
-                let original_links = DenseCarpet(n).get_raw_links(n);
-                let mut links = Vec::new();
-
-                for dy in 0..n {
-                    for dx in 0..n {
-                        links.extend(original_links.clone().into_iter().map(
-                            |((sudoku1, square1), (sudoku2, square2))| {
-                                let new_sudoku_x1 = ((sudoku1 % n) + dx) % n;
-                                let new_sudoku_y1 = ((sudoku1 / n) + dy) % n;
-
-                                let new_sudoku_x2 = ((sudoku2 % n) + dx) % n;
-                                let new_sudoku_y2 = ((sudoku2 / n) + dy) % n;
-
-                                (
-                                    (new_sudoku_y1 * n + new_sudoku_x1, square1),
-                                    (new_sudoku_y2 * n + new_sudoku_x2, square2),
-                                )
-                            },
-                        ));
-                    }
-                }
-
-                links
-            }
-            Custom(_) => vec![],
+            DenseTorus => CarpetLayout::new(grid(n, n), Some((n2, n2))),
+            Sohei => CarpetLayout::new(
+                vec![
+                    (n2 - n, 0),
+                    (0, n2 - n),
+                    (2 * n2 - 2 * n, n2 - n),
+                    (n2 - n, 2 * n2 - 2 * n),
+                ],
+                None,
+            ),
+            Butterfly => CarpetLayout::new(grid(2, n), None),
+            Flower => CarpetLayout::new(vec![(n, n), (n, 0), (0, n), (2 * n, n), (n, 2 * n)], None),
+            Windmill => CarpetLayout::new(
+                vec![
+                    (n2 - n, n2 - n),
+                    (n2 - 2 * n, 0),
+                    (2 * n2 - 2 * n, n2 - 2 * n),
+                    (n2, 2 * n2 - 2 * n),
+                    (0, n2),
+                ],
+                None,
+            ),
+            Gattai8 => CarpetLayout::new(
+                vec![
+                    (n2 - n, n2 - n),
+                    (3 * n2 - 3 * n, n2 - n),
+                    (0, 0),
+                    (2 * n2 - 2 * n, 0),
+                    (4 * n2 - 4 * n, 0),
+                    (0, 2 * n2 - 2 * n),
+                    (2 * n2 - 2 * n, 2 * n2 - 2 * n),
+                    (4 * n2 - 4 * n, 2 * n2 - 2 * n),
+                ],
+                None,
+            ),
+            Custom(_) => CarpetLayout::default(),
         }
     }
 
-    pub fn get_carpet_links(&self, n: usize) -> CarpetLinks {
-        let mut links: CarpetLinks = HashMap::new();
-
-        for ((sudoku1, square1), (sudoku2, square2)) in self.get_raw_links(n) {
-            links
-                .entry(sudoku1)
-                .and_modify(|sudoku1_links| {
-                    sudoku1_links.insert((square1, sudoku2, square2));
-                })
-                .or_insert_with(|| vec![(square1, sudoku2, square2)].into_iter().collect());
-
-            links
-                .entry(sudoku2)
-                .and_modify(|sudoku2_links| {
-                    sudoku2_links.insert((square2, sudoku1, square1));
-                })
-                .or_insert_with(|| vec![(square2, sudoku1, square1)].into_iter().collect());
-        }
+    pub fn get_raw_links(&self, n: usize) -> Vec<RawLink> {
+        self.get_layout(n).get_raw_links(n)
+    }
 
-        links
+    pub fn get_carpet_links(&self, n: usize) -> CarpetLinks {
+        self.get_layout(n).get_carpet_links(n)
     }
 
     pub fn get_sub_links(&self, n: usize) -> Vec<CarpetLinks> {
@@ -374,6 +276,11 @@ impl CarpetPattern {
         sub_links
     }
 
+    /// Sub links of a custom carpet, which can't be deduced from its pattern
+    pub fn get_custom_sub_links(n_sudokus: usize, links: &CarpetLinks) -> Vec<CarpetLinks> {
+        Self::_get_sub_links(n_sudokus, links, &mut HashSet::new())
+    }
+
     fn _get_sub_links(
         n_sudokus: usize,
         current_links: &CarpetLinks,
@@ -460,6 +367,11 @@ impl std::fmt::Display for CarpetPattern {
             DenseCarpet(size) => write!(f, "DenseCarpet({size})"),
             Torus(size) => write!(f, "Torus({size})"),
             DenseTorus => write!(f, "DenseTorus"),
+            Sohei => write!(f, "Sohei"),
+            Butterfly => write!(f, "Butterfly"),
+            Flower => write!(f, "Flower"),
+            Windmill => write!(f, "Windmill"),
+            Gattai8 => write!(f, "Gattai-8"),
             Custom(size) => write!(f, "Custom({size})"),
         }
     }
@@ -8,13 +8,16 @@ use diesel::{
     JoinOnDsl, PgConnection, PgExpressionMethods, QueryDsl, RunQueryDsl,
 };
 
-use crate::{carpet_sudoku::CarpetSudoku, simple_sudoku::Sudoku as SimpleSudoku};
+use crate::{
+    carpet_sudoku::CarpetSudoku,
+    simple_sudoku::{Sudoku as SimpleSudoku, SudokuError},
+};
 
 use super::{
     schema::{
         canonical_carpet_games::dsl::*, canonical_carpet_sudokus::dsl::*,
         canonical_carpets::dsl::*, canonical_sudoku_games::dsl::*,
-        canonical_sudoku_squares::dsl::*, canonical_sudokus::dsl::*,
+        canonical_sudoku_squares::dsl::*, canonical_sudokus::dsl::*, player_games::dsl::*,
     },
     *,
 };
@@ -23,6 +26,14 @@ define_sql_function! {
     fn random() -> Text;
 }
 
+/// 10 values per game, postgres accepts at most 65535 bind parameters per query
+const PLAYER_GAMES_CHUNK_SIZE: usize = 1000;
+
+/// The rows were read but don't make a valid carpet: the reason is kept for the caller
+fn invalid_rows(error: SudokuError) -> diesel::result::Error {
+    diesel::result::Error::DeserializationError(Box::new(error))
+}
+
 impl Database {
     pub fn connect() -> Option<Self> {
         dotenv::dotenv().ok();
@@ -89,7 +100,7 @@ impl Database {
             .order(random())
             .limit(canonical_number)
             .get_results::<DBCanonicalCarpet>(&mut self.connection)?;
-        db_carpets.sort_by(|a, b| a.carpet_filled_board_hash.cmp(&b.carpet_filled_board_hash));
+        db_carpets.sort_by_key(|db_carpet| db_carpet.carpet_filled_board_hash);
 
         let filled_board_hashes = db_carpets
             .iter()
@@ -391,6 +402,22 @@ impl Database {
             .get_results::<DBCanonicalCarpetGame>(&mut self.connection)
     }
 
+    /// The games already in the table (same player and end time) are skipped,
+    /// the games are sent by chunks to stay under the bind parameters limit of postgres
+    pub fn insert_ignore_multiple_player_games(
+        &mut self,
+        games: Vec<DBNewPlayerGame>,
+    ) -> Result<usize, diesel::result::Error> {
+        let mut inserted = 0;
+        for chunk in games.chunks(PLAYER_GAMES_CHUNK_SIZE) {
+            inserted += diesel::insert_into(player_games)
+                .values(chunk)
+                .on_conflict_do_nothing()
+                .execute(&mut self.connection)?;
+        }
+        Ok(inserted)
+    }
+
     ////////////////////////////////////////////////////////////////////////////////////////////////////////////////////////////////
     ///////////////////////////////////////////////////////   GET RANDOM   /////////////////////////////////////////////////////////
     ////////////////////////////////////////////////////////////////////////////////////////////////////////////////////////////////
@@ -438,7 +465,23 @@ impl Database {
             .order(random())
             .limit(1)
             .get_result::<DBCanonicalCarpet>(&mut self.connection)?;
+        self.load_canonical_carpet(db_carpet)
+    }
+
+    pub fn get_canonical_carpet(
+        &mut self,
+        carpet_hash: i64,
+    ) -> Result<CarpetSudoku, diesel::result::Error> {
+        let db_carpet = canonical_carpets
+            .find(carpet_hash)
+            .get_result::<DBCanonicalCarpet>(&mut self.connection)?;
+        self.load_canonical_carpet(db_carpet)
+    }
 
+    fn load_canonical_carpet(
+        &mut self,
+        db_carpet: DBCanonicalCarpet,
+    ) -> Result<CarpetSudoku, diesel::result::Error> {
         let (db_carpet_sudokus, db_sudokus): (Vec<_>, Vec<_>) = canonical_carpet_sudokus
             .left_join(canonical_sudokus.on(filled_board_hash.eq(carpet_sudoku_filled_board_hash)))
             .filter(carpet_sudoku_carpet_filled_board_hash.eq(db_carpet.carpet_filled_board_hash))
@@ -460,11 +503,8 @@ impl Database {
         {
             Err(diesel::result::Error::NotFound)
         } else {
-            Ok(CarpetSudoku::db_from_filled(
-                db_carpet,
-                db_carpet_sudokus,
-                db_sudokus,
-            ))
+            CarpetSudoku::db_from_filled(db_carpet, db_carpet_sudokus, db_sudokus)
+                .map_err(invalid_rows)
         }
     }
 
@@ -513,12 +553,8 @@ impl Database {
         {
             Err(diesel::result::Error::NotFound)
         } else {
-            Ok(CarpetSudoku::db_from_game(
-                game_info,
-                db_carpet,
-                db_carpet_sudokus,
-                db_sudokus,
-            ))
+            CarpetSudoku::db_from_game(game_info, db_carpet, db_carpet_sudokus, db_sudokus)
+                .map_err(invalid_rows)
         }
     }
 
@@ -622,12 +658,8 @@ impl Database {
         {
             Err(diesel::result::Error::NotFound)
         } else {
-            Ok(CarpetSudoku::db_from_game(
-                game_info,
-                db_carpet,
-                db_carpet_sudokus,
-                db_sudokus,
-            ))
+            CarpetSudoku::db_from_game(game_info, db_carpet, db_carpet_sudokus, db_sudokus)
+                .map_err(invalid_rows)
         }
     }
 
@@ -635,16 +667,84 @@ impl Database {
     //////////////////////////////////////////////////////////   OTHER   ///////////////////////////////////////////////////////////
     ////////////////////////////////////////////////////////////////////////////////////////////////////////////////////////////////
 
-    // TODO:
-    // pub fn construct_canonical_carpet(
-    //     &mut self,
-    //     n: i16,
-    //     pattern: CarpetPattern,
-    // ) -> Result<DBFilledCarpetData, SudokuError> {
-    //     let n_sudokus = pattern.get_n_sudokus();
-    //     let mut truc = canonical_sudokus
-    //         .left_join(canonical_sudoku_squares.on(square_filled_board_hash.eq(filled_board_hash)));
-    //     let raw_links = pattern.get_raw_links(n as usize);
-    //     todo!()
-    // }
+    pub fn get_canonical_carpet_hashes(&mut self) -> Result<Vec<i64>, diesel::result::Error> {
+        canonical_carpets
+            .select(carpet_filled_board_hash)
+            .load::<i64>(&mut self.connection)
+    }
+
+    /// Moves a canonical carpet with its sudokus and games to a new filled board hash.
+    /// If another carpet is already stored under that hash, it's the same carpet read in another orientation:
+    /// the moved one is deleted and its games are moved to the matching cells of the kept one.
+    /// Returns whether the carpet collided with a stored one
+    pub fn rehash_canonical_carpet(
+        &mut self,
+        old_hash: i64,
+        new_hash: i64,
+    ) -> Result<bool, diesel::result::Error> {
+        if old_hash == new_hash {
+            return Ok(false);
+        }
+        let moved_carpet = self.get_canonical_carpet(old_hash)?;
+        let kept_carpet = match self.get_canonical_carpet(new_hash) {
+            Ok(kept_carpet) => Some(kept_carpet),
+            Err(diesel::result::Error::NotFound) => None,
+            Err(err) => return Err(err),
+        };
+
+        self.connection.transaction(|connection| {
+            let old_carpet_sudokus = canonical_carpet_sudokus
+                .filter(carpet_sudoku_carpet_filled_board_hash.eq(old_hash));
+            let old_carpet_games =
+                canonical_carpet_games.filter(carpet_game_carpet_filled_board_hash.eq(old_hash));
+
+            let Some(kept_carpet) = kept_carpet else {
+                let mut db_carpet = canonical_carpets
+                    .find(old_hash)
+                    .get_result::<DBCanonicalCarpet>(connection)?;
+                db_carpet.carpet_filled_board_hash = new_hash;
+                diesel::insert_into(canonical_carpets)
+                    .values(&db_carpet)
+                    .execute(connection)?;
+                diesel::update(old_carpet_sudokus)
+                    .set(carpet_sudoku_carpet_filled_board_hash.eq(new_hash))
+                    .execute(connection)?;
+                diesel::update(old_carpet_games)
+                    .set(carpet_game_carpet_filled_board_hash.eq(new_hash))
+                    .execute(connection)?;
+                diesel::delete(canonical_carpets.find(old_hash)).execute(connection)?;
+                return Ok(false);
+            };
+
+            for game in old_carpet_games.load::<DBCanonicalCarpetGame>(connection)? {
+                let filled_cells = moved_carpet
+                    .move_filled_cells_to(&kept_carpet, &game.carpet_game_filled_cells)
+                    .ok_or_else(|| {
+                        invalid_rows(SudokuError::InvalidState(format!(
+                            "carpet {old_hash} isn't an orientation of carpet {new_hash}"
+                        )))
+                    })?;
+                diesel::update(canonical_carpet_games.find(game.carpet_game_id))
+                    .set((
+                        carpet_game_carpet_filled_board_hash.eq(new_hash),
+                        carpet_game_filled_cells.eq(filled_cells),
+                    ))
+                    .execute(connection)?;
+            }
+            diesel::delete(old_carpet_sudokus).execute(connection)?;
+            diesel::delete(canonical_carpets.find(old_hash)).execute(connection)?;
+            Ok(true)
+        })
+    }
+
+    /// Every game of the player, from the oldest
+    pub fn get_player_games(
+        &mut self,
+        player_id: i64,
+    ) -> Result<Vec<DBPlayerGame>, diesel::result::Error> {
+        player_games
+            .filter(player_game_player_id.eq(player_id))
+            .order(player_game_finished_at)
+            .load::<DBPlayerGame>(&mut self.connection)
+    }
 }
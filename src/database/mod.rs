@@ -117,6 +117,40 @@ impl From<DBCanonicalCarpetGame> for DBNewCanonicalCarpetGame {
     }
 }
 
+#[derive(Queryable, Selectable, Clone)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+#[diesel(table_name = schema::player_games)]
+pub struct DBPlayerGame {
+    pub player_game_id: i32,
+    pub player_game_player_id: i64,
+    pub player_game_finished_at: i64,
+    pub player_game_n: i16,
+    pub player_game_pattern: i16,
+    pub player_game_pattern_size: Option<i16>,
+    pub player_game_difficulty: i16,
+    pub player_game_time: i64,
+    pub player_game_mistakes: i16,
+    pub player_game_hints: i16,
+    pub player_game_won: bool,
+}
+
+#[derive(Insertable, Clone)]
+#[diesel(treat_none_as_default_value = false)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+#[diesel(table_name = schema::player_games)]
+pub struct DBNewPlayerGame {
+    pub player_game_player_id: i64,
+    pub player_game_finished_at: i64,
+    pub player_game_n: i16,
+    pub player_game_pattern: i16,
+    pub player_game_pattern_size: Option<i16>,
+    pub player_game_difficulty: i16,
+    pub player_game_time: i64,
+    pub player_game_mistakes: i16,
+    pub player_game_hints: i16,
+    pub player_game_won: bool,
+}
+
 pub type DBFilledCarpetData = (
     DBCanonicalCarpet,
     Vec<DBCanonicalCarpetSudoku>,
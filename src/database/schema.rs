@@ -55,6 +55,22 @@ diesel::table! {
     }
 }
 
+diesel::table! {
+    player_games (player_game_id) {
+        player_game_id -> Int4,
+        player_game_player_id -> Int8,
+        player_game_finished_at -> Int8,
+        player_game_n -> Int2,
+        player_game_pattern -> Int2,
+        player_game_pattern_size -> Nullable<Int2>,
+        player_game_difficulty -> Int2,
+        player_game_time -> Int8,
+        player_game_mistakes -> Int2,
+        player_game_hints -> Int2,
+        player_game_won -> Bool,
+    }
+}
+
 diesel::joinable!(canonical_carpet_games -> canonical_carpets (carpet_game_carpet_filled_board_hash));
 diesel::joinable!(canonical_carpet_sudokus -> canonical_carpets (carpet_sudoku_carpet_filled_board_hash));
 diesel::joinable!(canonical_carpet_sudokus -> canonical_sudokus (carpet_sudoku_filled_board_hash));
@@ -68,4 +84,5 @@ diesel::allow_tables_to_appear_in_same_query!(
     canonical_sudoku_games,
     canonical_sudoku_squares,
     canonical_sudokus,
+    player_games,
 );
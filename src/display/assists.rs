@@ -0,0 +1,244 @@
+use std::collections::HashSet;
+
+use macroquad::prelude::*;
+
+use super::display::{
+    ASSISTS, AUTO_REMOVE_NOTES, BACKGROUND_COLOR, DIGIT_COUNTS, EQUAL_DIGIT_COLOR,
+    FOREGROUND_COLOR, HIGHLIGHT_EQUAL_DIGITS, PLAY, SHOW_CONFLICTS, WRONG_COLOR,
+};
+use super::SudokuDisplay;
+use crate::simple_sudoku::SudokuGroups::All;
+
+/// The helps the player can turn on and off
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Assists {
+    /// Highlights every cell holding the digit of the selected cell, twins included
+    pub highlight_equal_digits: bool,
+    /// A wrong digit stays in its cell instead of costing a life, the repeated digits are shown in conflict
+    pub show_conflicts: bool,
+    /// Shows how many of each digit are left to place on the number buttons
+    pub digit_counts: bool,
+    /// Removes the notes of a digit from the group of the cell where it is placed
+    pub auto_remove_notes: bool,
+}
+
+impl Default for Assists {
+    fn default() -> Self {
+        Self {
+            highlight_equal_digits: true,
+            show_conflicts: false,
+            digit_counts: true,
+            auto_remove_notes: true,
+        }
+    }
+}
+
+impl SudokuDisplay {
+    pub(super) fn assists_btn(&mut self) {
+        if self.show_stats {
+            self.stats_btn();
+        }
+        if self.show_settings {
+            self.settings_btn();
+        }
+        self.show_assists = !self.show_assists;
+        self.update_assists_buttons();
+    }
+
+    pub(super) fn toggle_assist(&mut self, assist: &str) {
+        match assist {
+            HIGHLIGHT_EQUAL_DIGITS => {
+                self.assists.highlight_equal_digits = !self.assists.highlight_equal_digits
+            }
+            SHOW_CONFLICTS => self.assists.show_conflicts = !self.assists.show_conflicts,
+            DIGIT_COUNTS => self.assists.digit_counts = !self.assists.digit_counts,
+            AUTO_REMOVE_NOTES => self.assists.auto_remove_notes = !self.assists.auto_remove_notes,
+            _ => (),
+        }
+        self.update_assists_buttons();
+    }
+
+    /// The toggles are only shown with the panel, and clicked when their assist is on
+    pub(super) fn update_assists_buttons(&mut self) {
+        for button in self.button_list.iter_mut() {
+            let enabled = match button.text.as_str() {
+                ASSISTS => {
+                    button.set_clicked(self.show_assists);
+                    continue;
+                }
+                HIGHLIGHT_EQUAL_DIGITS => self.assists.highlight_equal_digits,
+                SHOW_CONFLICTS => self.assists.show_conflicts,
+                DIGIT_COUNTS => self.assists.digit_counts,
+                AUTO_REMOVE_NOTES => self.assists.auto_remove_notes,
+                _ => continue,
+            };
+            button.set_clicked(enabled);
+            button.set_enabled(self.show_assists);
+        }
+    }
+
+    /// With the conflicts shown, a value in conflict can be replaced by another one, or removed with its own button
+    pub(super) fn is_replaceable_cell(&self, cell: (usize, usize, usize)) -> bool {
+        self.assists.show_conflicts && self.mode == PLAY && self.is_conflict_cell(cell)
+    }
+
+    /// Whether another cell of a group of the cell, its twins apart, holds the same value
+    fn is_conflict_cell(&self, (sudoku_id, x, y): (usize, usize, usize)) -> bool {
+        let value = self.carpet.get_cell_value(sudoku_id, x, y);
+        if value == 0 {
+            return false;
+        }
+        let twin_cells = self.carpet.get_twin_cells(sudoku_id, x, y);
+        self.carpet
+            .get_global_cell_group(sudoku_id, x, y, All)
+            .into_iter()
+            .any(|(sudoku2, x2, y2)| {
+                !twin_cells.contains(&(sudoku2, x2, y2))
+                    && self.carpet.get_cell_value(sudoku2, x2, y2) == value
+            })
+    }
+
+    /// The cells whose value is repeated in one of their groups, with their twins,
+    /// found from the board only so that they don't tell the solution
+    pub(super) fn get_conflict_cells(&self) -> HashSet<(usize, usize, usize)> {
+        let mut conflicts = HashSet::new();
+        if !self.assists.show_conflicts || self.mode != PLAY {
+            return conflicts;
+        }
+        let n2 = self.carpet.get_n2();
+        for sudoku_id in 0..self.carpet.get_n_sudokus() {
+            for y in 0..n2 {
+                for x in 0..n2 {
+                    if !conflicts.contains(&(sudoku_id, x, y))
+                        && self.is_conflict_cell((sudoku_id, x, y))
+                    {
+                        conflicts.extend(self.carpet.get_twin_cells(sudoku_id, x, y));
+                    }
+                }
+            }
+        }
+        conflicts
+    }
+
+    /// The cells with the same digit as the selected cell, and the cells in conflict
+    pub(super) fn draw_assists_highlights(
+        &self,
+        sudoku_i: usize,
+        (sudoku_x_offset, sudoku_y_offset): (f32, f32),
+        conflicts: &HashSet<(usize, usize, usize)>,
+    ) {
+        let selected_value = match self.selected_cell {
+            Some((sudoku_id, x, y)) if self.assists.highlight_equal_digits => {
+                self.carpet.get_cell_value(sudoku_id, x, y)
+            }
+            _ => 0,
+        };
+        for (y, line) in self.carpet.get_sudokus()[sudoku_i]
+            .get_board()
+            .iter()
+            .enumerate()
+        {
+            for (x, &value) in line.iter().enumerate() {
+                let color = if conflicts.contains(&(sudoku_i, x, y)) {
+                    WRONG_COLOR
+                } else if value != 0 && value == selected_value {
+                    EQUAL_DIGIT_COLOR
+                } else {
+                    continue;
+                };
+                draw_rectangle(
+                    (x as f32) * self.pixel_per_cell + sudoku_x_offset,
+                    (y as f32) * self.pixel_per_cell + sudoku_y_offset,
+                    self.pixel_per_cell,
+                    self.pixel_per_cell,
+                    Color::from_hex(color),
+                );
+            }
+        }
+    }
+
+    /// How many times each digit is still missing, a cell shared by several sudokus counts once
+    pub fn get_remaining_digits(&self) -> Vec<usize> {
+        let n2 = self.carpet.get_n2();
+        let mut unique_cells = 0;
+        let mut placed = vec![0; n2 + 1];
+        for sudoku_id in 0..self.carpet.get_n_sudokus() {
+            for y in 0..n2 {
+                for x in 0..n2 {
+                    let twin_cells = self.carpet.get_twin_cells(sudoku_id, x, y);
+                    if twin_cells.iter().any(|&twin| twin < (sudoku_id, x, y)) {
+                        continue;
+                    }
+                    unique_cells += 1;
+                    placed[self.carpet.get_cell_value(sudoku_id, x, y)] += 1;
+                }
+            }
+        }
+        (1..=n2)
+            .map(|value| (unique_cells / n2).saturating_sub(placed[value]))
+            .collect()
+    }
+
+    /// The number of missing digits in the corner of each number button
+    pub(super) fn draw_digit_counts(&self, font: &Font) {
+        if !self.assists.digit_counts || self.mode != PLAY {
+            return;
+        }
+        let remaining = self.get_remaining_digits();
+        for button in self.button_list.iter() {
+            let Ok(value) = button.text.parse::<usize>() else {
+                continue;
+            };
+            let Some(&count) = remaining.get(value.wrapping_sub(1)) else {
+                continue;
+            };
+            let text = count.to_string();
+            let font_size = (button.height() / 5.) as u16;
+            let size = measure_text(&text, Some(font), font_size, 1.);
+            draw_text_ex(
+                &text,
+                button.x() + button.width() - size.width - button.width() / 12.,
+                button.y() + size.height + button.height() / 12.,
+                TextParams {
+                    font: Some(font),
+                    font_size,
+                    color: Color::from_hex(FOREGROUND_COLOR),
+                    ..Default::default()
+                },
+            );
+        }
+    }
+
+    /// The panel over the grid, its toggles are drawn again above it
+    pub(super) async fn draw_assists_panel(&self, font: &Font) {
+        if !self.show_assists {
+            return;
+        }
+        draw_rectangle(
+            self.x_offset,
+            self.y_offset,
+            self.grid_size,
+            self.grid_size,
+            Color::from_hex(BACKGROUND_COLOR),
+        );
+        let font_size = self.grid_size / 30.;
+        draw_text_ex(
+            "Assists",
+            self.x_offset + self.grid_size / 20.,
+            self.y_offset + font_size * 2.,
+            TextParams {
+                font: Some(font),
+                font_size: font_size as u16,
+                color: Color::from_hex(FOREGROUND_COLOR),
+                ..Default::default()
+            },
+        );
+        for button in self.button_list.iter() {
+            if let HIGHLIGHT_EQUAL_DIGITS | SHOW_CONFLICTS | DIGIT_COUNTS | AUTO_REMOVE_NOTES =
+                button.text.as_str()
+            {
+                button.draw(font.clone()).await;
+            }
+        }
+    }
+}
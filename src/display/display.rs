@@ -1,11 +1,17 @@
 use crate::carpet_sudoku::{CarpetPattern, CarpetSudoku};
 #[cfg(feature = "database")]
 use crate::database::Database;
-use crate::simple_sudoku::{Coords, Sudoku, SudokuDifficulty, SudokuGroups::*};
-
+use crate::simple_sudoku::{
+    Coords, SudokuDifficulty, SudokuGroups::*, SudokuOutsideClue, SudokuParity, SudokuRelation,
+};
+
+use super::assists::Assists;
+use super::history::History;
+use super::marks::MarkMode;
+use super::save::{AUTOSAVE_DELAY_SECS, STATS_FILE};
+use super::settings::Settings;
 use super::{Button, ButtonFunction, SudokuDisplay};
-use ::rand::rng;
-use ::rand::seq::IteratorRandom;
+use crate::player_stats::PlayerStats;
 use macroquad::prelude::*;
 use std::collections::{HashMap, HashSet};
 use std::rc::Rc;
@@ -14,14 +20,26 @@ use std::time::{Duration, Instant};
 
 pub const PLAY: &str = "Play";
 pub const ANALYSE: &str = "Analyse";
+pub const EDIT: &str = "Edit";
 pub const SOLVE_ONCE: &str = "Solve\nonce";
 pub const SOLVE: &str = "Solve";
 pub const HINT: &str = "Hint";
 
 pub const NOTE: &str = "Note";
 pub const FILL_NOTES: &str = "Fill\nnotes";
+pub const CORNER_MARKS: &str = "Corner";
+pub const CENTRE_MARKS: &str = "Centre";
+pub const CELL_COLOR: &str = "Color";
 pub const UNDO: &str = "Undo";
+pub const REDO: &str = "Redo";
 pub const REVERT_SOLVE: &str = "Revert\nsolve";
+pub const PREVIOUS_STEP: &str = "<";
+pub const PLAY_SOLVE: &str = "Play\nsteps";
+pub const NEXT_STEP: &str = ">";
+pub const SLOWER: &str = "Slower";
+pub const FASTER: &str = "Faster";
+pub const EXPORT: &str = "Export";
+pub const EXPORT_DB: &str = "Export\nto db";
 
 pub const NEW_GAME: &str = "New game";
 pub const CANCEL: &str = "Cancel";
@@ -31,14 +49,40 @@ pub const DECREASE: &str = "_";
 pub const CREATE: &str = "Create";
 pub const BROWSE: &str = "Browse";
 pub const COLOR_INDICATOR: &str = "COULEUR";
+pub const SAVE: &str = "Save";
+pub const LOAD: &str = "Load";
+pub const STATS: &str = "Stats";
+pub const ASSISTS: &str = "Assists";
+pub const HIGHLIGHT_EQUAL_DIGITS: &str = "Highlight equal digits";
+pub const SHOW_CONFLICTS: &str = "Show conflicts instead of losing lifes";
+pub const DIGIT_COUNTS: &str = "Show the digits left to place";
+pub const AUTO_REMOVE_NOTES: &str = "Remove the notes of placed digits";
+pub const SETTINGS: &str = "Settings";
+pub const SETTING_GRID_SIZE: &str = "Grid size";
+pub const SETTING_LIFES: &str = "Lifes";
+pub const SETTING_WINDOW_SIZE: &str = "Window size";
+pub const SETTING_FONT: &str = "Font";
+pub const SETTING_COLORS: &str = "Colors";
+pub const SETTING_KEY_REPEAT: &str = "Key repeat delay";
 
 pub const BACKGROUND_COLOR: u32 = 0xffffff;
 pub const HOVERED_COLOR: u32 = 0xf1f5f9;
 pub const SELECTED_COLOR: u32 = 0xe4ebf2;
 pub const GROUP_COLOR: u32 = 0xc2ddf8;
 pub const WRONG_COLOR: u32 = 0xed8f98;
+pub const EQUAL_DIGIT_COLOR: u32 = 0x9cc3ea;
 pub const LINE_COLOR: u32 = 0x444444;
 pub const FOREGROUND_COLOR: u32 = 0x000000;
+pub const LINE_CONSTRAINT_COLOR: u32 = 0xc8c8c8;
+pub const PARITY_COLOR: u32 = 0xdcdcdc;
+pub const MINIMAP_VIEW_COLOR: u32 = 0xdb3425;
+pub const HINT_UNIT_COLOR: u32 = 0xfbefc4;
+pub const HINT_PATTERN_COLOR: u32 = 0xf4d368;
+pub const HINT_ELIMINATION_COLOR: u32 = 0xc62828;
+pub const EDITOR_SOLUTION_COLOR: u32 = 0xb4b4b4;
+
+pub const MAX_ZOOM: f32 = 8.0;
+pub const ZOOM_STEP: f32 = 1.2;
 
 pub const COLORS: [u32; 12] = [
     0xffffff, 0xc1c1c1, 0xdb3425, 0xee7930, 0xfbe54d, 0x5cc93b, 0x75fb9c, 0x4faff9, 0x5552ff,
@@ -46,7 +90,8 @@ pub const COLORS: [u32; 12] = [
 ];
 
 impl SudokuDisplay {
-    pub async fn new(n: usize, font: Font) -> Self {
+    pub async fn new(settings: Settings, font: Font) -> Self {
+        let n = settings.n;
         let max_height = screen_height() * 1.05;
         let max_width = screen_width() * 1.05;
         let scale_factor = 1.0;
@@ -59,24 +104,32 @@ impl SudokuDisplay {
         let mode = PLAY.to_string();
         let analyse_text = vec!["Ready to analyze".to_string()];
         let hint_text = String::new();
-        let history = Vec::new();
+        let history = History::new();
         let player_pboard = vec![
             vec![vec![HashMap::new(); carpet.get_n2()]; carpet.get_n2()];
             carpet.get_n_sudokus()
         ];
-        let selected_color = COLORS[0];
+        let player_marks = SudokuDisplay::new_player_marks(carpet.get_n2(), carpet.get_n_sudokus());
+        let selected_color = settings.colors[0];
         let correction_board =
             vec![vec![vec![0; carpet.get_n2()]; carpet.get_n2()]; carpet.get_n_sudokus()];
         let note = true;
         let mut button_list = Vec::new();
         let mut buttons_action: HashMap<String, ButtonFunction> = HashMap::new();
-        let lifes = 3;
+        let lifes = settings.lifes;
         let wrong_cell = Arc::new(Mutex::new(None));
         let wrong_cell_handle = Arc::new(Mutex::new(None));
         let difficulty = SudokuDifficulty::Easy;
         let pattern: CarpetPattern = CarpetPattern::Simple;
         let pattern_list = CarpetPattern::iter().collect::<Vec<_>>();
         let torus_view = (0, 0);
+        let stats_path = Settings::get_data_path(STATS_FILE);
+        let stats = PlayerStats::load(&stats_path).unwrap_or_else(|error| {
+            eprintln!("SudokuDisplay Error: {error}");
+            PlayerStats::new(&stats_path)
+        });
+        let zoom = 1.0;
+        let view_offset = (0., 0.);
         #[cfg(feature = "database")]
         let cloud_texture = load_texture("res/icons/cloud.png").await.unwrap();
         #[cfg(feature = "database")]
@@ -122,6 +175,30 @@ impl SudokuDisplay {
         );
         button_list.push(button_analyse);
 
+        let button_save = Button {
+            x: 2.0 * b_padding + 2. * (b_padding + button_sizex),
+            y: 2.0 * b_padding,
+            width: button_sizex,
+            height: (button_sizey - b_padding) / 2.,
+            text: SAVE.to_string(),
+            scale_factor,
+            ..Default::default()
+        };
+        buttons_action.insert(SAVE.to_string(), Rc::new(Box::new(SudokuDisplay::save_btn)));
+        button_list.push(button_save);
+
+        let button_load = Button {
+            x: 2.0 * b_padding + 2. * (b_padding + button_sizex),
+            y: 2.0 * b_padding + (button_sizey + b_padding) / 2.,
+            width: button_sizex,
+            height: (button_sizey - b_padding) / 2.,
+            text: LOAD.to_string(),
+            scale_factor,
+            ..Default::default()
+        };
+        buttons_action.insert(LOAD.to_string(), Rc::new(Box::new(SudokuDisplay::load_btn)));
+        button_list.push(button_load);
+
         let game_button_sizex = x_offset - b_padding * 4.;
         let game_button_sizey = button_sizey * 0.9;
         let game_button_offsety = 4.0 * b_padding + button_sizey;
@@ -170,12 +247,16 @@ impl SudokuDisplay {
         // ================= Sudoku Pattern Buttons =================
         // ==========================================================
 
+        let pattern_button_sizey = game_button_sizey * 0.6;
         for (i, &pattern) in pattern_list.iter().enumerate() {
             let mut bouton = Button {
                 x: 2.0 * b_padding,
-                y: game_button_offsety + ((i + 1) as f32) * (b_padding + game_button_sizey),
+                y: game_button_offsety
+                    + game_button_sizey
+                    + b_padding
+                    + (i as f32) * (b_padding + pattern_button_sizey),
                 width: game_button_sizex,
-                height: game_button_sizey,
+                height: pattern_button_sizey,
                 text: pattern.to_string(),
                 scale_factor,
                 ..Default::default()
@@ -199,7 +280,9 @@ impl SudokuDisplay {
         let mut decrease_button = Button {
             x: 2.0 * b_padding,
             y: game_button_offsety
-                + ((pattern_list.len() + 1) as f32) * (b_padding + game_button_sizey),
+                + game_button_sizey
+                + b_padding
+                + (pattern_list.len() as f32) * (b_padding + pattern_button_sizey),
             width: game_button_sizex / 2.,
             height: game_button_sizey / 3.,
             text: decrease_string.clone(),
@@ -219,7 +302,9 @@ impl SudokuDisplay {
         let mut increase_button = Button {
             x: 2.0 * b_padding + game_button_sizex / 2.,
             y: game_button_offsety
-                + ((pattern_list.len() + 1) as f32) * (b_padding + game_button_sizey),
+                + game_button_sizey
+                + b_padding
+                + (pattern_list.len() as f32) * (b_padding + pattern_button_sizey),
             width: game_button_sizex / 2.,
             height: game_button_sizey / 3.,
             text: increase_string.clone(),
@@ -283,6 +368,7 @@ impl SudokuDisplay {
                 sudoku_display.set_mode_btn(false);
                 sudoku_display.set_new_game_btn(true);
                 sudoku_display.new_game(true, false);
+                sudoku_display.set_mode(EDIT);
             })),
         );
 
@@ -393,21 +479,36 @@ impl SudokuDisplay {
         let pad_x_offset = x_offset + grid_size + button_sizey;
         let pad_y_offset = y_offset + pad_size / 10.;
 
-        let button_note = Button {
-            x: pad_x_offset,
-            y: pad_y_offset,
-            width: pad_size / 3. - b_padding,
-            height: button_sizey,
-            text: NOTE.to_string(),
-            scale_factor,
-            clicked: note,
-            ..Default::default()
-        };
-        buttons_action.insert(
-            button_note.text.to_string(),
-            Rc::new(Box::new(SudokuDisplay::notes_btn)),
-        );
-        button_list.push(button_note);
+        // the note slot holds a button per mark mode
+        let mark_button_sizex = (pad_size / 3. - 2. * b_padding) / 2.;
+        let mark_button_sizey = (button_sizey - b_padding) / 2.;
+        for (i, (text, mark_mode)) in [
+            (NOTE, MarkMode::Candidate),
+            (CORNER_MARKS, MarkMode::Corner),
+            (CENTRE_MARKS, MarkMode::Centre),
+            (CELL_COLOR, MarkMode::CellColor),
+        ]
+        .into_iter()
+        .enumerate()
+        {
+            let button_mark = Button {
+                x: pad_x_offset + (i % 2) as f32 * (mark_button_sizex + b_padding),
+                y: pad_y_offset + (i / 2) as f32 * (mark_button_sizey + b_padding),
+                width: mark_button_sizex,
+                height: mark_button_sizey,
+                text: text.to_string(),
+                scale_factor,
+                clicked: note && mark_mode == MarkMode::Candidate,
+                ..Default::default()
+            };
+            buttons_action.insert(
+                text.to_string(),
+                Rc::new(Box::new(move |sudoku_display| {
+                    sudoku_display.mark_mode_btn(mark_mode);
+                })),
+            );
+            button_list.push(button_mark);
+        }
 
         let button_note_fill = Button {
             x: pad_x_offset + pad_size / 3.,
@@ -430,7 +531,7 @@ impl SudokuDisplay {
             x: pad_x_offset + 2. * pad_size / 3.,
             y: pad_y_offset,
             width: pad_size / 3. - b_padding,
-            height: button_sizey,
+            height: (button_sizey - b_padding) / 2.,
             text: UNDO.to_string(),
             scale_factor,
             ..Default::default()
@@ -441,6 +542,94 @@ impl SudokuDisplay {
         );
         button_list.push(button_undo);
 
+        let button_redo = Button {
+            x: pad_x_offset + 2. * pad_size / 3.,
+            y: pad_y_offset + (button_sizey + b_padding) / 2.,
+            width: pad_size / 3. - b_padding,
+            height: (button_sizey - b_padding) / 2.,
+            text: REDO.to_string(),
+            scale_factor,
+            ..Default::default()
+        };
+        buttons_action.insert(
+            button_redo.text.to_string(),
+            Rc::new(Box::new(SudokuDisplay::redo_btn)),
+        );
+        button_list.push(button_redo);
+
+        // ==========================================================
+        // ==================== Playback Buttons ====================
+        // ==========================================================
+        // analyse mode has no notes, the playback controls take their place
+        let playback_button_sizex = pad_size / 5. - b_padding;
+        for (i, text) in [PREVIOUS_STEP, PLAY_SOLVE, NEXT_STEP, SLOWER, FASTER]
+            .into_iter()
+            .enumerate()
+        {
+            let button_playback = Button {
+                x: pad_x_offset + i as f32 * (playback_button_sizex + b_padding),
+                y: pad_y_offset,
+                width: playback_button_sizex,
+                height: button_sizey,
+                text: text.to_string(),
+                scale_factor,
+                enabled: mode == ANALYSE,
+                ..Default::default()
+            };
+            button_list.push(button_playback);
+        }
+        buttons_action.insert(
+            PREVIOUS_STEP.to_string(),
+            Rc::new(Box::new(|sudoku_display| sudoku_display.step_btn(false))),
+        );
+        buttons_action.insert(
+            PLAY_SOLVE.to_string(),
+            Rc::new(Box::new(SudokuDisplay::play_solve_btn)),
+        );
+        buttons_action.insert(
+            NEXT_STEP.to_string(),
+            Rc::new(Box::new(|sudoku_display| sudoku_display.step_btn(true))),
+        );
+        buttons_action.insert(
+            SLOWER.to_string(),
+            Rc::new(Box::new(|sudoku_display| {
+                sudoku_display.playback_speed_btn(false)
+            })),
+        );
+        buttons_action.insert(
+            FASTER.to_string(),
+            Rc::new(Box::new(|sudoku_display| {
+                sudoku_display.playback_speed_btn(true)
+            })),
+        );
+
+        // ==========================================================
+        // ===================== Export Buttons =====================
+        // ==========================================================
+        // the editor has no notes either, its export buttons take the place of the fill notes one
+        for (i, text) in [EXPORT, EXPORT_DB].into_iter().enumerate() {
+            let button_export = Button {
+                x: pad_x_offset + pad_size / 3.,
+                y: pad_y_offset + i as f32 * (button_sizey + b_padding) / 2.,
+                width: pad_size / 3. - b_padding,
+                height: (button_sizey - b_padding) / 2.,
+                text: text.to_string(),
+                scale_factor,
+                enabled: false,
+                ..Default::default()
+            };
+            button_list.push(button_export);
+        }
+        buttons_action.insert(
+            EXPORT.to_string(),
+            Rc::new(Box::new(SudokuDisplay::export_btn)),
+        );
+        #[cfg(feature = "database")]
+        buttons_action.insert(
+            EXPORT_DB.to_string(),
+            Rc::new(Box::new(SudokuDisplay::export_db_btn)),
+        );
+
         // ==========================================================
         // ===================== Number Buttons =====================
         // ==========================================================
@@ -459,11 +648,10 @@ impl SudokuDisplay {
                     ..Default::default()
                 };
 
-                let button_id = button_list.len();
                 buttons_action.insert(
                     value1.to_string(),
                     Rc::new(Box::new(move |sudoku_display| {
-                        sudoku_display.value_btn(button_id, (x, y));
+                        sudoku_display.value_btn((x, y));
                     })),
                 );
 
@@ -474,7 +662,7 @@ impl SudokuDisplay {
         let life_button = Button {
             x: pad_x_offset,
             y: pad_y_offset + button_sizey + pad_size + b_padding,
-            width: pad_size - b_padding,
+            width: pad_size / 3. - b_padding,
             height: button_sizey,
             text: format!("Lifes: {lifes}"),
             scale_factor,
@@ -482,13 +670,126 @@ impl SudokuDisplay {
         };
         button_list.push(life_button);
 
+        let stats_button = Button {
+            x: pad_x_offset + pad_size / 3.,
+            y: pad_y_offset + button_sizey + pad_size + b_padding,
+            width: pad_size / 3. - b_padding,
+            height: button_sizey,
+            text: STATS.to_string(),
+            scale_factor,
+            ..Default::default()
+        };
+        buttons_action.insert(
+            STATS.to_string(),
+            Rc::new(Box::new(SudokuDisplay::stats_btn)),
+        );
+        button_list.push(stats_button);
+
+        let assists_button = Button {
+            x: pad_x_offset + 2. * pad_size / 3.,
+            y: pad_y_offset + button_sizey + pad_size + b_padding,
+            width: pad_size / 3. - b_padding,
+            height: (button_sizey - b_padding) / 2.,
+            text: ASSISTS.to_string(),
+            scale_factor,
+            ..Default::default()
+        };
+        buttons_action.insert(
+            ASSISTS.to_string(),
+            Rc::new(Box::new(SudokuDisplay::assists_btn)),
+        );
+        button_list.push(assists_button);
+
+        let settings_button = Button {
+            x: pad_x_offset + 2. * pad_size / 3.,
+            y: pad_y_offset + button_sizey + pad_size + b_padding + (button_sizey + b_padding) / 2.,
+            width: pad_size / 3. - b_padding,
+            height: (button_sizey - b_padding) / 2.,
+            text: SETTINGS.to_string(),
+            scale_factor,
+            ..Default::default()
+        };
+        buttons_action.insert(
+            SETTINGS.to_string(),
+            Rc::new(Box::new(SudokuDisplay::settings_btn)),
+        );
+        button_list.push(settings_button);
+
+        // ==========================================================
+        // ===================== Assists Buttons ====================
+        // ==========================================================
+
+        let assists = Assists::default();
+        for (i, (assist, enabled)) in [
+            (HIGHLIGHT_EQUAL_DIGITS, assists.highlight_equal_digits),
+            (SHOW_CONFLICTS, assists.show_conflicts),
+            (DIGIT_COUNTS, assists.digit_counts),
+            (AUTO_REMOVE_NOTES, assists.auto_remove_notes),
+        ]
+        .into_iter()
+        .enumerate()
+        {
+            let assist_button = Button {
+                x: x_offset + grid_size / 10.,
+                y: y_offset + grid_size / 8. + i as f32 * (button_sizey + b_padding),
+                width: grid_size * 0.8,
+                height: button_sizey * 0.8,
+                text: assist.to_string(),
+                scale_factor,
+                clicked: enabled,
+                enabled: false,
+                ..Default::default()
+            };
+            buttons_action.insert(
+                assist.to_string(),
+                Rc::new(Box::new(move |sudoku_display| {
+                    sudoku_display.toggle_assist(assist)
+                })),
+            );
+            button_list.push(assist_button);
+        }
+
+        // ==========================================================
+        // ==================== Settings Buttons ====================
+        // ==========================================================
+
+        for (i, setting) in [
+            SETTING_GRID_SIZE,
+            SETTING_LIFES,
+            SETTING_WINDOW_SIZE,
+            SETTING_FONT,
+            SETTING_COLORS,
+            SETTING_KEY_REPEAT,
+        ]
+        .into_iter()
+        .enumerate()
+        {
+            let setting_button = Button {
+                x: x_offset + grid_size / 10.,
+                y: y_offset + grid_size / 8. + i as f32 * (button_sizey + b_padding),
+                width: grid_size * 0.4,
+                height: button_sizey * 0.8,
+                text: setting.to_string(),
+                scale_factor,
+                enabled: false,
+                ..Default::default()
+            };
+            buttons_action.insert(
+                setting.to_string(),
+                Rc::new(Box::new(move |sudoku_display| {
+                    sudoku_display.change_setting(setting)
+                })),
+            );
+            button_list.push(setting_button);
+        }
+
         // ==========================================================
         // ===================== Color Buttons ======================
         // ==========================================================
 
-        let color_line = COLORS.len() / 2;
+        let color_line = settings.colors.len() / 2;
         let color_button_size = (pad_size - color_line as f32 * b_padding) / color_line as f32;
-        for (i, color) in COLORS.into_iter().enumerate() {
+        for (i, &color) in settings.colors.iter().enumerate() {
             let x = (i % color_line) as f32 * (b_padding + color_button_size);
             let y = (i / color_line) as f32 * (b_padding + color_button_size);
             let bouton_couleur = Button {
@@ -528,7 +829,7 @@ impl SudokuDisplay {
             height: color_button_size / 2.,
             text: COLOR_INDICATOR.to_string(),
             scale_factor,
-            background_color: Color::from_hex(COLORS[0]),
+            background_color: Color::from_hex(settings.colors[0]),
             draw_text: false,
             draw_border: true,
             ..Default::default()
@@ -551,22 +852,49 @@ impl SudokuDisplay {
             mode,
             analyse_text,
             hint_text,
+            hint: None,
+            playback: None,
+            editor: None,
             history,
             player_pboard,
+            player_marks,
+            mark_mode: MarkMode::default(),
+            selected_cells: HashSet::new(),
+            drag_selecting: false,
             selected_color,
             note,
             button_list,
             font,
             buttons_action,
             lifes,
+            timer: Duration::ZERO,
+            timer_start: None,
+            mistakes: 0,
+            hints_used: 0,
+            solve_steps: 0,
+            completed: false,
+            solved_by_assist: false,
+            completion_overlay: false,
+            stats,
+            show_stats: false,
+            assists,
+            show_assists: false,
+            settings,
+            show_settings: false,
             wrong_cell,
             wrong_cell_handle,
             difficulty,
             pattern,
             pattern_list,
             torus_view,
+            zoom,
+            view_offset,
+            drag_origin: None,
             correction_board,
             last_processed_keys: None,
+            last_autosave: Instant::now(),
+            focus_subscriber: macroquad::input::utils::register_input_subscriber(),
+            window_focused: true,
             #[cfg(feature = "database")]
             cloud_texture,
             #[cfg(feature = "database")]
@@ -582,21 +910,24 @@ impl SudokuDisplay {
 
     pub fn init(&mut self) {
         self.set_mode(PLAY);
-        self.selected_cell = None;
+        self.clear_selection();
         self.hovered_cell = None;
         if !self.note {
             self.notes_btn();
         }
-        self.lifes = 3;
+        self.lifes = self.settings.lifes;
+        self.reset_game_stats();
         self.player_pboard =
             vec![
                 vec![vec![HashMap::new(); self.carpet.get_n2()]; self.carpet.get_n2()];
                 self.carpet.get_n_sudokus()
             ];
-        self.history.clear();
-        self.hint_text.clear();
+        self.reset_player_marks();
+        self.clear_history();
+        self.clear_hint();
         self.analyse_text = vec!["Ready to analyze".to_string()];
         self.torus_view = (0, 0);
+        self.reset_view();
     }
 
     #[cfg(feature = "database")]
@@ -605,6 +936,8 @@ impl SudokuDisplay {
             if button.text.eq(BROWSE) && button.clickable != database.is_some() {
                 button.set_clickable(database.is_some());
                 self.database = database;
+                // the games finished while offline are sent once connected
+                self.sync_stats();
                 break;
             }
         }
@@ -737,6 +1070,7 @@ impl SudokuDisplay {
                     vec![vec![HashMap::new(); self.carpet.get_n2()]; self.carpet.get_n2()];
                     self.carpet.get_n_sudokus()
                 ];
+            self.reset_player_marks();
         }
 
         for button in self.button_list.iter_mut() {
@@ -747,6 +1081,12 @@ impl SudokuDisplay {
             }
         }
 
+        self.update_correction_board();
+        self.set_new_game_btn(true);
+    }
+
+    /// The values the rules find for the game, a wrong value is one that isn't there
+    pub(super) fn update_correction_board(&mut self) {
         let mut corrected_board = self.carpet.clone();
         while let Ok((true, _, _)) = corrected_board.rule_solve(None) {}
         self.correction_board = corrected_board
@@ -754,26 +1094,37 @@ impl SudokuDisplay {
             .iter()
             .map(|sudoku| sudoku.get_board().clone())
             .collect();
-        self.set_new_game_btn(true);
     }
 
     fn set_mode(&mut self, mode: &str) {
+        if self.mode == EDIT && mode != EDIT {
+            self.finish_editing();
+        }
         self.mode = mode.to_string();
         if self.note {
-            if let Some(action) = self.buttons_action.get(NOTE).cloned() {
-                action(self);
-            }
+            self.notes_btn();
         }
 
-        self.history.clear();
-        self.hint_text.clear();
+        self.clear_history();
+        self.clear_hint();
+        self.stop_playback();
+        if mode == EDIT {
+            self.start_editing();
+        }
 
         for button in self.button_list.iter_mut() {
             match button.text.as_str() {
                 PLAY => button.set_clicked(mode == PLAY),
                 ANALYSE => button.set_clicked(mode == ANALYSE),
-                REVERT_SOLVE => button.set_enabled(mode == ANALYSE),
-                NOTE | FILL_NOTES | UNDO | HINT => button.set_enabled(mode == PLAY),
+                REVERT_SOLVE | PREVIOUS_STEP | PLAY_SOLVE | NEXT_STEP | SLOWER | FASTER => {
+                    button.set_enabled(mode == ANALYSE)
+                }
+                NOTE | CORNER_MARKS | CENTRE_MARKS | CELL_COLOR | FILL_NOTES | HINT => {
+                    button.set_enabled(mode == PLAY)
+                }
+                UNDO | REDO => button.set_enabled(mode == PLAY || mode == EDIT),
+                SOLVE_ONCE | SOLVE => button.set_enabled(mode != EDIT),
+                EXPORT | EXPORT_DB => button.set_enabled(mode == EDIT),
                 text => {
                     if text.starts_with("Lifes: ") {
                         button.set_enabled(mode == PLAY);
@@ -784,26 +1135,31 @@ impl SudokuDisplay {
     }
 
     pub fn solve_once(&mut self) {
-        let old_state = (self.carpet.clone(), self.player_pboard.clone());
+        // the editor keeps its givens apart from the solved cells
+        if self.mode == EDIT {
+            return;
+        }
+        self.stop_playback();
+        let old_state = self.get_history_snapshot();
         let (did_anything, rules_used) = self.carpet.rule_solve_until((true, true), None);
         if !did_anything {
             return;
         }
 
-        self.history.push(old_state);
         self.analyse_text.clear();
 
         if self.mode == PLAY {
-            self.history.clear();
-            self.hint_text.clear();
+            self.hints_used += 1;
+            self.clear_hint();
         }
 
         for used_rules in rules_used.iter() {
-            for (sudoku, rule) in used_rules.iter().enumerate() {
-                self.analyse_text.push(format!(
-                    "Sudoku {sudoku} used \"{}\"",
-                    Sudoku::get_rule_name_by_id(*rule)
-                ));
+            for (sudoku, rule) in used_rules.iter() {
+                let rule_name = CarpetSudoku::get_rule_name_by_id(*rule);
+                self.analyse_text.push(match sudoku {
+                    Some(sudoku) => format!("Sudoku {sudoku} used \"{rule_name}\""),
+                    None => format!("Carpet used \"{rule_name}\""),
+                });
             }
         }
 
@@ -817,110 +1173,71 @@ impl SudokuDisplay {
                             .contains(&value)
                         {
                             self.player_pboard[sudoku_id][y][x].remove(&value);
+                            self.player_marks[sudoku_id][y][x].remove(value);
                         }
                     }
                 }
             }
         }
-    }
 
-    pub fn hint(&mut self) {
-        if self.carpet.get_filled_cells() == 0 {
-            return;
-        }
-        if self.mode != PLAY {
-            if let Some(action) = self.buttons_action.get(PLAY).cloned() {
-                action(self);
-            }
-        }
-        self.hint_text.clear();
-        let mut rules_used: HashMap<usize, usize> = HashMap::new();
-        for i in 0..self.carpet.get_n_sudokus() {
-            if let Some(sudoku) = self.carpet.get_sudoku(i) {
-                if let Ok(Some(rule)) = sudoku
-                    .clone()
-                    .rule_solve(None, Some(self.carpet.get_difficulty()))
-                {
-                    rules_used.insert(i, rule);
-                } else {
-                    return;
-                }
-            }
-        }
-        match (self.carpet.get_pattern(), self.mode.as_str()) {
-            (CarpetPattern::Torus(_), PLAY) | (CarpetPattern::DenseTorus, PLAY) => {
-                let torus_size = self.carpet.get_pattern().get_size(self.carpet.get_n());
-                let torus_view = self.torus_view.1 * torus_size + self.torus_view.0;
-                if let Some(rule) = rules_used.get(&torus_view) {
-                    self.hint_text = format!(
-                        "Viewing sudoku can use \"{}\"",
-                        Sudoku::get_rule_name_by_id(*rule)
-                    );
-                } else {
-                    self.hint_text = "No hint available".to_string();
-                }
-            }
-            (CarpetPattern::Simple, _) => {
-                let rule = rules_used.get(&0).unwrap();
-                self.hint_text =
-                    format!("Sudoku can use \"{}\"", Sudoku::get_rule_name_by_id(*rule));
-            }
-            _ => {
-                let mut rng = rng();
-                let (sudoku, rule) = rules_used.iter().choose(&mut rng).unwrap();
-                if self.carpet.get_n_sudokus() > 1 {
-                    self.hint_text = format!(
-                        "Sudoku {} can use \"{}\"",
-                        sudoku,
-                        Sudoku::get_rule_name_by_id(*rule)
-                    );
-                }
-            }
+        if self.mode == PLAY {
+            self.clear_history();
+        } else {
+            self.push_history(old_state);
         }
     }
 
     fn solve(&mut self) {
-        self.hint_text.clear();
+        if self.mode == EDIT {
+            return;
+        }
+        self.clear_hint();
+        self.stop_playback();
         self.analyse_text.clear();
 
-        let old_state = (self.carpet.clone(), self.player_pboard.clone());
+        let old_state = self.get_history_snapshot();
         let (did_something, _) = self.carpet.rule_solve_until((false, false), None);
-        if did_something {
-            self.history.push(old_state);
-        }
 
         if self.mode == PLAY {
-            self.history.clear();
-            self.hint_text.clear();
+            self.hints_used += 1;
+            // a game solved by the button isn't a won game
+            self.solved_by_assist = true;
+            self.clear_hint();
         }
 
         for sudoku_i in 0..self.carpet.get_n_sudokus() {
             for x in 0..self.carpet.get_n2() {
                 for y in 0..self.carpet.get_n2() {
                     self.player_pboard[sudoku_i][y][x].clear();
+                    self.player_marks[sudoku_i][y][x].clear_digits();
                 }
             }
         }
+
+        if self.mode == PLAY {
+            self.clear_history();
+        } else if did_something {
+            self.push_history(old_state);
+        }
     }
 
-    fn notes_btn(&mut self) {
+    pub(super) fn notes_btn(&mut self) {
         self.note = !self.note;
         for bouton in self.button_list.iter_mut() {
-            if bouton.text.eq(NOTE) {
-                bouton.set_clicked(!bouton.clicked());
-            } else if bouton.text.eq(COLOR_INDICATOR) {
+            if bouton.text.eq(COLOR_INDICATOR) {
                 bouton.enabled = self.note;
             } else if let Ok(valeur) = bouton.text.parse::<u32>() {
-                if COLORS.contains(&valeur) {
+                if self.settings.colors.contains(&valeur) {
                     bouton.enabled = self.note;
                 }
             }
         }
+        self.update_mark_buttons();
     }
 
     fn fill_notes_btn(&mut self, easy: bool) {
         let mut changed = false;
-        let old_state = (self.carpet.clone(), self.player_pboard.clone());
+        let old_state = self.get_history_snapshot();
         for sudoku_i in 0..self.carpet.get_n_sudokus() {
             for x in 0..self.carpet.get_n2() {
                 for y in 0..self.carpet.get_n2() {
@@ -930,11 +1247,13 @@ impl SudokuDisplay {
                         changed = true;
                         if easy {
                             for i in self.carpet.get_cell_possibilities(sudoku_i, x, y) {
-                                self.player_pboard[sudoku_i][y][x].insert(i, COLORS[0]);
+                                self.player_pboard[sudoku_i][y][x]
+                                    .insert(i, self.settings.colors[0]);
                             }
                         } else {
                             for i in 1..=self.carpet.get_n2() {
-                                self.player_pboard[sudoku_i][y][x].insert(i, COLORS[0]);
+                                self.player_pboard[sudoku_i][y][x]
+                                    .insert(i, self.settings.colors[0]);
                             }
                         }
                     }
@@ -942,86 +1261,102 @@ impl SudokuDisplay {
             }
         }
         if changed {
-            self.history.push(old_state);
+            self.push_history(old_state);
         }
     }
 
-    fn undo_btn(&mut self) {
-        if let Some((last_carpet, last_pboard)) = self.history.pop() {
-            self.carpet = last_carpet;
-            self.player_pboard = last_pboard;
-        }
-        self.hint_text.clear();
-        self.analyse_text.clear();
-    }
-
-    fn value_btn(&mut self, button_id: usize, (x, y): Coords) {
+    /// Places the value in the selected cell, or marks the selected cells with it in note mode,
+    /// shift and ctrl make corner and centre marks whatever the mode
+    fn value_btn(&mut self, (x, y): Coords) {
         if self.selected_cell.is_none() {
             return;
         }
+        if self.completed && self.mode == PLAY {
+            return;
+        }
         let (sudoku_i, x1, y1) = self.selected_cell.unwrap();
 
         let value = y * self.carpet.get_n() + x + 1;
+        if self.mode == EDIT {
+            self.edit_given((sudoku_i, x1, y1), value);
+            return;
+        }
         let current_value = self.carpet.get_cell_value(sudoku_i, x1, y1);
-
-        if current_value != 0 {
+        let mark_mode = if is_key_down(KeyCode::LeftShift) || is_key_down(KeyCode::RightShift) {
+            Some(MarkMode::Corner)
+        } else if is_key_down(KeyCode::LeftControl) || is_key_down(KeyCode::RightControl) {
+            Some(MarkMode::Centre)
+        } else {
+            self.note.then_some(self.mark_mode)
+        };
+        if let Some(mark_mode) = mark_mode {
+            let old_state = self.get_history_snapshot();
+            self.toggle_mark(mark_mode, value);
+            self.push_history(old_state);
             return;
         }
 
-        self.history
-            .push((self.carpet.clone(), self.player_pboard.clone()));
+        let correct_value = self.correction_board[sudoku_i][y1][x1];
+        let is_replaceable = self.is_replaceable_cell((sudoku_i, x1, y1));
 
-        if self.note {
-            for (sudoku2, x2, y2) in self.carpet.get_twin_cells(sudoku_i, x1, y1) {
-                if self.button_list[button_id].clicked {
-                    if self.selected_color == self.player_pboard[sudoku2][y2][x2][&value] {
-                        self.player_pboard[sudoku2][y2][x2].remove(&value);
-                    } else {
-                        self.player_pboard[sudoku2][y2][x2].insert(value, self.selected_color);
-                    }
-                } else {
-                    self.player_pboard[sudoku2][y2][x2].insert(value, self.selected_color);
-                }
-            }
+        if current_value != 0 && !is_replaceable {
             return;
         }
 
-        if (self.correction_board[sudoku_i][y1][x1] == 0
+        let old_state = self.get_history_snapshot();
+        if current_value != 0 {
+            let _ = self.carpet.remove_value(sudoku_i, x1, y1);
+            if current_value == value {
+                self.push_history(old_state);
+                return;
+            }
+        }
+
+        if (correct_value == 0
             && self
                 .carpet
                 .get_cell_possibilities(sudoku_i, x1, y1)
                 .contains(&value))
-            || self.correction_board[sudoku_i][y1][x1] == value
+            || correct_value == value
         {
-            if self.carpet.set_value(sudoku_i, x1, y1, value).is_err() {
+            // a wrong value shown in conflict doesn't stop the right one from being placed
+            let placed = if self.assists.show_conflicts {
+                let _ = self.carpet.set_conflicting_value(sudoku_i, x1, y1, value);
+                true
+            } else if self.carpet.set_value(sudoku_i, x1, y1, value).is_err() {
                 let _ = self.carpet.remove_value(sudoku_i, x1, y1);
+                false
             } else {
-                self.history.clear();
-                self.hint_text.clear();
-                for (sudoku2, x2, y2) in self.carpet.get_twin_cells(sudoku_i, x1, y1) {
-                    self.player_pboard[sudoku2][y2][x2].clear();
-                }
-                for (sudoku2, x2, y2) in self.carpet.get_global_cell_group(sudoku_i, x1, y1, All) {
-                    for (sudoku3, x3, y3) in self.carpet.get_twin_cells(sudoku2, x2, y2) {
-                        if self.carpet.get_cell_value(sudoku3, x3, y3) == 0 {
-                            self.player_pboard[sudoku3][y3][x3].remove(&value);
-                        }
-                    }
-                }
+                true
+            };
+            if placed {
+                self.solve_steps += 1;
+                self.clear_hint();
+                self.clear_placed_notes(sudoku_i, x1, y1, value);
 
+                self.push_history(old_state);
                 *self.wrong_cell.lock().unwrap() = None;
                 return;
             }
         }
 
+        self.mistakes += 1;
+        if self.assists.show_conflicts {
+            let _ = self.carpet.set_conflicting_value(sudoku_i, x1, y1, value);
+            for (sudoku2, x2, y2) in self.carpet.get_twin_cells(sudoku_i, x1, y1) {
+                self.player_pboard[sudoku2][y2][x2].clear();
+                self.player_marks[sudoku2][y2][x2].clear_digits();
+            }
+            self.push_history(old_state);
+            return;
+        }
+
         self.lifes -= 1;
-        let old_state = (self.carpet.clone(), self.player_pboard.clone());
-        if self.player_pboard[sudoku_i][y1][x1]
-            .remove(&value)
-            .is_some()
-        {
-            self.history.push(old_state);
+        if self.lifes == 0 {
+            self.finish_game(false);
         }
+        self.player_pboard[sudoku_i][y1][x1].remove(&value);
+        self.push_history(old_state);
         *self.wrong_cell.lock().unwrap() = Some((sudoku_i, x1, y1, value));
 
         let thread_wrong_cell = Arc::clone(&self.wrong_cell);
@@ -1034,15 +1369,6 @@ impl SudokuDisplay {
         *self.wrong_cell_handle.lock().unwrap() = Some(handle);
     }
 
-    fn color_btn(&mut self, color: u32) {
-        self.selected_color = color;
-        for button in self.button_list.iter_mut() {
-            if button.text == COLOR_INDICATOR {
-                button.background_color = Color::from_hex(color);
-            }
-        }
-    }
-
     // =============================================
     // ============== DRAW FUNCTIONS ===============
     // =============================================
@@ -1050,8 +1376,11 @@ impl SudokuDisplay {
     async fn draw_simple_sudoku(&mut self, font: Font, sudoku_i: usize, x1: usize, y1: usize) {
         let n = self.carpet.get_n();
         let n2 = self.carpet.get_n2();
-        let sudoku_x_offset = self.x_offset + (x1 as f32) * self.pixel_per_cell;
-        let sudoku_y_offset = self.y_offset + (y1 as f32) * self.pixel_per_cell;
+        let margin = self.get_clue_margin();
+        let sudoku_x_offset =
+            self.x_offset + self.view_offset.0 + ((x1 + margin) as f32) * self.pixel_per_cell;
+        let sudoku_y_offset =
+            self.y_offset + self.view_offset.1 + ((y1 + margin) as f32) * self.pixel_per_cell;
 
         // outline
         draw_rectangle(
@@ -1100,8 +1429,9 @@ impl SudokuDisplay {
             }
 
             for (i, x, y) in self
-                .carpet
-                .get_twin_cells(selected_sudoku, selected_x, selected_y)
+                .get_selected_cells()
+                .into_iter()
+                .flat_map(|(sudoku, x, y)| self.carpet.get_twin_cells(sudoku, x, y))
             {
                 if i != sudoku_i {
                     continue;
@@ -1117,6 +1447,11 @@ impl SudokuDisplay {
             }
         }
 
+        // the paint of the player lets the selection show through
+        if self.mode == PLAY {
+            self.draw_cell_colors(sudoku_i, (sudoku_x_offset, sudoku_y_offset));
+        }
+
         // draw the wrong cell
         if let Some((wrong_sudoku, wrong_x, wrong_y, _)) = *self.wrong_cell.lock().unwrap() {
             if wrong_sudoku == sudoku_i {
@@ -1130,6 +1465,10 @@ impl SudokuDisplay {
             }
         }
 
+        let conflicts = self.get_conflict_cells();
+        self.draw_assists_highlights(sudoku_i, (sudoku_x_offset, sudoku_y_offset), &conflicts);
+        self.draw_step_cells(sudoku_i, (sudoku_x_offset, sudoku_y_offset));
+
         // draw grid
         for i in 0..n2 {
             let i = i as f32;
@@ -1166,6 +1505,201 @@ impl SudokuDisplay {
             }
         }
 
+        // draw the parities, thermometers and arrows under the numbers
+        let sudoku = &self.carpet.get_sudokus()[sudoku_i];
+        let cell_center = |(x, y): Coords| {
+            (
+                ((x as f32) + 0.5) * self.pixel_per_cell + sudoku_x_offset,
+                ((y as f32) + 0.5) * self.pixel_per_cell + sudoku_y_offset,
+            )
+        };
+        let line_thickness = self.pixel_per_cell / 4.0;
+
+        // even cells are grey squares, odd cells grey circles
+        for (&(x, y), parity) in sudoku.get_parities() {
+            let (center_x, center_y) = cell_center((x, y));
+            match parity {
+                SudokuParity::Even => draw_rectangle(
+                    center_x - self.pixel_per_cell * 0.4,
+                    center_y - self.pixel_per_cell * 0.4,
+                    self.pixel_per_cell * 0.8,
+                    self.pixel_per_cell * 0.8,
+                    Color::from_hex(PARITY_COLOR),
+                ),
+                SudokuParity::Odd => draw_circle(
+                    center_x,
+                    center_y,
+                    self.pixel_per_cell * 0.4,
+                    Color::from_hex(PARITY_COLOR),
+                ),
+            }
+        }
+
+        for thermometer in sudoku.get_thermometers() {
+            let (bulb_x, bulb_y) = cell_center(thermometer[0]);
+            draw_circle(
+                bulb_x,
+                bulb_y,
+                self.pixel_per_cell / 3.0,
+                Color::from_hex(LINE_CONSTRAINT_COLOR),
+            );
+            for cells in thermometer.windows(2) {
+                let (from_x, from_y) = cell_center(cells[0]);
+                let (to_x, to_y) = cell_center(cells[1]);
+                draw_line(
+                    from_x,
+                    from_y,
+                    to_x,
+                    to_y,
+                    line_thickness,
+                    Color::from_hex(LINE_CONSTRAINT_COLOR),
+                );
+                draw_circle(
+                    to_x,
+                    to_y,
+                    line_thickness / 2.0,
+                    Color::from_hex(LINE_CONSTRAINT_COLOR),
+                );
+            }
+        }
+        for (circle, path) in sudoku.get_arrows() {
+            let (circle_x, circle_y) = cell_center(*circle);
+            let circle_radius = self.pixel_per_cell * 0.4;
+            draw_circle_lines(
+                circle_x,
+                circle_y,
+                circle_radius,
+                line_thickness / 2.0,
+                Color::from_hex(LINE_CONSTRAINT_COLOR),
+            );
+
+            // the shaft starts on the edge of the circle
+            let mut from = cell_center(*circle);
+            for (i, &cell) in path.iter().enumerate() {
+                let to = cell_center(cell);
+                let (dx, dy) = (to.0 - from.0, to.1 - from.1);
+                let length = (dx * dx + dy * dy).sqrt();
+                let start = if i == 0 { circle_radius } else { 0.0 };
+                draw_line(
+                    from.0 + dx / length * start,
+                    from.1 + dy / length * start,
+                    to.0,
+                    to.1,
+                    line_thickness / 2.0,
+                    Color::from_hex(LINE_CONSTRAINT_COLOR),
+                );
+
+                // arrow head on the last cell
+                if i == path.len() - 1 {
+                    let head_size = self.pixel_per_cell / 4.0;
+                    for side in [-1.0, 1.0] {
+                        draw_line(
+                            to.0,
+                            to.1,
+                            to.0 - (dx - side * dy) / length * head_size,
+                            to.1 - (dy + side * dx) / length * head_size,
+                            line_thickness / 2.0,
+                            Color::from_hex(LINE_CONSTRAINT_COLOR),
+                        );
+                    }
+                }
+                from = to;
+            }
+        }
+
+        // draw the relations on the border between their cells
+        let marker_size = self.pixel_per_cell / 6.0;
+        for (&((x1, y1), (x2, y2)), &relation) in
+            self.carpet.get_sudokus()[sudoku_i].get_relations()
+        {
+            let center_x = ((x1 + x2 + 1) as f32) / 2.0 * self.pixel_per_cell + sudoku_x_offset;
+            let center_y = ((y1 + y2 + 1) as f32) / 2.0 * self.pixel_per_cell + sudoku_y_offset;
+            match relation {
+                SudokuRelation::GreaterThan => {
+                    // the tip points to the smaller cell
+                    let dx = (x2 as f32) - (x1 as f32);
+                    let dy = (y2 as f32) - (y1 as f32);
+                    let tip_x = center_x + dx * marker_size / 2.0;
+                    let tip_y = center_y + dy * marker_size / 2.0;
+                    for side in [-1.0, 1.0] {
+                        draw_line(
+                            tip_x,
+                            tip_y,
+                            center_x - dx * marker_size / 2.0 + side * dy * marker_size,
+                            center_y - dy * marker_size / 2.0 + side * dx * marker_size,
+                            2.0,
+                            Color::from_hex(FOREGROUND_COLOR),
+                        );
+                    }
+                }
+                SudokuRelation::WhiteDot => {
+                    draw_circle(
+                        center_x,
+                        center_y,
+                        marker_size / 2.0,
+                        Color::from_hex(BACKGROUND_COLOR),
+                    );
+                    draw_circle_lines(
+                        center_x,
+                        center_y,
+                        marker_size / 2.0,
+                        1.5,
+                        Color::from_hex(FOREGROUND_COLOR),
+                    );
+                }
+                SudokuRelation::BlackDot => {
+                    draw_circle(
+                        center_x,
+                        center_y,
+                        marker_size / 2.0,
+                        Color::from_hex(FOREGROUND_COLOR),
+                    );
+                }
+            }
+        }
+
+        // draw the outside clues in the margins, next to their first cell
+        let clue_font_size = (self.pixel_per_cell as u16) / 3;
+        for (&clue, sum) in self.carpet.get_sudokus()[sudoku_i].get_outside_clues() {
+            let ((x, y), (dx, dy)) = match clue {
+                SudokuOutsideClue::Sandwich(Row, y) => ((0, y), (1, 0)),
+                SudokuOutsideClue::Sandwich(_, x) => ((x, 0), (0, 1)),
+                SudokuOutsideClue::LittleKiller(start, direction) => (start, direction),
+            };
+            let center_x =
+                ((x as f32) + 0.5 - (dx as f32) * 0.75) * self.pixel_per_cell + sudoku_x_offset;
+            let center_y =
+                ((y as f32) + 0.5 - (dy as f32) * 0.75) * self.pixel_per_cell + sudoku_y_offset;
+
+            let text = sum.to_string();
+            let text_dimensions = measure_text(&text, Some(&font), clue_font_size, 1.0);
+            draw_text_ex(
+                &text,
+                center_x - text_dimensions.width / 2.0,
+                center_y + text_dimensions.height / 2.0,
+                TextParams {
+                    font: Some(&font),
+                    font_size: clue_font_size,
+                    color: Color::from_hex(FOREGROUND_COLOR),
+                    ..Default::default()
+                },
+            );
+
+            // the little killers show their direction
+            if let SudokuOutsideClue::LittleKiller(_, _) = clue {
+                let arrow_x = center_x + (dx as f32) * self.pixel_per_cell * 0.3;
+                let arrow_y = center_y + (dy as f32) * self.pixel_per_cell * 0.3;
+                draw_line(
+                    arrow_x,
+                    arrow_y,
+                    arrow_x + (dx as f32) * self.pixel_per_cell * 0.15,
+                    arrow_y + (dy as f32) * self.pixel_per_cell * 0.15,
+                    1.5,
+                    Color::from_hex(FOREGROUND_COLOR),
+                );
+            }
+        }
+
         // draw numbers
         for (y, line) in self.carpet.get_sudokus()[sudoku_i]
             .get_board()
@@ -1173,6 +1707,11 @@ impl SudokuDisplay {
             .enumerate()
         {
             for (x, &cell) in line.iter().enumerate() {
+                // the editor shows the solution of its givens lighter than them
+                let (cell, color) = match self.get_editor_solution_value(sudoku_i, x, y) {
+                    Some(value) => (value, EDITOR_SOLUTION_COLOR),
+                    None => (cell, FOREGROUND_COLOR),
+                };
                 if cell == 0 {
                     continue;
                 }
@@ -1190,7 +1729,7 @@ impl SudokuDisplay {
                     TextParams {
                         font: Some(&font),
                         font_size,
-                        color: Color::from_hex(FOREGROUND_COLOR),
+                        color: Color::from_hex(color),
                         ..Default::default()
                     },
                 );
@@ -1199,6 +1738,8 @@ impl SudokuDisplay {
 
         let pb = if self.mode.eq(PLAY) {
             self.player_pboard[sudoku_i].clone()
+        } else if self.mode.eq(EDIT) {
+            vec![vec![HashMap::new(); n2]; n2]
         } else {
             self.carpet
                 .get_sudoku_possibility_board(sudoku_i)
@@ -1208,7 +1749,7 @@ impl SudokuDisplay {
                         .map(|values| {
                             values
                                 .into_iter()
-                                .map(|possibility| (possibility, COLORS[0]))
+                                .map(|possibility| (possibility, self.settings.colors[0]))
                                 .collect::<HashMap<_, _>>()
                         })
                         .collect::<Vec<_>>()
@@ -1243,7 +1784,7 @@ impl SudokuDisplay {
                             + (((i as f32) + 1.0) * self.pixel_per_cell) / (n as f32)
                             + (self.pixel_per_cell / (n as f32) + text_dimensions.height) / 2.0;
 
-                        if pb_cell[&number] != COLORS[0] {
+                        if pb_cell[&number] != self.settings.colors[0] {
                             draw_rectangle(
                                 sudoku_x_offset + text_x - text_dimensions.width * 0.2,
                                 sudoku_y_offset + text_y - text_dimensions.height * 1.2,
@@ -1268,90 +1809,17 @@ impl SudokuDisplay {
                 }
             }
         }
-    }
-
-    async fn draw_samurai_sudoku(&mut self, font: Font) {
-        let n = self.carpet.get_n();
-        let n2 = self.carpet.get_n2();
-
-        self.draw_simple_sudoku(font.clone(), 0, n2 - n, n2 - n)
-            .await;
-        for x in 0..2 {
-            for y in 0..2 {
-                self.draw_simple_sudoku(
-                    font.clone(),
-                    1 + x + y * 2,
-                    (2 * n2 - 2 * n) * x,
-                    (2 * n2 - 2 * n) * y,
-                )
-                .await;
-            }
-        }
-
-        if let Some((selected_i, selected_x, selected_y)) = self.selected_cell {
-            for (i, _, _) in self
-                .carpet
-                .get_twin_cells(selected_i, selected_x, selected_y)
-            {
-                if i == 0 {
-                    self.draw_simple_sudoku(font.clone(), 0, n2 - n, n2 - n)
-                        .await;
-                } else {
-                    let y = (i - 1) / 2;
-                    let x = (i - 1) % 2;
-                    self.draw_simple_sudoku(
-                        font.clone(),
-                        i,
-                        (2 * n2 - 2 * n) * x,
-                        (2 * n2 - 2 * n) * y,
-                    )
-                    .await;
-                }
-            }
-        }
-    }
 
-    async fn draw_diag_sudoku(&mut self, dense: bool, font: Font) {
-        let n = self.carpet.get_n();
-        let n2 = self.carpet.get_n2();
-        let n_sudokus = self.carpet.get_n_sudokus();
-
-        for i in 0..n_sudokus {
-            let (x1, y1) = if dense {
-                (i * n, (n_sudokus - i - 1) * n)
-            } else {
-                (i * (n2 - n), (n_sudokus - i - 1) * (n2 - n))
-            };
-            self.draw_simple_sudoku(font.clone(), i, x1, y1).await;
-        }
-
-        if let Some((selected_i, selected_x, selected_y)) = self.selected_cell {
-            for (i, _, _) in self
-                .carpet
-                .get_twin_cells(selected_i, selected_x, selected_y)
-            {
-                let (x1, y1) = if dense {
-                    (i * n, (n_sudokus - i - 1) * n)
-                } else {
-                    (i * (n2 - n), (n_sudokus - i - 1) * (n2 - n))
-                };
-                self.draw_simple_sudoku(font.clone(), i, x1, y1).await;
-            }
+        if self.mode == PLAY {
+            self.draw_pencil_marks(&font, sudoku_i, (sudoku_x_offset, sudoku_y_offset));
         }
+        self.draw_step_eliminations(&font, sudoku_i, (sudoku_x_offset, sudoku_y_offset));
     }
 
-    async fn draw_carpet_sudoku(&mut self, dense: bool, font: Font) {
-        let n = self.carpet.get_n();
-        let n2 = self.carpet.get_n2();
-        let n_sudokus = self.carpet.get_n_sudokus();
-        let carpet_size = self.carpet.get_pattern().get_size(self.carpet.get_n());
+    async fn draw_carpet_sudoku(&mut self, font: Font) {
+        let positions = self.carpet.get_layout().get_positions().clone();
 
-        for i in 0..n_sudokus {
-            let (x1, y1) = if dense {
-                ((i % carpet_size) * n, (i / carpet_size) * n)
-            } else {
-                ((i % carpet_size) * (n2 - n), (i / carpet_size) * (n2 - n))
-            };
+        for (i, &(x1, y1)) in positions.iter().enumerate() {
             self.draw_simple_sudoku(font.clone(), i, x1, y1).await;
         }
 
@@ -1360,11 +1828,7 @@ impl SudokuDisplay {
                 .carpet
                 .get_twin_cells(selected_i, selected_x, selected_y)
             {
-                let (x1, y1) = if dense {
-                    ((i % carpet_size) * n, (i / carpet_size) * n)
-                } else {
-                    ((i % carpet_size) * (n2 - n), (i / carpet_size) * (n2 - n))
-                };
+                let (x1, y1) = positions[i];
                 self.draw_simple_sudoku(font.clone(), i, x1, y1).await;
             }
         }
@@ -1374,114 +1838,30 @@ impl SudokuDisplay {
         &mut self,
         (pixel_x, pixel_y): (f32, f32),
     ) -> Option<(usize, usize, usize)> {
-        let x = ((pixel_x - self.x_offset) / self.pixel_per_cell) as usize;
-        let y = ((pixel_y - self.y_offset) / self.pixel_per_cell) as usize;
+        let margin = self.get_clue_margin() as f32;
+        let x = (pixel_x - self.x_offset - self.view_offset.0) / self.pixel_per_cell - margin;
+        let y = (pixel_y - self.y_offset - self.view_offset.1) / self.pixel_per_cell - margin;
         let n = self.carpet.get_n();
-        let n2 = self.carpet.get_n2();
 
         if pixel_x < self.x_offset
             || pixel_x > self.x_offset + self.grid_size
             || pixel_y < self.y_offset
             || pixel_y > self.y_offset + self.grid_size
+            || x < 0.
+            || y < 0.
+            || self.is_on_minimap((pixel_x, pixel_y))
         {
             return None;
         }
+        let (x, y) = (x as usize, y as usize);
 
         match (self.carpet.get_pattern(), self.mode.as_str()) {
-            (CarpetPattern::Simple, _)
-            | (CarpetPattern::Diagonal(1), _)
-            | (CarpetPattern::Carpet(1), _)
-            | (CarpetPattern::DenseDiagonal(1), _)
-            | (CarpetPattern::DenseCarpet(1), _) => Some((0, x, y)),
-            (CarpetPattern::Diagonal(size), _) => {
-                let max_n = n2 + (size - 1) * n * (n - 1);
-                for i in 0..size {
-                    let min_x = i * n * (n - 1);
-                    let max_x = min_x + n2;
-                    let max_y = max_n - min_x;
-                    let min_y = max_y - n2;
-                    if x >= min_x && x < max_x && y >= min_y && y < max_y {
-                        return Some((i, x - min_x, y - min_y));
-                    }
-                }
-                None
-            }
-            (CarpetPattern::Samurai, _) => {
-                if x < n2 {
-                    if y < n2 {
-                        Some((1, x, y))
-                    } else if y >= n2 + n {
-                        Some((3, x, y - n2 - n))
-                    } else if x >= n * (n - 1) {
-                        Some((0, x - n * (n - 1), y - n * (n - 1)))
-                    } else {
-                        None
-                    }
-                } else if x < n2 + n {
-                    if y >= n * (n - 1) && y < n2 + n * (n - 1) {
-                        Some((0, x - n * (n - 1), y - n * (n - 1)))
-                    } else {
-                        None
-                    }
-                } else if y < n2 {
-                    Some((2, x - n2 - n, y))
-                } else if y >= n2 + n {
-                    Some((4, x - n2 - n, y - n2 - n))
-                } else if x < n2 + n * (n - 1) {
-                    Some((0, x - n * (n - 1), y - n * (n - 1)))
-                } else {
-                    None
-                }
-            }
-            (CarpetPattern::Carpet(size), _) | (CarpetPattern::Torus(size), ANALYSE) => {
-                for y0 in 0..size {
-                    for x0 in 0..size {
-                        let i = y0 * size + x0;
-                        let min_x = x0 * (n2 - n);
-                        let max_x = min_x + n2;
-                        let min_y = y0 * (n2 - n);
-                        let max_y = min_y + n2;
-                        if x >= min_x && x < max_x && y >= min_y && y < max_y {
-                            return Some((i, x - min_x, y - min_y));
-                        }
-                    }
-                }
-                None
-            }
-            (CarpetPattern::DenseDiagonal(size), _) => {
-                let max_n = n2 + (size - 1) * n;
-                for i in 0..size {
-                    let min_x = i * n;
-                    let max_x = min_x + n2;
-                    let max_y = max_n - min_x;
-                    let min_y = max_y - n2;
-                    if x >= min_x && x < max_x && y >= min_y && y < max_y {
-                        return Some((i, x - min_x, y - min_y));
-                    }
-                }
-                None
-            }
-            (CarpetPattern::DenseCarpet(_), _) | (CarpetPattern::DenseTorus, ANALYSE) => {
-                let size = self.carpet.get_pattern().get_size(self.carpet.get_n());
-                for y0 in 0..size {
-                    for x0 in 0..size {
-                        let i = y0 * size + x0;
-                        let min_x = x0 * n;
-                        let max_x = min_x + n2;
-                        let min_y = y0 * n;
-                        let max_y = min_y + n2;
-                        if x >= min_x && x < max_x && y >= min_y && y < max_y {
-                            return Some((i, x - min_x, y - min_y));
-                        }
-                    }
-                }
-                None
-            }
-            (CarpetPattern::Torus(_) | CarpetPattern::DenseTorus, _) => {
+            (CarpetPattern::Torus(_) | CarpetPattern::DenseTorus, PLAY) => {
+                let n2 = self.carpet.get_n2();
                 let size = self.carpet.get_pattern().get_size(self.carpet.get_n());
-                Some((self.torus_view.1 * size + self.torus_view.0, x, y))
+                (x < n2 && y < n2).then_some((self.torus_view.1 * size + self.torus_view.0, x, y))
             }
-            (CarpetPattern::Custom(_), _) => panic!("Custom pattern not implemented"),
+            _ => self.carpet.get_layout().get_sudoku_at(n, x, y),
         }
     }
 
@@ -1493,7 +1873,6 @@ impl SudokuDisplay {
 
     pub fn update_scale(&mut self) {
         let n2 = self.carpet.get_n2();
-        let n = self.carpet.get_n();
 
         let ratio = screen_width() / screen_height();
         let ratio_voulu = 411.0 / 245.0;
@@ -1504,37 +1883,189 @@ impl SudokuDisplay {
         }
 
         self.grid_size = 900. * self.scale_factor;
-        self.pixel_per_cell = match (self.carpet.get_pattern(), self.mode.as_str()) {
-            (CarpetPattern::Simple, _)
-            | (CarpetPattern::Torus(_) | CarpetPattern::DenseTorus, PLAY) => {
-                self.grid_size / n2 as f32
-            }
-            (CarpetPattern::Samurai, _) => self.grid_size / (n2 * 3 - 2 * n) as f32,
-            (
-                CarpetPattern::Diagonal(size)
-                | CarpetPattern::Carpet(size)
-                | CarpetPattern::Torus(size),
-                _,
-            ) => self.grid_size / (n2 + (n2 - n) * (size - 1)) as f32,
-            (
-                CarpetPattern::DenseDiagonal(_)
-                | CarpetPattern::DenseCarpet(_)
-                | CarpetPattern::DenseTorus,
-                _,
-            ) => {
-                let size = self.carpet.get_pattern().get_size(self.carpet.get_n());
-                self.grid_size / (n2 + n * (size - 1)) as f32
-            }
-            (CarpetPattern::Custom(_), _) => panic!("Custom pattern not implemented"),
-        };
+        let (width, height) = self.get_view_size();
+        self.pixel_per_cell = self.grid_size / width.max(height).max(n2) as f32 * self.zoom;
 
         self.x_offset = 250. * self.scale_factor;
         self.y_offset = 150. * self.scale_factor;
+        self.clamp_view();
+    }
+
+    // =============================================
+    // ================== VIEWPORT =================
+    // =============================================
+
+    /// The size in cells of what is drawn in the grid area, margins included
+    fn get_view_size(&self) -> Coords {
+        let (width, height) = match (self.carpet.get_pattern(), self.mode.as_str()) {
+            (CarpetPattern::Torus(_) | CarpetPattern::DenseTorus, PLAY) => {
+                (self.carpet.get_n2(), self.carpet.get_n2())
+            }
+            _ => self.carpet.get_layout().get_size(self.carpet.get_n()),
+        };
+        let margin = self.get_clue_margin();
+        (width + 2 * margin, height + 2 * margin)
+    }
+
+    /// The cells kept empty around the carpet, where the outside clues are drawn
+    fn get_clue_margin(&self) -> usize {
+        let has_clues = self
+            .carpet
+            .get_sudokus()
+            .iter()
+            .any(|sudoku| !sudoku.get_outside_clues().is_empty());
+        usize::from(has_clues)
+    }
+
+    pub(super) fn reset_view(&mut self) {
+        self.zoom = 1.0;
+        self.view_offset = (0., 0.);
+        self.drag_origin = None;
+    }
+
+    /// Keeps the carpet inside the grid area: it can't be moved further than its borders
+    fn clamp_view(&mut self) {
+        let (width, height) = self.get_view_size();
+        let view_width = width as f32 * self.pixel_per_cell;
+        let view_height = height as f32 * self.pixel_per_cell;
+        self.view_offset.0 = self
+            .view_offset
+            .0
+            .min(0.)
+            .max((self.grid_size - view_width).min(0.));
+        self.view_offset.1 = self
+            .view_offset
+            .1
+            .min(0.)
+            .max((self.grid_size - view_height).min(0.));
+    }
+
+    /// Zooms in (or out with a negative step) while keeping the pixel under the cursor in place
+    fn zoom_view(&mut self, steps: f32, (pixel_x, pixel_y): (f32, f32)) {
+        let new_zoom = (self.zoom * ZOOM_STEP.powf(steps)).clamp(1.0, MAX_ZOOM);
+        let ratio = new_zoom / self.zoom;
+        let cursor_x = pixel_x - self.x_offset;
+        let cursor_y = pixel_y - self.y_offset;
+        self.view_offset.0 = cursor_x - (cursor_x - self.view_offset.0) * ratio;
+        self.view_offset.1 = cursor_y - (cursor_y - self.view_offset.1) * ratio;
+        self.pixel_per_cell *= ratio;
+        self.zoom = new_zoom;
+        self.clamp_view();
+    }
+
+    /// Moves the view so that the cell is drawn inside the grid area
+    fn scroll_to_cell(&mut self, (sudoku_i, x, y): (usize, usize, usize)) {
+        let (x1, y1) = match (self.carpet.get_pattern(), self.mode.as_str()) {
+            (CarpetPattern::Torus(_) | CarpetPattern::DenseTorus, PLAY) => (0, 0),
+            _ => self.carpet.get_layout().get_positions()[sudoku_i],
+        };
+        let margin = self.get_clue_margin();
+        let cell_x = ((x1 + x + margin) as f32) * self.pixel_per_cell + self.view_offset.0;
+        let cell_y = ((y1 + y + margin) as f32) * self.pixel_per_cell + self.view_offset.1;
+        if cell_x < 0. {
+            self.view_offset.0 -= cell_x;
+        } else if cell_x + self.pixel_per_cell > self.grid_size {
+            self.view_offset.0 -= cell_x + self.pixel_per_cell - self.grid_size;
+        }
+        if cell_y < 0. {
+            self.view_offset.1 -= cell_y;
+        } else if cell_y + self.pixel_per_cell > self.grid_size {
+            self.view_offset.1 -= cell_y + self.pixel_per_cell - self.grid_size;
+        }
+        self.clamp_view();
+    }
+
+    /// The minimap rectangle and its pixels per cell, when the carpet doesn't fit the grid area
+    fn get_minimap(&self) -> Option<(f32, f32, f32, f32, f32)> {
+        if self.zoom <= 1.0 {
+            return None;
+        }
+        let (width, height) = self.get_view_size();
+        let minimap_size = self.grid_size / 5.;
+        let pixel_per_cell = minimap_size / width.max(height) as f32;
+        let minimap_width = width as f32 * pixel_per_cell;
+        let minimap_height = height as f32 * pixel_per_cell;
+        let padding = 10. * self.scale_factor;
+        Some((
+            self.x_offset + self.grid_size - minimap_width - padding,
+            self.y_offset + self.grid_size - minimap_height - padding,
+            minimap_width,
+            minimap_height,
+            pixel_per_cell,
+        ))
+    }
+
+    fn is_on_minimap(&self, (pixel_x, pixel_y): (f32, f32)) -> bool {
+        self.get_minimap().is_some_and(|(x, y, width, height, _)| {
+            pixel_x >= x && pixel_x <= x + width && pixel_y >= y && pixel_y <= y + height
+        })
+    }
+
+    /// Centers the view on the point of the carpet under the cursor in the minimap
+    fn minimap_click(&mut self, (pixel_x, pixel_y): (f32, f32)) {
+        if let Some((x, y, _, _, minimap_pixel_per_cell)) = self.get_minimap() {
+            let cell_x = (pixel_x - x) / minimap_pixel_per_cell;
+            let cell_y = (pixel_y - y) / minimap_pixel_per_cell;
+            self.view_offset.0 = self.grid_size / 2. - cell_x * self.pixel_per_cell;
+            self.view_offset.1 = self.grid_size / 2. - cell_y * self.pixel_per_cell;
+            self.clamp_view();
+        }
+    }
+
+    fn draw_minimap(&self) {
+        let Some((x, y, width, height, minimap_pixel_per_cell)) = self.get_minimap() else {
+            return;
+        };
+        let n2 = self.carpet.get_n2() as f32;
+
+        draw_rectangle(
+            x,
+            y,
+            width,
+            height,
+            Color::from_hex(BACKGROUND_COLOR).with_alpha(0.85),
+        );
+        let margin = self.get_clue_margin();
+        match (self.carpet.get_pattern(), self.mode.as_str()) {
+            (CarpetPattern::Torus(_) | CarpetPattern::DenseTorus, PLAY) => draw_rectangle_lines(
+                x + margin as f32 * minimap_pixel_per_cell,
+                y + margin as f32 * minimap_pixel_per_cell,
+                n2 * minimap_pixel_per_cell,
+                n2 * minimap_pixel_per_cell,
+                1.0,
+                Color::from_hex(LINE_COLOR),
+            ),
+            _ => {
+                for &(x1, y1) in self.carpet.get_layout().get_positions() {
+                    draw_rectangle_lines(
+                        x + (x1 + margin) as f32 * minimap_pixel_per_cell,
+                        y + (y1 + margin) as f32 * minimap_pixel_per_cell,
+                        n2 * minimap_pixel_per_cell,
+                        n2 * minimap_pixel_per_cell,
+                        1.0,
+                        Color::from_hex(LINE_COLOR),
+                    );
+                }
+            }
+        }
+
+        // the part of the carpet visible in the grid area
+        let ratio = minimap_pixel_per_cell / self.pixel_per_cell;
+        draw_rectangle_lines(
+            x - self.view_offset.0 * ratio,
+            y - self.view_offset.1 * ratio,
+            (self.grid_size * ratio).min(width),
+            (self.grid_size * ratio).min(height),
+            2.0,
+            Color::from_hex(MINIMAP_VIEW_COLOR),
+        );
     }
 
     pub fn update_selected_buttons(&mut self) {
         if let Some((sudoku_i, x, y)) = self.selected_cell {
-            if self.carpet.get_cell_value(sudoku_i, x, y) != 0 {
+            if self.carpet.get_cell_value(sudoku_i, x, y) != 0
+                && !self.is_replaceable_cell((sudoku_i, x, y))
+            {
                 for i in 1..=self.carpet.get_n2() {
                     for button in self.button_list.iter_mut() {
                         if button.text == i.to_string() {
@@ -1565,7 +2096,16 @@ impl SudokuDisplay {
                         }
                     }
                 }
-                if self.mode.eq(PLAY) {
+                let marks = self.player_marks[sudoku_i][y][x].get_digits(self.mark_mode);
+                if let (true, true, Some(marks)) = (self.mode.eq(PLAY), self.note, marks) {
+                    for i in marks.clone() {
+                        for button in self.button_list.iter_mut() {
+                            if button.text == i.to_string() {
+                                button.set_clicked(true);
+                            }
+                        }
+                    }
+                } else if self.mode.eq(PLAY) {
                     for (i, _color) in self.player_pboard[sudoku_i][y][x].clone() {
                         for button in self.button_list.iter_mut() {
                             if button.text == i.to_string() {
@@ -1726,11 +2266,7 @@ impl SudokuDisplay {
                     }
                 }
             }
-            KeyCode::N => {
-                if let Some(action) = self.buttons_action.get(NOTE).cloned() {
-                    action(self);
-                }
-            }
+            KeyCode::N => self.notes_btn(),
             KeyCode::F => {
                 if let Some(action) = self.buttons_action.get(FILL_NOTES).cloned() {
                     action(self);
@@ -1741,8 +2277,46 @@ impl SudokuDisplay {
                     action(self);
                 }
             }
+            KeyCode::Z | KeyCode::Y
+                if is_key_down(KeyCode::LeftControl) || is_key_down(KeyCode::RightControl) =>
+            {
+                let shift = is_key_down(KeyCode::LeftShift) || is_key_down(KeyCode::RightShift);
+                let button = if last_key_pressed == KeyCode::Y || shift {
+                    REDO
+                } else {
+                    UNDO
+                };
+                if let Some(action) = self.buttons_action.get(button).cloned() {
+                    action(self);
+                }
+            }
             KeyCode::Escape => {
-                self.selected_cell = None;
+                self.clear_selection();
+            }
+            KeyCode::Backspace | KeyCode::Delete if self.mode == EDIT => {
+                if let Some((sudoku_id, x, y)) = self.selected_cell {
+                    let value = self.carpet.get_cell_value(sudoku_id, x, y);
+                    if value != 0 {
+                        self.edit_given((sudoku_id, x, y), value);
+                    }
+                }
+            }
+            KeyCode::KpAdd | KeyCode::Equal => {
+                let center = (
+                    self.x_offset + self.grid_size / 2.,
+                    self.y_offset + self.grid_size / 2.,
+                );
+                self.zoom_view(1., center);
+            }
+            KeyCode::KpSubtract | KeyCode::Minus => {
+                let center = (
+                    self.x_offset + self.grid_size / 2.,
+                    self.y_offset + self.grid_size / 2.,
+                );
+                self.zoom_view(-1., center);
+            }
+            KeyCode::Home => {
+                self.reset_view();
             }
             KeyCode::A => {
                 if let Some(action) = self.buttons_action.get(ANALYSE).cloned() {
@@ -1801,29 +2375,21 @@ impl SudokuDisplay {
                 let (mut sudoku_i, mut x1, mut y1) = self.selected_cell.unwrap();
                 let mut modified = false;
                 match last_key_pressed {
-                    KeyCode::Up => {
-                        if y1 > 0 {
-                            y1 -= 1;
-                            modified = true;
-                        }
+                    KeyCode::Up if y1 > 0 => {
+                        y1 -= 1;
+                        modified = true;
                     }
-                    KeyCode::Down => {
-                        if y1 < n2 - 1 {
-                            y1 += 1;
-                            modified = true;
-                        }
+                    KeyCode::Down if y1 < n2 - 1 => {
+                        y1 += 1;
+                        modified = true;
                     }
-                    KeyCode::Left => {
-                        if x1 > 0 {
-                            x1 -= 1;
-                            modified = true;
-                        }
+                    KeyCode::Left if x1 > 0 => {
+                        x1 -= 1;
+                        modified = true;
                     }
-                    KeyCode::Right => {
-                        if x1 < n2 - 1 {
-                            x1 += 1;
-                            modified = true;
-                        }
+                    KeyCode::Right if x1 < n2 - 1 => {
+                        x1 += 1;
+                        modified = true;
                     }
                     _ => (),
                 };
@@ -1950,6 +2516,7 @@ impl SudokuDisplay {
     pub async fn run(&mut self, font: Font) {
         self.update_scale();
         self.update_selected_buttons();
+        self.update_timer();
 
         // BACKGROUND DRAWING
         clear_background(Color::from_hex(BACKGROUND_COLOR));
@@ -1974,29 +2541,74 @@ impl SudokuDisplay {
         // MOUSE LOGIC
         let (mouse_x, mouse_y) = (mouse_position().0, mouse_position().1);
         let is_mouse_pressed = is_mouse_button_pressed(MouseButton::Left);
+        self.update_playback();
+        self.update_editor_analysis();
+        if is_mouse_pressed && self.mode == ANALYSE {
+            self.playback_click((mouse_x, mouse_y));
+        }
+
+        // the wheel zooms on the cursor, dragging with the right or middle button pans the view
+        let is_mouse_on_grid = mouse_x >= self.x_offset
+            && mouse_x <= self.x_offset + self.grid_size
+            && mouse_y >= self.y_offset
+            && mouse_y <= self.y_offset + self.grid_size;
+        let (_, wheel_y) = mouse_wheel();
+        if wheel_y != 0. && is_mouse_on_grid {
+            self.zoom_view(wheel_y.signum(), (mouse_x, mouse_y));
+        }
+        let pan_buttons = [MouseButton::Right, MouseButton::Middle];
+        if is_mouse_on_grid && pan_buttons.into_iter().any(is_mouse_button_pressed) {
+            self.drag_origin = Some(((mouse_x, mouse_y), self.view_offset));
+        }
+        if let Some(((origin_x, origin_y), (offset_x, offset_y))) = self.drag_origin {
+            if pan_buttons.into_iter().any(is_mouse_button_down) {
+                self.view_offset = (offset_x + mouse_x - origin_x, offset_y + mouse_y - origin_y);
+                self.clamp_view();
+            } else {
+                self.drag_origin = None;
+            }
+        }
+        if is_mouse_button_down(MouseButton::Left) && self.is_on_minimap((mouse_x, mouse_y)) {
+            self.minimap_click((mouse_x, mouse_y));
+        }
+
+        // the completion overlay hides the grid until it is clicked
+        if self.completion_overlay && is_mouse_pressed && is_mouse_on_grid {
+            self.completion_overlay = false;
+        }
+        let is_grid_hidden =
+            self.completion_overlay || self.show_stats || self.show_assists || self.show_settings;
+
+        // a click selects a cell, shift or ctrl add it to the selection, and dragging selects the cells on the way
+        if !is_mouse_button_down(MouseButton::Left) {
+            self.drag_selecting = false;
+        }
         match self.get_cell_from_pixel((mouse_x, mouse_y)) {
-            Some(cell) => {
+            Some(cell) if !is_grid_hidden => {
                 if is_mouse_pressed {
-                    if self.selected_cell != Some(cell) {
-                        self.selected_cell = Some(cell);
+                    self.click_cell(cell);
+                    self.drag_selecting = self.selected_cell == Some(cell);
+                    if self.selected_cell == Some(cell) {
                         if let CarpetPattern::Torus(_) | CarpetPattern::DenseTorus =
                             self.carpet.get_pattern()
                         {
                             let size = self.carpet.get_pattern().get_size(self.carpet.get_n());
                             self.torus_view = (cell.0 % size, cell.0 / size);
                         }
-                    } else {
-                        self.selected_cell = None;
                     }
                 } else {
+                    if self.drag_selecting {
+                        self.drag_selection(cell);
+                    }
                     self.hovered_cell = Some(cell);
                 }
             }
-            None => self.hovered_cell = None,
+            _ => self.hovered_cell = None,
         }
 
         // BUTTONS DRAWING
         let mut action = None;
+        let (can_export, can_export_db) = (self.can_export(false), self.can_export(true));
         for bouton in self.button_list.iter_mut() {
             if self.mode == ANALYSE && bouton.text.chars().all(|c| c.is_ascii_digit()) {
                 continue;
@@ -2005,12 +2617,21 @@ impl SudokuDisplay {
                 bouton.text = format!("Lifes: {}", self.lifes);
             }
             if bouton.text == UNDO || bouton.text == REVERT_SOLVE {
-                if self.history.is_empty() {
+                if self.history.undo.is_empty() {
                     bouton.set_clickable(false);
                 } else {
                     bouton.set_clickable(true);
                 }
             }
+            if bouton.text == REDO {
+                bouton.set_clickable(!self.history.redo.is_empty());
+            }
+            if bouton.text == EXPORT {
+                bouton.set_clickable(can_export);
+            }
+            if bouton.text == EXPORT_DB {
+                bouton.set_clickable(can_export_db);
+            }
             bouton.set_scale_factor(self.scale_factor);
             if !bouton.enabled() {
                 continue;
@@ -2039,8 +2660,12 @@ impl SudokuDisplay {
         if let Some(action) = action {
             action(self);
         }
+        self.draw_game_stats(&font);
+        self.draw_digit_counts(&font);
 
-        if self.mode == ANALYSE {
+        if self.mode == ANALYSE && self.playback.is_some() {
+            self.draw_playback(&font);
+        } else if self.mode == ANALYSE {
             let font_size = self.grid_size / 45.;
             let bx_offset = 100. * self.scale_factor - self.pixel_per_cell / 2.;
             for (index, rule) in self.analyse_text.iter().enumerate() {
@@ -2058,6 +2683,8 @@ impl SudokuDisplay {
                     },
                 );
             }
+        } else if self.mode == EDIT {
+            self.draw_editor(&font);
         } else {
             let font_size = self.grid_size / 40.;
             draw_text_ex(
@@ -2080,33 +2707,65 @@ impl SudokuDisplay {
         }
 
         if self.last_processed_keys.is_none()
-            || self.last_processed_keys.unwrap().elapsed() > Duration::from_millis(200)
+            || self.last_processed_keys.unwrap().elapsed() > self.settings.key_repeat_delay
         {
             self.last_processed_keys = None;
+            let old_selected_cell = self.selected_cell;
             if self.move_torus_view(&pressed_keys)
                 || pressed_keys.iter().any(|key| self.process_single_key(*key))
             {
                 self.last_processed_keys = Some(Instant::now());
             }
+            if let Some(selected_cell) = self.selected_cell {
+                if self.selected_cell != old_selected_cell {
+                    self.move_selection(old_selected_cell);
+                    self.scroll_to_cell(selected_cell);
+                }
+            }
+        }
+
+        self.update_completion();
+        if self.last_autosave.elapsed() > Duration::from_secs(AUTOSAVE_DELAY_SECS) {
+            self.autosave();
         }
 
         // CARPET DRAWING
+        // a zoomed carpet is clipped to the grid area (its margins already hold the outside clues),
+        // plus half of the thickest line so that the border of the carpet is whole.
+        // The camera shows this part of the screen on the same pixels
+        let margin = self.pixel_per_cell / 8.;
+        let clip = Rect::new(
+            self.x_offset - margin,
+            self.y_offset - margin,
+            self.grid_size + 2. * margin,
+            self.grid_size + 2. * margin,
+        );
+        let dpi_scale = screen_dpi_scale();
+        let mut camera = Camera2D::from_display_rect(clip);
+        // without a render target, a display rect is drawn upside down
+        camera.zoom.y = -camera.zoom.y;
+        // the viewport is in physical pixels, from the bottom left corner of the window
+        camera.viewport = Some((
+            (clip.x * dpi_scale) as i32,
+            ((screen_height() - clip.y - clip.h) * dpi_scale) as i32,
+            (clip.w * dpi_scale) as i32,
+            (clip.h * dpi_scale) as i32,
+        ));
+        set_camera(&camera);
         match (self.carpet.get_pattern(), self.mode.as_str()) {
-            (CarpetPattern::Simple, _) => self.draw_simple_sudoku(font.clone(), 0, 0, 0).await,
-            (CarpetPattern::Samurai, _) => self.draw_samurai_sudoku(font.clone()).await,
-            (CarpetPattern::Diagonal(_), _) => self.draw_diag_sudoku(false, font.clone()).await,
-            (CarpetPattern::DenseDiagonal(_), _) => self.draw_diag_sudoku(true, font.clone()).await,
-            (CarpetPattern::Carpet(_), _) => self.draw_carpet_sudoku(false, font.clone()).await,
-            (CarpetPattern::DenseCarpet(_), _) => self.draw_carpet_sudoku(true, font.clone()).await,
             (CarpetPattern::Torus(_) | CarpetPattern::DenseTorus, PLAY) => {
                 let size = self.carpet.get_pattern().get_size(self.carpet.get_n());
                 let (sudoku_x, sudoku_y) = self.torus_view;
                 let sudoku_i = sudoku_y * size + sudoku_x;
                 self.draw_simple_sudoku(font.clone(), sudoku_i, 0, 0).await
             }
-            (CarpetPattern::Torus(_), _) => self.draw_carpet_sudoku(false, font.clone()).await,
-            (CarpetPattern::DenseTorus, _) => self.draw_carpet_sudoku(true, font.clone()).await,
-            (CarpetPattern::Custom(_), _) => panic!("Custom pattern not implemented"),
+            _ => self.draw_carpet_sudoku(font.clone()).await,
         }
+        set_default_camera();
+        self.draw_minimap();
+        self.draw_completion_overlay(&font);
+        self.draw_stats(&font);
+        self.draw_assists_panel(&font).await;
+        self.draw_settings_panel(&font).await;
     }
 }
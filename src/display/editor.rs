@@ -0,0 +1,267 @@
+use std::fs;
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, Receiver, TryRecvError};
+use std::sync::Arc;
+use std::thread;
+
+use macroquad::prelude::*;
+
+use super::display::FOREGROUND_COLOR;
+use super::settings::Settings;
+use super::SudokuDisplay;
+#[cfg(feature = "database")]
+use crate::carpet_sudoku::CarpetPattern;
+use crate::carpet_sudoku::CarpetSudoku;
+use crate::simple_sudoku::{SudokuDifficulty, SudokuError};
+
+/// Next to the settings, see [`Settings::get_data_path`]
+pub const EXPORT_FILE: &str = "puzzle.sudoku";
+
+/// The number of solutions of the givens, their difficulty and their solution when it is unique
+pub type EditorAnalysis = (usize, SudokuDifficulty, Option<CarpetSudoku>);
+
+/// What the editor tells about the givens, computed again after each change
+pub struct Editor {
+    /// The number of solutions of the givens, counted up to 2
+    pub solutions: usize,
+    /// The difficulty of the givens when their solution is unique
+    pub difficulty: SudokuDifficulty,
+    /// The solution of the givens when it is unique, its cells are shown lighter than the givens
+    pub solution: Option<CarpetSudoku>,
+    /// The analysis of the last givens while a worker thread computes it, a newer change drops it
+    pub analysis: Option<Receiver<EditorAnalysis>>,
+    /// Stops the worker thread of the analysis, set when the givens change or the editor closes
+    pub cancel: Arc<AtomicBool>,
+    /// What the last action of the editor did
+    pub message: String,
+}
+
+impl Drop for Editor {
+    fn drop(&mut self) {
+        self.cancel.store(true, Ordering::Relaxed);
+    }
+}
+
+impl SudokuDisplay {
+    pub(super) fn start_editing(&mut self) {
+        self.editor = Some(Editor {
+            solutions: 0,
+            difficulty: SudokuDifficulty::Unknown,
+            solution: None,
+            analysis: None,
+            cancel: Arc::new(AtomicBool::new(false)),
+            message: String::new(),
+        });
+        self.update_editor();
+    }
+
+    /// The givens become the game to play
+    pub(super) fn finish_editing(&mut self) {
+        if self.editor.take().is_none() {
+            return;
+        }
+        self.difficulty = self.carpet.rate_difficulty();
+        self.update_correction_board();
+        self.reset_game_stats();
+    }
+
+    /// Counts the solutions of the givens in a worker thread, and rates them when there is only one,
+    /// the worker of the previous givens is stopped so that only one keeps running
+    pub(super) fn update_editor(&mut self) {
+        let Some(editor) = self.editor.as_mut() else {
+            return;
+        };
+        editor.solutions = 0;
+        editor.difficulty = SudokuDifficulty::Unknown;
+        editor.solution = None;
+        editor.cancel.store(true, Ordering::Relaxed);
+        let cancel = Arc::new(AtomicBool::new(false));
+        editor.cancel = Arc::clone(&cancel);
+
+        let (tx, rx) = mpsc::channel();
+        let mut carpet = self.carpet.clone();
+        thread::spawn(move || {
+            let Some(solutions) = carpet.count_solutions_cancellable(Some(2), &cancel) else {
+                return;
+            };
+            let analysis = if solutions == 1 {
+                let mut solution = carpet.clone();
+                solution.backtrack_solve();
+                (solutions, carpet.rate_difficulty(), Some(solution))
+            } else {
+                (solutions, SudokuDifficulty::Unknown, None)
+            };
+            // the receiver is gone when the givens changed in the meantime
+            let _ = tx.send(analysis);
+        });
+        editor.analysis = Some(rx);
+    }
+
+    /// Takes the analysis of the worker thread once it's done
+    pub(super) fn update_editor_analysis(&mut self) {
+        let Some(editor) = self.editor.as_mut() else {
+            return;
+        };
+        let Some(analysis) = editor.analysis.as_ref() else {
+            return;
+        };
+        match analysis.try_recv() {
+            Ok((solutions, difficulty, solution)) => {
+                editor.solutions = solutions;
+                editor.difficulty = difficulty;
+                editor.solution = solution;
+                editor.analysis = None;
+            }
+            Err(TryRecvError::Empty) => (),
+            Err(TryRecvError::Disconnected) => editor.analysis = None,
+        }
+    }
+
+    /// Places the value as a given of the cell, or removes it if it is already there,
+    /// a value breaking a rule of the carpet isn't placed
+    pub(super) fn edit_given(&mut self, (sudoku_id, x, y): (usize, usize, usize), value: usize) {
+        let old_state = self.get_history_snapshot();
+        let current_value = self.carpet.get_cell_value(sudoku_id, x, y);
+        if current_value != 0 {
+            let _ = self.carpet.remove_value(sudoku_id, x, y);
+        }
+        let mut message = String::new();
+        if current_value != value && self.carpet.set_value(sudoku_id, x, y, value).is_err() {
+            let _ = self.carpet.remove_value(sudoku_id, x, y);
+            if current_value != 0 {
+                let _ = self.carpet.set_value(sudoku_id, x, y, current_value);
+            }
+            message = format!("{value} can't go there");
+        }
+        self.push_history(old_state);
+        self.update_editor();
+        if let Some(editor) = self.editor.as_mut() {
+            editor.message = message;
+        }
+    }
+
+    /// The value of the solution in a cell without given, when the editor knows the solution
+    pub(super) fn get_editor_solution_value(
+        &self,
+        sudoku_id: usize,
+        x: usize,
+        y: usize,
+    ) -> Option<usize> {
+        let solution = self.editor.as_ref()?.solution.as_ref()?;
+        match self.carpet.get_cell_value(sudoku_id, x, y) {
+            0 => Some(solution.get_cell_value(sudoku_id, x, y)),
+            _ => None,
+        }
+    }
+
+    /// Only a puzzle with a unique solution can be exported, and the database only stores classic sudokus
+    pub(super) fn can_export(&self, to_db: bool) -> bool {
+        let unique = self
+            .editor
+            .as_ref()
+            .is_some_and(|editor| editor.solutions == 1);
+        #[cfg(feature = "database")]
+        let connected =
+            self.database.is_some() && self.carpet.get_pattern() == CarpetPattern::Simple;
+        #[cfg(not(feature = "database"))]
+        let connected = false;
+        unique && (!to_db || connected)
+    }
+
+    /// Writes the rated givens to `file_path` (see [`CarpetSudoku::to_save_string`])
+    pub fn export_puzzle(&self, file_path: &str) -> Result<(), SudokuError> {
+        let mut puzzle = self.carpet.clone();
+        puzzle.rate_difficulty();
+
+        let write_error = |error: std::io::Error| {
+            SudokuError::WriteFile((file_path.to_string(), error.to_string()))
+        };
+        if let Some(dir) = Path::new(file_path).parent() {
+            fs::create_dir_all(dir).map_err(write_error)?;
+        }
+        fs::write(file_path, puzzle.to_save_string()).map_err(write_error)
+    }
+
+    pub(super) fn export_btn(&mut self) {
+        let file_path = Settings::get_data_path(EXPORT_FILE);
+        let message = match self.export_puzzle(&file_path) {
+            Ok(()) => format!("Exported to {file_path}"),
+            Err(error) => {
+                eprintln!("SudokuDisplay Error: {error}");
+                "Couldn't export the puzzle".to_string()
+            }
+        };
+        if let Some(editor) = self.editor.as_mut() {
+            editor.message = message;
+        }
+    }
+
+    /// Stores the givens in `canonical_sudoku_games` once canonized (see [`crate::simple_sudoku::Sudoku::to_canonical_game`]),
+    /// with their solution in `canonical_sudokus` if it isn't there yet
+    #[cfg(feature = "database")]
+    pub fn export_puzzle_to_db(&mut self) -> Result<(), String> {
+        let mut puzzle = self.carpet.clone();
+        puzzle.rate_difficulty();
+        let game = puzzle.get_sudokus()[0]
+            .to_canonical_game()
+            .map_err(|error| error.to_string())?;
+        let mut filled = game.clone();
+        filled.backtrack_solve(0, 0);
+        let (db_sudoku, db_squares) = filled.filled_to_db().map_err(|error| error.to_string())?;
+        let db_game = game.game_to_db().map_err(|error| error.to_string())?;
+
+        let database = self
+            .database
+            .as_mut()
+            .ok_or("not connected to the database")?;
+        database
+            .insert_canonical_sudoku(true, db_sudoku, db_squares)
+            .map_err(|error| error.to_string())?;
+        database
+            .insert_canonical_sudoku_game(false, db_game)
+            .map_err(|error| error.to_string())?;
+        Ok(())
+    }
+
+    #[cfg(feature = "database")]
+    pub(super) fn export_db_btn(&mut self) {
+        let message = match self.export_puzzle_to_db() {
+            Ok(()) => "Exported to the database".to_string(),
+            Err(error) => {
+                eprintln!("SudokuDisplay Error: {error}");
+                "Couldn't export the puzzle to the database".to_string()
+            }
+        };
+        if let Some(editor) = self.editor.as_mut() {
+            editor.message = message;
+        }
+    }
+
+    /// The number of solutions and the difficulty of the givens, then what the last action did
+    pub(super) fn draw_editor(&self, font: &Font) {
+        let Some(editor) = &self.editor else {
+            return;
+        };
+        let solutions = match editor.solutions {
+            _ if editor.analysis.is_some() => "Counting solutions…".to_string(),
+            0 => "No solution".to_string(),
+            1 => format!("Unique solution, {}", editor.difficulty),
+            _ => "Several solutions".to_string(),
+        };
+        let font_size = self.grid_size / 40.;
+        for (i, line) in [solutions, editor.message.clone()].iter().enumerate() {
+            draw_text_ex(
+                line,
+                self.x_offset + self.grid_size / 2.0 + (320. * self.scale_factor) + 10.,
+                60. * self.scale_factor + i as f32 * 1.5 * font_size,
+                TextParams {
+                    font: Some(font),
+                    font_size: font_size as u16,
+                    color: Color::from_hex(FOREGROUND_COLOR),
+                    ..Default::default()
+                },
+            );
+        }
+    }
+}
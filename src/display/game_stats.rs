@@ -0,0 +1,310 @@
+use std::time::{Duration, Instant};
+
+use macroquad::miniquad::{EventHandler, KeyMods};
+use macroquad::prelude::*;
+
+use super::display::{BACKGROUND_COLOR, FOREGROUND_COLOR, PLAY, STATS};
+use super::SudokuDisplay;
+use crate::player_stats::{PlayerGame, PlayerStatsSummary};
+
+/// Listens to the window events that macroquad doesn't expose as state.
+/// miniquad sends its minimized event when the window loses the focus (on X11, macOS, Windows and wasm),
+/// but its restored event only on X11 and wasm: elsewhere a click or a key press means the window is focused again
+#[derive(Default)]
+pub struct WindowFocus {
+    focused: Option<bool>,
+}
+
+impl EventHandler for WindowFocus {
+    fn update(&mut self) {}
+
+    fn draw(&mut self) {}
+
+    fn window_minimized_event(&mut self) {
+        self.focused = Some(false);
+    }
+
+    fn window_restored_event(&mut self) {
+        self.focused = Some(true);
+    }
+
+    fn mouse_button_down_event(&mut self, _button: MouseButton, _x: f32, _y: f32) {
+        self.focused = Some(true);
+    }
+
+    fn key_down_event(&mut self, _keycode: KeyCode, _keymods: KeyMods, _repeat: bool) {
+        self.focused = Some(true);
+    }
+}
+
+impl SudokuDisplay {
+    pub fn get_elapsed_time(&self) -> Duration {
+        self.timer
+            + self
+                .timer_start
+                .map_or(Duration::ZERO, |start| start.elapsed())
+    }
+
+    pub(super) fn reset_game_stats(&mut self) {
+        self.timer = Duration::ZERO;
+        self.timer_start = None;
+        self.mistakes = 0;
+        self.hints_used = 0;
+        self.solve_steps = 0;
+        self.completed = false;
+        self.solved_by_assist = false;
+        self.completion_overlay = false;
+    }
+
+    /// The clock only runs while playing an unfinished game in a focused window
+    pub(super) fn update_timer(&mut self) {
+        let mut window_focus = WindowFocus::default();
+        macroquad::input::utils::repeat_all_miniquad_input(
+            &mut window_focus,
+            self.focus_subscriber,
+        );
+        if let Some(focused) = window_focus.focused {
+            self.window_focused = focused;
+        }
+
+        let running = self.window_focused && self.mode == PLAY && !self.completed;
+        match (running, self.timer_start) {
+            (true, None) => self.timer_start = Some(Instant::now()),
+            (false, Some(start)) => {
+                self.timer += start.elapsed();
+                self.timer_start = None;
+            }
+            _ => (),
+        }
+    }
+
+    /// A game is completed when every cell is filled with the value of the correction board,
+    /// it's only won if the player filled it, not the solve button
+    pub(super) fn update_completion(&mut self) {
+        if self.completed || self.mode != PLAY || !self.carpet.is_filled() {
+            return;
+        }
+        for (sudoku_id, board) in self.correction_board.iter().enumerate() {
+            for (y, line) in board.iter().enumerate() {
+                for (x, &value) in line.iter().enumerate() {
+                    if value != 0 && self.carpet.get_cell_value(sudoku_id, x, y) != value {
+                        return;
+                    }
+                }
+            }
+        }
+
+        self.finish_game(!self.solved_by_assist);
+    }
+
+    /// Stops the clock, shows the completion overlay and records the game in the stats
+    pub(super) fn finish_game(&mut self, won: bool) {
+        self.completed = true;
+        self.completion_overlay = true;
+        self.update_timer();
+
+        let game = PlayerGame::new(
+            self.carpet.get_n(),
+            self.carpet.get_pattern(),
+            self.carpet.get_difficulty(),
+            self.get_elapsed_time(),
+            self.mistakes,
+            self.hints_used,
+            won,
+        );
+        if let Err(error) = self.stats.record(game) {
+            eprintln!("SudokuDisplay Error: {error}");
+        }
+        #[cfg(feature = "database")]
+        self.sync_stats();
+    }
+
+    #[cfg(feature = "database")]
+    pub(super) fn sync_stats(&mut self) {
+        if let Some(database) = &mut self.database {
+            if let Err(error) = self.stats.sync_db(database) {
+                eprintln!("SudokuDisplay Error: couldn't sync the stats: {error}");
+            }
+        }
+    }
+
+    pub(super) fn stats_btn(&mut self) {
+        if self.show_assists {
+            self.assists_btn();
+        }
+        if self.show_settings {
+            self.settings_btn();
+        }
+        self.show_stats = !self.show_stats;
+        for button in self.button_list.iter_mut() {
+            if button.text == STATS {
+                button.set_clicked(self.show_stats);
+            }
+        }
+    }
+
+    pub fn format_time(time: Duration) -> String {
+        let seconds = time.as_secs();
+        if seconds >= 3600 {
+            format!(
+                "{}:{:02}:{:02}",
+                seconds / 3600,
+                (seconds / 60) % 60,
+                seconds % 60
+            )
+        } else {
+            format!("{:02}:{:02}", seconds / 60, seconds % 60)
+        }
+    }
+
+    /// The clock and the mistakes above the pad
+    pub(super) fn draw_game_stats(&self, font: &Font) {
+        if self.mode != PLAY {
+            return;
+        }
+        let font_size = self.grid_size / 30.;
+        draw_text_ex(
+            format!(
+                "Time: {}   Mistakes: {}",
+                Self::format_time(self.get_elapsed_time()),
+                self.mistakes
+            ),
+            self.x_offset + self.grid_size + 100. * self.scale_factor,
+            self.y_offset + self.grid_size / 40.,
+            TextParams {
+                font: Some(font),
+                font_size: font_size as u16,
+                color: Color::from_hex(FOREGROUND_COLOR),
+                ..Default::default()
+            },
+        );
+    }
+
+    /// The summary of a completed game over the grid, until it is clicked
+    pub(super) fn draw_completion_overlay(&self, font: &Font) {
+        if !self.completion_overlay {
+            return;
+        }
+        let mut background = Color::from_hex(BACKGROUND_COLOR);
+        background.a = 0.9;
+        draw_rectangle(
+            self.x_offset,
+            self.y_offset,
+            self.grid_size,
+            self.grid_size,
+            background,
+        );
+
+        let elapsed = self.get_elapsed_time();
+        let won = self.lifes > 0 && !self.solved_by_assist;
+        let lines = [
+            if won {
+                "Completed!"
+            } else if self.solved_by_assist {
+                "Solved by the assists"
+            } else {
+                "Game over"
+            }
+            .to_string(),
+            format!(
+                "{} - {}",
+                self.carpet.get_pattern(),
+                self.carpet.get_difficulty()
+            ),
+            format!("Time: {}", Self::format_time(elapsed)),
+            format!("Mistakes: {}", self.mistakes),
+            format!("Hints used: {}", self.hints_used),
+            format!("Solve steps: {}", self.solve_steps),
+            format!(
+                "Score: {}",
+                if won {
+                    self.carpet
+                        .get_game_score(elapsed, self.mistakes, self.hints_used)
+                } else {
+                    0
+                }
+            ),
+            String::new(),
+            "Click to close".to_string(),
+        ];
+        let font_size = self.grid_size / 18.;
+        for (index, line) in lines.iter().enumerate() {
+            let size = measure_text(line, Some(font), font_size as u16, 1.);
+            draw_text_ex(
+                line,
+                self.x_offset + (self.grid_size - size.width) / 2.,
+                self.y_offset
+                    + (self.grid_size - font_size * lines.len() as f32) / 2.
+                    + font_size * (index + 1) as f32,
+                TextParams {
+                    font: Some(font),
+                    font_size: font_size as u16,
+                    color: Color::from_hex(FOREGROUND_COLOR),
+                    ..Default::default()
+                },
+            );
+        }
+    }
+
+    /// The games played, the streaks, and a summary per difficulty and per pattern over the grid
+    pub(super) fn draw_stats(&self, font: &Font) {
+        if !self.show_stats {
+            return;
+        }
+        draw_rectangle(
+            self.x_offset,
+            self.y_offset,
+            self.grid_size,
+            self.grid_size,
+            Color::from_hex(BACKGROUND_COLOR),
+        );
+
+        let summary_line = |name: String, summary: &PlayerStatsSummary| {
+            let time = |time: Option<Duration>| time.map_or("-".to_string(), Self::format_time);
+            format!(
+                "{name:<18}{:>6}{:>6.0}%  {:<16}{}",
+                summary.played,
+                100. * summary.get_win_rate(),
+                time(summary.best_time),
+                time(summary.average_time)
+            )
+        };
+        let header = format!(
+            "{:<18}{:>6}{:>7}  {:<16}{}",
+            "", "Games", "Wins", "Best", "Average"
+        );
+
+        let (current_streak, best_streak) = self.stats.get_streaks();
+        let mut lines = vec![
+            "Statistics".to_string(),
+            String::new(),
+            summary_line("All games".to_string(), &self.stats.get_summary(|_| true)),
+            format!("Current streak: {current_streak}   Best streak: {best_streak}"),
+            String::new(),
+            header.clone(),
+        ];
+        for (difficulty, summary) in self.stats.get_summaries_by_difficulty() {
+            lines.push(summary_line(difficulty.to_string(), &summary));
+        }
+        lines.push(String::new());
+        lines.push(header);
+        for (pattern, summary) in self.stats.get_summaries_by_pattern() {
+            lines.push(summary_line(pattern.to_string(), &summary));
+        }
+
+        let font_size = (self.grid_size / 40.).min(self.grid_size / (lines.len() + 2) as f32);
+        for (index, line) in lines.iter().enumerate() {
+            draw_text_ex(
+                line,
+                self.x_offset + self.grid_size / 20.,
+                self.y_offset + font_size * (index + 2) as f32,
+                TextParams {
+                    font: Some(font),
+                    font_size: font_size as u16,
+                    color: Color::from_hex(FOREGROUND_COLOR),
+                    ..Default::default()
+                },
+            );
+        }
+    }
+}
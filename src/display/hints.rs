@@ -0,0 +1,337 @@
+use std::collections::{HashMap, HashSet};
+
+use ::rand::rng;
+use ::rand::seq::IteratorRandom;
+use macroquad::prelude::*;
+
+use super::display::{ANALYSE, HINT_ELIMINATION_COLOR, HINT_PATTERN_COLOR, HINT_UNIT_COLOR, PLAY};
+use super::SudokuDisplay;
+use crate::carpet_sudoku::{CarpetDiff, CarpetPattern, CarpetSudoku};
+use crate::simple_sudoku::{Sudoku, SudokuGroups::All, SudokuStep};
+
+/// The cells of a rule step in every sudoku they belong to
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct StepCells {
+    pub unit: HashSet<(usize, usize, usize)>,
+    pub pattern: HashSet<(usize, usize, usize)>,
+    pub eliminations: HashSet<((usize, usize, usize), usize)>,
+    pub placements: HashSet<((usize, usize, usize), usize)>,
+}
+
+impl StepCells {
+    pub fn new(carpet: &CarpetSudoku, sudoku_id: usize, step: &SudokuStep) -> Self {
+        let twins = |cells: &HashSet<(usize, usize)>| {
+            cells
+                .iter()
+                .flat_map(|&(x, y)| carpet.get_twin_cells(sudoku_id, x, y))
+                .collect()
+        };
+        let twin_values = |values: &[((usize, usize), usize)]| {
+            values
+                .iter()
+                .flat_map(|&((x, y), value)| {
+                    carpet
+                        .get_twin_cells(sudoku_id, x, y)
+                        .into_iter()
+                        .map(move |cell| (cell, value))
+                })
+                .collect()
+        };
+        Self {
+            unit: twins(&step.unit),
+            pattern: twins(&step.pattern),
+            eliminations: twin_values(&step.eliminations),
+            placements: twin_values(&step.placements),
+        }
+    }
+
+    /// The carpet rules don't tell their pattern, the cells losing candidates stand for it
+    pub fn from_diff(diff: &CarpetDiff) -> Self {
+        let mut cells = Self::default();
+        for &(cell, (value, ref possibilities), (value_after, ref possibilities_after)) in
+            diff.iter()
+        {
+            if value != value_after {
+                cells.placements.insert((cell, value_after));
+                cells.pattern.insert(cell);
+                continue;
+            }
+            for &value in possibilities.difference(possibilities_after) {
+                cells.eliminations.insert((cell, value));
+                cells.pattern.insert(cell);
+            }
+        }
+        cells.unit = cells.pattern.clone();
+        cells
+    }
+}
+
+/// The hint shown to the player, each click on the hint button tells a bit more
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Hint {
+    /// 1 names the rule, 2 shows its unit, 3 shows its pattern and what it removes
+    pub tier: usize,
+    pub sudoku_id: usize,
+    /// How the hinted sudoku is named in the hint text
+    pub sudoku_name: String,
+    pub step: SudokuStep,
+    pub cells: StepCells,
+}
+
+impl SudokuDisplay {
+    pub(super) fn clear_hint(&mut self) {
+        self.hint = None;
+        self.hint_text.clear();
+    }
+
+    /// Finds the next step of the hinted sudoku, the same step shows more of itself until it's applied
+    pub fn hint(&mut self) {
+        if self.carpet.get_filled_cells() == 0 {
+            return;
+        }
+        if self.mode != PLAY {
+            if let Some(action) = self.buttons_action.get(PLAY).cloned() {
+                action(self);
+            }
+        }
+        if let Some(hint) = self.hint.as_mut() {
+            if hint.tier < 3 {
+                hint.tier += 1;
+                self.update_hint_text();
+            } else {
+                self.apply_hint();
+            }
+            return;
+        }
+
+        self.hint_text.clear();
+        let mut steps: HashMap<usize, SudokuStep> = HashMap::new();
+        for i in 0..self.carpet.get_n_sudokus() {
+            if let Some(step) = self
+                .carpet
+                .get_sudoku(i)
+                .and_then(|sudoku| sudoku.find_step(Some(self.carpet.get_difficulty())))
+            {
+                steps.insert(i, step);
+            }
+        }
+        if steps.is_empty() {
+            return;
+        }
+        self.hints_used += 1;
+
+        let (sudoku_id, sudoku_name) = match self.carpet.get_pattern() {
+            CarpetPattern::Torus(_) | CarpetPattern::DenseTorus => {
+                let torus_size = self.carpet.get_pattern().get_size(self.carpet.get_n());
+                let torus_view = self.torus_view.1 * torus_size + self.torus_view.0;
+                (torus_view, "Viewing sudoku".to_string())
+            }
+            CarpetPattern::Simple => (0, "Sudoku".to_string()),
+            _ => {
+                let sudoku_id = *steps.keys().choose(&mut rng()).unwrap();
+                (sudoku_id, format!("Sudoku {sudoku_id}"))
+            }
+        };
+        let Some(step) = steps.remove(&sudoku_id) else {
+            self.hint_text = "No hint available".to_string();
+            return;
+        };
+
+        let cells = StepCells::new(&self.carpet, sudoku_id, &step);
+        self.hint = Some(Hint {
+            tier: 1,
+            sudoku_id,
+            sudoku_name,
+            step,
+            cells,
+        });
+        self.update_hint_text();
+    }
+
+    fn update_hint_text(&mut self) {
+        let Some(hint) = &self.hint else {
+            return;
+        };
+        let rule_name = Sudoku::get_rule_name_by_id(hint.step.rule);
+        self.hint_text = match hint.tier {
+            1 => format!("{} can use \"{rule_name}\"", hint.sudoku_name),
+            2 => format!("\"{rule_name}\" happens in the highlighted cells"),
+            _ => match (hint.step.placements.first(), hint.step.eliminations.len()) {
+                (Some(&(_, value)), _) => format!("\"{rule_name}\" places a {value}"),
+                (None, 1) => format!("\"{rule_name}\" removes 1 candidate"),
+                (None, count) => format!("\"{rule_name}\" removes {count} candidates"),
+            },
+        };
+    }
+
+    /// The last tier of the hint does the step for the player
+    fn apply_hint(&mut self) {
+        let Some(hint) = self.hint.take() else {
+            return;
+        };
+        let old_state = self.get_history_snapshot();
+        let sudoku_id = hint.sudoku_id;
+
+        for &((x, y), value) in hint.step.placements.iter() {
+            if self.carpet.get_cell_value(sudoku_id, x, y) != 0
+                || self.carpet.set_value(sudoku_id, x, y, value).is_err()
+            {
+                continue;
+            }
+            self.solve_steps += 1;
+            self.clear_placed_notes(sudoku_id, x, y, value);
+        }
+
+        for &((sudoku_id, x, y), value) in hint.cells.eliminations.iter() {
+            self.carpet
+                .get_cell_possibilities_mut(sudoku_id, x, y)
+                .remove(&value);
+            self.player_pboard[sudoku_id][y][x].remove(&value);
+            self.player_marks[sudoku_id][y][x].remove(value);
+        }
+
+        self.push_history(old_state);
+        self.clear_hint();
+    }
+
+    /// Removes the notes of the cell where a value is placed, and of its group with the assist
+    pub(super) fn clear_placed_notes(
+        &mut self,
+        sudoku_i: usize,
+        x1: usize,
+        y1: usize,
+        value: usize,
+    ) {
+        for (sudoku2, x2, y2) in self.carpet.get_twin_cells(sudoku_i, x1, y1) {
+            self.player_pboard[sudoku2][y2][x2].clear();
+            self.player_marks[sudoku2][y2][x2].clear_digits();
+        }
+        if !self.assists.auto_remove_notes {
+            return;
+        }
+        for (sudoku2, x2, y2) in self.carpet.get_global_cell_group(sudoku_i, x1, y1, All) {
+            for (sudoku3, x3, y3) in self.carpet.get_twin_cells(sudoku2, x2, y2) {
+                if self.carpet.get_cell_value(sudoku3, x3, y3) == 0 {
+                    self.player_pboard[sudoku3][y3][x3].remove(&value);
+                    self.player_marks[sudoku3][y3][x3].remove(value);
+                }
+            }
+        }
+    }
+
+    /// The step of the hint, or the one of the playback in analyse mode, with how much of it is shown
+    /// (see [`Hint::tier`]) and its opacity
+    fn get_shown_step(&self) -> Option<(&StepCells, usize, f32)> {
+        if self.mode == ANALYSE {
+            return self.get_playback_step();
+        }
+        self.hint.as_ref().map(|hint| (&hint.cells, hint.tier, 1.))
+    }
+
+    /// The unit of the step from the second tier, its pattern from the third one
+    pub(super) fn draw_step_cells(
+        &self,
+        sudoku_i: usize,
+        (sudoku_x_offset, sudoku_y_offset): (f32, f32),
+    ) {
+        let Some((cells, tier, alpha)) = self.get_shown_step() else {
+            return;
+        };
+        if tier < 2 {
+            return;
+        }
+        for &(sudoku_id, x, y) in cells.unit.iter().chain(cells.pattern.iter()) {
+            if sudoku_id != sudoku_i {
+                continue;
+            }
+            let color = if tier >= 3 && cells.pattern.contains(&(sudoku_id, x, y)) {
+                HINT_PATTERN_COLOR
+            } else {
+                HINT_UNIT_COLOR
+            };
+            draw_rectangle(
+                (x as f32) * self.pixel_per_cell + sudoku_x_offset,
+                (y as f32) * self.pixel_per_cell + sudoku_y_offset,
+                self.pixel_per_cell,
+                self.pixel_per_cell,
+                Color::from_hex(color).with_alpha(alpha),
+            );
+        }
+    }
+
+    /// At the third tier, the candidates the step removes are crossed out in their place of the cell,
+    /// and the value it places is written in its cell
+    pub(super) fn draw_step_eliminations(
+        &self,
+        font: &Font,
+        sudoku_i: usize,
+        (sudoku_x_offset, sudoku_y_offset): (f32, f32),
+    ) {
+        let Some((cells, tier, alpha)) = self.get_shown_step() else {
+            return;
+        };
+        if tier < 3 {
+            return;
+        }
+        let n = self.carpet.get_n();
+        let mini_cell = self.pixel_per_cell / (n as f32);
+        let color = Color::from_hex(HINT_ELIMINATION_COLOR).with_alpha(alpha);
+
+        let font_size = ((self.pixel_per_cell as u16) * 2) / (3 * (n as u16));
+        for &((sudoku_id, x, y), value) in cells.eliminations.iter() {
+            if sudoku_id != sudoku_i {
+                continue;
+            }
+            let (i, j) = ((value - 1) % n, (value - 1) / n);
+            let text = value.to_string();
+            let size = measure_text(&text, Some(font), font_size, 1.0);
+            let cell_x =
+                (x as f32) * self.pixel_per_cell + (i as f32) * mini_cell + sudoku_x_offset;
+            let cell_y =
+                (y as f32) * self.pixel_per_cell + (j as f32) * mini_cell + sudoku_y_offset;
+            draw_text_ex(
+                &text,
+                cell_x + (mini_cell - size.width) / 2.0,
+                cell_y + (mini_cell + size.height) / 2.0,
+                TextParams {
+                    font: Some(font),
+                    font_size,
+                    color,
+                    ..Default::default()
+                },
+            );
+            draw_line(
+                cell_x + mini_cell * 0.15,
+                cell_y + mini_cell * 0.85,
+                cell_x + mini_cell * 0.85,
+                cell_y + mini_cell * 0.15,
+                2.0,
+                color,
+            );
+        }
+
+        let font_size = ((self.pixel_per_cell as u16) * 2) / 3;
+        for &((sudoku_id, x, y), value) in cells.placements.iter() {
+            if sudoku_id != sudoku_i {
+                continue;
+            }
+            let text = value.to_string();
+            let size = measure_text(&text, Some(font), font_size, 1.0);
+            draw_text_ex(
+                &text,
+                (x as f32) * self.pixel_per_cell
+                    + (self.pixel_per_cell - size.width) / 2.0
+                    + sudoku_x_offset,
+                (y as f32) * self.pixel_per_cell
+                    + (self.pixel_per_cell + size.height) / 2.0
+                    + sudoku_y_offset,
+                TextParams {
+                    font: Some(font),
+                    font_size,
+                    color,
+                    ..Default::default()
+                },
+            );
+        }
+    }
+}
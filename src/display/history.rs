@@ -0,0 +1,200 @@
+use super::display::PLAY;
+use super::{CellState, HistoryDiff, HistorySnapshot, MarksBoard, PlayerBoard, SudokuDisplay};
+use crate::carpet_sudoku::CarpetSudoku;
+
+/// The actions of the player that can be undone, and the undone actions that can be redone
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct History {
+    pub undo: Vec<HistoryDiff>,
+    pub redo: Vec<HistoryDiff>,
+}
+
+impl History {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The values, possibilities, notes and marks of every cell, to compare with after an action
+    pub fn snapshot(
+        carpet: &CarpetSudoku,
+        player_pboard: &PlayerBoard,
+        player_marks: &MarksBoard,
+    ) -> HistorySnapshot {
+        (
+            carpet
+                .get_sudokus()
+                .iter()
+                .map(|sudoku| sudoku.get_board().clone())
+                .collect(),
+            carpet.get_possibility_board(),
+            player_pboard.clone(),
+            player_marks.clone(),
+        )
+    }
+
+    /// The cells changed since the snapshot, with their state before and after
+    pub fn diff(
+        (old_board, old_possibilities, old_pboard, old_marks): HistorySnapshot,
+        carpet: &CarpetSudoku,
+        player_pboard: &PlayerBoard,
+        player_marks: &MarksBoard,
+    ) -> HistoryDiff {
+        let mut diff = HistoryDiff::new();
+        for (sudoku_id, board) in old_board.iter().enumerate() {
+            for (y, line) in board.iter().enumerate() {
+                for (x, &old_value) in line.iter().enumerate() {
+                    let value = carpet.get_cell_value(sudoku_id, x, y);
+                    let possibilities = carpet.get_cell_possibilities(sudoku_id, x, y);
+                    let notes = &player_pboard[sudoku_id][y][x];
+                    let marks = &player_marks[sudoku_id][y][x];
+                    let old_cell_possibilities = &old_possibilities[sudoku_id][y][x];
+                    let old_notes = &old_pboard[sudoku_id][y][x];
+                    let old_cell_marks = &old_marks[sudoku_id][y][x];
+                    if value != old_value
+                        || &possibilities != old_cell_possibilities
+                        || notes != old_notes
+                        || marks != old_cell_marks
+                    {
+                        diff.push((
+                            (sudoku_id, x, y),
+                            (
+                                old_value,
+                                old_cell_possibilities.clone(),
+                                old_notes.clone(),
+                                old_cell_marks.clone(),
+                            ),
+                            (value, possibilities, notes.clone(), marks.clone()),
+                        ));
+                    }
+                }
+            }
+        }
+        diff
+    }
+
+    /// Records the cells changed since the snapshot as one undoable action, and forgets the undone actions.
+    /// Returns false when nothing changed, nothing is recorded then
+    pub fn push(
+        &mut self,
+        snapshot: HistorySnapshot,
+        carpet: &CarpetSudoku,
+        player_pboard: &PlayerBoard,
+        player_marks: &MarksBoard,
+    ) -> bool {
+        let diff = Self::diff(snapshot, carpet, player_pboard, player_marks);
+        if diff.is_empty() {
+            return false;
+        }
+        self.undo.push(diff);
+        self.redo.clear();
+        true
+    }
+
+    pub fn clear(&mut self) {
+        self.undo.clear();
+        self.redo.clear();
+    }
+
+    /// Puts the cells of the diff back as they were before (or after, to redo) the action
+    pub fn apply_diff(
+        diff: &HistoryDiff,
+        undo: bool,
+        carpet: &mut CarpetSudoku,
+        player_pboard: &mut PlayerBoard,
+        player_marks: &mut MarksBoard,
+    ) {
+        for ((sudoku_id, x, y), before, after) in diff.iter() {
+            let (value, possibilities, notes, marks): &CellState =
+                if undo { before } else { after };
+            carpet.restore_cell(*sudoku_id, *x, *y, *value, possibilities.clone());
+            player_pboard[*sudoku_id][*y][*x] = notes.clone();
+            player_marks[*sudoku_id][*y][*x] = marks.clone();
+        }
+    }
+
+    /// Cancels the last action, returns false when there is none
+    pub fn undo(
+        &mut self,
+        carpet: &mut CarpetSudoku,
+        player_pboard: &mut PlayerBoard,
+        player_marks: &mut MarksBoard,
+    ) -> bool {
+        let Some(diff) = self.undo.pop() else {
+            return false;
+        };
+        Self::apply_diff(&diff, true, carpet, player_pboard, player_marks);
+        self.redo.push(diff);
+        true
+    }
+
+    /// Replays the last undone action, returns false when there is none
+    pub fn redo(
+        &mut self,
+        carpet: &mut CarpetSudoku,
+        player_pboard: &mut PlayerBoard,
+        player_marks: &mut MarksBoard,
+    ) -> bool {
+        let Some(diff) = self.redo.pop() else {
+            return false;
+        };
+        Self::apply_diff(&diff, false, carpet, player_pboard, player_marks);
+        self.undo.push(diff);
+        true
+    }
+}
+
+impl SudokuDisplay {
+    pub(super) fn get_history_snapshot(&self) -> HistorySnapshot {
+        History::snapshot(&self.carpet, &self.player_pboard, &self.player_marks)
+    }
+
+    pub(super) fn push_history(&mut self, old_state: HistorySnapshot) {
+        self.history.push(
+            old_state,
+            &self.carpet,
+            &self.player_pboard,
+            &self.player_marks,
+        );
+    }
+
+    pub(super) fn clear_history(&mut self) {
+        self.history.clear();
+    }
+
+    /// What an undone or redone action invalidates
+    fn after_history_change(&mut self) {
+        self.clear_hint();
+        self.stop_playback();
+        self.analyse_text.clear();
+        self.update_editor();
+    }
+
+    /// A finished game can't be changed anymore
+    fn can_change_history(&self) -> bool {
+        !(self.completed && self.mode == PLAY)
+    }
+
+    pub(super) fn undo_btn(&mut self) {
+        if self.can_change_history()
+            && self.history.undo(
+                &mut self.carpet,
+                &mut self.player_pboard,
+                &mut self.player_marks,
+            )
+        {
+            self.after_history_change();
+        }
+    }
+
+    pub(super) fn redo_btn(&mut self) {
+        if self.can_change_history()
+            && self.history.redo(
+                &mut self.carpet,
+                &mut self.player_pboard,
+                &mut self.player_marks,
+            )
+        {
+            self.after_history_change();
+        }
+    }
+}
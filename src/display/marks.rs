@@ -0,0 +1,333 @@
+use std::collections::BTreeSet;
+
+use macroquad::prelude::*;
+
+use super::display::{
+    CELL_COLOR, CENTRE_MARKS, COLOR_INDICATOR, CORNER_MARKS, FOREGROUND_COLOR, NOTE,
+};
+use super::{MarksBoard, SudokuDisplay};
+
+/// What a digit does in note mode
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum MarkMode {
+    /// The notes in their fixed place of the mini grid of the cell, with the selected color
+    #[default]
+    Candidate,
+    /// Snyder marks, packed in the corners and on the borders of the cell
+    Corner,
+    /// The digits the cell can hold, written in its centre
+    Centre,
+    /// The digits and the color buttons paint the cell
+    CellColor,
+}
+
+/// The pencil marks and the color the player put on a cell, besides the candidate notes
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct PlayerMarks {
+    pub corner: BTreeSet<usize>,
+    pub centre: BTreeSet<usize>,
+    pub color: Option<u32>,
+}
+
+impl PlayerMarks {
+    pub fn is_empty(&self) -> bool {
+        self.corner.is_empty() && self.centre.is_empty() && self.color.is_none()
+    }
+
+    /// Removes a digit from the corner and centre marks
+    pub fn remove(&mut self, value: usize) {
+        self.corner.remove(&value);
+        self.centre.remove(&value);
+    }
+
+    /// The corner or the centre marks
+    pub fn get_digits(&self, mark_mode: MarkMode) -> Option<&BTreeSet<usize>> {
+        match mark_mode {
+            MarkMode::Corner => Some(&self.corner),
+            MarkMode::Centre => Some(&self.centre),
+            _ => None,
+        }
+    }
+
+    pub fn get_digits_mut(&mut self, mark_mode: MarkMode) -> Option<&mut BTreeSet<usize>> {
+        match mark_mode {
+            MarkMode::Corner => Some(&mut self.corner),
+            MarkMode::Centre => Some(&mut self.centre),
+            _ => None,
+        }
+    }
+
+    /// Removes the corner and centre marks, the color stays
+    pub fn clear_digits(&mut self) {
+        self.corner.clear();
+        self.centre.clear();
+    }
+}
+
+impl SudokuDisplay {
+    pub(super) fn new_player_marks(n2: usize, n_sudokus: usize) -> MarksBoard {
+        vec![vec![vec![PlayerMarks::default(); n2]; n2]; n_sudokus]
+    }
+
+    pub(super) fn reset_player_marks(&mut self) {
+        self.player_marks =
+            Self::new_player_marks(self.carpet.get_n2(), self.carpet.get_n_sudokus());
+    }
+
+    /// The cell under the cursor and the other cells of the selection
+    pub fn get_selected_cells(&self) -> Vec<(usize, usize, usize)> {
+        let mut cells = self.selected_cells.iter().copied().collect::<Vec<_>>();
+        if let Some(cell) = self.selected_cell {
+            if !cells.contains(&cell) {
+                cells.push(cell);
+            }
+        }
+        cells.sort();
+        cells
+    }
+
+    /// A plain click selects the cell alone (or unselects it), shift and ctrl add it to the selection or remove it
+    pub(super) fn click_cell(&mut self, cell: (usize, usize, usize)) {
+        let extend = [
+            KeyCode::LeftShift,
+            KeyCode::RightShift,
+            KeyCode::LeftControl,
+            KeyCode::RightControl,
+        ]
+        .into_iter()
+        .any(is_key_down);
+        if !extend {
+            let unselect = self.selected_cell == Some(cell) && self.selected_cells.is_empty();
+            self.selected_cells.clear();
+            self.selected_cell = (!unselect).then_some(cell);
+            return;
+        }
+
+        if let Some(selected_cell) = self.selected_cell {
+            self.selected_cells.insert(selected_cell);
+        }
+        if self.selected_cells.remove(&cell) {
+            self.selected_cell = self.selected_cells.iter().next().copied();
+        } else {
+            self.selected_cell = Some(cell);
+        }
+    }
+
+    /// Dragging from a selected cell adds every cell the cursor goes over
+    pub(super) fn drag_selection(&mut self, cell: (usize, usize, usize)) {
+        let Some(selected_cell) = self.selected_cell else {
+            return;
+        };
+        if selected_cell == cell {
+            return;
+        }
+        self.selected_cells.insert(selected_cell);
+        self.selected_cell = Some(cell);
+    }
+
+    /// Moving the cursor with the keyboard keeps the previous cell in the selection while shift is down
+    pub(super) fn move_selection(&mut self, old_selected_cell: Option<(usize, usize, usize)>) {
+        match old_selected_cell {
+            Some(old_cell)
+                if is_key_down(KeyCode::LeftShift) || is_key_down(KeyCode::RightShift) =>
+            {
+                self.selected_cells.insert(old_cell);
+            }
+            _ => self.selected_cells.clear(),
+        }
+    }
+
+    pub(super) fn clear_selection(&mut self) {
+        self.selected_cell = None;
+        self.selected_cells.clear();
+    }
+
+    /// The same mode button leaves note mode, another one switches to its mode
+    pub(super) fn mark_mode_btn(&mut self, mark_mode: MarkMode) {
+        if !self.note || self.mark_mode == mark_mode {
+            self.notes_btn();
+        }
+        self.mark_mode = mark_mode;
+        self.update_mark_buttons();
+    }
+
+    pub(super) fn update_mark_buttons(&mut self) {
+        for button in self.button_list.iter_mut() {
+            let mark_mode = match button.text.as_str() {
+                NOTE => MarkMode::Candidate,
+                CORNER_MARKS => MarkMode::Corner,
+                CENTRE_MARKS => MarkMode::Centre,
+                CELL_COLOR => MarkMode::CellColor,
+                _ => continue,
+            };
+            button.set_clicked(self.note && self.mark_mode == mark_mode);
+        }
+    }
+
+    /// Adds the digit to the marks of every selected empty cell, or removes it when they all have it
+    pub(super) fn toggle_mark(&mut self, mark_mode: MarkMode, value: usize) {
+        let cells = self
+            .get_selected_cells()
+            .into_iter()
+            .filter(|&(sudoku_id, x, y)| self.carpet.get_cell_value(sudoku_id, x, y) == 0)
+            .flat_map(|(sudoku_id, x, y)| self.carpet.get_twin_cells(sudoku_id, x, y))
+            .collect::<Vec<_>>();
+
+        match mark_mode {
+            MarkMode::Candidate => {
+                let color = self.selected_color;
+                let remove = cells.iter().all(|&(sudoku_id, x, y)| {
+                    self.player_pboard[sudoku_id][y][x].get(&value) == Some(&color)
+                });
+                for (sudoku_id, x, y) in cells {
+                    if remove {
+                        self.player_pboard[sudoku_id][y][x].remove(&value);
+                    } else {
+                        self.player_pboard[sudoku_id][y][x].insert(value, color);
+                    }
+                }
+            }
+            MarkMode::Corner | MarkMode::Centre => {
+                let remove = cells.iter().all(|&(sudoku_id, x, y)| {
+                    self.player_marks[sudoku_id][y][x]
+                        .get_digits(mark_mode)
+                        .is_some_and(|digits| digits.contains(&value))
+                });
+                for (sudoku_id, x, y) in cells {
+                    if let Some(digits) =
+                        self.player_marks[sudoku_id][y][x].get_digits_mut(mark_mode)
+                    {
+                        if remove {
+                            digits.remove(&value);
+                        } else {
+                            digits.insert(value);
+                        }
+                    }
+                }
+            }
+            // the digits pick the colors in the order of the color buttons
+            MarkMode::CellColor => {
+                if let Some(&color) = self.settings.colors.get(value - 1) {
+                    self.paint_cells(color);
+                }
+            }
+        }
+    }
+
+    /// Paints the selected cells, filled or not, the first color removes the paint
+    pub(super) fn paint_cells(&mut self, color: u32) {
+        let color = (color != self.settings.colors[0]).then_some(color);
+        for (sudoku_id, x, y) in self.get_selected_cells() {
+            for (sudoku2, x2, y2) in self.carpet.get_twin_cells(sudoku_id, x, y) {
+                self.player_marks[sudoku2][y2][x2].color = color;
+            }
+        }
+    }
+
+    /// In cell color mode the color buttons paint the selection, otherwise they pick the color of the notes
+    pub(super) fn color_btn(&mut self, color: u32) {
+        if self.note && self.mark_mode == MarkMode::CellColor {
+            let old_state = self.get_history_snapshot();
+            self.paint_cells(color);
+            self.push_history(old_state);
+            return;
+        }
+        self.selected_color = color;
+        for button in self.button_list.iter_mut() {
+            if button.text == COLOR_INDICATOR {
+                button.background_color = Color::from_hex(color);
+            }
+        }
+    }
+
+    pub(super) fn draw_cell_colors(
+        &self,
+        sudoku_i: usize,
+        (sudoku_x_offset, sudoku_y_offset): (f32, f32),
+    ) {
+        for (y, line) in self.player_marks[sudoku_i].iter().enumerate() {
+            for (x, marks) in line.iter().enumerate() {
+                if let Some(color) = marks.color {
+                    draw_rectangle(
+                        (x as f32) * self.pixel_per_cell + sudoku_x_offset,
+                        (y as f32) * self.pixel_per_cell + sudoku_y_offset,
+                        self.pixel_per_cell,
+                        self.pixel_per_cell,
+                        Color::from_hex(color).with_alpha(0.6),
+                    );
+                }
+            }
+        }
+    }
+
+    /// The corner marks fill the corners of the mini grid of the cell first, then its other places,
+    /// the centre marks are written in a line that shrinks to fit the cell
+    pub(super) fn draw_pencil_marks(
+        &self,
+        font: &Font,
+        sudoku_i: usize,
+        (sudoku_x_offset, sudoku_y_offset): (f32, f32),
+    ) {
+        let n = self.carpet.get_n();
+        let mut places = vec![(0, 0), (n - 1, 0), (0, n - 1), (n - 1, n - 1)];
+        places.dedup();
+        for j in 0..n {
+            for i in 0..n {
+                if !places.contains(&(i, j)) {
+                    places.push((i, j));
+                }
+            }
+        }
+
+        let font_size = ((self.pixel_per_cell as u16) * 2) / (3 * (n as u16));
+        let mini_cell = self.pixel_per_cell / (n as f32);
+        let params = |font_size: u16| TextParams {
+            font: Some(font),
+            font_size,
+            color: Color::from_hex(FOREGROUND_COLOR),
+            ..Default::default()
+        };
+        for (y, line) in self.player_marks[sudoku_i].iter().enumerate() {
+            for (x, marks) in line.iter().enumerate() {
+                if self.carpet.get_cell_value(sudoku_i, x, y) != 0 {
+                    continue;
+                }
+                let cell_x = (x as f32) * self.pixel_per_cell + sudoku_x_offset;
+                let cell_y = (y as f32) * self.pixel_per_cell + sudoku_y_offset;
+
+                for (&value, &(i, j)) in marks.corner.iter().zip(places.iter()) {
+                    let text = value.to_string();
+                    let size = measure_text(&text, Some(font), font_size, 1.0);
+                    draw_text_ex(
+                        &text,
+                        cell_x + (i as f32) * mini_cell + (mini_cell - size.width) / 2.0,
+                        cell_y + (j as f32) * mini_cell + (mini_cell + size.height) / 2.0,
+                        params(font_size),
+                    );
+                }
+
+                if marks.centre.is_empty() {
+                    continue;
+                }
+                let text = marks
+                    .centre
+                    .iter()
+                    .map(|value| value.to_string())
+                    .collect::<String>();
+                let mut centre_font_size = font_size * 3 / 2;
+                let mut size = measure_text(&text, Some(font), centre_font_size, 1.0);
+                if size.width > self.pixel_per_cell * 0.8 {
+                    centre_font_size =
+                        (centre_font_size as f32 * self.pixel_per_cell * 0.8 / size.width) as u16;
+                    size = measure_text(&text, Some(font), centre_font_size, 1.0);
+                }
+                draw_text_ex(
+                    &text,
+                    cell_x + (self.pixel_per_cell - size.width) / 2.0,
+                    cell_y + (self.pixel_per_cell + size.height) / 2.0,
+                    params(centre_font_size),
+                );
+            }
+        }
+    }
+}
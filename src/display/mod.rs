@@ -1,27 +1,59 @@
+pub mod assists;
 pub mod button;
 #[allow(clippy::module_inception)]
 pub mod display;
+pub mod editor;
+pub mod game_stats;
+pub mod hints;
+pub mod history;
+pub mod marks;
+pub mod playback;
+pub mod save;
+pub mod settings;
 
 use crate::{
     carpet_sudoku::{CarpetPattern, CarpetSudoku},
+    player_stats::PlayerStats,
     simple_sudoku::{Coords, SudokuDifficulty},
 };
+use assists::Assists;
+use editor::Editor;
+use hints::Hint;
+use history::History;
+use marks::{MarkMode, PlayerMarks};
+use playback::Playback;
+use settings::Settings;
 
 use macroquad::color::Color;
 #[cfg(feature = "database")]
 use macroquad::texture::Texture2D;
 use std::{
-    collections::HashMap,
+    collections::{HashMap, HashSet},
     rc::Rc,
     sync::{Arc, Mutex},
     thread::JoinHandle,
-    time::Instant,
+    time::{Duration, Instant},
 };
 
 #[cfg(feature = "database")]
 use crate::database::Database;
 
 pub type ButtonFunction = Rc<Box<dyn Fn(&mut SudokuDisplay)>>;
+/// The notes of the player and their colors, per cell
+pub type PlayerBoard = Vec<Vec<Vec<HashMap<usize, u32>>>>;
+/// The corner and centre marks and the color of the player, per cell
+pub type MarksBoard = Vec<Vec<Vec<PlayerMarks>>>;
+/// The value, the possibilities, the notes and the marks of a cell
+pub type CellState = (usize, HashSet<usize>, HashMap<usize, u32>, PlayerMarks);
+/// The cells changed by an action, with their state before and after it
+pub type HistoryDiff = Vec<((usize, usize, usize), CellState, CellState)>;
+/// The values, the possibilities, the notes and the marks of every cell
+pub type HistorySnapshot = (
+    Vec<Vec<Vec<usize>>>,
+    Vec<Vec<Vec<HashSet<usize>>>>,
+    PlayerBoard,
+    MarksBoard,
+);
 pub struct SudokuDisplay {
     max_height: f32,
     max_width: f32,
@@ -34,23 +66,49 @@ pub struct SudokuDisplay {
     button_list: Vec<Button>,
     buttons_action: HashMap<String, ButtonFunction>,
     last_processed_keys: Option<Instant>,
+    last_autosave: Instant,
+    focus_subscriber: usize,
+    window_focused: bool,
 
     mode: String,
     analyse_text: Vec<String>,
     hint_text: String,
+    hint: Option<Hint>,
+    playback: Option<Playback>,
+    editor: Option<Editor>,
     hovered_cell: Option<(usize, usize, usize)>,
     selected_cell: Option<(usize, usize, usize)>,
     note: bool,
     lifes: usize,
+    timer: Duration,
+    timer_start: Option<Instant>,
+    mistakes: usize,
+    hints_used: usize,
+    solve_steps: usize,
+    completed: bool,
+    solved_by_assist: bool,
+    completion_overlay: bool,
+    stats: PlayerStats,
+    show_stats: bool,
+    assists: Assists,
+    show_assists: bool,
+    settings: Settings,
+    show_settings: bool,
     #[allow(clippy::type_complexity)]
     wrong_cell: Arc<Mutex<Option<(usize, usize, usize, usize)>>>,
     wrong_cell_handle: Arc<Mutex<Option<JoinHandle<()>>>>,
-    player_pboard: Vec<Vec<Vec<HashMap<usize, u32>>>>,
-    #[allow(clippy::type_complexity)]
-    history: Vec<(CarpetSudoku, Vec<Vec<Vec<HashMap<usize, u32>>>>)>,
+    player_pboard: PlayerBoard,
+    player_marks: MarksBoard,
+    mark_mode: MarkMode,
+    selected_cells: HashSet<(usize, usize, usize)>,
+    drag_selecting: bool,
+    history: History,
     selected_color: u32,
     pattern_list: Vec<CarpetPattern>,
     torus_view: Coords,
+    zoom: f32,
+    view_offset: (f32, f32),
+    drag_origin: Option<((f32, f32), (f32, f32))>,
 
     #[cfg(feature = "database")]
     database: Option<Database>,
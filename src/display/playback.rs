@@ -0,0 +1,269 @@
+use std::ops::Range;
+use std::time::Instant;
+
+use macroquad::prelude::*;
+
+use super::display::{FOREGROUND_COLOR, PLAY_SOLVE, SELECTED_COLOR};
+use super::hints::StepCells;
+use super::SudokuDisplay;
+use crate::carpet_sudoku::{CarpetDiff, CarpetPattern, CarpetSudoku};
+use crate::simple_sudoku::Sudoku;
+
+/// The steps played in a second, the playback starts at the second one
+pub const PLAYBACK_SPEEDS: [f32; 5] = [0.5, 1., 2., 4., 8.];
+/// How many steps the list shows around the current one
+const PLAYBACK_LINES: usize = 20;
+
+pub struct PlaybackStep {
+    /// None for the carpet rules
+    pub sudoku_id: Option<usize>,
+    pub rule: usize,
+    pub cells: StepCells,
+    /// What the step changes in the carpet
+    pub diff: CarpetDiff,
+}
+
+/// The whole rule solve of the carpet of analyse mode, shown one step at a time
+pub struct Playback {
+    pub steps: Vec<PlaybackStep>,
+    /// How many steps are done on the shown carpet
+    pub position: usize,
+    pub playing: bool,
+    /// Index in [`PLAYBACK_SPEEDS`]
+    pub speed: usize,
+    pub last_step: Instant,
+}
+
+impl SudokuDisplay {
+    /// Solves a copy of the carpet once, the playback then moves along its steps
+    fn start_playback(&mut self) {
+        if self.playback.is_some() {
+            return;
+        }
+        let steps = self
+            .carpet
+            .get_solve_timeline(None)
+            .into_iter()
+            .map(|timeline_step| PlaybackStep {
+                sudoku_id: timeline_step.sudoku_id,
+                rule: timeline_step.rule,
+                cells: match (timeline_step.sudoku_id, &timeline_step.step) {
+                    (Some(sudoku_id), Some(step)) => StepCells::new(&self.carpet, sudoku_id, step),
+                    _ => StepCells::from_diff(&timeline_step.diff),
+                },
+                diff: timeline_step.diff,
+            })
+            .collect();
+        self.playback = Some(Playback {
+            steps,
+            position: 0,
+            playing: false,
+            speed: 1,
+            last_step: Instant::now(),
+        });
+    }
+
+    /// Forgets the playback, the carpet stays at its step
+    pub(super) fn stop_playback(&mut self) {
+        self.playback = None;
+        self.update_playback_buttons();
+    }
+
+    pub(super) fn play_solve_btn(&mut self) {
+        self.start_playback();
+        if let Some(playback) = self.playback.as_mut() {
+            playback.playing = !playback.playing;
+            playback.last_step = Instant::now();
+            // playing again from the end starts over
+            if playback.playing && playback.position == playback.steps.len() {
+                self.jump_to_step(0);
+            }
+        }
+        self.update_playback_buttons();
+    }
+
+    pub(super) fn step_btn(&mut self, forward: bool) {
+        self.start_playback();
+        let Some(playback) = self.playback.as_mut() else {
+            return;
+        };
+        playback.playing = false;
+        let position = if forward {
+            playback.position + 1
+        } else {
+            playback.position.saturating_sub(1)
+        };
+        self.jump_to_step(position);
+        self.update_playback_buttons();
+    }
+
+    pub(super) fn playback_speed_btn(&mut self, faster: bool) {
+        self.start_playback();
+        if let Some(playback) = self.playback.as_mut() {
+            playback.speed = if faster {
+                (playback.speed + 1).min(PLAYBACK_SPEEDS.len() - 1)
+            } else {
+                playback.speed.saturating_sub(1)
+            };
+        }
+    }
+
+    /// Shows the carpet once `position` steps are done, by doing or undoing the steps in between
+    fn jump_to_step(&mut self, position: usize) {
+        let Some(playback) = self.playback.as_mut() else {
+            return;
+        };
+        let position = position.min(playback.steps.len());
+        while playback.position < position {
+            self.carpet
+                .apply_diff(&playback.steps[playback.position].diff, false);
+            playback.position += 1;
+        }
+        while playback.position > position {
+            playback.position -= 1;
+            self.carpet
+                .apply_diff(&playback.steps[playback.position].diff, true);
+        }
+        playback.last_step = Instant::now();
+    }
+
+    fn update_playback_buttons(&mut self) {
+        let playing = self
+            .playback
+            .as_ref()
+            .is_some_and(|playback| playback.playing);
+        for button in self.button_list.iter_mut() {
+            if button.text == PLAY_SOLVE {
+                button.set_clicked(playing);
+            }
+        }
+    }
+
+    /// Moves to the next step when its time has come, and pauses at the end
+    pub(super) fn update_playback(&mut self) {
+        let Some(playback) = self.playback.as_mut() else {
+            return;
+        };
+        if !playback.playing
+            || playback.last_step.elapsed().as_secs_f32() < 1. / PLAYBACK_SPEEDS[playback.speed]
+        {
+            return;
+        }
+        if playback.position < playback.steps.len() {
+            let position = playback.position + 1;
+            self.jump_to_step(position);
+        } else {
+            playback.playing = false;
+            self.update_playback_buttons();
+        }
+    }
+
+    /// The cells of the last done step, fading in while playing
+    pub(super) fn get_playback_step(&self) -> Option<(&StepCells, usize, f32)> {
+        let playback = self.playback.as_ref()?;
+        let step = playback.steps.get(playback.position.checked_sub(1)?)?;
+        let alpha = if playback.playing {
+            let progress =
+                playback.last_step.elapsed().as_secs_f32() * PLAYBACK_SPEEDS[playback.speed];
+            (0.2 + progress * 1.6).min(1.)
+        } else {
+            1.
+        };
+        Some((&step.cells, 3, alpha))
+    }
+
+    fn get_playback_step_name(&self, step: &PlaybackStep) -> String {
+        match (step.sudoku_id, self.carpet.get_pattern()) {
+            (Some(_), CarpetPattern::Simple) => Sudoku::get_rule_name_by_id(step.rule).to_string(),
+            (Some(sudoku_id), _) => format!(
+                "Sudoku {sudoku_id}: {}",
+                Sudoku::get_rule_name_by_id(step.rule)
+            ),
+            (None, _) => format!("Carpet: {}", CarpetSudoku::get_rule_name_by_id(step.rule)),
+        }
+    }
+
+    /// The steps listed around the current one, the font size, and the position of the first line
+    fn get_playback_lines(&self, playback: &Playback) -> (Range<usize>, f32, (f32, f32)) {
+        let font_size = self.grid_size / 45.;
+        let first = playback
+            .position
+            .saturating_sub(PLAYBACK_LINES / 2)
+            .min(playback.steps.len().saturating_sub(PLAYBACK_LINES));
+        let lines = first..(first + PLAYBACK_LINES).min(playback.steps.len());
+        let x =
+            self.x_offset + self.grid_size + 100. * self.scale_factor - self.pixel_per_cell / 2.;
+        let y = self.y_offset + (self.grid_size - font_size * (PLAYBACK_LINES + 1) as f32) / 2.;
+        (lines, font_size, (x, y))
+    }
+
+    /// A click on a listed step shows the carpet once it is done, returns whether a step was clicked
+    pub(super) fn playback_click(&mut self, (mouse_x, mouse_y): (f32, f32)) -> bool {
+        let Some(playback) = &self.playback else {
+            return false;
+        };
+        let (lines, font_size, (x, y)) = self.get_playback_lines(playback);
+        if mouse_x < x || mouse_x > x + self.grid_size / 2. {
+            return false;
+        }
+        for (line, index) in lines.enumerate() {
+            let line_y = y + font_size * (line + 1) as f32 + font_size / 4.;
+            if mouse_y > line_y && mouse_y < line_y + font_size {
+                if let Some(playback) = self.playback.as_mut() {
+                    playback.playing = false;
+                }
+                self.jump_to_step(index + 1);
+                self.update_playback_buttons();
+                return true;
+            }
+        }
+        false
+    }
+
+    /// The position in the solve and the speed, then the list of the steps, the current one highlighted
+    pub(super) fn draw_playback(&self, font: &Font) {
+        let Some(playback) = &self.playback else {
+            return;
+        };
+        let (lines, font_size, (x, y)) = self.get_playback_lines(playback);
+        let params = TextParams {
+            font: Some(font),
+            font_size: font_size as u16,
+            color: Color::from_hex(FOREGROUND_COLOR),
+            ..Default::default()
+        };
+        draw_text_ex(
+            format!(
+                "Step {}/{}, {} steps/s",
+                playback.position,
+                playback.steps.len(),
+                PLAYBACK_SPEEDS[playback.speed]
+            ),
+            x,
+            y,
+            params.clone(),
+        );
+        for (line, index) in lines.enumerate() {
+            let line_y = y + font_size * (line + 1) as f32;
+            if index + 1 == playback.position {
+                draw_rectangle(
+                    x - font_size / 4.,
+                    line_y + font_size / 4.,
+                    self.grid_size / 2.,
+                    font_size,
+                    Color::from_hex(SELECTED_COLOR),
+                );
+            }
+            draw_text_ex(
+                format!(
+                    "{}. {}",
+                    index + 1,
+                    self.get_playback_step_name(&playback.steps[index])
+                ),
+                x,
+                line_y + font_size,
+                params.clone(),
+            );
+        }
+    }
+}
@@ -0,0 +1,534 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+use super::history::History;
+use super::marks::PlayerMarks;
+use super::settings::Settings;
+use super::{CellState, HistoryDiff, MarksBoard, PlayerBoard, SudokuDisplay};
+use crate::carpet_sudoku::{CarpetPattern, CarpetSudoku};
+use crate::parse_value;
+use crate::simple_sudoku::{SudokuDifficulty, SudokuError};
+
+/// The files next to the settings, see [`Settings::get_data_path`]
+pub const SAVE_FILE: &str = "save.sudoku";
+pub const AUTOSAVE_FILE: &str = "autosave.sudoku";
+pub const STATS_FILE: &str = "stats.txt";
+pub const AUTOSAVE_DELAY_SECS: u64 = 30;
+
+const CARPET_SECTION: &str = "[carpet]";
+
+impl SudokuDisplay {
+    /// Writes the game in progress to `file_path`: the lifes, the clock and the game stats, the notes and their colors,
+    /// the pencil marks and the cell colors, the correction board, the undo and redo histories, and the carpet itself (see [`CarpetSudoku::to_save_string`])
+    pub fn save_game(&self, file_path: &str) -> Result<(), SudokuError> {
+        let (pattern, pattern_size) = self.pattern.to_db();
+        let mut lines = vec![
+            format!("lifes: {}", self.lifes),
+            format!("time: {}", self.get_elapsed_time().as_millis()),
+            format!("mistakes: {}", self.mistakes),
+            format!("hints_used: {}", self.hints_used),
+            format!("solve_steps: {}", self.solve_steps),
+            format!("completed: {}", self.completed),
+            format!("solved_by_assist: {}", self.solved_by_assist),
+            format!("difficulty: {}", self.difficulty as i16),
+            format!(
+                "pattern: {pattern} {}",
+                pattern_size.map_or("-".to_string(), |size| size.to_string())
+            ),
+        ];
+        for (sudoku_id, board) in self.correction_board.iter().enumerate() {
+            for line in board {
+                lines.push(format!(
+                    "correction: {sudoku_id} {}",
+                    line.iter()
+                        .map(|value| value.to_string())
+                        .collect::<Vec<_>>()
+                        .join(" ")
+                ));
+            }
+        }
+        Self::player_board_to_save(&self.player_pboard, &mut lines);
+        Self::marks_board_to_save(&self.player_marks, &mut lines);
+        Self::history_to_save("undo", &self.history.undo, &mut lines);
+        Self::history_to_save("redo", &self.history.redo, &mut lines);
+        lines.push(CARPET_SECTION.to_string());
+        lines.push(self.carpet.to_save_string());
+
+        let write_error = |error: std::io::Error| {
+            SudokuError::WriteFile((file_path.to_string(), error.to_string()))
+        };
+        if let Some(dir) = Path::new(file_path).parent() {
+            fs::create_dir_all(dir).map_err(write_error)?;
+        }
+        fs::write(file_path, lines.join("\n")).map_err(write_error)
+    }
+
+    /// Replaces the current game by the one saved in `file_path` (see [`SudokuDisplay::save_game`]),
+    /// the current game is kept if the file can't be read
+    pub fn load_game(&mut self, file_path: &str) -> Result<(), SudokuError> {
+        let data = fs::read_to_string(file_path)
+            .map_err(|error| SudokuError::ReadFile((file_path.to_string(), error.to_string())))?;
+
+        // the file is cut on the section line: the game, then its carpet
+        let mut sections = vec![(String::new(), Vec::new())];
+        for line in data.lines() {
+            let line = line.trim();
+            if line == CARPET_SECTION {
+                sections.push((line.to_string(), Vec::new()));
+            } else if !line.is_empty() {
+                sections.last_mut().unwrap().1.push(line);
+            }
+        }
+        let mut sections = sections.into_iter();
+        let (_, game_lines) = sections.next().unwrap();
+        let carpet = Self::carpet_from_save(sections.next())?;
+        // the number buttons are made for the grid size of the settings
+        if carpet.get_n() != self.carpet.get_n() {
+            return Err(SudokuError::InvalidState(format!(
+                "the save is a {0}x{0} grid, change the grid size in the settings to load it",
+                carpet.get_n2()
+            )));
+        }
+        if let Some((header, _)) = sections.next() {
+            return Err(SudokuError::ParseString((
+                header,
+                "unexpected section".to_string(),
+            )));
+        }
+
+        let mut lifes = None;
+        // the game stats are optional, so that the saves written before them still load
+        let mut time = 0;
+        let mut mistakes = 0;
+        let mut hints_used = 0;
+        let mut solve_steps = 0;
+        let mut completed = false;
+        let mut solved_by_assist = false;
+        let mut difficulty = None;
+        let mut pattern = None;
+        let mut correction_board =
+            vec![vec![vec![0; carpet.get_n2()]; carpet.get_n2()]; carpet.get_n_sudokus()];
+        let mut correction_lines = vec![0; carpet.get_n_sudokus()];
+        let mut note_lines = Vec::new();
+        let mut mark_lines = Vec::new();
+        let mut undo_lines = Vec::new();
+        let mut redo_lines = Vec::new();
+        for line in game_lines {
+            let (key, value) = Self::split_save_line(line)?;
+            match key {
+                "lifes" => lifes = Some(parse_value::<usize>(line, value)?),
+                "time" => time = parse_value(line, value)?,
+                "mistakes" => mistakes = parse_value(line, value)?,
+                "hints_used" => hints_used = parse_value(line, value)?,
+                "solve_steps" => solve_steps = parse_value(line, value)?,
+                "completed" => completed = parse_value(line, value)?,
+                "solved_by_assist" => solved_by_assist = parse_value(line, value)?,
+                "difficulty" => {
+                    difficulty = Some(SudokuDifficulty::from(parse_value::<i16>(line, value)?))
+                }
+                "pattern" => {
+                    let (pattern_id, pattern_size) = value.split_once(' ').ok_or_else(|| {
+                        SudokuError::ParseString((
+                            line.to_string(),
+                            "expected \"pattern size\"".to_string(),
+                        ))
+                    })?;
+                    let pattern_size = match pattern_size {
+                        "-" => None,
+                        size => Some(parse_value(line, size)?),
+                    };
+                    pattern = Some(CarpetPattern::from_db(
+                        parse_value(line, pattern_id)?,
+                        pattern_size,
+                    ));
+                }
+                "correction" => {
+                    let values = value
+                        .split_whitespace()
+                        .map(|value| parse_value::<usize>(line, value))
+                        .collect::<Result<Vec<_>, _>>()?;
+                    match values.split_first() {
+                        Some((&sudoku_id, row))
+                            if sudoku_id < carpet.get_n_sudokus()
+                                && row.len() == carpet.get_n2()
+                                && correction_lines[sudoku_id] < carpet.get_n2() =>
+                        {
+                            correction_board[sudoku_id][correction_lines[sudoku_id]] = row.to_vec();
+                            correction_lines[sudoku_id] += 1;
+                        }
+                        _ => {
+                            return Err(SudokuError::ParseString((
+                                line.to_string(),
+                                "expected a row of the carpet".to_string(),
+                            )))
+                        }
+                    }
+                }
+                "notes" => note_lines.push(line),
+                "marks" => mark_lines.push(line),
+                "undo" => undo_lines.push(line),
+                "redo" => redo_lines.push(line),
+                _ => {
+                    return Err(SudokuError::ParseString((
+                        line.to_string(),
+                        "unexpected key".to_string(),
+                    )))
+                }
+            }
+        }
+        let player_pboard = Self::player_board_from_save(&carpet, &note_lines)?;
+        let player_marks = Self::marks_board_from_save(&carpet, &mark_lines)?;
+        let history = History {
+            undo: Self::history_from_save(&carpet, &undo_lines)?,
+            redo: Self::history_from_save(&carpet, &redo_lines)?,
+        };
+
+        let missing = |key: &str| {
+            SudokuError::ParseString((String::new(), format!("missing the key \"{key}\"")))
+        };
+        let lifes = lifes.ok_or_else(|| missing("lifes"))?;
+        let difficulty = difficulty.ok_or_else(|| missing("difficulty"))?;
+        let pattern = pattern.ok_or_else(|| missing("pattern"))?;
+
+        self.init();
+        self.carpet = carpet;
+        self.player_pboard = player_pboard;
+        self.player_marks = player_marks;
+        self.correction_board = correction_board;
+        self.history = history;
+        self.lifes = lifes;
+        self.difficulty = difficulty;
+        self.pattern = pattern;
+        self.timer = Duration::from_millis(time);
+        self.mistakes = mistakes;
+        self.hints_used = hints_used;
+        self.solve_steps = solve_steps;
+        self.completed = completed;
+        self.solved_by_assist = solved_by_assist;
+        self.reset_view();
+        Ok(())
+    }
+
+    pub fn save_btn(&mut self) {
+        if let Err(error) = self.save_game(&Settings::get_data_path(SAVE_FILE)) {
+            eprintln!("SudokuDisplay Error: {error}");
+        }
+    }
+
+    pub fn load_btn(&mut self) {
+        if let Err(error) = self.load_game(&Settings::get_data_path(SAVE_FILE)) {
+            eprintln!("SudokuDisplay Error: {error}");
+        }
+    }
+
+    pub fn autosave(&mut self) {
+        self.last_autosave = Instant::now();
+        if let Err(error) = self.save_game(&Settings::get_data_path(AUTOSAVE_FILE)) {
+            eprintln!("SudokuDisplay Error: {error}");
+        }
+    }
+
+    /// Restores the autosave slot, nothing happens on the first launch
+    pub fn load_autosave(&mut self) {
+        let file_path = Settings::get_data_path(AUTOSAVE_FILE);
+        if !Path::new(&file_path).exists() {
+            return;
+        }
+        if let Err(error) = self.load_game(&file_path) {
+            eprintln!("SudokuDisplay Error: {error}");
+        }
+    }
+
+    // SAVE FORMAT
+
+    fn carpet_from_save(section: Option<(String, Vec<&str>)>) -> Result<CarpetSudoku, SudokuError> {
+        match section {
+            Some((header, lines)) if header == CARPET_SECTION => {
+                CarpetSudoku::parse_save_string(&lines.join("\n"))
+            }
+            Some((header, _)) => Err(SudokuError::ParseString((
+                header,
+                format!("expected {CARPET_SECTION}"),
+            ))),
+            None => Err(SudokuError::ParseString((
+                String::new(),
+                format!("missing {CARPET_SECTION}"),
+            ))),
+        }
+    }
+
+    fn split_save_line(line: &str) -> Result<(&str, &str), SudokuError> {
+        line.split_once(": ")
+            .map(|(key, value)| (key, value.trim()))
+            .ok_or_else(|| {
+                SudokuError::ParseString((line.to_string(), "expected \"key: value\"".to_string()))
+            })
+    }
+
+    /// One `notes: sudoku x y value:color,...` line per cell with notes
+    fn player_board_to_save(player_pboard: &PlayerBoard, lines: &mut Vec<String>) {
+        for (sudoku_id, board) in player_pboard.iter().enumerate() {
+            for (y, line) in board.iter().enumerate() {
+                for (x, notes) in line.iter().enumerate() {
+                    if notes.is_empty() {
+                        continue;
+                    }
+                    lines.push(format!(
+                        "notes: {sudoku_id} {x} {y} {}",
+                        Self::notes_to_save(notes)
+                    ));
+                }
+            }
+        }
+    }
+
+    fn player_board_from_save(
+        carpet: &CarpetSudoku,
+        lines: &[&str],
+    ) -> Result<PlayerBoard, SudokuError> {
+        let mut player_pboard = vec![
+            vec![vec![HashMap::new(); carpet.get_n2()]; carpet.get_n2()];
+            carpet.get_n_sudokus()
+        ];
+        for line in lines {
+            let ((sudoku_id, x, y), notes) = Self::cell_line_from_save(carpet, line, "notes")?;
+            player_pboard[sudoku_id][y][x] = Self::notes_from_save(notes)?;
+        }
+        Ok(player_pboard)
+    }
+
+    /// One `marks: sudoku x y corner;centre;color` line per cell with marks or a color
+    fn marks_board_to_save(player_marks: &MarksBoard, lines: &mut Vec<String>) {
+        for (sudoku_id, board) in player_marks.iter().enumerate() {
+            for (y, line) in board.iter().enumerate() {
+                for (x, marks) in line.iter().enumerate() {
+                    if marks.is_empty() {
+                        continue;
+                    }
+                    lines.push(format!(
+                        "marks: {sudoku_id} {x} {y} {}",
+                        Self::marks_to_save(marks)
+                    ));
+                }
+            }
+        }
+    }
+
+    fn marks_board_from_save(
+        carpet: &CarpetSudoku,
+        lines: &[&str],
+    ) -> Result<MarksBoard, SudokuError> {
+        let mut player_marks = Self::new_player_marks(carpet.get_n2(), carpet.get_n_sudokus());
+        for line in lines {
+            let ((sudoku_id, x, y), marks) = Self::cell_line_from_save(carpet, line, "marks")?;
+            player_marks[sudoku_id][y][x] = Self::marks_from_save(marks)?;
+        }
+        Ok(player_marks)
+    }
+
+    /// Splits a `key: sudoku x y data` line, the cell has to be in the carpet
+    fn cell_line_from_save<'a>(
+        carpet: &CarpetSudoku,
+        line: &'a str,
+        expected_key: &str,
+    ) -> Result<((usize, usize, usize), &'a str), SudokuError> {
+        let (key, value) = Self::split_save_line(line)?;
+        let mut parts = value.split_whitespace();
+        let (Some(sudoku_id), Some(x), Some(y), Some(data), None) = (
+            parts.next(),
+            parts.next(),
+            parts.next(),
+            parts.next(),
+            parts.next(),
+        ) else {
+            return Err(SudokuError::ParseString((
+                line.to_string(),
+                format!("expected \"{expected_key}: sudoku x y data\""),
+            )));
+        };
+        let sudoku_id = parse_value::<usize>(line, sudoku_id)?;
+        let x = parse_value::<usize>(line, x)?;
+        let y = parse_value::<usize>(line, y)?;
+        if key != expected_key
+            || sudoku_id >= carpet.get_n_sudokus()
+            || x >= carpet.get_n2()
+            || y >= carpet.get_n2()
+        {
+            return Err(SudokuError::ParseString((
+                line.to_string(),
+                format!("expected the {expected_key} of a cell of the carpet"),
+            )));
+        }
+        Ok(((sudoku_id, x, y), data))
+    }
+
+    /// `value:color,...` sorted by value, `-` when there is no note
+    fn notes_to_save(notes: &HashMap<usize, u32>) -> String {
+        if notes.is_empty() {
+            return "-".to_string();
+        }
+        let mut notes = notes.iter().collect::<Vec<_>>();
+        notes.sort();
+        notes
+            .into_iter()
+            .map(|(value, color)| format!("{value}:{color}"))
+            .collect::<Vec<_>>()
+            .join(",")
+    }
+
+    fn notes_from_save(notes: &str) -> Result<HashMap<usize, u32>, SudokuError> {
+        let mut cell_notes = HashMap::new();
+        if notes == "-" {
+            return Ok(cell_notes);
+        }
+        for note in notes.split(',') {
+            let (value, color) = note.split_once(':').ok_or_else(|| {
+                SudokuError::ParseString((note.to_string(), "expected \"value:color\"".to_string()))
+            })?;
+            cell_notes.insert(parse_value(note, value)?, parse_value(note, color)?);
+        }
+        Ok(cell_notes)
+    }
+
+    /// A sorted `value,...` list, `-` when it is empty
+    fn values_to_save<'a>(values: impl IntoIterator<Item = &'a usize>) -> String {
+        let mut values = values.into_iter().collect::<Vec<_>>();
+        if values.is_empty() {
+            return "-".to_string();
+        }
+        values.sort();
+        values
+            .into_iter()
+            .map(|value| value.to_string())
+            .collect::<Vec<_>>()
+            .join(",")
+    }
+
+    fn values_from_save<T: FromIterator<usize>>(values: &str) -> Result<T, SudokuError> {
+        match values {
+            "-" => Ok(std::iter::empty().collect()),
+            values => values
+                .split(',')
+                .map(|value| parse_value(values, value))
+                .collect(),
+        }
+    }
+
+    /// `corner;centre;color`, each of them is `-` when there is none
+    fn marks_to_save(marks: &PlayerMarks) -> String {
+        format!(
+            "{};{};{}",
+            Self::values_to_save(&marks.corner),
+            Self::values_to_save(&marks.centre),
+            marks
+                .color
+                .map_or("-".to_string(), |color| color.to_string())
+        )
+    }
+
+    fn marks_from_save(marks: &str) -> Result<PlayerMarks, SudokuError> {
+        let [corner, centre, color] = marks.split(';').collect::<Vec<_>>()[..] else {
+            return Err(SudokuError::ParseString((
+                marks.to_string(),
+                "expected \"corner;centre;color\"".to_string(),
+            )));
+        };
+        Ok(PlayerMarks {
+            corner: Self::values_from_save(corner)?,
+            centre: Self::values_from_save(centre)?,
+            color: match color {
+                "-" => None,
+                color => Some(parse_value(marks, color)?),
+            },
+        })
+    }
+
+    /// `value possibilities notes marks`, the possibilities are a `value,...` list
+    fn cell_state_to_save((value, possibilities, notes, marks): &CellState) -> String {
+        format!(
+            "{value} {} {} {}",
+            Self::values_to_save(possibilities),
+            Self::notes_to_save(notes),
+            Self::marks_to_save(marks)
+        )
+    }
+
+    /// The marks are missing from the saves written before them
+    fn cell_state_from_save(state: &[&str]) -> Result<CellState, SudokuError> {
+        let (value, possibilities, notes, marks) = match *state {
+            [value, possibilities, notes] => (value, possibilities, notes, None),
+            [value, possibilities, notes, marks] => (value, possibilities, notes, Some(marks)),
+            _ => {
+                return Err(SudokuError::ParseString((
+                    state.join(" "),
+                    "expected \"value possibilities notes marks\"".to_string(),
+                )))
+            }
+        };
+        Ok((
+            parse_value(&state.join(" "), value)?,
+            Self::values_from_save(possibilities)?,
+            Self::notes_from_save(notes)?,
+            marks.map_or(Ok(PlayerMarks::default()), Self::marks_from_save)?,
+        ))
+    }
+
+    /// One `key: step sudoku x y` line per changed cell, followed by the cell state before and after the step,
+    /// the steps are numbered from the oldest
+    fn history_to_save(key: &str, history: &[HistoryDiff], lines: &mut Vec<String>) {
+        for (step, diff) in history.iter().enumerate() {
+            for ((sudoku_id, x, y), before, after) in diff.iter() {
+                lines.push(format!(
+                    "{key}: {step} {sudoku_id} {x} {y} {} {}",
+                    Self::cell_state_to_save(before),
+                    Self::cell_state_to_save(after)
+                ));
+            }
+        }
+    }
+
+    fn history_from_save(
+        carpet: &CarpetSudoku,
+        lines: &[&str],
+    ) -> Result<Vec<HistoryDiff>, SudokuError> {
+        let mut history: Vec<HistoryDiff> = Vec::new();
+        for line in lines {
+            let (_, value) = Self::split_save_line(line)?;
+            let values = value.split_whitespace().collect::<Vec<_>>();
+            let [step, sudoku_id, x, y, ref states @ ..] = values[..] else {
+                return Err(SudokuError::ParseString((
+                    line.to_string(),
+                    "expected \"step sudoku x y state_before state_after\"".to_string(),
+                )));
+            };
+            let (state_before, state_after) = states.split_at(states.len() / 2);
+            let step = parse_value::<usize>(line, step)?;
+            let sudoku_id = parse_value::<usize>(line, sudoku_id)?;
+            let x = parse_value::<usize>(line, x)?;
+            let y = parse_value::<usize>(line, y)?;
+            // the lines of a step follow each other, and the steps come in order
+            if step + 1 < history.len()
+                || step > history.len()
+                || sudoku_id >= carpet.get_n_sudokus()
+                || x >= carpet.get_n2()
+                || y >= carpet.get_n2()
+            {
+                return Err(SudokuError::ParseString((
+                    line.to_string(),
+                    "expected a change of a cell of the carpet".to_string(),
+                )));
+            }
+            if step == history.len() {
+                history.push(HistoryDiff::new());
+            }
+            history[step].push((
+                (sudoku_id, x, y),
+                Self::cell_state_from_save(state_before)?,
+                Self::cell_state_from_save(state_after)?,
+            ));
+        }
+        Ok(history)
+    }
+}
@@ -0,0 +1,394 @@
+use std::fs;
+use std::path::Path;
+use std::time::Duration;
+
+use macroquad::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use super::display::{
+    BACKGROUND_COLOR, COLORS, FOREGROUND_COLOR, SETTINGS, SETTING_COLORS, SETTING_FONT,
+    SETTING_GRID_SIZE, SETTING_KEY_REPEAT, SETTING_LIFES, SETTING_WINDOW_SIZE,
+};
+use super::SudokuDisplay;
+use crate::simple_sudoku::SudokuError;
+
+/// The directory of the settings, the saves and the stats in the config directory of the user
+pub const SETTINGS_DIR: &str = "hai606i_sudoku";
+pub const SETTINGS_FILE: &str = "settings.toml";
+/// Where the files of [`SETTINGS_DIR`] go when the system has no config directory
+pub const FALLBACK_DATA_DIR: &str = "saves";
+pub const FONT_DIR: &str = "./res/font";
+pub const DEFAULT_FONT: &str = "./res/font/RobotoMono-Thin.ttf";
+
+pub const GRID_SIZES: [usize; 3] = [2, 3, 4];
+pub const LIFES: [usize; 5] = [1, 2, 3, 5, 10];
+pub const WINDOW_SIZES: [(i32, i32); 4] = [(1280, 720), (1600, 900), (1920, 1080), (2560, 1440)];
+/// The bounds of a window size written by hand in the settings file
+pub const MIN_WINDOW_SIZE: (i32, i32) = (640, 360);
+pub const MAX_WINDOW_SIZE: (i32, i32) = (7680, 4320);
+pub const KEY_REPEAT_DELAYS: [u64; 5] = [100, 150, 200, 300, 500];
+/// The palettes the settings screen goes through, the first color of each is the one of a cell without color
+pub const PALETTES: [(&str, [u32; 12]); 3] = [
+    ("Default", COLORS),
+    (
+        "Pastel",
+        [
+            0xffffff, 0xdcdcdc, 0xf4a6a0, 0xf8c9a0, 0xfcf1a8, 0xb5e6a8, 0xb8f5d0, 0xa8d4f8,
+            0xb3b2ff, 0xd4a5e0, 0xf0bcd6, 0xd2b49c,
+        ],
+    ),
+    (
+        "Contrast",
+        [
+            0xffffff, 0x808080, 0xff0000, 0xff8000, 0xffff00, 0x00c000, 0x00ffff, 0x0080ff,
+            0x0000ff, 0x8000ff, 0xff00ff, 0x804000,
+        ],
+    ),
+];
+
+/// The preferences of the player, stored in a TOML file of their config directory
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Settings {
+    file_path: String,
+    pub window_width: i32,
+    pub window_height: i32,
+    pub font_path: String,
+    /// The size of the boxes, 3 for the classic 9x9 grid
+    pub n: usize,
+    /// The lifes of a new game
+    pub lifes: usize,
+    /// The colors of the notes and the cells, the first one is the color of a cell without color
+    pub colors: Vec<u32>,
+    /// How long a held key waits before it is processed again
+    pub key_repeat_delay: Duration,
+    /// Set when the file couldn't be read, the changes aren't saved so that the file can still be fixed by hand
+    read_only: bool,
+}
+
+/// The settings as written in the file, the keys left out keep their default value
+#[derive(Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct SettingsFile {
+    window_width: i32,
+    window_height: i32,
+    font_path: String,
+    n: usize,
+    lifes: usize,
+    colors: Vec<u32>,
+    key_repeat_delay_ms: u64,
+}
+
+impl Default for SettingsFile {
+    fn default() -> Self {
+        Self::from(&Settings::new(""))
+    }
+}
+
+impl From<&Settings> for SettingsFile {
+    fn from(settings: &Settings) -> Self {
+        Self {
+            window_width: settings.window_width,
+            window_height: settings.window_height,
+            font_path: settings.font_path.clone(),
+            n: settings.n,
+            lifes: settings.lifes,
+            colors: settings.colors.clone(),
+            key_repeat_delay_ms: settings.key_repeat_delay.as_millis() as u64,
+        }
+    }
+}
+
+impl Settings {
+    pub fn new(file_path: &str) -> Self {
+        Self {
+            file_path: file_path.to_string(),
+            window_width: 1920,
+            window_height: 1080,
+            font_path: DEFAULT_FONT.to_string(),
+            n: 3,
+            lifes: 3,
+            colors: COLORS.to_vec(),
+            key_repeat_delay: Duration::from_millis(200),
+            read_only: false,
+        }
+    }
+
+    /// The default settings, used when the file at `file_path` couldn't be read: they never overwrite it
+    pub fn read_only(file_path: &str) -> Self {
+        Self {
+            read_only: true,
+            ..Self::new(file_path)
+        }
+    }
+
+    /// The settings file in the config directory of the user, see [`Settings::get_data_path`]
+    pub fn get_default_path() -> String {
+        Self::get_data_path(SETTINGS_FILE)
+    }
+
+    /// A file of the game in the config directory of the user, or in [`FALLBACK_DATA_DIR`] when there is none
+    pub fn get_data_path(file_name: &str) -> String {
+        dirs::config_dir()
+            .map_or(Path::new(FALLBACK_DATA_DIR).to_path_buf(), |dir| {
+                dir.join(SETTINGS_DIR)
+            })
+            .join(file_name)
+            .to_string_lossy()
+            .to_string()
+    }
+
+    pub fn get_file_path(&self) -> &str {
+        &self.file_path
+    }
+
+    pub fn is_read_only(&self) -> bool {
+        self.read_only
+    }
+
+    /// Reads the settings stored in `file_path`, a missing file or key keeps the default value
+    pub fn load(file_path: &str) -> Result<Self, SudokuError> {
+        if !Path::new(file_path).exists() {
+            return Ok(Self::new(file_path));
+        }
+        let data = fs::read_to_string(file_path)
+            .map_err(|error| SudokuError::ReadFile((file_path.to_string(), error.to_string())))?;
+        Self::parse_toml_string(file_path, &data)
+    }
+
+    pub fn save(&self) -> Result<(), SudokuError> {
+        let write_error = |error: String| SudokuError::WriteFile((self.file_path.clone(), error));
+        if self.read_only {
+            return Err(write_error(
+                "the file couldn't be read, fix it or remove it to save the settings".to_string(),
+            ));
+        }
+        let data = self.to_toml_string()?;
+        if let Some(dir) = Path::new(&self.file_path).parent() {
+            fs::create_dir_all(dir).map_err(|error| write_error(error.to_string()))?;
+        }
+        fs::write(&self.file_path, data).map_err(|error| write_error(error.to_string()))
+    }
+
+    /// One `key = value` line per setting
+    pub fn to_toml_string(&self) -> Result<String, SudokuError> {
+        toml::to_string(&SettingsFile::from(self))
+            .map_err(|error| SudokuError::WriteFile((self.file_path.clone(), error.to_string())))
+    }
+
+    /// Reads the settings written by [`Settings::to_toml_string`], the keys left out keep their default value
+    pub fn parse_toml_string(file_path: &str, data: &str) -> Result<Self, SudokuError> {
+        let file: SettingsFile = toml::from_str(data).map_err(|error| {
+            SudokuError::ParseString((file_path.to_string(), error.to_string()))
+        })?;
+
+        if !GRID_SIZES.contains(&file.n) {
+            return Err(SudokuError::ParseString((
+                format!("n = {}", file.n),
+                format!("the grid size should be one of {GRID_SIZES:?}"),
+            )));
+        }
+        if file.lifes == 0 {
+            return Err(SudokuError::ParseString((
+                "lifes = 0".to_string(),
+                "a game needs at least one life".to_string(),
+            )));
+        }
+        if file.colors.len() < 2 || !file.colors.len().is_multiple_of(2) {
+            return Err(SudokuError::ParseString((
+                "colors".to_string(),
+                "expected an even number of colors, at least 2".to_string(),
+            )));
+        }
+        Ok(Self {
+            window_width: file
+                .window_width
+                .clamp(MIN_WINDOW_SIZE.0, MAX_WINDOW_SIZE.0),
+            window_height: file
+                .window_height
+                .clamp(MIN_WINDOW_SIZE.1, MAX_WINDOW_SIZE.1),
+            font_path: file.font_path,
+            n: file.n,
+            lifes: file.lifes,
+            colors: file.colors,
+            key_repeat_delay: Duration::from_millis(file.key_repeat_delay_ms),
+            ..Self::new(file_path)
+        })
+    }
+
+    /// The name of the palette of the colors, `Custom` when they were written by hand in the file
+    pub fn get_palette_name(&self) -> &str {
+        PALETTES
+            .iter()
+            .find(|(_, colors)| colors[..] == self.colors[..])
+            .map_or("Custom", |(name, _)| name)
+    }
+
+    /// The fonts of the font directory, sorted by name
+    pub fn get_fonts() -> Vec<String> {
+        let mut fonts = fs::read_dir(FONT_DIR)
+            .map(|entries| {
+                entries
+                    .filter_map(|entry| entry.ok())
+                    .map(|entry| entry.path())
+                    .filter(|path| path.extension().is_some_and(|extension| extension == "ttf"))
+                    .map(|path| path.to_string_lossy().to_string())
+                    .collect::<Vec<_>>()
+            })
+            .unwrap_or_default();
+        fonts.sort();
+        fonts
+    }
+
+    /// The choice after `current`, or the first one when `current` isn't a choice
+    fn next_choice<T: PartialEq + Clone>(choices: &[T], current: &T) -> T {
+        let index = choices
+            .iter()
+            .position(|choice| choice == current)
+            .map_or(0, |index| (index + 1) % choices.len());
+        choices[index].clone()
+    }
+
+    /// Goes to the next value of the setting, as the settings screen does
+    pub fn cycle(&mut self, setting: &str) {
+        match setting {
+            SETTING_GRID_SIZE => self.n = Self::next_choice(&GRID_SIZES, &self.n),
+            SETTING_LIFES => self.lifes = Self::next_choice(&LIFES, &self.lifes),
+            SETTING_WINDOW_SIZE => {
+                (self.window_width, self.window_height) =
+                    Self::next_choice(&WINDOW_SIZES, &(self.window_width, self.window_height))
+            }
+            SETTING_FONT => {
+                let fonts = Self::get_fonts();
+                if !fonts.is_empty() {
+                    self.font_path = Self::next_choice(&fonts, &self.font_path);
+                }
+            }
+            SETTING_COLORS => {
+                let palettes = PALETTES.map(|(_, colors)| colors.to_vec());
+                self.colors = Self::next_choice(&palettes, &self.colors);
+            }
+            SETTING_KEY_REPEAT => {
+                let delay = self.key_repeat_delay.as_millis() as u64;
+                self.key_repeat_delay =
+                    Duration::from_millis(Self::next_choice(&KEY_REPEAT_DELAYS, &delay));
+            }
+            _ => (),
+        }
+    }
+
+    /// The value of the setting, as shown next to its button
+    pub fn get_value_text(&self, setting: &str) -> String {
+        match setting {
+            SETTING_GRID_SIZE => format!("{0}x{0}", self.n * self.n),
+            SETTING_LIFES => self.lifes.to_string(),
+            SETTING_WINDOW_SIZE => format!("{}x{}", self.window_width, self.window_height),
+            SETTING_FONT => Path::new(&self.font_path)
+                .file_stem()
+                .map_or(self.font_path.clone(), |name| {
+                    name.to_string_lossy().to_string()
+                }),
+            SETTING_COLORS => self.get_palette_name().to_string(),
+            SETTING_KEY_REPEAT => format!("{}ms", self.key_repeat_delay.as_millis()),
+            _ => String::new(),
+        }
+    }
+}
+
+impl SudokuDisplay {
+    pub(super) fn settings_btn(&mut self) {
+        if self.show_stats {
+            self.stats_btn();
+        }
+        if self.show_assists {
+            self.assists_btn();
+        }
+        self.show_settings = !self.show_settings;
+        for button in self.button_list.iter_mut() {
+            match button.text.as_str() {
+                SETTINGS => button.set_clicked(self.show_settings),
+                SETTING_GRID_SIZE | SETTING_LIFES | SETTING_WINDOW_SIZE | SETTING_FONT
+                | SETTING_COLORS | SETTING_KEY_REPEAT => button.set_enabled(self.show_settings),
+                _ => (),
+            }
+        }
+    }
+
+    /// Changes the setting and writes the file, the window is resized at once,
+    /// the lifes count from the next game and the rest from the next start
+    pub(super) fn change_setting(&mut self, setting: &str) {
+        self.settings.cycle(setting);
+        if setting == SETTING_WINDOW_SIZE {
+            request_new_screen_size(
+                self.settings.window_width as f32,
+                self.settings.window_height as f32,
+            );
+        }
+        if let Err(error) = self.settings.save() {
+            eprintln!("SudokuDisplay Error: {error}");
+        }
+    }
+
+    /// The panel over the grid, its buttons are drawn again above it with their values on their right
+    pub(super) async fn draw_settings_panel(&self, font: &Font) {
+        if !self.show_settings {
+            return;
+        }
+        draw_rectangle(
+            self.x_offset,
+            self.y_offset,
+            self.grid_size,
+            self.grid_size,
+            Color::from_hex(BACKGROUND_COLOR),
+        );
+        let text_params = |font_size: f32| TextParams {
+            font: Some(font),
+            font_size: font_size as u16,
+            color: Color::from_hex(FOREGROUND_COLOR),
+            ..Default::default()
+        };
+        let font_size = self.grid_size / 30.;
+        draw_text_ex(
+            "Settings",
+            self.x_offset + self.grid_size / 20.,
+            self.y_offset + font_size * 2.,
+            text_params(font_size),
+        );
+        for button in self.button_list.iter() {
+            if let SETTING_GRID_SIZE | SETTING_LIFES | SETTING_WINDOW_SIZE | SETTING_FONT
+            | SETTING_COLORS | SETTING_KEY_REPEAT = button.text.as_str()
+            {
+                button.draw(font.clone()).await;
+                draw_text_ex(
+                    self.settings.get_value_text(&button.text),
+                    button.x() + button.width() + self.grid_size / 20.,
+                    button.y() + (button.height() + font_size) / 2.,
+                    text_params(font_size),
+                );
+            }
+        }
+
+        let font_size = self.grid_size / 45.;
+        for (i, line) in [
+            "The lifes change with the next game,".to_string(),
+            "the grid size, the font and the colors with the next start".to_string(),
+            if self.settings.is_read_only() {
+                format!(
+                    "Not saved, {} couldn't be read",
+                    self.settings.get_file_path()
+                )
+            } else {
+                format!("Saved in {}", self.settings.get_file_path())
+            },
+        ]
+        .iter()
+        .enumerate()
+        {
+            draw_text_ex(
+                line,
+                self.x_offset + self.grid_size / 20.,
+                self.y_offset + self.grid_size - (4 - i) as f32 * 1.5 * font_size,
+                text_params(font_size),
+            );
+        }
+    }
+}
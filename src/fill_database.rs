@@ -13,7 +13,10 @@ use hai606i_sudoku::{
 
 fn main() {
     let args: Vec<String> = std::env::args().collect();
-    if args.len() == 4 {
+    if args.len() == 3 && args[1].eq("carpet") && args[2].eq("rehash") {
+        carpet_rehash();
+        return;
+    } else if args.len() == 4 {
         let max_number = args[3].parse::<usize>().unwrap();
 
         if args[1].eq("sudoku") {
@@ -35,7 +38,7 @@ fn main() {
         }
     } else if args.len() == 1 {
         println!(
-			"Hint: you can start this executalbe with the arguments : {} <sudoku|carpet> <filled|games> <max_number>", 
+			"Hint: you can start this executalbe with the arguments : {0} <sudoku|carpet> <filled|games> <max_number>, or {0} carpet rehash", 
 			args[0]
 		);
 
@@ -80,12 +83,16 @@ fn main() {
         return;
     }
 
-    eprintln!("Wrong usage: either needed 0 or 3 args, got {}", args.len());
+    eprintln!(
+        "Wrong usage: either needed 0, 2 or 3 args, got {}",
+        args.len()
+    );
     eprintln!(
         "Usage 1: {} <sudoku|carpet> <filled|games> <max_number>",
         args[0]
     );
-    eprintln!("Usage 2: {}", args[0]);
+    eprintln!("Usage 2: {} carpet rehash", args[0]);
+    eprintln!("Usage 3: {}", args[0]);
 }
 
 fn sudoku_filled(max_number: usize) {
@@ -317,6 +324,47 @@ fn carpet_filled(max_number: usize) {
     }
 }
 
+/// Recomputes the filled board hash of every stored carpet, to run once the way
+/// [`CarpetSudoku::compute_filled_board_hash`] hashes a carpet has changed
+fn carpet_rehash() {
+    let mut database = Database::connect().unwrap();
+    let carpet_hashes = database
+        .get_canonical_carpet_hashes()
+        .unwrap_or_else(|err| panic!("ERROR COULDN'T GET THE CARPETS FROM DATABASE: {err}"));
+    let mut moved_count = 0;
+    let mut merged_count = 0;
+
+    for (i, &old_hash) in carpet_hashes.iter().enumerate() {
+        print!(
+            "{i}/{} carpets rehashed: {moved_count} moved ({merged_count} merged)\r",
+            carpet_hashes.len()
+        );
+        stdout().flush().unwrap();
+
+        // custom or incomplete carpets can't be rebuilt, they are left as they are
+        let Ok(carpet) = database.get_canonical_carpet(old_hash) else {
+            continue;
+        };
+        // same shift as CarpetSudoku::db_to_filled(), the database only stores signed integers
+        let new_hash = carpet
+            .compute_filled_board_hash()
+            .wrapping_sub(u64::MAX / 2 + 1) as i64;
+        if new_hash != old_hash {
+            if database
+                .rehash_canonical_carpet(old_hash, new_hash)
+                .unwrap_or_else(|err| panic!("ERROR COULDN'T REHASH CARPET IN DATABSE: {err}"))
+            {
+                merged_count += 1;
+            }
+            moved_count += 1;
+        }
+    }
+    println!(
+        "{0}/{0} carpets rehashed: {moved_count} moved ({merged_count} merged into an already stored orientation)",
+        carpet_hashes.len()
+    );
+}
+
 fn carpet_games(max_number: usize) {
     let database = Arc::new(Mutex::new(Database::connect().unwrap()));
     let mut join_handle: Option<thread::JoinHandle<Result<(), diesel::result::Error>>> = None;
@@ -325,7 +373,8 @@ fn carpet_games(max_number: usize) {
         for pattern in patterns.iter_mut() {
             println!("\n{}: ", pattern);
 
-            let filled = CarpetSudoku::generate_full(3, *pattern);
+            let filled =
+                CarpetSudoku::generate_filled_from_db(&mut database.lock().unwrap(), 3, *pattern);
 
             if let Some(my_join_handle) = join_handle {
                 if let Err(err) = my_join_handle.join().unwrap() {
@@ -1,3 +1,8 @@
+#[cfg(feature = "database")]
+use hai606i_sudoku::{
+    carpet_sudoku::{CarpetPattern, CarpetSudoku},
+    database::Database,
+};
 use hai606i_sudoku::{
     duration_to_string,
     simple_sudoku::{Sudoku, SudokuDifficulty},
@@ -70,4 +75,49 @@ fn main() {
     }
 
     end_function(time_samples, iterations);
+
+    #[cfg(feature = "database")]
+    filled_carpets_benchmark(iterations);
+}
+
+/// Compares the filled carpets assembled from the stored grids with the ones backtracked from scratch
+#[cfg(feature = "database")]
+fn filled_carpets_benchmark(iterations: usize) {
+    let Some(mut database) = Database::connect() else {
+        println!("No database, the filled carpets aren't benchmarked");
+        return;
+    };
+
+    for pattern in CarpetPattern::iter() {
+        let mut from_db_samples = Vec::new();
+        let mut backtracked_samples = Vec::new();
+        for j in 0..iterations {
+            println!("{pattern} iteration {j}:{}", " ".repeat(50));
+
+            let start = std::time::Instant::now();
+            let _carpet = CarpetSudoku::generate_filled_from_db(&mut database, 3, pattern);
+            from_db_samples.push(start.elapsed());
+
+            let start = std::time::Instant::now();
+            let _carpet = CarpetSudoku::generate_full(3, pattern);
+            backtracked_samples.push(start.elapsed());
+        }
+        from_db_samples.sort();
+        backtracked_samples.sort();
+
+        let null_duration = Duration::from_millis(0);
+        println!(
+            "Filled {pattern}:\n\tfrom database median: {}\n\tbacktracked median: {}",
+            duration_to_string(
+                from_db_samples
+                    .get(iterations / 2)
+                    .unwrap_or(&null_duration)
+            ),
+            duration_to_string(
+                backtracked_samples
+                    .get(iterations / 2)
+                    .unwrap_or(&null_duration)
+            )
+        );
+    }
 }
@@ -1,4 +1,6 @@
-use std::time::Duration;
+use std::{str::FromStr, time::Duration};
+
+use simple_sudoku::SudokuError;
 
 #[macro_use]
 #[cfg(feature = "database")]
@@ -8,6 +10,7 @@ pub mod carpet_sudoku;
 #[cfg(feature = "database")]
 pub mod database;
 pub mod display;
+pub mod player_stats;
 pub mod simple_sudoku;
 pub mod tests;
 
@@ -44,3 +47,13 @@ pub fn duration_to_string(duration: &Duration) -> String {
         format!("{}ms", milliseconds % 1000)
     }
 }
+
+/// Parses one value of a line read from a file, the error shows the whole line
+pub fn parse_value<T: FromStr>(line: &str, value: &str) -> Result<T, SudokuError>
+where
+    T::Err: std::fmt::Display,
+{
+    value
+        .parse::<T>()
+        .map_err(|error| SudokuError::ParseString((line.to_string(), error.to_string())))
+}
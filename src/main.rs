@@ -1,8 +1,9 @@
 #![allow(dead_code)] // no warning due to unused code
 
-use std::{fs, io::stdin};
 #[cfg(feature = "database")]
 use std::{
+    fs,
+    io::stdin,
     sync::mpsc,
     thread::{self, sleep},
     time::Duration,
@@ -11,14 +12,26 @@ use std::{
 #[cfg(feature = "database")]
 use hai606i_sudoku::database::Database;
 
-use hai606i_sudoku::display::SudokuDisplay;
+use hai606i_sudoku::display::{
+    settings::{Settings, DEFAULT_FONT},
+    SudokuDisplay,
+};
 use macroquad::prelude::*;
 
+fn load_settings() -> Settings {
+    let file_path = Settings::get_default_path();
+    Settings::load(&file_path).unwrap_or_else(|error| {
+        eprintln!("{error}");
+        Settings::read_only(&file_path)
+    })
+}
+
 fn window_conf() -> Conf {
+    let settings = load_settings();
     Conf {
         window_title: "Sudoku".to_owned(),
-        window_width: 1920,
-        window_height: 1080,
+        window_width: settings.window_width,
+        window_height: settings.window_height,
         ..Default::default()
     }
 }
@@ -39,11 +52,18 @@ async fn main() {
     }
 
     // env_logger::Builder::from_env(Env::default().default_filter_or("debug")).init();
-    let font = load_ttf_font("./res/font/RobotoMono-Thin.ttf")
-        .await
-        .unwrap();
+    let settings = load_settings();
+    let font = match load_ttf_font(&settings.font_path).await {
+        Ok(font) => font,
+        Err(error) => {
+            eprintln!("{error}");
+            load_ttf_font(DEFAULT_FONT).await.unwrap()
+        }
+    };
 
-    let mut sudoku_display = SudokuDisplay::new(3, font.clone()).await;
+    let mut sudoku_display = SudokuDisplay::new(settings, font.clone()).await;
+    sudoku_display.load_autosave();
+    prevent_quit();
 
     #[cfg(feature = "database")]
     let (tx, rx) = mpsc::channel::<Option<Database>>();
@@ -54,6 +74,10 @@ async fn main() {
     });
 
     loop {
+        if is_quit_requested() {
+            sudoku_display.autosave();
+            break;
+        }
         #[cfg(feature = "database")]
         if let Ok(db) = rx.try_recv() {
             sudoku_display.set_db(db);
@@ -0,0 +1,361 @@
+use std::{
+    fs,
+    path::Path,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+#[cfg(feature = "database")]
+use crate::database::{DBNewPlayerGame, DBPlayerGame, Database};
+use crate::{
+    carpet_sudoku::CarpetPattern,
+    parse_value,
+    simple_sudoku::{SudokuDifficulty, SudokuError},
+};
+
+/// A finished game, won or lost (out of lifes)
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PlayerGame {
+    /// Milliseconds since the unix epoch, it also identifies the game when syncing
+    pub finished_at: u64,
+    pub n: usize,
+    pub pattern: CarpetPattern,
+    pub difficulty: SudokuDifficulty,
+    pub time: Duration,
+    pub mistakes: usize,
+    pub hints: usize,
+    pub won: bool,
+    /// Already sent to the database
+    pub synced: bool,
+}
+
+/// Games played, wins and times of a group of games, the times only count the won games
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct PlayerStatsSummary {
+    pub played: usize,
+    pub won: usize,
+    pub best_time: Option<Duration>,
+    pub average_time: Option<Duration>,
+}
+
+/// The finished games of the player, stored in a local file.
+/// The player id tells the games of the player apart in the database:
+/// copying the `player` line of the file to another computer shares the games between both
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct PlayerStats {
+    file_path: String,
+    player_id: u64,
+    games: Vec<PlayerGame>,
+}
+
+impl PlayerGame {
+    pub fn new(
+        n: usize,
+        pattern: CarpetPattern,
+        difficulty: SudokuDifficulty,
+        time: Duration,
+        mistakes: usize,
+        hints: usize,
+        won: bool,
+    ) -> Self {
+        let finished_at = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |since_epoch| since_epoch.as_millis() as u64);
+        Self {
+            finished_at,
+            n,
+            pattern,
+            difficulty,
+            time,
+            mistakes,
+            hints,
+            won,
+            synced: false,
+        }
+    }
+
+    /// `finished_at n pattern pattern_size difficulty time mistakes hints won synced`, the pattern size is `-` when there is none
+    pub fn to_line(&self) -> String {
+        let (pattern, pattern_size) = self.pattern.to_db();
+        format!(
+            "{} {} {pattern} {} {} {} {} {} {} {}",
+            self.finished_at,
+            self.n,
+            pattern_size.map_or("-".to_string(), |size| size.to_string()),
+            self.difficulty as i16,
+            self.time.as_millis(),
+            self.mistakes,
+            self.hints,
+            self.won,
+            self.synced
+        )
+    }
+
+    /// The lines written before the sync state was saved have no `synced` value
+    pub fn parse_line(line: &str) -> Result<Self, SudokuError> {
+        let values = line.split_whitespace().collect::<Vec<_>>();
+        let [finished_at, n, pattern, pattern_size, difficulty, time, mistakes, hints, won, ref synced @ ..] =
+            values[..]
+        else {
+            return Err(SudokuError::ParseString((
+                line.to_string(),
+                format!("expected 9 or 10 values, got {}", values.len()),
+            )));
+        };
+        if synced.len() > 1 {
+            return Err(SudokuError::ParseString((
+                line.to_string(),
+                format!("expected 9 or 10 values, got {}", values.len()),
+            )));
+        }
+
+        let pattern_size = match pattern_size {
+            "-" => None,
+            size => Some(parse_value(line, size)?),
+        };
+        let pattern = CarpetPattern::try_from_db(parse_value(line, pattern)?, pattern_size)
+            .map_err(|error| SudokuError::ParseString((line.to_string(), error.to_string())))?;
+        Ok(Self {
+            finished_at: parse_value(line, finished_at)?,
+            n: parse_value(line, n)?,
+            pattern,
+            difficulty: SudokuDifficulty::from(parse_value::<i16>(line, difficulty)?),
+            time: Duration::from_millis(parse_value(line, time)?),
+            mistakes: parse_value(line, mistakes)?,
+            hints: parse_value(line, hints)?,
+            won: parse_value(line, won)?,
+            synced: synced
+                .first()
+                .map_or(Ok(false), |synced| parse_value(line, synced))?,
+        })
+    }
+}
+
+impl PlayerStatsSummary {
+    /// Share of the games played that were won, between 0 and 1
+    pub fn get_win_rate(&self) -> f32 {
+        if self.played == 0 {
+            return 0.;
+        }
+        self.won as f32 / self.played as f32
+    }
+}
+
+impl PlayerStats {
+    /// Empty history of a new player
+    pub fn new(file_path: &str) -> Self {
+        Self {
+            file_path: file_path.to_string(),
+            player_id: rand::random(),
+            games: Vec::new(),
+        }
+    }
+
+    /// Reads the games stored in `file_path`, a missing file is an empty history.
+    /// The file starts with a `player id` line, a file without it gets a new player id
+    pub fn load(file_path: &str) -> Result<Self, SudokuError> {
+        let mut stats = Self::new(file_path);
+        if !Path::new(file_path).exists() {
+            return Ok(stats);
+        }
+        let data = fs::read_to_string(file_path)
+            .map_err(|error| SudokuError::ReadFile((file_path.to_string(), error.to_string())))?;
+        let mut lines = data
+            .lines()
+            .map(|line| line.trim())
+            .filter(|line| !line.is_empty())
+            .peekable();
+        if let Some(player_id) = lines.next_if(|line| line.starts_with("player ")) {
+            stats.player_id = parse_value(player_id, &player_id["player ".len()..])?;
+        }
+        let games = lines
+            .map(PlayerGame::parse_line)
+            .collect::<Result<Vec<_>, _>>()?;
+        stats.merge(games);
+        Ok(stats)
+    }
+
+    pub fn save(&self) -> Result<(), SudokuError> {
+        let write_error = |error: std::io::Error| {
+            SudokuError::WriteFile((self.file_path.clone(), error.to_string()))
+        };
+        if let Some(dir) = Path::new(&self.file_path).parent() {
+            fs::create_dir_all(dir).map_err(write_error)?;
+        }
+        let data = format!("player {}\n", self.player_id)
+            + &self
+                .games
+                .iter()
+                .map(|game| game.to_line() + "\n")
+                .collect::<String>();
+        fs::write(&self.file_path, data).map_err(write_error)
+    }
+
+    pub fn get_player_id(&self) -> u64 {
+        self.player_id
+    }
+
+    pub fn get_games(&self) -> &Vec<PlayerGame> {
+        &self.games
+    }
+
+    /// Adds the game and writes the file
+    pub fn record(&mut self, game: PlayerGame) -> Result<(), SudokuError> {
+        self.merge(vec![game]);
+        self.save()
+    }
+
+    /// Adds the games that aren't already known, keeping the games sorted by end time,
+    /// returns the number of added games
+    pub fn merge(&mut self, games: Vec<PlayerGame>) -> usize {
+        let mut added = 0;
+        for game in games {
+            if let Err(index) = self
+                .games
+                .binary_search_by_key(&game.finished_at, |known| known.finished_at)
+            {
+                self.games.insert(index, game);
+                added += 1;
+            }
+        }
+        added
+    }
+
+    pub fn get_summary(&self, filter: impl Fn(&PlayerGame) -> bool) -> PlayerStatsSummary {
+        let games = self
+            .games
+            .iter()
+            .filter(|game| filter(game))
+            .collect::<Vec<_>>();
+        let times = games
+            .iter()
+            .filter(|game| game.won)
+            .map(|game| game.time)
+            .collect::<Vec<_>>();
+        PlayerStatsSummary {
+            played: games.len(),
+            won: times.len(),
+            best_time: times.iter().min().copied(),
+            average_time: (!times.is_empty())
+                .then(|| times.iter().sum::<Duration>() / times.len() as u32),
+        }
+    }
+
+    /// One summary per difficulty played, from the easiest
+    pub fn get_summaries_by_difficulty(&self) -> Vec<(SudokuDifficulty, PlayerStatsSummary)> {
+        let mut difficulties = self
+            .games
+            .iter()
+            .map(|game| game.difficulty)
+            .collect::<Vec<_>>();
+        difficulties.sort();
+        difficulties.dedup();
+        difficulties
+            .into_iter()
+            .map(|difficulty| {
+                (
+                    difficulty,
+                    self.get_summary(|game| game.difficulty == difficulty),
+                )
+            })
+            .collect()
+    }
+
+    /// One summary per pattern played, in the order they were first played
+    pub fn get_summaries_by_pattern(&self) -> Vec<(CarpetPattern, PlayerStatsSummary)> {
+        let mut patterns = Vec::new();
+        for game in self.games.iter() {
+            if !patterns.contains(&game.pattern) {
+                patterns.push(game.pattern);
+            }
+        }
+        patterns
+            .into_iter()
+            .map(|pattern| (pattern, self.get_summary(|game| game.pattern == pattern)))
+            .collect()
+    }
+
+    /// (current, best) numbers of games won in a row
+    pub fn get_streaks(&self) -> (usize, usize) {
+        let mut current = 0;
+        let mut best = 0;
+        for game in self.games.iter() {
+            current = if game.won { current + 1 } else { 0 };
+            best = best.max(current);
+        }
+        (current, best)
+    }
+}
+
+#[cfg(feature = "database")]
+impl PlayerGame {
+    pub fn to_db(&self, player_id: u64) -> DBNewPlayerGame {
+        let (pattern, pattern_size) = self.pattern.to_db();
+        DBNewPlayerGame {
+            player_game_player_id: player_id as i64,
+            player_game_finished_at: self.finished_at as i64,
+            player_game_n: self.n as i16,
+            player_game_pattern: pattern,
+            player_game_pattern_size: pattern_size,
+            player_game_difficulty: self.difficulty as i16,
+            player_game_time: self.time.as_millis() as i64,
+            player_game_mistakes: self.mistakes as i16,
+            player_game_hints: self.hints as i16,
+            player_game_won: self.won,
+        }
+    }
+
+    pub fn from_db(game: DBPlayerGame) -> Result<Self, SudokuError> {
+        Ok(Self {
+            finished_at: game.player_game_finished_at as u64,
+            n: game.player_game_n as usize,
+            pattern: CarpetPattern::try_from_db(
+                game.player_game_pattern,
+                game.player_game_pattern_size,
+            )?,
+            difficulty: SudokuDifficulty::from(game.player_game_difficulty),
+            time: Duration::from_millis(game.player_game_time as u64),
+            mistakes: game.player_game_mistakes as usize,
+            hints: game.player_game_hints as usize,
+            won: game.player_game_won,
+            synced: true,
+        })
+    }
+}
+
+#[cfg(feature = "database")]
+impl PlayerStats {
+    /// Sends the games that aren't synced yet to the database and adds the ones of the player only found there
+    /// (played on another computer), returns the number of games added locally.
+    /// The rows that can't be read (unknown pattern...) are skipped
+    pub fn sync_db(&mut self, database: &mut Database) -> Result<usize, diesel::result::Error> {
+        let unsynced = self
+            .games
+            .iter()
+            .filter(|game| !game.synced)
+            .map(|game| game.to_db(self.player_id))
+            .collect::<Vec<_>>();
+        let sent = !unsynced.is_empty();
+        database.insert_ignore_multiple_player_games(unsynced)?;
+        for game in self.games.iter_mut() {
+            game.synced = true;
+        }
+
+        let added = self.merge(
+            database
+                .get_player_games(self.player_id as i64)?
+                .into_iter()
+                .filter_map(|game| {
+                    PlayerGame::from_db(game)
+                        .inspect_err(|error| eprintln!("{error}"))
+                        .ok()
+                })
+                .collect(),
+        );
+        if sent || added > 0 {
+            if let Err(error) = self.save() {
+                eprintln!("{error}");
+            }
+        }
+        Ok(added)
+    }
+}
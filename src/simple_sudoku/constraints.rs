@@ -0,0 +1,1080 @@
+use super::{
+    Coords, Sudoku, SudokuError, SudokuGroups::*, SudokuOutsideClue, SudokuParity, SudokuRelation,
+};
+use crate::parse_value;
+use std::{
+    collections::{HashMap, HashSet},
+    sync::{LazyLock, RwLock},
+};
+
+type CellPeersKey = (usize, bool, bool);
+type CellPeersBoard = Vec<Vec<HashSet<Coords>>>;
+static CELL_PEERS: LazyLock<RwLock<HashMap<CellPeersKey, CellPeersBoard>>> =
+    LazyLock::new(Default::default);
+
+const KNIGHT_MOVES: [(isize, isize); 8] = [
+    (1, 2),
+    (2, 1),
+    (2, -1),
+    (1, -2),
+    (-1, -2),
+    (-2, -1),
+    (-2, 1),
+    (-1, 2),
+];
+const ORTHOGONAL_MOVES: [(isize, isize); 4] = [(0, 1), (1, 0), (0, -1), (-1, 0)];
+const KING_MOVES: [(isize, isize); 8] = [
+    (0, 1),
+    (1, 1),
+    (1, 0),
+    (1, -1),
+    (0, -1),
+    (-1, -1),
+    (-1, 0),
+    (-1, 1),
+];
+
+impl Sudoku {
+    ///////////////////////////////////////////////////////////////////////////////////////////////////
+    // GETTERS / SETTERS
+
+    pub fn is_anti_knight(&self) -> bool {
+        self.anti_knight
+    }
+
+    pub fn is_anti_king(&self) -> bool {
+        self.anti_king
+    }
+
+    pub fn has_constraints(&self) -> bool {
+        self.anti_knight || self.anti_king || self.has_propagated_constraints()
+    }
+
+    // the constraints that aren't only about peers
+    pub fn has_propagated_constraints(&self) -> bool {
+        !self.relations.is_empty()
+            || !self.thermometers.is_empty()
+            || !self.arrows.is_empty()
+            || !self.parities.is_empty()
+            || self.non_consecutive
+            || !self.outside_clues.is_empty()
+    }
+
+    pub fn get_outside_clues(&self) -> &HashMap<SudokuOutsideClue, usize> {
+        &self.outside_clues
+    }
+
+    pub fn is_non_consecutive(&self) -> bool {
+        self.non_consecutive
+    }
+
+    pub fn get_parities(&self) -> &HashMap<Coords, SudokuParity> {
+        &self.parities
+    }
+
+    pub fn get_cell_parity(&self, x: usize, y: usize) -> Option<SudokuParity> {
+        self.parities.get(&(x, y)).cloned()
+    }
+
+    pub fn get_relations(&self) -> &HashMap<(Coords, Coords), SudokuRelation> {
+        &self.relations
+    }
+
+    pub fn get_thermometers(&self) -> &Vec<Vec<Coords>> {
+        &self.thermometers
+    }
+
+    pub fn get_arrows(&self) -> &Vec<(Coords, Vec<Coords>)> {
+        &self.arrows
+    }
+
+    pub fn set_anti_knight(&mut self, anti_knight: bool) -> Result<(), SudokuError> {
+        self.anti_knight = anti_knight;
+        self.reset_possibility_board()
+    }
+
+    pub fn set_anti_king(&mut self, anti_king: bool) -> Result<(), SudokuError> {
+        self.anti_king = anti_king;
+        self.reset_possibility_board()
+    }
+
+    // orthogonal neighbours can't hold consecutive digits
+    pub fn set_non_consecutive(&mut self, non_consecutive: bool) -> Result<(), SudokuError> {
+        self.non_consecutive = non_consecutive;
+        self.reset_possibility_board()
+    }
+
+    pub fn set_cell_parity(
+        &mut self,
+        x: usize,
+        y: usize,
+        parity: Option<SudokuParity>,
+    ) -> Result<(), SudokuError> {
+        if x >= self.n2 || y >= self.n2 {
+            return Err(SudokuError::WrongInput(format!(
+                "set_cell_parity({x}, {y}, {parity:?}); cells should be in [0..{}[",
+                self.n2
+            )));
+        }
+        match parity {
+            Some(parity) => self.parities.insert((x, y), parity),
+            None => self.parities.remove(&(x, y)),
+        };
+        self.reset_possibility_board()
+    }
+
+    // for GreaterThan, cell1 is the greater cell
+    pub fn add_relation(
+        &mut self,
+        cell1: Coords,
+        cell2: Coords,
+        relation: SudokuRelation,
+    ) -> Result<(), SudokuError> {
+        let ((x1, y1), (x2, y2)) = (cell1, cell2);
+        if x1 >= self.n2 || y1 >= self.n2 || x2 >= self.n2 || y2 >= self.n2 {
+            return Err(SudokuError::WrongInput(format!(
+                "add_relation({cell1:?}, {cell2:?}, {relation}); cells should be in [0..{}[",
+                self.n2
+            )));
+        }
+        if x1.abs_diff(x2) + y1.abs_diff(y2) != 1 {
+            return Err(SudokuError::WrongInput(format!(
+                "add_relation({cell1:?}, {cell2:?}, {relation}); cells should be orthogonally adjacent"
+            )));
+        }
+
+        let old_relations = [(cell1, cell2), (cell2, cell1)]
+            .map(|key| self.relations.remove(&key).map(|old| (key, old)));
+        let key = Self::relation_key(cell1, cell2, relation);
+        self.add_constraint(
+            |sudoku| {
+                sudoku.relations.insert(key, relation);
+            },
+            |sudoku| {
+                sudoku.relations.remove(&key);
+                sudoku.relations.extend(old_relations.into_iter().flatten());
+            },
+        )
+    }
+
+    pub fn remove_relation(
+        &mut self,
+        cell1: Coords,
+        cell2: Coords,
+    ) -> Result<Option<SudokuRelation>, SudokuError> {
+        let removed = self
+            .relations
+            .remove(&(cell1, cell2))
+            .or_else(|| self.relations.remove(&(cell2, cell1)));
+        if removed.is_some() {
+            self.reset_possibility_board()?;
+        }
+        Ok(removed)
+    }
+
+    // the digits must strictly increase from the bulb (the first cell)
+    pub fn add_thermometer(&mut self, path: Vec<Coords>) -> Result<(), SudokuError> {
+        if path.len() < 2 || path.len() > self.n2 {
+            return Err(SudokuError::WrongInput(format!(
+                "add_thermometer({path:?}); a thermometer should have between 2 and {} cells",
+                self.n2
+            )));
+        }
+        self.check_line("add_thermometer", &path)?;
+
+        self.add_constraint(
+            |sudoku| sudoku.thermometers.push(path),
+            |sudoku| {
+                sudoku.thermometers.pop();
+            },
+        )
+    }
+
+    // the digit in the circle is the sum of the digits along the path
+    pub fn add_arrow(&mut self, circle: Coords, path: Vec<Coords>) -> Result<(), SudokuError> {
+        if path.is_empty() {
+            return Err(SudokuError::WrongInput(format!(
+                "add_arrow({circle:?}, {path:?}); an arrow should have at least 1 cell"
+            )));
+        }
+        let mut line = vec![circle];
+        line.extend(path.iter().cloned());
+        self.check_line("add_arrow", &line)?;
+
+        self.add_constraint(
+            |sudoku| sudoku.arrows.push((circle, path)),
+            |sudoku| {
+                sudoku.arrows.pop();
+            },
+        )
+    }
+
+    pub fn add_outside_clue(
+        &mut self,
+        clue: SudokuOutsideClue,
+        sum: usize,
+    ) -> Result<(), SudokuError> {
+        let is_valid = match clue {
+            SudokuOutsideClue::Sandwich(group, i) => matches!(group, Row | Column) && i < self.n2,
+            SudokuOutsideClue::LittleKiller((x, y), (dx, dy)) => {
+                x < self.n2
+                    && y < self.n2
+                    && dx.abs() == 1
+                    && dy.abs() == 1
+                    && self.get_movement_cells(x, y, &[(-dx, -dy)]).is_empty()
+            }
+        };
+        if !is_valid {
+            return Err(SudokuError::WrongInput(format!(
+                "add_outside_clue({clue}, {sum}); a sandwich is on a Row or a Column in [0..{0}[, a little killer starts on the border of the grid and goes along a diagonal",
+                self.n2
+            )));
+        }
+
+        let old_sum = self.outside_clues.get(&clue).copied();
+        self.add_constraint(
+            |sudoku| {
+                sudoku.outside_clues.insert(clue, sum);
+            },
+            |sudoku| match old_sum {
+                Some(old_sum) => {
+                    sudoku.outside_clues.insert(clue, old_sum);
+                }
+                None => {
+                    sudoku.outside_clues.remove(&clue);
+                }
+            },
+        )
+    }
+
+    // adds the constraint and propagates it, when it leaves a cell without possibility
+    // the constraint is taken back and the possibilities are restored
+    fn add_constraint(
+        &mut self,
+        add: impl FnOnce(&mut Self),
+        rollback: impl FnOnce(&mut Self),
+    ) -> Result<(), SudokuError> {
+        let old_possibility_board = self.possibility_board.clone();
+        add(self);
+        let result = self.propagate_constraints().map(|_| ());
+        if result.is_err() {
+            rollback(self);
+            self.possibility_board = old_possibility_board;
+        }
+        result
+    }
+
+    pub fn remove_outside_clue(
+        &mut self,
+        clue: SudokuOutsideClue,
+    ) -> Result<Option<usize>, SudokuError> {
+        let removed = self.outside_clues.remove(&clue);
+        if removed.is_some() {
+            self.reset_possibility_board()?;
+        }
+        Ok(removed)
+    }
+
+    // the cells of the clue, in order
+    pub fn get_outside_clue_cells(&self, clue: SudokuOutsideClue) -> Vec<Coords> {
+        match clue {
+            SudokuOutsideClue::Sandwich(Row, y) => (0..self.n2).map(|x| (x, y)).collect(),
+            SudokuOutsideClue::Sandwich(_, x) => (0..self.n2).map(|y| (x, y)).collect(),
+            SudokuOutsideClue::LittleKiller(start, direction) => {
+                let mut cells = vec![start];
+                while let Some(&next) = self
+                    .get_movement_cells(
+                        cells.last().unwrap().0,
+                        cells.last().unwrap().1,
+                        &[direction],
+                    )
+                    .first()
+                {
+                    cells.push(next);
+                }
+                cells
+            }
+        }
+    }
+
+    pub fn outside_clue_contains(&self, clue: SudokuOutsideClue, (x, y): Coords) -> bool {
+        match clue {
+            SudokuOutsideClue::Sandwich(Row, y1) => y == y1,
+            SudokuOutsideClue::Sandwich(_, x1) => x == x1,
+            SudokuOutsideClue::LittleKiller((x1, y1), (dx, dy)) => {
+                let step = (x as isize - x1 as isize) * dx;
+                step >= 0 && (y as isize - y1 as isize) == step * dy
+            }
+        }
+    }
+
+    // the sum of the clue on the current board, if its cells are filled
+    pub fn get_outside_clue_sum(&self, clue: SudokuOutsideClue) -> Option<usize> {
+        let values = self
+            .get_outside_clue_cells(clue)
+            .into_iter()
+            .map(|(x, y)| self.board[y][x])
+            .collect::<Vec<_>>();
+        if values.contains(&0) {
+            return None;
+        }
+        match clue {
+            SudokuOutsideClue::Sandwich(_, _) => {
+                let i1 = values.iter().position(|&value| value == 1)?;
+                let i2 = values.iter().position(|&value| value == self.n2)?;
+                Some(values[i1.min(i2) + 1..i1.max(i2)].iter().sum())
+            }
+            SudokuOutsideClue::LittleKiller(_, _) => Some(values.iter().sum()),
+        }
+    }
+
+    pub fn remove_lines(&mut self) -> Result<(), SudokuError> {
+        self.thermometers.clear();
+        self.arrows.clear();
+        self.reset_possibility_board()
+    }
+
+    // a line is made of distinct cells, each one touching the previous one (diagonals included)
+    fn check_line(&self, function_name: &str, line: &[Coords]) -> Result<(), SudokuError> {
+        for (i, &(x, y)) in line.iter().enumerate() {
+            if x >= self.n2 || y >= self.n2 {
+                return Err(SudokuError::WrongInput(format!(
+                    "{function_name}({line:?}); cells should be in [0..{}[",
+                    self.n2
+                )));
+            }
+            if line[..i].contains(&(x, y)) {
+                return Err(SudokuError::WrongInput(format!(
+                    "{function_name}({line:?}); the cell {:?} is used twice",
+                    (x, y)
+                )));
+            }
+            if i > 0 {
+                let (x0, y0) = line[i - 1];
+                if x0.abs_diff(x) > 1 || y0.abs_diff(y) > 1 {
+                    return Err(SudokuError::WrongInput(format!(
+                        "{function_name}({line:?}); the cells {:?} and {:?} should touch",
+                        (x0, y0),
+                        (x, y)
+                    )));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    // the dots don't have a direction, so their cells are stored in order
+    fn relation_key(cell1: Coords, cell2: Coords, relation: SudokuRelation) -> (Coords, Coords) {
+        match relation {
+            SudokuRelation::GreaterThan => (cell1, cell2),
+            _ => (cell1.min(cell2), cell1.max(cell2)),
+        }
+    }
+
+    // whether value1 in cell1 and value2 in cell2 satisfy the relation between them (if any),
+    // and the non-consecutive rule if they are orthogonal neighbours
+    pub fn relation_allows(
+        &self,
+        cell1: Coords,
+        value1: usize,
+        cell2: Coords,
+        value2: usize,
+    ) -> bool {
+        if self.non_consecutive
+            && value1.abs_diff(value2) == 1
+            && cell1.0.abs_diff(cell2.0) + cell1.1.abs_diff(cell2.1) == 1
+        {
+            false
+        } else if self.relations.is_empty() {
+            true
+        } else if let Some(relation) = self.relations.get(&(cell1, cell2)) {
+            relation.is_satisfied(value1, value2)
+        } else if let Some(relation) = self.relations.get(&(cell2, cell1)) {
+            relation.is_satisfied(value2, value1)
+        } else {
+            true
+        }
+    }
+
+    // remove every possibility that can't satisfy a relation or a line, until nothing changes
+    pub(super) fn propagate_constraints(&mut self) -> Result<bool, SudokuError> {
+        if self.non_consecutive {
+            let cells = (0..self.n2 * self.n2)
+                .map(|cell_i| (cell_i % self.n2, cell_i / self.n2))
+                .collect();
+            return self.propagate_constraints_from(cells);
+        }
+
+        let mut cells = self
+            .relations
+            .keys()
+            .flat_map(|&(cell1, cell2)| [cell1, cell2])
+            .collect::<Vec<_>>();
+        cells.extend(self.parities.keys());
+        let clue_cells = self
+            .outside_clues
+            .keys()
+            .flat_map(|&clue| self.get_outside_clue_cells(clue))
+            .collect::<Vec<_>>();
+        cells.extend(clue_cells);
+        cells.extend(self.thermometers.iter().flatten());
+        cells.extend(
+            self.arrows
+                .iter()
+                .flat_map(|(circle, path)| path.iter().chain([circle])),
+        );
+        self.propagate_constraints_from(cells)
+    }
+
+    // same, but only starting from the cells that changed
+    pub(super) fn propagate_constraints_from(
+        &mut self,
+        mut changed_cells: Vec<Coords>,
+    ) -> Result<bool, SudokuError> {
+        let clues = self
+            .outside_clues
+            .iter()
+            .map(|(&clue, &sum)| (clue, sum))
+            .collect::<Vec<_>>();
+        let mut modified = false;
+        while let Some((x, y)) = changed_cells.pop() {
+            if let Some(parity) = self.parities.get(&(x, y)) {
+                if self.board[y][x] != 0 && !parity.allows(self.board[y][x]) {
+                    return Err(SudokuError::UnsatisfiedConstraint(vec![(x, y)]));
+                }
+            }
+
+            // lines going through this cell
+            for thermometer_i in 0..self.thermometers.len() {
+                if self.thermometers[thermometer_i].contains(&(x, y)) {
+                    let shrunk_cells = self.propagate_thermometer(thermometer_i)?;
+                    modified |= !shrunk_cells.is_empty();
+                    changed_cells.extend(shrunk_cells);
+                }
+            }
+            for arrow_i in 0..self.arrows.len() {
+                let (circle, path) = &self.arrows[arrow_i];
+                if *circle == (x, y) || path.contains(&(x, y)) {
+                    let shrunk_cells = self.propagate_arrow(arrow_i)?;
+                    modified |= !shrunk_cells.is_empty();
+                    changed_cells.extend(shrunk_cells);
+                }
+            }
+
+            // clues going through this cell
+            for &(clue, sum) in clues.iter() {
+                if self.outside_clue_contains(clue, (x, y)) {
+                    let cells = self.get_outside_clue_cells(clue);
+                    let shrunk_cells = match clue {
+                        SudokuOutsideClue::Sandwich(_, _) => {
+                            self.propagate_sandwich(&cells, sum)?
+                        }
+                        SudokuOutsideClue::LittleKiller(_, _) => {
+                            self.restrict_sum(&cells, sum, sum, &cells)?
+                        }
+                    };
+                    modified |= !shrunk_cells.is_empty();
+                    changed_cells.extend(shrunk_cells);
+                }
+            }
+
+            // relations with the adjacent cells
+            for (x1, y1) in self.get_movement_cells(x, y, &ORTHOGONAL_MOVES) {
+                if !self.non_consecutive
+                    && !self.relations.contains_key(&((x, y), (x1, y1)))
+                    && !self.relations.contains_key(&((x1, y1), (x, y)))
+                {
+                    continue;
+                }
+
+                if self.board[y1][x1] != 0 {
+                    if self.board[y][x] != 0
+                        && !self.relation_allows(
+                            (x, y),
+                            self.board[y][x],
+                            (x1, y1),
+                            self.board[y1][x1],
+                        )
+                    {
+                        return Err(SudokuError::UnsatisfiedConstraint(vec![(x, y), (x1, y1)]));
+                    }
+                    continue;
+                }
+
+                let impossible_values = self.possibility_board[y1][x1]
+                    .iter()
+                    .filter(|&&value1| {
+                        if self.board[y][x] != 0 {
+                            !self.relation_allows((x1, y1), value1, (x, y), self.board[y][x])
+                        } else {
+                            !self.possibility_board[y][x]
+                                .iter()
+                                .any(|&value| self.relation_allows((x1, y1), value1, (x, y), value))
+                        }
+                    })
+                    .cloned()
+                    .collect::<Vec<_>>();
+                if impossible_values.is_empty() {
+                    continue;
+                }
+
+                for value in impossible_values {
+                    self.possibility_board[y1][x1].remove(&value);
+                }
+                if self.possibility_board[y1][x1].is_empty() {
+                    return Err(SudokuError::NoPossibilityCell((x1, y1)));
+                }
+                changed_cells.push((x1, y1));
+                modified = true;
+            }
+        }
+        Ok(modified)
+    }
+
+    // smallest and biggest values the cell can still hold
+    fn get_cell_bounds(&self, x: usize, y: usize) -> (usize, usize) {
+        if self.board[y][x] != 0 {
+            return (self.board[y][x], self.board[y][x]);
+        }
+        let possibilities = &self.possibility_board[y][x];
+        (
+            possibilities.iter().min().cloned().unwrap_or(self.n2 + 1),
+            possibilities.iter().max().cloned().unwrap_or(0),
+        )
+    }
+
+    // keep only the values in [min, max], returns whether the cell changed
+    fn restrict_cell(
+        &mut self,
+        x: usize,
+        y: usize,
+        min: usize,
+        max: usize,
+        line: &[Coords],
+    ) -> Result<bool, SudokuError> {
+        let value = self.board[y][x];
+        if value != 0 {
+            if value < min || value > max {
+                return Err(SudokuError::UnsatisfiedConstraint(line.to_vec()));
+            }
+            return Ok(false);
+        }
+
+        let before = self.possibility_board[y][x].len();
+        self.possibility_board[y][x].retain(|&value| min <= value && value <= max);
+        if self.possibility_board[y][x].is_empty() {
+            return Err(SudokuError::NoPossibilityCell((x, y)));
+        }
+        Ok(before != self.possibility_board[y][x].len())
+    }
+
+    fn propagate_thermometer(&mut self, thermometer_i: usize) -> Result<Vec<Coords>, SudokuError> {
+        let thermometer = self.thermometers[thermometer_i].clone();
+        let mut shrunk_cells = Vec::new();
+
+        // each cell is bigger than the previous one
+        let mut min = 1;
+        for &(x, y) in thermometer.iter() {
+            if self.restrict_cell(x, y, min, self.n2, &thermometer)? {
+                shrunk_cells.push((x, y));
+            }
+            min = self.get_cell_bounds(x, y).0 + 1;
+        }
+
+        // and smaller than the next one
+        let mut max = self.n2;
+        for &(x, y) in thermometer.iter().rev() {
+            if self.restrict_cell(x, y, 1, max, &thermometer)? {
+                shrunk_cells.push((x, y));
+            }
+            max = self.get_cell_bounds(x, y).1.saturating_sub(1);
+        }
+
+        Ok(shrunk_cells)
+    }
+
+    fn propagate_arrow(&mut self, arrow_i: usize) -> Result<Vec<Coords>, SudokuError> {
+        let (circle, path) = self.arrows[arrow_i].clone();
+        let mut line = vec![circle];
+        line.extend(path.iter().cloned());
+        let mut shrunk_cells = Vec::new();
+
+        // the circle is between the smallest and the biggest possible sums
+        let (sum_min, sum_max) = path
+            .iter()
+            .map(|&(x, y)| self.get_cell_bounds(x, y))
+            .fold((0, 0), |(sum_min, sum_max), (min, max)| {
+                (sum_min + min, sum_max + max)
+            });
+        if self.restrict_cell(circle.0, circle.1, sum_min, sum_max, &line)? {
+            shrunk_cells.push(circle);
+        }
+
+        let (circle_min, circle_max) = self.get_cell_bounds(circle.0, circle.1);
+        shrunk_cells.extend(self.restrict_sum(&path, circle_min, circle_max, &line)?);
+        Ok(shrunk_cells)
+    }
+
+    // each cell is what's left of the sum once the other cells are summed
+    fn restrict_sum(
+        &mut self,
+        cells: &[Coords],
+        min_sum: usize,
+        max_sum: usize,
+        constraint_cells: &[Coords],
+    ) -> Result<Vec<Coords>, SudokuError> {
+        let bounds = cells
+            .iter()
+            .map(|&(x, y)| self.get_cell_bounds(x, y))
+            .collect::<Vec<_>>();
+        let cells_min = bounds.iter().map(|(min, _)| min).sum::<usize>();
+        let cells_max = bounds.iter().map(|(_, max)| max).sum::<usize>();
+        if cells_min > max_sum || cells_max < min_sum {
+            return Err(SudokuError::UnsatisfiedConstraint(
+                constraint_cells.to_vec(),
+            ));
+        }
+
+        let mut shrunk_cells = Vec::new();
+        for (&(x, y), &(min, max)) in cells.iter().zip(bounds.iter()) {
+            let others_min = cells_min - min;
+            let others_max = cells_max - max;
+            if self.restrict_cell(
+                x,
+                y,
+                min_sum.saturating_sub(others_max),
+                max_sum.saturating_sub(others_min),
+                constraint_cells,
+            )? {
+                shrunk_cells.push((x, y));
+            }
+        }
+        Ok(shrunk_cells)
+    }
+
+    // the 1 and the biggest digit can only be where the sum between them is reachable
+    fn propagate_sandwich(
+        &mut self,
+        cells: &[Coords],
+        sum: usize,
+    ) -> Result<Vec<Coords>, SudokuError> {
+        let can_hold = |sudoku: &Self, (x, y): Coords, value: usize| {
+            sudoku.board[y][x] == value
+                || (sudoku.board[y][x] == 0 && sudoku.possibility_board[y][x].contains(&value))
+        };
+        // the cells between them hold neither 1 nor the biggest digit
+        let bounds = cells
+            .iter()
+            .map(|&(x, y)| {
+                let (min, max) = self.get_cell_bounds(x, y);
+                (min.max(2), max.min(self.n2 - 1))
+            })
+            .collect::<Vec<_>>();
+
+        let mut valid_pairs = Vec::new();
+        for i1 in (0..cells.len()).filter(|&i| can_hold(self, cells[i], 1)) {
+            for i2 in (0..cells.len()).filter(|&i| can_hold(self, cells[i], self.n2)) {
+                if i1 == i2 {
+                    continue;
+                }
+                let between = &bounds[i1.min(i2) + 1..i1.max(i2)];
+                let between_min = between.iter().map(|(min, _)| min).sum::<usize>();
+                let between_max = between.iter().map(|(_, max)| max).sum::<usize>();
+                if between_min <= sum && sum <= between_max {
+                    valid_pairs.push((i1, i2));
+                }
+            }
+        }
+        if valid_pairs.is_empty() {
+            return Err(SudokuError::UnsatisfiedConstraint(cells.to_vec()));
+        }
+
+        let mut shrunk_cells = Vec::new();
+        for (i, &(x, y)) in cells.iter().enumerate() {
+            if self.board[y][x] != 0 {
+                continue;
+            }
+            let mut changed = false;
+            if !valid_pairs.iter().any(|&(i1, _)| i1 == i) {
+                changed |= self.possibility_board[y][x].remove(&1);
+            }
+            if !valid_pairs.iter().any(|&(_, i2)| i2 == i) {
+                changed |= self.possibility_board[y][x].remove(&self.n2);
+            }
+            if self.possibility_board[y][x].is_empty() {
+                return Err(SudokuError::NoPossibilityCell((x, y)));
+            }
+            if changed {
+                shrunk_cells.push((x, y));
+            }
+        }
+
+        // the ends are known: the sum is on the cells between them
+        if let [(i1, i2)] = valid_pairs[..] {
+            let between = &cells[i1.min(i2) + 1..i1.max(i2)];
+            shrunk_cells.extend(self.restrict_sum(between, sum, sum, cells)?);
+        }
+        Ok(shrunk_cells)
+    }
+
+    // every cell that can't hold the same value as (x, y), (x, y) included
+    pub fn get_cell_peers(&self, x: usize, y: usize) -> HashSet<Coords> {
+        if !self.anti_knight && !self.anti_king {
+            return self.get_cell_group(x, y, All);
+        }
+
+        let key = (self.n, self.anti_knight, self.anti_king);
+        if let Some(peers) = CELL_PEERS.read().unwrap().get(&key) {
+            return peers[y][x].clone();
+        }
+
+        let mut peers = vec![vec![HashSet::new(); self.n2]; self.n2];
+        for (y1, peers_line) in peers.iter_mut().enumerate() {
+            for (x1, cell_peers) in peers_line.iter_mut().enumerate() {
+                *cell_peers = self.get_cell_group(x1, y1, All);
+                if self.anti_knight {
+                    cell_peers.extend(self.get_movement_cells(x1, y1, &KNIGHT_MOVES));
+                }
+                if self.anti_king {
+                    cell_peers.extend(self.get_movement_cells(x1, y1, &KING_MOVES));
+                }
+            }
+        }
+
+        let cell_peers = peers[y][x].clone();
+        CELL_PEERS.write().unwrap().insert(key, peers);
+        cell_peers
+    }
+
+    pub fn is_peer(&self, x1: usize, y1: usize, x2: usize, y2: usize) -> bool {
+        self.is_same_group(x1, y1, x2, y2)
+            || ((self.anti_knight || self.anti_king)
+                && self.get_cell_peers(x1, y1).contains(&(x2, y2)))
+    }
+
+    fn get_movement_cells(&self, x: usize, y: usize, moves: &[(isize, isize)]) -> Vec<Coords> {
+        moves
+            .iter()
+            .filter_map(|&(dx, dy)| {
+                let x1 = x.checked_add_signed(dx)?;
+                let y1 = y.checked_add_signed(dy)?;
+                if x1 < self.n2 && y1 < self.n2 {
+                    Some((x1, y1))
+                } else {
+                    None
+                }
+            })
+            .collect()
+    }
+
+    // the cells sharing a relation, a line or a clue with (x, y)
+    fn get_constraint_neighbours(&self, x: usize, y: usize) -> HashSet<Coords> {
+        let mut neighbours = self
+            .get_movement_cells(x, y, &ORTHOGONAL_MOVES)
+            .into_iter()
+            .filter(|&cell1| {
+                self.non_consecutive
+                    || self.relations.contains_key(&((x, y), cell1))
+                    || self.relations.contains_key(&(cell1, (x, y)))
+            })
+            .collect::<HashSet<_>>();
+        for thermometer in self.thermometers.iter() {
+            if thermometer.contains(&(x, y)) {
+                neighbours.extend(thermometer);
+            }
+        }
+        for (circle, path) in self.arrows.iter() {
+            if *circle == (x, y) || path.contains(&(x, y)) {
+                neighbours.extend(path.iter().chain([circle]));
+            }
+        }
+        for &clue in self.outside_clues.keys() {
+            if self.outside_clue_contains(clue, (x, y)) {
+                neighbours.extend(self.get_outside_clue_cells(clue));
+            }
+        }
+        neighbours.remove(&(x, y));
+        neighbours
+    }
+
+    // every cell reached from the given cells through relations, lines and clues,
+    // the given cells included when they are in a constraint
+    pub(super) fn get_constraint_linked_cells(&self, cells: &[Coords]) -> HashSet<Coords> {
+        let mut linked_cells = HashSet::new();
+        let mut to_visit = cells.to_vec();
+        while let Some((x, y)) = to_visit.pop() {
+            let neighbours = self.get_constraint_neighbours(x, y);
+            if neighbours.is_empty() || !linked_cells.insert((x, y)) {
+                continue;
+            }
+            to_visit.extend(neighbours);
+        }
+        linked_cells
+    }
+
+    // the values an empty cell can hold from its parity and the values of its peers
+    pub(super) fn get_allowed_values(&self, x: usize, y: usize) -> HashSet<usize> {
+        let mut values: HashSet<usize> = (1..=self.n2)
+            .filter(|&value| {
+                self.parities
+                    .get(&(x, y))
+                    .is_none_or(|parity| parity.allows(value))
+            })
+            .collect();
+        for (x1, y1) in self.get_cell_peers(x, y) {
+            values.remove(&self.board[y1][x1]);
+        }
+        values
+    }
+
+    // recompute every empty cell's possibilities from the values placed on the board
+    pub fn reset_possibility_board(&mut self) -> Result<(), SudokuError> {
+        for y in 0..self.n2 {
+            for x in 0..self.n2 {
+                if self.board[y][x] != 0 {
+                    self.possibility_board[y][x].clear();
+                    continue;
+                }
+                self.possibility_board[y][x] = (1..=self.n2)
+                    .filter(|&value| {
+                        self.parities
+                            .get(&(x, y))
+                            .is_none_or(|parity| parity.allows(value))
+                    })
+                    .collect();
+            }
+        }
+
+        let mut res = Ok(());
+        for y in 0..self.n2 {
+            for x in 0..self.n2 {
+                let value = self.board[y][x];
+                if value == 0 {
+                    continue;
+                }
+                for (x1, y1) in self.get_cell_peers(x, y) {
+                    self.possibility_board[y1][x1].remove(&value);
+                    if self.board[y1][x1] == value && (x, y) != (x1, y1) {
+                        res = Err(SudokuError::SameValueCells(((x, y), (x1, y1))));
+                    }
+                }
+            }
+        }
+
+        if res.is_ok() && self.has_propagated_constraints() {
+            res = self.propagate_constraints().map(|_| ());
+        }
+        res
+    }
+
+    ///////////////////////////////////////////////////////////////////////////////////////////////////
+    // PARSING
+
+    pub fn parse_constraint_line(&mut self, line: &str) -> Result<(), SudokuError> {
+        let words = line.split_whitespace().collect::<Vec<_>>();
+        if let Some(relation) =
+            SudokuRelation::iter().find(|r| words.first() == Some(&&*r.to_string()))
+        {
+            let coords = words[1..]
+                .iter()
+                .map(|word| word.parse::<usize>())
+                .collect::<Result<Vec<_>, _>>()
+                .map_err(|error| SudokuError::ParseString((line.to_string(), error.to_string())))?;
+            if coords.len() != 4 {
+                return Err(SudokuError::ParseString((
+                    line.to_string(),
+                    "a relation needs 4 coordinates: x1 y1 x2 y2".to_string(),
+                )));
+            }
+            return self.add_relation((coords[0], coords[1]), (coords[2], coords[3]), relation);
+        }
+
+        if matches!(words.first(), Some(&"thermometer") | Some(&"arrow")) {
+            let coords = words[1..]
+                .iter()
+                .map(|word| word.parse::<usize>())
+                .collect::<Result<Vec<_>, _>>()
+                .map_err(|error| SudokuError::ParseString((line.to_string(), error.to_string())))?;
+            if coords.len() % 2 != 0 {
+                return Err(SudokuError::ParseString((
+                    line.to_string(),
+                    "a line needs pairs of coordinates: x y".to_string(),
+                )));
+            }
+            let mut cells = coords
+                .chunks(2)
+                .map(|coords| (coords[0], coords[1]))
+                .collect::<Vec<_>>();
+            if words[0] == "thermometer" {
+                return self.add_thermometer(cells);
+            }
+            if cells.is_empty() {
+                return Err(SudokuError::ParseString((
+                    line.to_string(),
+                    "an arrow needs a circle".to_string(),
+                )));
+            }
+            let circle = cells.remove(0);
+            return self.add_arrow(circle, cells);
+        }
+
+        if let Some(&("sandwich" | "little_killer")) = words.first() {
+            // the sandwich's second word is its direction, every other word is a number
+            let numbers_start = if words[0] == "sandwich" { 2 } else { 1 };
+            let numbers = words
+                .get(numbers_start..words.len().saturating_sub(1))
+                .unwrap_or_default()
+                .iter()
+                .map(|word| parse_value::<isize>(line, word))
+                .collect::<Result<Vec<_>, _>>()?;
+            let sum = match words.last() {
+                Some(word) if words.len() > numbers_start => parse_value::<usize>(line, word)?,
+                _ => {
+                    return Err(SudokuError::ParseString((
+                        line.to_string(),
+                        "missing clue sum".to_string(),
+                    )))
+                }
+            };
+            let clue =
+                match (&words[..], &numbers[..]) {
+                    (["sandwich", "row", ..], &[y]) => SudokuOutsideClue::Sandwich(Row, y as usize),
+                    (["sandwich", "column", ..], &[x]) => {
+                        SudokuOutsideClue::Sandwich(Column, x as usize)
+                    }
+                    (["little_killer", ..], &[x, y, dx, dy]) if x >= 0 && y >= 0 => {
+                        SudokuOutsideClue::LittleKiller((x as usize, y as usize), (dx, dy))
+                    }
+                    _ => return Err(SudokuError::ParseString((
+                        line.to_string(),
+                        "expected \"sandwich row|column i sum\" or \"little_killer x y dx dy sum\""
+                            .to_string(),
+                    ))),
+                };
+            return self.add_outside_clue(clue, sum);
+        }
+
+        if let [parity @ ("even" | "odd"), x, y] = words[..] {
+            let parse = |word: &str| {
+                word.parse::<usize>().map_err(|error| {
+                    SudokuError::ParseString((line.to_string(), error.to_string()))
+                })
+            };
+            let parity = if parity == "even" {
+                SudokuParity::Even
+            } else {
+                SudokuParity::Odd
+            };
+            return self.set_cell_parity(parse(x)?, parse(y)?, Some(parity));
+        }
+
+        match line.trim() {
+            "" => Ok(()),
+            "anti_knight" => {
+                self.anti_knight = true;
+                Ok(())
+            }
+            "anti_king" => {
+                self.anti_king = true;
+                Ok(())
+            }
+            "non_consecutive" => {
+                self.non_consecutive = true;
+                Ok(())
+            }
+            other => Err(SudokuError::ParseString((
+                other.to_string(),
+                "unknown constraint".to_string(),
+            ))),
+        }
+    }
+
+    pub fn constraints_to_string_lines(&self) -> Vec<String> {
+        let mut lines = Vec::new();
+        if self.anti_knight {
+            lines.push("anti_knight".to_string());
+        }
+        if self.anti_king {
+            lines.push("anti_king".to_string());
+        }
+        if self.non_consecutive {
+            lines.push("non_consecutive".to_string());
+        }
+        let mut parities = self.parities.iter().collect::<Vec<_>>();
+        parities.sort();
+        for ((x, y), parity) in parities {
+            lines.push(format!("{parity} {x} {y}"));
+        }
+        let mut relations = self.relations.iter().collect::<Vec<_>>();
+        relations.sort();
+        for (((x1, y1), (x2, y2)), relation) in relations {
+            lines.push(format!("{relation} {x1} {y1} {x2} {y2}"));
+        }
+        let mut outside_clues = self.outside_clues.iter().collect::<Vec<_>>();
+        outside_clues.sort();
+        for (clue, sum) in outside_clues {
+            lines.push(format!("{clue} {sum}"));
+        }
+        let cells_to_string = |cells: &mut dyn Iterator<Item = &Coords>| {
+            cells
+                .map(|(x, y)| format!("{x} {y}"))
+                .collect::<Vec<_>>()
+                .join(" ")
+        };
+        for thermometer in self.thermometers.iter() {
+            lines.push(format!(
+                "thermometer {}",
+                cells_to_string(&mut thermometer.iter())
+            ));
+        }
+        for (circle, path) in self.arrows.iter() {
+            lines.push(format!(
+                "arrow {}",
+                cells_to_string(&mut [circle].into_iter().chain(path.iter()))
+            ));
+        }
+        lines
+    }
+
+    pub(super) fn constraints_description(&self) -> String {
+        let mut constraints = Vec::new();
+        if self.anti_knight {
+            constraints.push("anti-knight");
+        }
+        if self.anti_king {
+            constraints.push("anti-king");
+        }
+        if self.non_consecutive {
+            constraints.push("non-consecutive");
+        }
+        let parity_count = format!("{} parity", self.parities.len());
+        if !self.parities.is_empty() {
+            constraints.push(&parity_count);
+        }
+        let relation_counts = SudokuRelation::iter()
+            .map(|relation| {
+                let count = self.relations.values().filter(|&&r| r == relation).count();
+                (relation, count)
+            })
+            .filter(|&(_, count)| count > 0)
+            .map(|(relation, count)| format!("{count} {relation}"))
+            .collect::<Vec<_>>();
+        let relation_counts = relation_counts.join(", ");
+        if !relation_counts.is_empty() {
+            constraints.push(&relation_counts);
+        }
+        let thermometer_count = format!("{} thermometer", self.thermometers.len());
+        if !self.thermometers.is_empty() {
+            constraints.push(&thermometer_count);
+        }
+        let arrow_count = format!("{} arrow", self.arrows.len());
+        if !self.arrows.is_empty() {
+            constraints.push(&arrow_count);
+        }
+        let outside_clue_count = format!("{} outside clue", self.outside_clues.len());
+        if !self.outside_clues.is_empty() {
+            constraints.push(&outside_clue_count);
+        }
+        constraints.join(" & ")
+    }
+}
@@ -2,6 +2,7 @@ pub type Coords = (usize, usize);
 
 use std::collections::{HashMap, HashSet};
 
+pub mod constraints;
 pub mod rules;
 pub mod sudoku;
 pub mod sudoku_generation;
@@ -27,6 +28,92 @@ impl std::fmt::Display for SudokuGroups {
     }
 }
 
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Clone, Copy, Hash)]
+pub enum SudokuRelation {
+    GreaterThan = 0,
+    WhiteDot = 1,
+    BlackDot = 2,
+}
+
+impl std::fmt::Display for SudokuRelation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SudokuRelation::GreaterThan => write!(f, "greater_than"),
+            SudokuRelation::WhiteDot => write!(f, "white_dot"),
+            SudokuRelation::BlackDot => write!(f, "black_dot"),
+        }
+    }
+}
+
+impl SudokuRelation {
+    pub fn iter() -> impl Iterator<Item = SudokuRelation> {
+        vec![
+            SudokuRelation::GreaterThan,
+            SudokuRelation::WhiteDot,
+            SudokuRelation::BlackDot,
+        ]
+        .into_iter()
+    }
+
+    // value1 is the value of the first cell of the relation, value2 the one of the second cell
+    pub fn is_satisfied(&self, value1: usize, value2: usize) -> bool {
+        match self {
+            SudokuRelation::GreaterThan => value1 > value2,
+            SudokuRelation::WhiteDot => value1.abs_diff(value2) == 1,
+            SudokuRelation::BlackDot => value1 == 2 * value2 || value2 == 2 * value1,
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Clone, Copy, Hash)]
+pub enum SudokuParity {
+    Even = 0,
+    Odd = 1,
+}
+
+impl std::fmt::Display for SudokuParity {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SudokuParity::Even => write!(f, "even"),
+            SudokuParity::Odd => write!(f, "odd"),
+        }
+    }
+}
+
+impl SudokuParity {
+    pub fn of(value: usize) -> Self {
+        if value.is_multiple_of(2) {
+            SudokuParity::Even
+        } else {
+            SudokuParity::Odd
+        }
+    }
+
+    pub fn allows(&self, value: usize) -> bool {
+        SudokuParity::of(value) == *self
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Clone, Copy, Hash)]
+pub enum SudokuOutsideClue {
+    // sum of the digits between the 1 and the biggest digit of a Row or a Column
+    Sandwich(SudokuGroups, usize),
+    // sum of the digits along the diagonal starting at the cell, in the direction
+    LittleKiller(Coords, (isize, isize)),
+}
+
+impl std::fmt::Display for SudokuOutsideClue {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SudokuOutsideClue::Sandwich(SudokuGroups::Row, y) => write!(f, "sandwich row {y}"),
+            SudokuOutsideClue::Sandwich(_, x) => write!(f, "sandwich column {x}"),
+            SudokuOutsideClue::LittleKiller((x, y), (dx, dy)) => {
+                write!(f, "little_killer {x} {y} {dx} {dy}")
+            }
+        }
+    }
+}
+
 #[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Clone, Copy, Hash)]
 pub enum SudokuDifficulty {
     Unknown = 0,
@@ -100,18 +187,34 @@ impl SudokuDifficulty {
             _ => SudokuDifficulty::Unknown,
         }
     }
+
+    /// Weight of one solving step of this difficulty in a difficulty score
+    pub fn get_weight(&self) -> usize {
+        match self {
+            SudokuDifficulty::Easy => 1,
+            SudokuDifficulty::Medium => 4,
+            SudokuDifficulty::Hard => 16,
+            SudokuDifficulty::Master => 48,
+            SudokuDifficulty::Extreme => 128,
+
+            _ => 0,
+        }
+    }
 }
 
 #[derive(Debug, PartialEq, Eq, Clone)]
 pub enum SudokuError {
     CanonizationMismatch(Box<Sudoku>, u64),
+    InvalidLink(((usize, usize), (usize, usize), String)),
     InvalidState(String),
     NoPossibilityCell(Coords),
     ParseString((String, String)),
     ReadFile((String, String)),
     SameValueCells((Coords, Coords)),
+    UnsatisfiedConstraint(Vec<Coords>),
     WrongFunction(String),
     WrongInput(String),
+    WriteFile((String, String)),
 }
 
 impl std::fmt::Display for SudokuError {
@@ -125,6 +228,12 @@ impl std::fmt::Display for SudokuError {
                     board_hash
                 )
             }
+            SudokuError::InvalidLink(((sudoku1, square1), (sudoku2, square2), reason)) => {
+                write!(
+                    f,
+                    "SudokuError: Invalid link between square {square1} of sudoku {sudoku1} and square {square2} of sudoku {sudoku2}: {reason}"
+                )
+            }
             SudokuError::InvalidState(string) => {
                 write!(f, "SudokuError: Invalid sudoku state for {string}")
             }
@@ -143,19 +252,45 @@ impl std::fmt::Display for SudokuError {
                     "SudokuError: Cells at ({x1},{y1}) and ({x2},{y2}) have the same value"
                 )
             }
+            SudokuError::UnsatisfiedConstraint(cells) => {
+                write!(
+                    f,
+                    "SudokuError: Constraint between cells {cells:?} isn't satisfied"
+                )
+            }
             SudokuError::WrongFunction(string) => {
                 write!(f, "SudokuError: Wrong function for {string}")
             }
             SudokuError::WrongInput(string) => {
                 write!(f, "SudokuError: Wrong input for {string}")
             }
+            SudokuError::WriteFile((file_path, error)) => {
+                write!(f, "SudokuError: couldn't write file {file_path}: {error}")
+            }
         }
     }
 }
 
+impl std::error::Error for SudokuError {}
+
+/// One step of a rule, seen from the changes it makes on the board
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct SudokuStep {
+    pub rule: usize,
+    /// The rows, columns or squares where the step happens
+    pub unit: HashSet<Coords>,
+    /// The cells whose candidates make the rule work
+    pub pattern: HashSet<Coords>,
+    /// The values the step places
+    pub placements: Vec<(Coords, usize)>,
+    /// The candidates the step removes
+    pub eliminations: Vec<(Coords, usize)>,
+}
+
 pub type SudokuRule = fn(&mut Sudoku) -> Result<bool, SudokuError>;
 type GroupMap = HashMap<SudokuGroups, Vec<HashSet<Coords>>>;
 type CellGroupMap = HashMap<(Coords, SudokuGroups), HashSet<Coords>>;
+type RelationMap = HashMap<(Coords, Coords), SudokuRelation>;
 
 #[derive(Debug, Clone)]
 pub struct Sudoku {
@@ -165,6 +300,17 @@ pub struct Sudoku {
     possibility_board: Vec<Vec<HashSet<usize>>>,
     filled_cells: usize,
     difficulty: SudokuDifficulty,
+    /// The cells the last applied rule relied on, only told by the rules whose pattern can't be read from their changes
+    rule_pattern: HashSet<Coords>,
+
+    anti_knight: bool,
+    anti_king: bool,
+    relations: RelationMap,
+    thermometers: Vec<Vec<Coords>>,
+    arrows: Vec<(Coords, Vec<Coords>)>,
+    parities: HashMap<Coords, SudokuParity>,
+    non_consecutive: bool,
+    outside_clues: HashMap<SudokuOutsideClue, usize>,
 
     is_canonical: bool,
     canonical_filled_board_hash: u64,
@@ -14,6 +14,7 @@ impl Sudoku {
     pub const RULES: &'static [(usize, SudokuDifficulty, SudokuRule)] = &[
         (0, Easy, Sudoku::naked_singles),
         (1, Easy, Sudoku::hidden_singles),
+        (55, Easy, Sudoku::constraint_propagation),
         (2, Easy, Sudoku::naked_pairs),
         (3, Easy, Sudoku::naked_triples),
         (4, Medium, Sudoku::hidden_pairs),
@@ -128,10 +129,26 @@ impl Sudoku {
             52 => "Death Blossom",
             53 => "Pattern Overlay",
             54 => "Bowman's Bingo",
+            55 => "Constraint Propagation",
             _ => "Unknown Rule",
         }
     }
 
+    /// Removes the candidate for a rule and keeps the cells of the pattern that removed it, to show them in the hints
+    fn remove_pattern_candidate(
+        &mut self,
+        (x, y): Coords,
+        value: usize,
+        pattern: &[Coords],
+    ) -> bool {
+        if !self.possibility_board[y][x].remove(&value) {
+            return false;
+        }
+        debug_only!("({}, {}): possibilité {} supprimée", x, y, value);
+        self.rule_pattern.extend(pattern.iter().copied());
+        true
+    }
+
     // RULES SOLVING
     // CHECK https://www.taupierbw.be/SudokuCoach
     // THE RULES ARE LISTED BY INCREASING DIFFICULTY
@@ -274,13 +291,14 @@ impl Sudoku {
                     if occurences_value1 != occurences_value2 {
                         continue;
                     }
-                    for &&(x, y) in occurences_value1.iter() {
+                    let pattern: Vec<Coords> =
+                        occurences_value1.iter().map(|&&cell| cell).collect();
+                    for &cell in pattern.iter() {
                         for value in 1..=self.n2 {
                             if value != value1
                                 && value != value2
-                                && self.possibility_board[y][x].remove(&value)
+                                && self.remove_pattern_candidate(cell, value, &pattern)
                             {
-                                debug_only!("({}, {}): possibilité {} supprimée", x, y, value);
                                 modified = true;
                             }
                         }
@@ -330,14 +348,15 @@ impl Sudoku {
                             continue;
                         }
 
-                        for &&(x, y) in common_occurences.into_iter() {
+                        let pattern: Vec<Coords> =
+                            common_occurences.into_iter().map(|&&cell| cell).collect();
+                        for &cell in pattern.iter() {
                             for value in 1..=self.n2 {
                                 if value != value1
                                     && value != value2
                                     && value != value3
-                                    && self.possibility_board[y][x].remove(&value)
+                                    && self.remove_pattern_candidate(cell, value, &pattern)
                                 {
-                                    debug_only!("({}, {}): possibilité {} supprimée", x, y, value);
                                     modified = true;
                                 }
                             }
@@ -454,20 +473,16 @@ impl Sudoku {
                             if common_occurences.len() != 4 {
                                 continue;
                             }
-                            for &&(x, y) in common_occurences.into_iter() {
+                            let pattern: Vec<Coords> =
+                                common_occurences.into_iter().map(|&&cell| cell).collect();
+                            for &cell in pattern.iter() {
                                 for value in 1..=self.n2 {
                                     if value != value1
                                         && value != value2
                                         && value != value3
                                         && value != value4
-                                        && self.possibility_board[y][x].remove(&value)
+                                        && self.remove_pattern_candidate(cell, value, &pattern)
                                     {
-                                        debug_only!(
-                                            "({}, {}): possibilité {} supprimée",
-                                            x,
-                                            y,
-                                            value
-                                        );
                                         modified = true;
                                     }
                                 }
@@ -649,22 +664,22 @@ impl Sudoku {
                 };
 
                 for i2 in i1 + 1..self.n2 {
-                    let mut picked_cells: Vec<(bool, Coords)> = Vec::new();
+                    let mut picked_cells: Vec<(Coords, [Coords; 4])> = Vec::new();
 
                     let row2_positions: HashSet<usize> = (0..self.n2)
                         .filter(|x| self.possibility_board[i2][*x].contains(&value))
                         .collect();
 
-                    if row1_pos.is_some() && row2_positions == row1_positions {
-                        let (x1, x2) = row1_pos.unwrap();
-
-                        let col1 = self.get_cell_group(*x1, i1, Column);
-                        let col2 = self.get_cell_group(*x2, i1, Column);
+                    if let Some((&x1, &x2)) = row1_pos.filter(|_| row2_positions == row1_positions)
+                    {
+                        let pattern = [(x1, i1), (x2, i1), (x1, i2), (x2, i2)];
+                        let col1 = self.get_cell_group(x1, i1, Column);
+                        let col2 = self.get_cell_group(x2, i1, Column);
                         for &(x, y) in col1.union(&col2) {
                             if y == i1 || y == i2 {
                                 continue;
                             }
-                            picked_cells.push((true, (x, y)));
+                            picked_cells.push(((x, y), pattern));
                         }
                     }
 
@@ -672,22 +687,20 @@ impl Sudoku {
                         .filter(|y| self.possibility_board[*y][i2].contains(&value))
                         .collect();
 
-                    if col1_pos.is_some() && col1_positions == col2_positions {
-                        let (y1, y2) = col1_pos.unwrap();
-
+                    if let Some((y1, y2)) = col1_pos.filter(|_| col1_positions == col2_positions) {
+                        let pattern = [(i1, y1), (i1, y2), (i2, y1), (i2, y2)];
                         let row1 = self.get_cell_group(i1, y1, Row);
                         let row2 = self.get_cell_group(i1, y2, Row);
                         for &(x, y) in row1.union(&row2) {
                             if x == i1 || x == i2 {
                                 continue;
                             }
-                            picked_cells.push((false, (x, y)));
+                            picked_cells.push(((x, y), pattern));
                         }
                     }
 
-                    for (_is_row, (x, y)) in picked_cells {
-                        if self.possibility_board[y][x].remove(&value) {
-                            debug_only!("({}, {}): possibilité {} supprimée", x, y, value);
+                    for (cell, pattern) in picked_cells {
+                        if self.remove_pattern_candidate(cell, value, &pattern) {
                             modified = true;
                         }
                     }
@@ -714,11 +727,16 @@ impl Sudoku {
                     if i1 / self.n == i2 / self.n {
                         continue;
                     }
-                    let mut picked_cells: Vec<(bool, Coords, Coords)> = Vec::new();
+                    let mut picked_cells: Vec<(Vec<Coords>, Coords, Coords)> = Vec::new();
 
                     let row2_positions: HashSet<usize> = (0..self.n2)
                         .filter(|x| self.possibility_board[i2][*x].contains(&value))
                         .collect();
+                    let rows_pattern = || {
+                        let row1 = row1_positions.iter().map(|&x| (x, i1));
+                        row1.chain(row2_positions.iter().map(|&x| (x, i2)))
+                            .collect()
+                    };
 
                     let (smaller_row, larger_row, is_row2_larger) =
                         if row1_positions.len() < row2_positions.len() {
@@ -737,15 +755,20 @@ impl Sudoku {
                         let smaller_vec: Vec<usize> = smaller_row.iter().cloned().collect();
                         let (x1, x2) = (smaller_vec[0], smaller_vec[1]);
                         if fin / self.n == x1 / self.n {
-                            picked_cells.push((true, (x1, fin_i), (*fin, fin_i)));
+                            picked_cells.push((rows_pattern(), (x1, fin_i), (*fin, fin_i)));
                         } else if fin / self.n == x2 / self.n {
-                            picked_cells.push((true, (x2, fin_i), (*fin, fin_i)));
+                            picked_cells.push((rows_pattern(), (x2, fin_i), (*fin, fin_i)));
                         }
                     }
 
                     let col2_positions: HashSet<usize> = (0..self.n2)
                         .filter(|y| self.possibility_board[*y][i2].contains(&value))
                         .collect();
+                    let cols_pattern = || {
+                        let col1 = col1_positions.iter().map(|&y| (i1, y));
+                        col1.chain(col2_positions.iter().map(|&y| (i2, y)))
+                            .collect()
+                    };
 
                     let (smaller_col, larger_col, is_col2_larger) =
                         if col1_positions.len() < col2_positions.len() {
@@ -764,13 +787,13 @@ impl Sudoku {
                         let smaller_vec: Vec<usize> = smaller_col.iter().cloned().collect();
                         let (y1, y2) = (smaller_vec[0], smaller_vec[1]);
                         if fin / self.n == y1 / self.n {
-                            picked_cells.push((false, (fin_i, y1), (fin_i, *fin)));
+                            picked_cells.push((cols_pattern(), (fin_i, y1), (fin_i, *fin)));
                         } else if fin / self.n == y2 / self.n {
-                            picked_cells.push((false, (fin_i, y2), (fin_i, *fin)));
+                            picked_cells.push((cols_pattern(), (fin_i, y2), (fin_i, *fin)));
                         }
                     }
 
-                    for (_is_row, (x1, y1), (fin_x, fin_y)) in picked_cells {
+                    for (pattern, (x1, y1), (fin_x, fin_y)) in picked_cells {
                         let removed_cells: Vec<Coords> = self
                             .get_cell_group(fin_x, fin_y, Square)
                             .into_iter()
@@ -779,9 +802,8 @@ impl Sudoku {
                                     || (x1 == fin_x && *x != x1 && *y == y1)
                             })
                             .collect();
-                        for (x, y) in removed_cells {
-                            if self.possibility_board[y][x].remove(&value) {
-                                debug_only!("({}, {}): possibilité {} supprimée", x, y, value);
+                        for cell in removed_cells {
+                            if self.remove_pattern_candidate(cell, value, &pattern) {
                                 modified = true;
                             }
                         }
@@ -851,12 +873,17 @@ impl Sudoku {
                         continue;
                     }
 
-                    for &(x, y) in yellow_cells1
+                    let pattern: Vec<Coords> = square
+                        .iter()
+                        .filter(|(x, y)| self.possibility_board[*y][*x].contains(&value))
+                        .chain([&(x1, y1), &(x2, y2)])
+                        .copied()
+                        .collect();
+                    for &cell in yellow_cells1
                         .difference(&square)
                         .chain(yellow_cells2.difference(&square))
                     {
-                        if self.possibility_board[y][x].remove(&value) {
-                            debug_only!("({}, {}): possibilité {} supprimée", x, y, value);
+                        if self.remove_pattern_candidate(cell, value, &pattern) {
                             modified = true;
                         }
                     }
@@ -897,35 +924,35 @@ impl Sudoku {
 
                 for i2 in i1 + 1..self.n2 {
                     // i1 and i2 represents rows or columns
-                    let mut picked_cells: Vec<(bool, Coords, Coords)> = Vec::new();
+                    let mut picked_cells: Vec<([Coords; 4], Coords, Coords)> = Vec::new();
 
                     let row2_positions: Vec<usize> = (0..self.n2)
                         .filter(|x| self.possibility_board[i2][*x].contains(&value))
                         .collect();
-                    if row1_pos.is_some() && row2_positions.len() == 2 {
-                        let (x11, x12) = row1_pos.unwrap();
+                    if let Some((x11, x12)) = row1_pos.filter(|_| row2_positions.len() == 2) {
                         let x21 = row2_positions[0];
                         let x22 = row2_positions[1];
                         if x11 == x21 || x12 == x22 {
                             let (x1, x2) = if x11 == x21 { (x12, x22) } else { (x11, x21) };
-                            picked_cells.push((true, (x1, i1), (x2, i2)));
+                            let pattern = [(x11, i1), (x12, i1), (x21, i2), (x22, i2)];
+                            picked_cells.push((pattern, (x1, i1), (x2, i2)));
                         }
                     }
 
                     let col2_positions: Vec<usize> = (0..self.n2)
                         .filter(|y| self.possibility_board[*y][i2].contains(&value))
                         .collect();
-                    if col1_pos.is_some() && col2_positions.len() == 2 {
-                        let (y11, y12) = col1_pos.unwrap();
+                    if let Some((y11, y12)) = col1_pos.filter(|_| col2_positions.len() == 2) {
                         let y21 = col2_positions[0];
                         let y22 = col2_positions[1];
                         if y11 == y21 || y12 == y22 {
                             let (y1, y2) = if y11 == y21 { (y12, y22) } else { (y11, y21) };
-                            picked_cells.push((false, (i1, y1), (i2, y2)));
+                            let pattern = [(i1, y11), (i1, y12), (i2, y21), (i2, y22)];
+                            picked_cells.push((pattern, (i1, y1), (i2, y2)));
                         }
                     }
 
-                    for (_is_row, (x1, y1), (x2, y2)) in picked_cells {
+                    for (pattern, (x1, y1), (x2, y2)) in picked_cells {
                         let cell_group1: HashSet<Coords> = self.get_cell_group(x1, y1, All);
                         let cell_group2: HashSet<Coords> = self.get_cell_group(x2, y2, All);
                         let common_cells: HashSet<&Coords> =
@@ -936,8 +963,7 @@ impl Sudoku {
                                 continue;
                             }
 
-                            if self.possibility_board[y][x].remove(&value) {
-                                debug_only!("({}, {}): possibilité {} supprimée", x, y, value);
+                            if self.remove_pattern_candidate((x, y), value, &pattern) {
                                 modified = true;
                             }
                         }
@@ -1033,27 +1059,15 @@ impl Sudoku {
                         if (x3, y3) == (x1, y1) || (x3, y3) == (x2, y2) {
                             continue;
                         }
-                        if self.possibility_board[y3][x3].remove(&value) {
-                            debug_only!(
-                                "({}, {}): possibilité {} supprimée (chaine {:?})",
-                                x3,
-                                y3,
-                                value,
-                                chain
-                            );
+                        if self.remove_pattern_candidate((x3, y3), value, &chain) {
+                            debug_only!("(chaine {:?})", chain);
                             modified = true;
                         }
                     }
-                } else if self.is_same_group(x1, y1, x2, y2) {
-                    for &(x, y) in chain.iter().step_by(2) {
-                        if self.possibility_board[y][x].remove(&value) {
-                            debug_only!(
-                                "({}, {}): possibilité {} supprimée (chaine {:?})",
-                                x,
-                                y,
-                                value,
-                                chain
-                            );
+                } else if self.is_peer(x1, y1, x2, y2) {
+                    for &cell in chain.iter().step_by(2) {
+                        if self.remove_pattern_candidate(cell, value, &chain) {
+                            debug_only!("(chaine {:?})", chain);
                             modified = true;
                         }
                     }
@@ -1107,6 +1121,7 @@ impl Sudoku {
                 }
 
                 for (value, (x1, y1), (x2, y2)) in bi_values {
+                    let pattern = [(x, y), (x1, y1), (x2, y2)];
                     let cell_group1: HashSet<Coords> = self.get_cell_group(x1, y1, All);
                     let cell_group2: HashSet<Coords> = self.get_cell_group(x2, y2, All);
                     let common_cells: HashSet<&Coords> =
@@ -1115,8 +1130,7 @@ impl Sudoku {
                         if (x3 == x1 && y3 == y1) || (x3 == x2 && y3 == y2) {
                             continue;
                         }
-                        if self.possibility_board[y3][x3].remove(&value) {
-                            debug_only!("({}, {}): possibilité {} supprimée", x3, y3, value);
+                        if self.remove_pattern_candidate((x3, y3), value, &pattern) {
                             modified = true;
                         }
                     }
@@ -1168,7 +1182,7 @@ impl Sudoku {
                             }
                             let (x2, y2) = strong_link.into_iter().next().unwrap();
 
-                            let mut picked_cells: Vec<Coords> = Vec::new();
+                            let mut picked_cells: Vec<(Coords, [Coords; 4])> = Vec::new();
 
                             let cell2_groups =
                                 self.get_cell_groups(x2, y2, vec![Row, Column, Square]);
@@ -1186,19 +1200,14 @@ impl Sudoku {
                                     {
                                         continue;
                                     }
-                                    picked_cells.push((x, y3));
-                                    picked_cells.push((x3, y));
+                                    let pattern = [(x, y), (x1, y1), (x2, y2), (x3, y3)];
+                                    picked_cells.push(((x, y3), pattern));
+                                    picked_cells.push(((x3, y), pattern));
                                 }
                             }
 
-                            for (x3, y3) in picked_cells {
-                                if self.possibility_board[y3][x3].remove(&value2) {
-                                    debug_only!(
-                                        "({}, {}): possibilité {} supprimée",
-                                        x3,
-                                        y3,
-                                        value2
-                                    );
+                            for (cell, pattern) in picked_cells {
+                                if self.remove_pattern_candidate(cell, value2, &pattern) {
                                     modified = true;
                                 }
                             }
@@ -1269,12 +1278,25 @@ impl Sudoku {
                             }
                         }
 
-                        for (_is_row, j1, j2, j3) in picked_cells {
+                        for (is_row, j1, j2, j3) in picked_cells {
+                            let pattern: Vec<Coords> = [i1, i2, i3]
+                                .into_iter()
+                                .flat_map(|i| {
+                                    [j1, j2, j3].into_iter().map(move |j| {
+                                        if is_row {
+                                            (j, i)
+                                        } else {
+                                            (i, j)
+                                        }
+                                    })
+                                })
+                                .filter(|&(x, y)| self.possibility_board[y][x].contains(&value))
+                                .collect();
                             let mut common_cells: HashSet<&Coords>;
                             let cell_groupe1: HashSet<Coords>;
                             let cell_groupe2: HashSet<Coords>;
                             let cell_groupe3: HashSet<Coords>;
-                            if _is_row {
+                            if is_row {
                                 cell_groupe1 = self.get_cell_group(j1, i1, Column);
                                 cell_groupe2 = self.get_cell_group(j2, i2, Column);
                                 cell_groupe3 = self.get_cell_group(j3, i3, Column);
@@ -1294,9 +1316,8 @@ impl Sudoku {
                                 common_cells.retain(|&&(x, _)| x != i1 && x != i2 && x != i3);
                             }
 
-                            for &(x, y) in common_cells {
-                                if self.possibility_board[y][x].remove(&value) {
-                                    debug_only!("({}, {}): possibilité {} supprimée", x, y, value);
+                            for &cell in common_cells {
+                                if self.remove_pattern_candidate(cell, value, &pattern) {
                                     modified = true;
                                 }
                             }
@@ -1335,7 +1356,7 @@ impl Sudoku {
                             .collect();
 
                         // i1, i2 and i3 represents rows or columns
-                        let mut picked_cells: Vec<(bool, Coords)> = Vec::new();
+                        let mut picked_cells: Vec<(bool, Coords, Vec<Coords>)> = Vec::new();
 
                         if 2 <= row1_positions.len()
                             && row1_positions.len() <= 3
@@ -1363,6 +1384,13 @@ impl Sudoku {
 
                                 if potential_fins.len() == 1 {
                                     let (fin_x, fin_y) = potential_fins[0];
+                                    let pattern: Vec<Coords> = [i1, i2, i3]
+                                        .into_iter()
+                                        .flat_map(|y| total_positions.iter().map(move |&x| (x, y)))
+                                        .filter(|&(x, y)| {
+                                            self.possibility_board[y][x].contains(&value)
+                                        })
+                                        .collect();
                                     for x in total_positions.into_iter() {
                                         if x != fin_x
                                             && x / self.n == fin_x / self.n
@@ -1371,7 +1399,7 @@ impl Sudoku {
                                             debug_only!(
                                                 "rows i1:{i1}, i2:{i2}, i3:{i3}: fin:{fin_x},{fin_y} picked:{x},{fin_y}"
                                             );
-                                            picked_cells.push((true, (x, fin_y)));
+                                            picked_cells.push((true, (x, fin_y), pattern.clone()));
                                         }
                                     }
                                 }
@@ -1404,6 +1432,13 @@ impl Sudoku {
 
                                 if potential_fins.len() == 1 {
                                     let (fin_x, fin_y) = potential_fins[0];
+                                    let pattern: Vec<Coords> = [i1, i2, i3]
+                                        .into_iter()
+                                        .flat_map(|x| total_positions.iter().map(move |&y| (x, y)))
+                                        .filter(|&(x, y)| {
+                                            self.possibility_board[y][x].contains(&value)
+                                        })
+                                        .collect();
                                     for y in total_positions.into_iter() {
                                         if y != fin_y
                                             && y / self.n == fin_y / self.n
@@ -1412,14 +1447,14 @@ impl Sudoku {
                                             debug_only!(
                                                 "cols i1:{i1}, i2:{i2}, i3:{i3}: fin:{fin_x},{fin_y} picked:{fin_x},{y}"
                                             );
-                                            picked_cells.push((false, (y, fin_x)));
+                                            picked_cells.push((false, (y, fin_x), pattern.clone()));
                                         }
                                     }
                                 }
                             }
                         }
 
-                        for (is_row, data) in picked_cells {
+                        for (is_row, data, pattern) in picked_cells {
                             if is_row {
                                 let (finned_cell_x, finned_cell_y) = data;
                                 let square_y = finned_cell_y - (finned_cell_y % self.n);
@@ -1428,13 +1463,11 @@ impl Sudoku {
                                     if y == i1 || y == i2 || y == i3 {
                                         continue;
                                     }
-                                    if self.possibility_board[y][finned_cell_x].remove(&value) {
-                                        debug_only!(
-                                            "({}, {}): possibilité {} supprimée",
-                                            finned_cell_x,
-                                            y,
-                                            value
-                                        );
+                                    if self.remove_pattern_candidate(
+                                        (finned_cell_x, y),
+                                        value,
+                                        &pattern,
+                                    ) {
                                         modified = true;
                                     }
                                 }
@@ -1446,13 +1479,11 @@ impl Sudoku {
                                     if x == i1 || x == i2 || x == i3 {
                                         continue;
                                     }
-                                    if self.possibility_board[finned_cell_y][x].remove(&value) {
-                                        debug_only!(
-                                            "({}, {}): possibilité {} supprimée",
-                                            x,
-                                            finned_cell_y,
-                                            value
-                                        );
+                                    if self.remove_pattern_candidate(
+                                        (x, finned_cell_y),
+                                        value,
+                                        &pattern,
+                                    ) {
                                         modified = true;
                                     }
                                 }
@@ -1938,4 +1969,16 @@ impl Sudoku {
         warn!("bowmans_bingo isn't implemented yet");
         Ok(false)
     }
+
+    // règle 55: contraintes entre cellules (signes, points, thermomètres et flèches)
+    fn constraint_propagation(&mut self) -> Result<bool, SudokuError> {
+        if !self.has_propagated_constraints() {
+            return Ok(false);
+        }
+        let modified = self.propagate_constraints()?;
+        if modified {
+            debug_only!("possibilités réduites par les contraintes");
+        }
+        Ok(modified)
+    }
 }
@@ -3,6 +3,7 @@ use super::{
     SudokuDifficulty::{self, *},
     SudokuError,
     SudokuGroups::{self, *},
+    SudokuStep,
 };
 use crate::debug_only;
 use rand::{rng, seq::SliceRandom};
@@ -101,6 +102,24 @@ impl Sudoku {
         self.is_canonical = is_canonical;
     }
 
+    pub fn set_difficulty(&mut self, difficulty: SudokuDifficulty) {
+        self.difficulty = difficulty;
+    }
+
+    /// Restores the swaps of a randomized sudoku, without moving its values
+    pub fn set_swaps(
+        &mut self,
+        rows_swap: HashMap<usize, Coords>,
+        values_swap: HashMap<usize, Coords>,
+    ) {
+        self.rows_swap = rows_swap;
+        self.values_swap = values_swap;
+    }
+
+    pub fn set_canonical_filled_board_hash(&mut self, canonical_filled_board_hash: u64) {
+        self.canonical_filled_board_hash = canonical_filled_board_hash;
+    }
+
     pub fn set_value(&mut self, x: usize, y: usize, value: usize) -> Result<(), SudokuError> {
         if value == 0 || value > self.n2 {
             return Err(SudokuError::WrongInput(format!(
@@ -119,34 +138,43 @@ impl Sudoku {
         self.board[y][x] = value;
         self.possibility_board[y][x].clear();
         let mut res = Ok(());
-        for (x1, y1) in self.get_cell_group(x, y, All) {
-            self.possibility_board[y1][x1].remove(&value);
+        let mut changed_cells = vec![(x, y)];
+        for (x1, y1) in self.get_cell_peers(x, y) {
+            if self.possibility_board[y1][x1].remove(&value) {
+                changed_cells.push((x1, y1));
+            }
             if self.board[y1][x1] == value && (x, y) != (x1, y1) {
                 res = Err(SudokuError::SameValueCells(((x, y), (x1, y1))));
             } else if self.board[y1][x1] == 0 && self.possibility_board[y1][x1].is_empty() {
                 res = Err(SudokuError::NoPossibilityCell((x1, y1)));
             }
         }
+        if res.is_ok() && self.has_propagated_constraints() {
+            res = self.propagate_constraints_from(changed_cells).map(|_| ());
+        }
 
         if res.is_ok()
             && self.is_canonical
             && self.canonical_filled_board_hash == 0
             && self.is_filled()
         {
-            self.canonical_filled_board_hash = {
-                let mut hasher = DefaultHasher::new();
-                for y in 0..self.n2 {
-                    for x in 0..self.n2 {
-                        self.board[y][x].hash(&mut hasher);
-                    }
-                }
-                hasher.finish()
-            };
+            self.canonical_filled_board_hash = self.get_board_hash();
         }
 
         res
     }
 
+    /// The hash of the values of the board, read row by row
+    fn get_board_hash(&self) -> u64 {
+        let mut hasher = DefaultHasher::new();
+        for y in 0..self.n2 {
+            for x in 0..self.n2 {
+                self.board[y][x].hash(&mut hasher);
+            }
+        }
+        hasher.finish()
+    }
+
     pub fn insert_possibility(
         &mut self,
         x: usize,
@@ -202,26 +230,64 @@ impl Sudoku {
 
         self.filled_cells -= 1;
         self.board[y][x] = 0;
-        self.possibility_board[y][x] = (1..=self.n2).collect();
 
-        for (x1, y1) in self.get_cell_group(x, y, All) {
-            if self.board[y1][x1] != 0 {
-                self.possibility_board[y][x].remove(&self.board[y1][x1]);
+        self.possibility_board[y][x] = self.get_allowed_values(x, y);
+        let mut changed_cells = vec![(x, y)];
+        for (x1, y1) in self.get_cell_peers(x, y) {
+            if self.board[y1][x1] != 0
+                || (x1, y1) == (x, y)
+                || self
+                    .get_cell_parity(x1, y1)
+                    .is_some_and(|parity| !parity.allows(removed_value))
+            {
                 continue;
             }
 
             if self
-                .get_cell_group(x1, y1, All)
+                .get_cell_peers(x1, y1)
                 .iter()
                 .all(|&(x2, y2)| self.board[y2][x2] != removed_value)
+                && self.possibility_board[y1][x1].insert(removed_value)
             {
-                self.possibility_board[y1][x1].insert(removed_value);
+                changed_cells.push((x1, y1));
+            }
+        }
+
+        // relations, lines and clues can spread the removal further than the peers:
+        // only the cells linked to a changed cell are recomputed from the board
+        if self.has_propagated_constraints() {
+            let linked_cells = self.get_constraint_linked_cells(&changed_cells);
+            for &(x1, y1) in linked_cells.iter() {
+                if self.board[y1][x1] == 0 {
+                    self.possibility_board[y1][x1] = self.get_allowed_values(x1, y1);
+                }
             }
+            changed_cells.extend(linked_cells);
+            self.propagate_constraints_from(changed_cells)?;
         }
 
         Ok(removed_value)
     }
 
+    /// Puts a cell back in a previous state as is, without any propagation to its peers:
+    /// the caller restores every cell that changed (like the undo history of the display)
+    pub fn restore_cell(
+        &mut self,
+        x: usize,
+        y: usize,
+        value: usize,
+        possibilities: HashSet<usize>,
+    ) {
+        match (self.board[y][x], value) {
+            (0, 0) => (),
+            (0, _) => self.filled_cells += 1,
+            (_, 0) => self.filled_cells -= 1,
+            _ => (),
+        }
+        self.board[y][x] = value;
+        self.possibility_board[y][x] = possibilities;
+    }
+
     pub fn is_same_group(&self, x1: usize, y1: usize, x2: usize, y2: usize) -> bool {
         x1 == x2 || y1 == y2 || (x1 / self.n == x2 / self.n && y1 / self.n == y2 / self.n)
     }
@@ -248,6 +314,15 @@ impl Sudoku {
         let board = vec![vec![0; n2]; n2];
         let possibility_board = vec![vec![(1..=n2).collect(); n2]; n2];
         let difficulty = Unknown;
+        let rule_pattern = HashSet::new();
+        let anti_knight = false;
+        let anti_king = false;
+        let relations = HashMap::new();
+        let thermometers = Vec::new();
+        let arrows = Vec::new();
+        let parities = HashMap::new();
+        let non_consecutive = false;
+        let outside_clues = HashMap::new();
         let is_canonical = false;
         let filled_cells = 0;
         let canonical_filled_board_hash = 0;
@@ -262,6 +337,16 @@ impl Sudoku {
                 possibility_board,
                 filled_cells,
                 difficulty,
+                rule_pattern,
+
+                anti_knight,
+                anti_king,
+                relations,
+                thermometers,
+                arrows,
+                parities,
+                non_consecutive,
+                outside_clues,
 
                 is_canonical,
                 canonical_filled_board_hash,
@@ -333,6 +418,16 @@ impl Sudoku {
             possibility_board,
             difficulty,
             filled_cells,
+            rule_pattern,
+
+            anti_knight,
+            anti_king,
+            relations,
+            thermometers,
+            arrows,
+            parities,
+            non_consecutive,
+            outside_clues,
 
             is_canonical,
             canonical_filled_board_hash,
@@ -402,10 +497,25 @@ impl Sudoku {
     }
 
     pub fn into_generate_full_from(self) -> Result<Self, SudokuError> {
-        let mut canonical = self.generate_canonical_from();
+        // the canonical first line and column may not be completable under the constraints,
+        // so a constrained grid is filled as it is and isn't canonical
+        let constrained = self.has_constraints();
+        let mut canonical = if constrained {
+            self
+        } else {
+            self.generate_canonical_from()
+        };
 
         // fill the rest of the sudoku
-        canonical.backtrack_solve(0, 0);
+        if !canonical.backtrack_solve(0, 0) {
+            return Err(SudokuError::InvalidState(format!(
+                "into_generate_full_from() when this sudoku has no solution: \n{canonical}"
+            )));
+        }
+        if constrained {
+            // the board canonize() goes back to
+            canonical.canonical_filled_board_hash = canonical.get_board_hash();
+        }
 
         Ok(canonical)
     }
@@ -416,13 +526,23 @@ impl Sudoku {
         values_swap: Option<HashMap<usize, Coords>>,
         shuffle_floors: bool,
     ) -> Result<(), SudokuError> {
-        if !self.is_canonical {
+        // a constrained grid isn't canonical, it can still be randomized once
+        let never_randomized =
+            self.has_constraints() && self.rows_swap.is_empty() && self.values_swap.is_empty();
+        if !self.is_canonical && !never_randomized {
             return Err(SudokuError::InvalidState(format!(
                 "randomize() when this sudoku is already randomized: {self}"
             )));
         }
+        if self.has_propagated_constraints() {
+            return Err(SudokuError::WrongFunction(format!(
+                "randomize() when this sudoku has relations or lines that depend on its values: {self}"
+            )));
+        }
         let mut rng = rng();
 
+        // swapping rows would break knight and king adjacency, so only the values get swapped
+        let keep_rows = self.anti_knight || self.anti_king;
         self.rows_swap = rows_swap.unwrap_or({
             let mut floors = (0..self.n2)
                 .collect::<Vec<_>>()
@@ -431,13 +551,15 @@ impl Sudoku {
                 .collect::<Vec<_>>();
 
             // shuffle each floor
-            if shuffle_floors {
+            if shuffle_floors && !keep_rows {
                 floors.shuffle(&mut rng);
             }
 
             // shuffle each row inside a floor
-            for floor in floors.iter_mut() {
-                floor.shuffle(&mut rng);
+            if !keep_rows {
+                for floor in floors.iter_mut() {
+                    floor.shuffle(&mut rng);
+                }
             }
 
             let shuffled_rows = floors.into_iter().flatten().enumerate().collect::<Vec<_>>();
@@ -535,15 +657,7 @@ impl Sudoku {
         }
 
         // check if the board is the same as the hash of the canonical board
-        let board_hash = {
-            let mut hasher = DefaultHasher::new();
-            for y in 0..self.n2 {
-                for x in 0..self.n2 {
-                    self.board[y][x].hash(&mut hasher);
-                }
-            }
-            hasher.finish()
-        };
+        let board_hash = self.get_board_hash();
 
         if board_hash != self.canonical_filled_board_hash {
             Err(SudokuError::CanonizationMismatch(
@@ -558,6 +672,77 @@ impl Sudoku {
         }
     }
 
+    /// The canonical form of a game made by hand, so that it can be stored with the generated ones:
+    /// as with [`Sudoku::generate_canonical_from`], its solution has 1..n2 on the first row and the smallest first column.
+    /// Each row of the solution in turn is relabelled 1..n2, the rows of each floor and the floors are sorted by their first cell,
+    /// the smallest first column (then the smallest board) gives the canonical solution,
+    /// so a generated game keeps its own unless another of its rows leads to the same first column
+    pub fn to_canonical_game(&self) -> Result<Self, SudokuError> {
+        if self.has_constraints() {
+            return Err(SudokuError::WrongFunction(format!(
+                "to_canonical_game() when the sudoku has constraints that would not survive its rows being swapped: \n{self}"
+            )));
+        }
+        if self.count_solutions(Some(2)) != 1 {
+            return Err(SudokuError::InvalidState(format!(
+                "to_canonical_game() when the sudoku doesn't have a unique solution: \n{self}"
+            )));
+        }
+        let mut solution = self.clone();
+        solution.backtrack_solve(0, 0);
+
+        #[allow(clippy::type_complexity)]
+        let mut canonical: Option<((Vec<usize>, Vec<usize>), Vec<usize>, Vec<usize>)> = None;
+        for first_row in 0..self.n2 {
+            let mut values = vec![0; self.n2 + 1];
+            for (x, &value) in solution.board[first_row].iter().enumerate() {
+                values[value] = x + 1;
+            }
+            let first_cell = |y: &usize| values[solution.board[*y][0]];
+            let mut floors = (0..self.n2)
+                .collect::<Vec<_>>()
+                .chunks(self.n)
+                .map(|floor| {
+                    let mut floor = floor.to_vec();
+                    floor.sort_by_key(first_cell);
+                    floor
+                })
+                .collect::<Vec<_>>();
+            floors.sort_by_key(|floor| first_cell(&floor[0]));
+            let rows = floors.concat();
+
+            let first_column = rows.iter().map(first_cell).collect::<Vec<_>>();
+            let board = rows
+                .iter()
+                .flat_map(|&y| solution.board[y].iter().map(|&value| values[value]))
+                .collect::<Vec<_>>();
+            let key = (first_column, board);
+            if canonical
+                .as_ref()
+                .is_none_or(|(canonical_key, _, _)| key < *canonical_key)
+            {
+                canonical = Some((key, rows, values));
+            }
+        }
+        let (_, rows, values) = canonical.unwrap();
+
+        let mut game = Self::new(self.n);
+        for (to_y, &y) in rows.iter().enumerate() {
+            for x in 0..self.n2 {
+                if self.board[y][x] != 0 {
+                    game.set_value(x, to_y, values[self.board[y][x]])?;
+                }
+            }
+        }
+        game.is_canonical = true;
+        game.difficulty = self.difficulty;
+        // a canonical sudoku gets its hash once filled, as the generated grids do
+        let mut canonical_solution = game.clone();
+        canonical_solution.backtrack_solve(0, 0);
+        game.canonical_filled_board_hash = canonical_solution.canonical_filled_board_hash;
+        Ok(game)
+    }
+
     pub fn parse_file(file_name: &str) -> Result<Self, SudokuError> {
         let mut file_path = current_dir().unwrap();
         file_path.push("res/sudoku_samples/");
@@ -579,7 +764,13 @@ impl Sudoku {
         })?;
 
         let mut sudoku = Self::new(n);
-        for (y, line) in lines.take(sudoku.n2).enumerate() {
+        let board_lines = lines.by_ref().take(sudoku.n2).collect::<Vec<_>>();
+        for line in lines {
+            sudoku.parse_constraint_line(line)?;
+        }
+        sudoku.reset_possibility_board()?;
+
+        for (y, line) in board_lines.into_iter().enumerate() {
             for (x, cell) in line.split_whitespace().enumerate() {
                 let value: usize = cell.parse().unwrap();
                 if value == 0 {
@@ -603,6 +794,7 @@ impl Sudoku {
                     .join(" "),
             );
         }
+        lines.extend(self.constraints_to_string_lines());
         lines.join("\n")
     }
 
@@ -637,6 +829,7 @@ impl Sudoku {
 
         // try the rules and set the difficulty in consequence
         for &&(rule_id, difficulty, rule) in rules.iter() {
+            self.rule_pattern.clear();
             // if the rule can't be applied, then pass to the next one
             if !rule(self).unwrap_or(false) {
                 continue;
@@ -667,6 +860,103 @@ impl Sudoku {
         did_anything
     }
 
+    /// Finds the next rule to use and what it would change, without changing the sudoku
+    pub fn find_step(&self, max_difficulty: Option<SudokuDifficulty>) -> Option<SudokuStep> {
+        let mut next = self.clone();
+        let rule = next.rule_solve(None, max_difficulty).ok()??;
+
+        let mut placements = Vec::new();
+        let mut eliminations = Vec::new();
+        for y in 0..self.n2 {
+            for x in 0..self.n2 {
+                if self.board[y][x] != next.board[y][x] {
+                    placements.push(((x, y), next.board[y][x]));
+                    continue;
+                }
+                for &value in self.possibility_board[y][x].difference(&next.possibility_board[y][x])
+                {
+                    eliminations.push(((x, y), value));
+                }
+            }
+        }
+        eliminations.sort();
+
+        // a placed value removes itself from its groups, the cell is the whole pattern
+        if let Some(&((x, y), value)) = placements.first() {
+            eliminations.clear();
+            // a hidden single happens in the group where no other cell could hold the value
+            let unit = self
+                .get_cell_groups(x, y, vec![Row, Column, Square])
+                .into_iter()
+                .find(|group| {
+                    group.iter().all(|&(x2, y2)| {
+                        (x2, y2) == (x, y) || !self.possibility_board[y2][x2].contains(&value)
+                    })
+                })
+                .unwrap_or_else(|| self.get_cell_group(x, y, Square));
+            return Some(SudokuStep {
+                rule,
+                unit,
+                pattern: placements.iter().map(|&(cell, _)| cell).collect(),
+                placements,
+                eliminations,
+            });
+        }
+
+        let changed_cells: HashSet<Coords> = eliminations.iter().map(|&(cell, _)| cell).collect();
+        // the hidden subsets, wings, chains and fishes tell their pattern, the unit is made of the groups linking its cells
+        if !next.rule_pattern.is_empty() {
+            let pattern = next.rule_pattern;
+            let unit = self
+                .get_group(All)
+                .into_iter()
+                .filter(|group| group.intersection(&pattern).nth(1).is_some())
+                .flatten()
+                .chain(pattern.iter().copied())
+                .collect();
+            return Some(SudokuStep {
+                rule,
+                unit,
+                pattern,
+                placements,
+                eliminations,
+            });
+        }
+
+        // the kind of group that holds the changes in the fewest groups
+        let units = [Row, Column, Square]
+            .into_iter()
+            .map(|groups| {
+                self.get_group(groups)
+                    .into_iter()
+                    .filter(|group| !group.is_disjoint(&changed_cells))
+                    .collect::<Vec<_>>()
+            })
+            .min_by_key(|units| units.len())
+            .unwrap_or_default();
+        let unit: HashSet<Coords> = units.into_iter().flatten().collect();
+
+        let values: HashSet<usize> = eliminations.iter().map(|&(_, value)| value).collect();
+        let pattern = unit
+            .iter()
+            .filter(|&&(x, y)| {
+                !changed_cells.contains(&(x, y))
+                    && self.possibility_board[y][x]
+                        .iter()
+                        .any(|value| values.contains(value))
+            })
+            .copied()
+            .collect();
+
+        Some(SudokuStep {
+            rule,
+            unit,
+            pattern,
+            placements,
+            eliminations,
+        })
+    }
+
     // BACKTRACK SOLVING
 
     pub fn backtrack_solve(&mut self, mut x: usize, mut y: usize) -> bool {
@@ -691,17 +981,24 @@ impl Sudoku {
             .cloned()
             .collect::<Vec<_>>();
         possibilities.shuffle(&mut rng());
+        // with relations or lines, remove_value recomputes the whole board: restoring a copy is faster
+        let saved_possibility_board = if !self.has_propagated_constraints() {
+            None
+        } else {
+            Some(self.possibility_board.clone())
+        };
         for value in possibilities {
-            if self.set_value(x, y, value).is_err() {
-                self.remove_value(x, y).unwrap();
-                continue;
-            }
-
-            if self.backtrack_solve(x + 1, y) {
+            if self.set_value(x, y, value).is_ok() && self.backtrack_solve(x + 1, y) {
                 return true;
             }
 
-            self.remove_value(x, y).unwrap();
+            if let Some(saved_possibility_board) = &saved_possibility_board {
+                self.board[y][x] = 0;
+                self.filled_cells -= 1;
+                self.possibility_board = saved_possibility_board.clone();
+            } else {
+                self.remove_value(x, y).unwrap();
+            }
         }
 
         false
@@ -769,9 +1066,31 @@ impl Sudoku {
             .cloned()
             .collect::<Vec<_>>();
         possibilities.shuffle(&mut rng());
+        // with relations or lines, remove_value recomputes the whole board: restoring a copy is faster
+        let saved_possibility_board = if !self.has_propagated_constraints() {
+            None
+        } else {
+            Some(self.possibility_board.clone())
+        };
         let mut sub_solutions = 0;
         for value in possibilities {
-            match self.set_value(x, y, value) {
+            let result = self.set_value(x, y, value);
+            if let Some(saved_possibility_board) = &saved_possibility_board {
+                if result.is_ok() {
+                    sub_solutions += self._count_solutions(empty_cells.clone(), max_solutions);
+                }
+                self.board[y][x] = 0;
+                self.filled_cells -= 1;
+                self.possibility_board = saved_possibility_board.clone();
+                if let Some(max_solutions) = max_solutions {
+                    if sub_solutions >= max_solutions {
+                        return sub_solutions;
+                    }
+                }
+                continue;
+            }
+
+            match result {
                 Ok(()) => (),
                 Err(_) => {
                     let _ = self.remove_value(x, y);
@@ -814,6 +1133,10 @@ impl Sudoku {
             );
         }
 
+        if self.has_constraints() {
+            lines.push(format!("constraints: {}", self.constraints_description()));
+        }
+
         for y in 0..self.n2 {
             if y != 0 && y % self.n == 0 {
                 let temp = "━".repeat(2 * self.n2 + 4 * self.n + 1);
@@ -928,6 +1251,11 @@ impl Sudoku {
     }
 
     pub fn game_to_db(&self) -> Result<DBNewCanonicalSudokuGame, SudokuError> {
+        if self.has_constraints() {
+            return Err(SudokuError::WrongFunction(format!(
+                "game_to_db() when the sudoku has constraints the database can't store: \n{self}"
+            )));
+        }
         if self.is_filled() {
             return Err(
                 SudokuError::WrongFunction(
@@ -959,6 +1287,11 @@ impl Sudoku {
     pub fn filled_to_db(
         &self,
     ) -> Result<(DBCanonicalSudoku, Vec<DBCanonicalSudokuSquare>), SudokuError> {
+        if self.has_constraints() {
+            return Err(SudokuError::WrongFunction(format!(
+                "filled_to_db() when the sudoku has constraints the database can't store: \n{self}"
+            )));
+        }
         if !self.is_filled() {
             return Err(
                 SudokuError::WrongFunction(
@@ -1051,7 +1384,16 @@ impl std::fmt::Display for Sudoku {
 
 impl PartialEq for Sudoku {
     fn eq(&self, other: &Self) -> bool {
-        if self.n != other.n {
+        if self.n != other.n
+            || self.anti_knight != other.anti_knight
+            || self.anti_king != other.anti_king
+            || self.relations != other.relations
+            || self.thermometers != other.thermometers
+            || self.arrows != other.arrows
+            || self.parities != other.parities
+            || self.non_consecutive != other.non_consecutive
+            || self.outside_clues != other.outside_clues
+        {
             return false;
         }
 
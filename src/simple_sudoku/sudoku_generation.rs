@@ -1,9 +1,15 @@
 use crate::duration_to_string;
 
-use super::{Sudoku, SudokuDifficulty};
-use rand::seq::SliceRandom;
+use super::{
+    Coords, Sudoku, SudokuDifficulty, SudokuError, SudokuGroups, SudokuOutsideClue, SudokuParity,
+    SudokuRelation,
+};
+use rand::{
+    seq::{IteratorRandom, SliceRandom},
+    Rng,
+};
 use std::{
-    collections::HashSet,
+    collections::{HashMap, HashSet},
     io::{stdout, Write},
     sync::{mpsc, Arc, Mutex},
     thread::{self, available_parallelism},
@@ -335,4 +341,326 @@ impl Sudoku {
         // we just found a solution !
         sudoku_generation_input.tx.send(Some(self.clone())).unwrap();
     }
+
+    ////////////////////////////////////////////////////////////////////////////////////////////////////////////////////////////////
+    ////////////////////////////////////////////////////////   RELATIONS   /////////////////////////////////////////////////////////
+    ////////////////////////////////////////////////////////////////////////////////////////////////////////////////////////////////
+
+    pub fn generate_relations_from(&self, relations: &[SudokuRelation]) -> Option<Self> {
+        self.clone().into_generate_relations_from(relations)
+    }
+
+    // from a filled sudoku, remove as many values as possible, and each time a removal breaks the
+    // uniqueness of the solution, place just enough markers around the removed cell to restore it
+    pub fn into_generate_relations_from(self, relations: &[SudokuRelation]) -> Option<Self> {
+        if !self.is_filled() || relations.is_empty() {
+            return None;
+        }
+        let mut rng = rand::rng();
+
+        // every marker that is true in the solution, at most one per pair of cells
+        let mut cell_markers: HashMap<Coords, Vec<(Coords, Coords, SudokuRelation)>> =
+            HashMap::new();
+        for y in 0..self.n2 {
+            for x in 0..self.n2 {
+                for (x2, y2) in [(x + 1, y), (x, y + 1)] {
+                    if x2 >= self.n2 || y2 >= self.n2 {
+                        continue;
+                    }
+                    let (v1, v2) = (self.board[y][x], self.board[y2][x2]);
+                    let marker = relations
+                        .iter()
+                        .filter_map(|&relation| match relation {
+                            SudokuRelation::GreaterThan if v1 > v2 => {
+                                Some(((x, y), (x2, y2), relation))
+                            }
+                            SudokuRelation::GreaterThan => Some(((x2, y2), (x, y), relation)),
+                            _ if relation.is_satisfied(v1, v2) => {
+                                Some(((x, y), (x2, y2), relation))
+                            }
+                            _ => None,
+                        })
+                        .choose(&mut rng);
+                    if let Some(marker) = marker {
+                        cell_markers.entry((x, y)).or_default().push(marker);
+                        cell_markers.entry((x2, y2)).or_default().push(marker);
+                    }
+                }
+            }
+        }
+
+        let mut sudoku = self.clone();
+        sudoku.difficulty = SudokuDifficulty::Unknown;
+        sudoku.relations.clear();
+
+        let mut cells = (0..self.n2 * self.n2)
+            .map(|cell_i| (cell_i % self.n2, cell_i / self.n2))
+            .collect::<Vec<_>>();
+        cells.shuffle(&mut rng);
+        for (x, y) in cells {
+            let value = sudoku.board[y][x];
+            sudoku.remove_value(x, y).ok()?;
+
+            let mut markers = cell_markers.remove(&(x, y)).unwrap_or_default();
+            markers.retain(|(cell1, cell2, _)| !sudoku.relations.contains_key(&(*cell1, *cell2)));
+            markers.shuffle(&mut rng);
+            let mut added_markers = Vec::new();
+            for (cell1, cell2, relation) in markers {
+                if sudoku.is_unique() {
+                    break;
+                }
+                sudoku.add_relation(cell1, cell2, relation).ok()?;
+                added_markers.push((cell1, cell2, relation));
+            }
+
+            // the markers around this cell aren't enough: keep its value
+            if !sudoku.is_unique() {
+                for &(cell1, cell2, _) in added_markers.iter() {
+                    sudoku.remove_relation(cell1, cell2).ok()?;
+                }
+                sudoku.set_value(x, y, value).ok()?;
+            }
+        }
+
+        // remove the markers that became useless
+        let mut placed_markers = sudoku
+            .relations
+            .iter()
+            .map(|(&(cell1, cell2), &relation)| (cell1, cell2, relation))
+            .collect::<Vec<_>>();
+        placed_markers.shuffle(&mut rng);
+        for (cell1, cell2, relation) in placed_markers {
+            sudoku.remove_relation(cell1, cell2).ok()?;
+            if !sudoku.is_unique() {
+                sudoku.add_relation(cell1, cell2, relation).ok()?;
+            }
+        }
+
+        Some(sudoku)
+    }
+
+    ////////////////////////////////////////////////////////////////////////////////////////////////////////////////////////////////
+    //////////////////////////////////////////////////////////   LINES   ///////////////////////////////////////////////////////////
+    ////////////////////////////////////////////////////////////////////////////////////////////////////////////////////////////////
+
+    pub fn generate_lines_from(
+        &self,
+        thermometer_count: usize,
+        arrow_count: usize,
+    ) -> Option<Self> {
+        self.clone()
+            .into_generate_lines_from(thermometer_count, arrow_count)
+    }
+
+    // from a filled sudoku, draw thermometers and arrows that are true in the solution,
+    // then remove as many values as possible while the solution stays unique
+    pub fn into_generate_lines_from(
+        self,
+        thermometer_count: usize,
+        arrow_count: usize,
+    ) -> Option<Self> {
+        if !self.is_filled() {
+            return None;
+        }
+        let mut rng = rand::rng();
+
+        let mut sudoku = self.clone();
+        sudoku.difficulty = SudokuDifficulty::Unknown;
+        sudoku.remove_lines().ok()?;
+
+        // lines don't cross each other, so they stay readable
+        let mut used_cells: HashSet<Coords> = HashSet::new();
+        let mut tries = 0;
+        while (sudoku.thermometers.len() < thermometer_count || sudoku.arrows.len() < arrow_count)
+            && tries < 1000
+        {
+            tries += 1;
+            let start = (rng.random_range(0..self.n2), rng.random_range(0..self.n2));
+            if used_cells.contains(&start) {
+                continue;
+            }
+
+            if sudoku.thermometers.len() < thermometer_count {
+                // walk towards bigger values
+                let path = self.random_line(start, &used_cells, &mut rng, |path, value| {
+                    value > self.board[path.last().unwrap().1][path.last().unwrap().0]
+                });
+                if path.len() >= 3 {
+                    used_cells.extend(path.iter());
+                    sudoku.add_thermometer(path).ok()?;
+                }
+            } else {
+                // walk while the sum stays below the circle
+                let circle_value = self.board[start.1][start.0];
+                let mut path = self.random_line(start, &used_cells, &mut rng, |path, value| {
+                    path.iter()
+                        .skip(1)
+                        .map(|&(x, y)| self.board[y][x])
+                        .sum::<usize>()
+                        + value
+                        <= circle_value
+                });
+                let sum = path
+                    .iter()
+                    .skip(1)
+                    .map(|&(x, y)| self.board[y][x])
+                    .sum::<usize>();
+                if path.len() >= 3 && sum == circle_value {
+                    used_cells.extend(path.iter());
+                    let circle = path.remove(0);
+                    sudoku.add_arrow(circle, path).ok()?;
+                }
+            }
+        }
+
+        sudoku.remove_values_while_unique(&mut rng).ok()?;
+        Some(sudoku)
+    }
+
+    // remove every value we can, in random order, while the solution stays unique
+    fn remove_values_while_unique(
+        &mut self,
+        rng: &mut rand::rngs::ThreadRng,
+    ) -> Result<(), SudokuError> {
+        let mut cells = (0..self.n2 * self.n2)
+            .map(|cell_i| (cell_i % self.n2, cell_i / self.n2))
+            .filter(|&(x, y)| self.board[y][x] != 0)
+            .collect::<Vec<_>>();
+        cells.shuffle(rng);
+        for (x, y) in cells {
+            // same minimal filled cells as the games generation, the uniqueness check explodes below
+            if self.filled_cells < 2 * self.n2 {
+                break;
+            }
+            let value = self.board[y][x];
+            self.remove_value(x, y)?;
+            if !self.is_unique() {
+                self.set_value(x, y, value)?;
+            }
+        }
+        Ok(())
+    }
+
+    // random walk from start on free cells, each step accepted by can_extend(path, next_value)
+    fn random_line(
+        &self,
+        start: Coords,
+        used_cells: &HashSet<Coords>,
+        rng: &mut rand::rngs::ThreadRng,
+        can_extend: impl Fn(&[Coords], usize) -> bool,
+    ) -> Vec<Coords> {
+        let mut path = vec![start];
+        loop {
+            let (x, y) = *path.last().unwrap();
+            let next = (x.saturating_sub(1)..=(x + 1).min(self.n2 - 1))
+                .flat_map(|x1| {
+                    (y.saturating_sub(1)..=(y + 1).min(self.n2 - 1)).map(move |y1| (x1, y1))
+                })
+                .filter(|cell| !path.contains(cell) && !used_cells.contains(cell))
+                .filter(|&(x1, y1)| can_extend(&path, self.board[y1][x1]))
+                .choose(rng);
+            match next {
+                Some(next) => path.push(next),
+                None => return path,
+            }
+        }
+    }
+
+    ////////////////////////////////////////////////////////////////////////////////////////////////////////////////////////////////
+    /////////////////////////////////////////////////////////   PARITIES   /////////////////////////////////////////////////////////
+    ////////////////////////////////////////////////////////////////////////////////////////////////////////////////////////////////
+
+    pub fn generate_parities_from(&self, parity_count: usize) -> Option<Self> {
+        self.clone().into_generate_parities_from(parity_count)
+    }
+
+    // from a filled sudoku, shade random cells with the parity of their value,
+    // then remove as many values as possible while the solution stays unique
+    pub fn into_generate_parities_from(self, parity_count: usize) -> Option<Self> {
+        if !self.is_filled() {
+            return None;
+        }
+        let mut rng = rand::rng();
+
+        let mut sudoku = self;
+        sudoku.difficulty = SudokuDifficulty::Unknown;
+        let cells = (0..sudoku.n2 * sudoku.n2)
+            .map(|cell_i| (cell_i % sudoku.n2, cell_i / sudoku.n2))
+            .filter(|cell| !sudoku.parities.contains_key(cell))
+            .choose_multiple(&mut rng, parity_count);
+        for (x, y) in cells {
+            let parity = SudokuParity::of(sudoku.board[y][x]);
+            sudoku.set_cell_parity(x, y, Some(parity)).ok()?;
+        }
+
+        sudoku.remove_values_while_unique(&mut rng).ok()?;
+        Some(sudoku)
+    }
+
+    ////////////////////////////////////////////////////////////////////////////////////////////////////////////////////////////////
+    ///////////////////////////////////////////////////////   OUTSIDE CLUES   //////////////////////////////////////////////////////
+    ////////////////////////////////////////////////////////////////////////////////////////////////////////////////////////////////
+
+    pub fn generate_outside_clues_from(
+        &self,
+        sandwiches: bool,
+        little_killers: bool,
+    ) -> Option<Self> {
+        self.clone()
+            .into_generate_outside_clues_from(sandwiches, little_killers)
+    }
+
+    // from a filled sudoku, place every clue and remove as many values as possible,
+    // then remove the clues that aren't needed for the solution to stay unique
+    pub fn into_generate_outside_clues_from(
+        self,
+        sandwiches: bool,
+        little_killers: bool,
+    ) -> Option<Self> {
+        if !self.is_filled() || (!sandwiches && !little_killers) {
+            return None;
+        }
+        let mut rng = rand::rng();
+
+        let mut clues = Vec::new();
+        if sandwiches {
+            for i in 0..self.n2 {
+                clues.push(SudokuOutsideClue::Sandwich(SudokuGroups::Row, i));
+                clues.push(SudokuOutsideClue::Sandwich(SudokuGroups::Column, i));
+            }
+        }
+        if little_killers {
+            // every diagonal going down, with at least 2 cells
+            for i in 0..self.n2 {
+                clues.push(SudokuOutsideClue::LittleKiller((i, 0), (1, 1)));
+                clues.push(SudokuOutsideClue::LittleKiller((i, 0), (-1, 1)));
+                if i > 0 {
+                    clues.push(SudokuOutsideClue::LittleKiller((0, i), (1, 1)));
+                    clues.push(SudokuOutsideClue::LittleKiller((self.n2 - 1, i), (-1, 1)));
+                }
+            }
+            clues.retain(|&clue| self.get_outside_clue_cells(clue).len() >= 2);
+        }
+
+        let mut sudoku = self.clone();
+        sudoku.difficulty = SudokuDifficulty::Unknown;
+        sudoku.outside_clues.clear();
+        for &clue in clues.iter() {
+            let sum = self.get_outside_clue_sum(clue)?;
+            sudoku.add_outside_clue(clue, sum).ok()?;
+        }
+
+        sudoku.remove_values_while_unique(&mut rng).ok()?;
+
+        // remove the clues that became useless
+        clues.shuffle(&mut rng);
+        for clue in clues {
+            let sum = sudoku.remove_outside_clue(clue).ok()??;
+            if !sudoku.is_unique() {
+                sudoku.add_outside_clue(clue, sum).ok()?;
+            }
+        }
+
+        Some(sudoku)
+    }
 }
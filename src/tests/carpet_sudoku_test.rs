@@ -1,9 +1,21 @@
 #[cfg(test)]
 mod tests {
+    use std::collections::HashSet;
     use std::io::{stdout, Write};
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::sync::Arc;
+    use std::time::{Duration, Instant};
 
-    use crate::carpet_sudoku::CarpetSudoku;
-    use crate::{carpet_sudoku::CarpetPattern, simple_sudoku::SudokuDifficulty};
+    use crate::carpet_sudoku::{CarpetLayout, CarpetLinks, CarpetSudoku};
+    #[cfg(feature = "database")]
+    use crate::database::Database;
+    use crate::{
+        carpet_sudoku::CarpetPattern,
+        simple_sudoku::{
+            Sudoku, SudokuDifficulty, SudokuError, SudokuGroups, SudokuOutsideClue, SudokuParity,
+            SudokuRelation,
+        },
+    };
 
     #[test]
     fn canonize_randomize() {
@@ -32,6 +44,1032 @@ mod tests {
         }
     }
 
+    #[test]
+    fn save_string() {
+        let mut carpets = [
+            CarpetPattern::Simple,
+            CarpetPattern::Samurai,
+            CarpetPattern::Diagonal(3),
+            CarpetPattern::Sohei,
+        ]
+        .map(|pattern| CarpetSudoku::generate_full(3, pattern))
+        .to_vec();
+        let layout = CarpetLayout::new(vec![(0, 0), (6, 3)], None);
+        carpets.push(CarpetSudoku::new_from_layout(3, layout).generate_full_from());
+
+        for mut original in carpets {
+            print!("{}: ", original.get_pattern());
+            stdout().flush().unwrap();
+
+            original.randomize().unwrap();
+            // a few empty cells, so that there are possibilities for the rules to reduce
+            for sudoku_id in 0..original.get_n_sudokus() {
+                for i in (0..81).step_by(4) {
+                    if original.get_cell_value(sudoku_id, i % 9, i / 9) != 0 {
+                        original.remove_value(sudoku_id, i % 9, i / 9).unwrap();
+                    }
+                }
+            }
+            original.rule_solve(None).unwrap();
+
+            let parsed = CarpetSudoku::parse_save_string(&original.to_save_string()).unwrap();
+            assert!(parsed == original);
+            assert_eq!(parsed.get_difficulty(), original.get_difficulty());
+            for (sudoku1, sudoku2) in parsed.get_sudokus().iter().zip(original.get_sudokus()) {
+                assert_eq!(
+                    sudoku1.get_possibility_board(),
+                    sudoku2.get_possibility_board()
+                );
+                assert_eq!(sudoku1.get_rows_swap(), sudoku2.get_rows_swap());
+                assert_eq!(sudoku1.get_values_swap(), sudoku2.get_values_swap());
+            }
+            println!("OK");
+        }
+
+        assert!(CarpetSudoku::parse_save_string("n: 3\npattern: x").is_err());
+
+        // custom links without any layout
+        let links = CarpetLinks::from([
+            (0, HashSet::from([(8, 1, 0)])),
+            (1, HashSet::from([(0, 0, 8)])),
+        ]);
+        let mut original = CarpetSudoku::try_new_custom(
+            3,
+            vec![Sudoku::new(3), Sudoku::new(3)],
+            links,
+            CarpetLayout::default(),
+        )
+        .unwrap();
+        original.set_value(0, 8, 8, 5).unwrap();
+        original.set_value(1, 4, 4, 2).unwrap();
+        let parsed = CarpetSudoku::parse_save_string(&original.to_save_string()).unwrap();
+        assert!(parsed == original);
+        assert_eq!(parsed.get_links(), original.get_links());
+        assert_eq!(parsed.get_pattern(), original.get_pattern());
+        assert_eq!(parsed.get_cell_value(1, 2, 2), 5);
+    }
+
+    #[test]
+    fn save_string_constraints() {
+        let samurai = CarpetSudoku::new(3, CarpetPattern::Samurai);
+        let mut sudokus = samurai.get_sudokus().clone();
+        sudokus[0].set_anti_knight(true).unwrap();
+        sudokus[0].set_anti_king(true).unwrap();
+        sudokus[1].set_non_consecutive(true).unwrap();
+        sudokus[2]
+            .set_cell_parity(4, 4, Some(SudokuParity::Even))
+            .unwrap();
+        sudokus[2]
+            .add_relation((0, 0), (1, 0), SudokuRelation::GreaterThan)
+            .unwrap();
+        sudokus[3]
+            .add_thermometer(vec![(0, 0), (1, 0), (2, 0)])
+            .unwrap();
+        sudokus[4].add_arrow((4, 4), vec![(5, 4), (6, 4)]).unwrap();
+        sudokus[4]
+            .add_outside_clue(SudokuOutsideClue::Sandwich(SudokuGroups::Row, 0), 10)
+            .unwrap();
+        let mut original = CarpetSudoku::try_new_custom(
+            3,
+            sudokus,
+            samurai.get_links(),
+            samurai.get_layout().clone(),
+        )
+        .unwrap();
+        original.set_value(4, 4, 4, 9).unwrap();
+
+        let parsed = CarpetSudoku::parse_save_string(&original.to_save_string()).unwrap();
+        assert!(parsed == original);
+        for (sudoku1, sudoku2) in parsed.get_sudokus().iter().zip(original.get_sudokus()) {
+            assert_eq!(sudoku1.is_anti_knight(), sudoku2.is_anti_knight());
+            assert_eq!(sudoku1.is_anti_king(), sudoku2.is_anti_king());
+            assert_eq!(sudoku1.is_non_consecutive(), sudoku2.is_non_consecutive());
+            assert_eq!(sudoku1.get_parities(), sudoku2.get_parities());
+            assert_eq!(sudoku1.get_relations(), sudoku2.get_relations());
+            assert_eq!(sudoku1.get_thermometers(), sudoku2.get_thermometers());
+            assert_eq!(sudoku1.get_arrows(), sudoku2.get_arrows());
+            assert_eq!(sudoku1.get_outside_clues(), sudoku2.get_outside_clues());
+            assert_eq!(
+                sudoku1.get_possibility_board(),
+                sudoku2.get_possibility_board()
+            );
+        }
+    }
+
+    #[test]
+    fn conflicting_value() {
+        let layout = CarpetLayout::new(vec![(0, 0), (6, 6)], None);
+        let mut carpet = CarpetSudoku::new_from_layout(3, layout);
+        carpet.set_value(0, 6, 6, 5).unwrap();
+
+        // the value is set in both twins even though the box already holds it
+        assert!(matches!(
+            carpet.set_conflicting_value(0, 7, 7, 5),
+            Err(SudokuError::SameValueCells(_))
+        ));
+        assert_eq!(carpet.get_cell_value(0, 7, 7), 5);
+        assert_eq!(carpet.get_cell_value(1, 1, 1), 5);
+        assert!(!carpet.get_cell_possibilities(1, 4, 1).contains(&5));
+
+        assert_eq!(carpet.remove_value(1, 1, 1), Ok(5));
+        assert_eq!(carpet.get_cell_value(0, 7, 7), 0);
+        assert_eq!(carpet.get_cell_value(1, 1, 1), 0);
+    }
+
+    #[test]
+    fn count_solutions_cancel() {
+        let game = CarpetSudoku::generate_new(3, CarpetPattern::Simple, SudokuDifficulty::Easy);
+        assert_eq!(
+            game.count_solutions_cancellable(Some(2), &AtomicBool::new(false)),
+            Some(1)
+        );
+        assert_eq!(
+            game.count_solutions_cancellable(Some(2), &AtomicBool::new(true)),
+            None
+        );
+
+        let carpet = CarpetSudoku::new(3, CarpetPattern::Samurai);
+
+        // counting every solution of an empty carpet never ends, the flag stops it
+        let cancel = Arc::new(AtomicBool::new(false));
+        let thread_cancel = Arc::clone(&cancel);
+        let handle =
+            std::thread::spawn(move || carpet.count_solutions_cancellable(None, &thread_cancel));
+        std::thread::sleep(Duration::from_millis(100));
+        let start = Instant::now();
+        cancel.store(true, Ordering::Relaxed);
+        assert_eq!(handle.join().unwrap(), None);
+        assert!(start.elapsed() < Duration::from_secs(1));
+    }
+
+    #[test]
+    fn solve_timeline() {
+        for pattern in [CarpetPattern::Simple, CarpetPattern::Diagonal(2)] {
+            let carpet = CarpetSudoku::generate_new(3, pattern, SudokuDifficulty::Easy);
+            let timeline = carpet.get_solve_timeline(None);
+
+            let mut solved = carpet.clone();
+            let (_, rules_used) = solved.rule_solve_until((false, false), None);
+            let rules = rules_used.into_iter().flatten().collect::<Vec<_>>();
+            assert_eq!(
+                timeline
+                    .iter()
+                    .map(|step| (step.sudoku_id, step.rule))
+                    .collect::<Vec<_>>(),
+                rules
+            );
+            assert!(timeline
+                .iter()
+                .filter(|step| step.sudoku_id.is_some())
+                .all(|step| step.step.as_ref().is_some_and(|s| s.rule == step.rule)));
+
+            // the carpet itself isn't solved, each step changes it
+            assert!(!carpet.is_filled());
+            let mut state = carpet.clone();
+            for step in timeline.iter() {
+                assert!(!step.diff.is_empty());
+                let before = state.get_possibility_board();
+                state.apply_diff(&step.diff, false);
+                assert_ne!(before, state.get_possibility_board());
+            }
+            assert!(state.is_filled());
+            assert_eq!(
+                state.get_possibility_board(),
+                solved.get_possibility_board()
+            );
+            assert_eq!(state.get_diff(&solved, None), Vec::new());
+
+            // undoing the steps the other way round gives the carpet back
+            for step in timeline.iter().rev() {
+                state.apply_diff(&step.diff, true);
+            }
+            assert_eq!(state.get_diff(&carpet, None), Vec::new());
+            assert!(!state.is_filled());
+        }
+    }
+
+    #[test]
+    fn gattai_patterns() {
+        let patterns = [
+            (CarpetPattern::Sohei, 4, 4),
+            (CarpetPattern::Butterfly, 4, 32),
+            (CarpetPattern::Flower, 5, 46),
+            (CarpetPattern::Windmill, 5, 8),
+            (CarpetPattern::Gattai8, 8, 8),
+        ];
+        for (pattern, n_sudokus, n_raw_links) in patterns {
+            println!("Pattern: {pattern}");
+
+            let (db_pattern, db_pattern_size) = pattern.to_db();
+            assert_eq!(CarpetPattern::from_db(db_pattern, db_pattern_size), pattern);
+            assert_eq!(pattern.get_n_sudokus(3), n_sudokus);
+            assert!(CarpetPattern::try_from_db(db_pattern, Some(2)).is_err());
+            assert!(CarpetPattern::try_from_db(-1, db_pattern_size).is_err());
+            assert_eq!(pattern.get_raw_links(3).len(), n_raw_links);
+
+            let mut filled = CarpetSudoku::generate_full(3, pattern);
+            assert_eq!(filled.get_n_sudokus(), n_sudokus);
+            for randomized in [false, true] {
+                if randomized {
+                    filled.randomize().unwrap();
+                }
+                assert!(filled.is_filled());
+                for sudoku_i in 0..n_sudokus {
+                    for y in 0..9 {
+                        for x in 0..9 {
+                            let value = filled.get_cell_value(sudoku_i, x, y);
+                            for (twin_i, twin_x, twin_y) in filled.get_twin_cells(sudoku_i, x, y) {
+                                assert_eq!(filled.get_cell_value(twin_i, twin_x, twin_y), value);
+                            }
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    /// The links of the wrapping and dense patterns as they were written by hand before the layouts
+    fn hand_written_links(
+        pattern: CarpetPattern,
+        n: usize,
+    ) -> Vec<((usize, usize), (usize, usize))> {
+        let up_left = 0;
+        let up_right = n - 1;
+        let bottom_left = n * (n - 1);
+        let bottom_right = n * n - 1;
+
+        // the squares of sudoku1 which are the squares of sudoku2 moved by (dx, dy)
+        let shifted_links = |sudoku1: usize, sudoku2: usize, dx: isize, dy: isize| {
+            let mut links = Vec::new();
+            for y1 in 0..n as isize {
+                for x1 in 0..n as isize {
+                    let (x2, y2) = (x1 - dx, y1 - dy);
+                    if (0..n as isize).contains(&x2) && (0..n as isize).contains(&y2) {
+                        links.push((
+                            (sudoku1, (y1 * n as isize + x1) as usize),
+                            (sudoku2, (y2 * n as isize + x2) as usize),
+                        ));
+                    }
+                }
+            }
+            links
+        };
+
+        let mut links = Vec::new();
+        match pattern {
+            CarpetPattern::Torus(size) => {
+                for y in 0..size {
+                    for x in 0..size {
+                        let sudoku_i = y * size + x;
+                        let bottom_i = ((y + 1) % size) * size + x;
+                        links.extend(
+                            (0..n).map(|k| ((sudoku_i, bottom_left + k), (bottom_i, up_left + k))),
+                        );
+                        let right_i = y * size + (x + 1) % size;
+                        links.extend(
+                            (0..n).map(|k| {
+                                ((sudoku_i, n * k + up_right), (right_i, n * k + up_left))
+                            }),
+                        );
+                        let corner_i = ((y + 1) % size) * size + (x + 1) % size;
+                        links.push(((sudoku_i, bottom_right), (corner_i, up_left)));
+                        let corner_i = ((y + 1) % size) * size + (x + size - 1) % size;
+                        links.push(((sudoku_i, bottom_left), (corner_i, up_right)));
+                    }
+                }
+            }
+            CarpetPattern::DenseDiagonal(size) => {
+                for sudoku_i in 0..size {
+                    for j in (1..n).filter(|j| sudoku_i + j < size) {
+                        links.extend(shifted_links(
+                            sudoku_i,
+                            sudoku_i + j,
+                            j as isize,
+                            -(j as isize),
+                        ));
+                    }
+                }
+            }
+            CarpetPattern::DenseCarpet(size) => {
+                for y in 0..size {
+                    for x in 0..size {
+                        for dy in 0..n.min(size - y) {
+                            for dx in (1 - n as isize)..n as isize {
+                                let x2 = x as isize + dx;
+                                if (dy == 0 && dx <= 0) || x2 < 0 || x2 >= size as isize {
+                                    continue;
+                                }
+                                links.extend(shifted_links(
+                                    y * size + x,
+                                    (y + dy) * size + x2 as usize,
+                                    dx,
+                                    dy as isize,
+                                ));
+                            }
+                        }
+                    }
+                }
+            }
+            CarpetPattern::DenseTorus => {
+                // the links of a dense carpet of n×n sudokus, moved around the torus
+                let original_links = hand_written_links(CarpetPattern::DenseCarpet(n), n);
+                for dy in 0..n {
+                    for dx in 0..n {
+                        let moved =
+                            |sudoku: usize| ((sudoku / n + dy) % n) * n + (sudoku % n + dx) % n;
+                        links.extend(original_links.iter().map(
+                            |&((sudoku1, square1), (sudoku2, square2))| {
+                                ((moved(sudoku1), square1), (moved(sudoku2), square2))
+                            },
+                        ));
+                    }
+                }
+            }
+            _ => panic!("{pattern} links weren't written by hand"),
+        }
+        links
+    }
+
+    #[test]
+    fn layout() {
+        // the links of the patterns as they were written by hand before the layouts
+        let link_set = |links: Vec<((usize, usize), (usize, usize))>| {
+            links
+                .into_iter()
+                .map(|(link1, link2)| (link1.min(link2), link1.max(link2)))
+                .collect::<HashSet<_>>()
+        };
+        let expected_links = [
+            (
+                CarpetPattern::Samurai,
+                vec![
+                    ((0, 0), (1, 8)),
+                    ((0, 2), (2, 6)),
+                    ((0, 6), (3, 2)),
+                    ((0, 8), (4, 0)),
+                ],
+            ),
+            (
+                CarpetPattern::Diagonal(3),
+                vec![((0, 2), (1, 6)), ((1, 2), (2, 6))],
+            ),
+            (
+                CarpetPattern::Carpet(2),
+                vec![
+                    ((0, 6), (2, 0)),
+                    ((0, 7), (2, 1)),
+                    ((0, 8), (2, 2)),
+                    ((1, 6), (3, 0)),
+                    ((1, 7), (3, 1)),
+                    ((1, 8), (3, 2)),
+                    ((0, 2), (1, 0)),
+                    ((0, 5), (1, 3)),
+                    ((0, 8), (1, 6)),
+                    ((2, 2), (3, 0)),
+                    ((2, 5), (3, 3)),
+                    ((2, 8), (3, 6)),
+                    ((0, 8), (3, 0)),
+                    ((1, 6), (2, 2)),
+                ],
+            ),
+        ];
+        for (pattern, links) in expected_links {
+            assert_eq!(
+                link_set(pattern.get_raw_links(3)),
+                link_set(links),
+                "{pattern}"
+            );
+        }
+        for pattern in [
+            CarpetPattern::Torus(3),
+            CarpetPattern::Torus(4),
+            CarpetPattern::DenseDiagonal(4),
+            CarpetPattern::DenseCarpet(3),
+            CarpetPattern::DenseCarpet(4),
+            CarpetPattern::DenseTorus,
+        ] {
+            assert_eq!(
+                link_set(pattern.get_raw_links(3)),
+                link_set(hand_written_links(pattern, 3)),
+                "{pattern}"
+            );
+        }
+
+        for pattern in CarpetPattern::iter() {
+            let carpet = CarpetSudoku::new(3, pattern);
+            let custom = CarpetSudoku::new_from_layout(3, carpet.get_layout().clone());
+            assert_eq!(carpet.get_n_sudokus(), custom.get_n_sudokus());
+            assert_eq!(carpet.get_links(), custom.get_links());
+        }
+
+        let samurai = CarpetPattern::Samurai.get_layout(3);
+        assert_eq!(samurai.get_size(3), (21, 21));
+        assert_eq!(samurai.get_sudoku_at(3, 10, 10), Some((0, 4, 4)));
+        assert_eq!(samurai.get_sudoku_at(3, 20, 0), Some((2, 8, 0)));
+        assert_eq!(samurai.get_sudoku_at(3, 10, 0), None);
+        assert_eq!(CarpetPattern::Gattai8.get_layout(3).get_size(3), (33, 21));
+
+        // the right column of the last sudoku of a torus row is linked to the first one
+        let torus = CarpetPattern::Torus(3).get_layout(3);
+        assert_eq!(torus.get_size(3), (21, 21));
+        assert!(torus.get_raw_links(3).contains(&((0, 0), (2, 2))));
+        assert!(!CarpetPattern::Carpet(3)
+            .get_raw_links(3)
+            .contains(&((0, 0), (2, 2))));
+    }
+
+    #[test]
+    fn parse_pattern_file() {
+        let samurai = CarpetSudoku::parse_file("samurai.txt").unwrap();
+        assert_eq!(samurai.get_pattern(), CarpetPattern::Custom(5));
+        assert_eq!(
+            samurai.get_layout().get_positions(),
+            &vec![(0, 0), (12, 0), (6, 6), (0, 12), (12, 12)]
+        );
+        let count_links = |carpet: &CarpetSudoku| {
+            carpet
+                .get_links()
+                .values()
+                .map(|links| links.len())
+                .sum::<usize>()
+        };
+        assert_eq!(
+            count_links(&samurai),
+            count_links(&CarpetSudoku::new(3, CarpetPattern::Samurai))
+        );
+
+        let (db_pattern, db_pattern_size) = samurai.get_pattern().to_db();
+        assert_eq!(
+            CarpetPattern::from_db(db_pattern, db_pattern_size),
+            samurai.get_pattern()
+        );
+        // the custom id is for the saves and the stats, the canonical carpets refuse it both ways
+        assert!(samurai.get_pattern().to_canonical_db().is_err());
+        assert!(CarpetPattern::try_from_canonical_db(db_pattern, db_pattern_size).is_err());
+        assert_eq!(
+            CarpetPattern::Samurai.to_canonical_db(),
+            Ok(CarpetPattern::Samurai.to_db())
+        );
+
+        for file_name in ["sohei.txt", "twin.txt", "staircase.txt"] {
+            let carpet = CarpetSudoku::parse_file(file_name).unwrap();
+            let filled = carpet.generate_full_from();
+            assert!(filled.is_filled());
+            assert_eq!(filled.get_layout(), carpet.get_layout());
+        }
+
+        let twin = CarpetSudoku::parse_file("twin.txt").unwrap();
+        let game = twin
+            .generate_full_from()
+            .generate_from(SudokuDifficulty::Easy)
+            .unwrap();
+        assert!(game.is_unique());
+
+        assert!(CarpetSudoku::parse_file("missing.txt").is_err());
+        assert!(CarpetSudoku::parse_string("three\nA").is_err());
+        assert!(CarpetSudoku::parse_string("3\n...\n...").is_err());
+    }
+
+    #[test]
+    fn custom_links() {
+        let samurai = CarpetSudoku::new(3, CarpetPattern::Samurai);
+        let sudokus = samurai.get_sudokus().clone();
+        let links = samurai.get_links();
+        let layout = samurai.get_layout().clone();
+
+        assert!(
+            CarpetSudoku::try_new_custom(3, sudokus.clone(), links.clone(), layout.clone()).is_ok()
+        );
+
+        let is_invalid_link = |links: CarpetLinks| {
+            matches!(
+                CarpetSudoku::try_new_custom(3, sudokus.clone(), links, layout.clone()),
+                Err(SudokuError::InvalidLink(_))
+            )
+        };
+
+        let mut out_of_range_sudoku = links.clone();
+        out_of_range_sudoku.get_mut(&0).unwrap().insert((0, 7, 0));
+        assert!(is_invalid_link(out_of_range_sudoku));
+
+        let mut out_of_range_square = links.clone();
+        out_of_range_square.get_mut(&0).unwrap().insert((9, 1, 0));
+        out_of_range_square.get_mut(&1).unwrap().insert((0, 0, 9));
+        assert!(is_invalid_link(out_of_range_square));
+
+        let mut self_link = links.clone();
+        self_link.get_mut(&1).unwrap().insert((4, 1, 4));
+        assert!(is_invalid_link(self_link));
+
+        let mut asymmetric = links.clone();
+        asymmetric.get_mut(&1).unwrap().clear();
+        assert!(is_invalid_link(asymmetric));
+
+        let mut double_link = links.clone();
+        double_link.get_mut(&0).unwrap().insert((0, 1, 7));
+        double_link.get_mut(&1).unwrap().insert((7, 0, 0));
+        assert!(is_invalid_link(double_link));
+
+        let mut not_transitive = links.clone();
+        not_transitive.get_mut(&1).unwrap().insert((8, 2, 0));
+        not_transitive.get_mut(&2).unwrap().insert((0, 1, 8));
+        assert!(is_invalid_link(not_transitive));
+
+        // the top band of the center sudoku would be linked to two bands of the top left sudoku
+        let mut two_bands = links.clone();
+        two_bands.get_mut(&0).unwrap().insert((1, 1, 5));
+        two_bands.get_mut(&1).unwrap().insert((5, 0, 1));
+        assert!(is_invalid_link(two_bands));
+
+        let mut two_stacks = links.clone();
+        two_stacks.get_mut(&0).unwrap().insert((4, 1, 5));
+        two_stacks.get_mut(&1).unwrap().insert((5, 0, 4));
+        assert!(is_invalid_link(two_stacks));
+
+        for pattern in CarpetPattern::iter().chain([CarpetPattern::Torus(3)]) {
+            let carpet = CarpetSudoku::new(3, pattern);
+            assert!(
+                CarpetSudoku::try_new_custom(
+                    3,
+                    carpet.get_sudokus().clone(),
+                    carpet.get_links(),
+                    carpet.get_layout().clone()
+                )
+                .is_ok(),
+                "{pattern}"
+            );
+        }
+
+        let mut conflicting_sudokus = sudokus.clone();
+        conflicting_sudokus[1].set_value(6, 6, 1).unwrap();
+        conflicting_sudokus[0].set_value(0, 0, 2).unwrap();
+        assert!(matches!(
+            CarpetSudoku::try_new_custom(3, conflicting_sudokus, links.clone(), layout.clone()),
+            Err(SudokuError::InvalidLink(_))
+        ));
+
+        assert!(matches!(
+            CarpetSudoku::try_new_custom(2, sudokus.clone(), links.clone(), layout.clone()),
+            Err(SudokuError::WrongInput(_))
+        ));
+        assert!(matches!(
+            CarpetSudoku::try_new_custom(3, sudokus[..4].to_vec(), links, layout),
+            Err(SudokuError::WrongInput(_))
+        ));
+        assert!(matches!(
+            CarpetSudoku::try_new_custom(
+                3,
+                Vec::new(),
+                CarpetLinks::new(),
+                CarpetLayout::default()
+            ),
+            Err(SudokuError::WrongInput(_))
+        ));
+    }
+
+    #[test]
+    fn carpet_rules() {
+        let rule = |rule_id: usize| {
+            CarpetSudoku::CARPET_RULES
+                .iter()
+                .find(|(id, _, _)| *id == rule_id)
+                .unwrap()
+                .2
+        };
+
+        // in the samurai, the 1 of the second row of the center sudoku is in the square shared with the top left sudoku,
+        // so it can't be anywhere else in the matching row of the top left sudoku
+        let mut samurai = CarpetSudoku::new(3, CarpetPattern::Samurai);
+        for x in 3..9 {
+            for (sudoku_id, x, y) in samurai.get_twin_cells(0, x, 1) {
+                samurai
+                    .get_cell_possibilities_mut(sudoku_id, x, y)
+                    .remove(&1);
+            }
+        }
+        assert!(rule(56)(&mut samurai).unwrap());
+        for x in 0..6 {
+            assert!(!samurai.get_cell_possibilities(1, x, 7).contains(&1));
+        }
+        assert!(samurai.get_cell_possibilities(1, 6, 7).contains(&1));
+        assert!(!samurai.get_cell_possibilities(1, 6, 6).contains(&1));
+        assert_eq!(
+            CarpetSudoku::get_rule_name_by_id(56),
+            "Carpet Locked Candidates"
+        );
+
+        // the 1 of the last but one row of the top left sudoku can be in its 3 last cells,
+        // but the center sudoku already removed it from the twins of the 2 last ones
+        let mut samurai = CarpetSudoku::new(3, CarpetPattern::Samurai);
+        for x in 0..6 {
+            samurai.get_cell_possibilities_mut(1, x, 7).remove(&1);
+        }
+        for x in 1..3 {
+            samurai.get_cell_possibilities_mut(0, x, 1).remove(&1);
+        }
+        assert!(rule(58)(&mut samurai).unwrap());
+        assert_eq!(samurai.get_cell_value(1, 6, 7), 1);
+        assert_eq!(samurai.get_cell_value(0, 0, 1), 1);
+        assert!(!rule(58)(&mut samurai).unwrap());
+        assert_eq!(
+            CarpetSudoku::get_rule_name_by_id(58),
+            "Carpet Hidden Single"
+        );
+
+        // the rules never remove the solution
+        let mut game = CarpetSudoku::new(3, CarpetPattern::Samurai);
+        for (sudoku_id, board) in HARD_SAMURAI.iter().enumerate() {
+            for (i, value) in board.chars().enumerate() {
+                let (x, y) = (i % 9, i / 9);
+                if let Some(value) = value.to_digit(10) {
+                    if game.get_cell_value(sudoku_id, x, y) == 0 {
+                        game.set_value(sudoku_id, x, y, value as usize).unwrap();
+                    }
+                }
+            }
+        }
+        let mut filled = game.clone();
+        assert!(filled.backtrack_solve());
+        check_rules_keep_solution(&game, &filled);
+    }
+
+    #[test]
+    #[ignore = "test too long: run it with `cargo test --release -- tests::carpet_sudoku_test::tests::carpet_rules_generated --exact --nocapture --ignored`"]
+    fn carpet_rules_generated() {
+        let filled = CarpetSudoku::generate_full(3, CarpetPattern::Samurai);
+        let game = filled.generate_from(SudokuDifficulty::Hard).unwrap();
+        check_rules_keep_solution(&game, &filled);
+    }
+
+    /// A hard samurai, one line of 81 cells per sudoku, so that [`carpet_rules`] doesn't have to generate one
+    const HARD_SAMURAI: [&str; 5] = [
+        ".2.4.67...9......5.7.....4......9856.....49.....8.3.7..8......3....97............",
+        "1.3..6......2....5..9.8.3.2.7.3....4....21.6.............8...2.....7..9.9....2.7.",
+        "..345...94.....6.55..7......64..78....1..9........3.2.7..1....6..5.......4..3.1..",
+        ".......8......9...59.......2...3...4...1...5.6..7..2..73..25..88...........3...7.",
+        "..34.6..........5............5.71.9.38.94..7.........1..216........9.41....8....7",
+    ];
+
+    fn check_rules_keep_solution(game: &CarpetSudoku, filled: &CarpetSudoku) {
+        let rule = |rule_id: usize| {
+            CarpetSudoku::CARPET_RULES
+                .iter()
+                .find(|(id, _, _)| *id == rule_id)
+                .unwrap()
+                .2
+        };
+        let keeps_solution = |carpet: &CarpetSudoku| {
+            (0..carpet.get_n_sudokus()).all(|sudoku_id| {
+                (0..81).all(|i| {
+                    let (x, y) = (i % 9, i / 9);
+                    let value = filled.get_cell_value(sudoku_id, x, y);
+                    match carpet.get_cell_value(sudoku_id, x, y) {
+                        0 => carpet
+                            .get_cell_possibilities(sudoku_id, x, y)
+                            .contains(&value),
+                        carpet_value => carpet_value == value,
+                    }
+                })
+            })
+        };
+        for &(rule_id, _, _) in CarpetSudoku::CARPET_RULES {
+            let mut carpet = game.clone();
+            while rule(rule_id)(&mut carpet).unwrap() {
+                assert!(keeps_solution(&carpet));
+            }
+        }
+
+        let mut solved = game.clone();
+        solved.rule_solve_until((false, false), None);
+        assert!(solved.is_filled());
+        assert!(keeps_solution(&solved));
+    }
+
+    #[test]
+    fn difficulty_score() {
+        // naked singles are easy, x-wings are hard
+        assert_eq!(
+            CarpetSudoku::get_solve_path_score(&[vec![(Some(0), 0), (Some(1), 0)]]),
+            4
+        );
+        let spread = CarpetSudoku::get_solve_path_score(&[vec![(Some(0), 11), (Some(1), 11)]]);
+        let single =
+            CarpetSudoku::get_solve_path_score(&[vec![(Some(0), 11)], vec![(Some(0), 11)]]);
+        assert!(spread > single);
+        assert_eq!(
+            CarpetSudoku::get_solve_path_score(&[vec![(None, 57)]]),
+            2 * SudokuDifficulty::Hard.get_weight()
+        );
+
+        let game = CarpetSudoku::generate_new(3, CarpetPattern::Samurai, SudokuDifficulty::Easy);
+        assert!(game.get_difficulty_score() > 0);
+        let mut solved = game.clone();
+        let (_, solve_path) = solved.rule_solve_until((false, false), Some(game.get_difficulty()));
+        assert_eq!(
+            solved.get_difficulty_score(),
+            CarpetSudoku::get_solve_path_score(&solve_path)
+        );
+        assert_eq!(solved.get_difficulty_score(), game.get_difficulty_score());
+    }
+
+    #[test]
+    fn rate_difficulty() {
+        let game = CarpetSudoku::generate_new(3, CarpetPattern::Samurai, SudokuDifficulty::Easy);
+        let mut rated = game.clone();
+        assert_eq!(rated.rate_difficulty(), SudokuDifficulty::Easy);
+        assert_eq!(rated.get_difficulty_score(), game.get_difficulty_score());
+        assert_eq!(
+            rated.get_sudoku_difficulties(),
+            game.get_sudoku_difficulties()
+        );
+        // only a copy is solved
+        assert_eq!(rated.get_filled_cells(), game.get_filled_cells());
+
+        let mut empty = CarpetSudoku::new(3, CarpetPattern::Samurai);
+        assert_eq!(empty.rate_difficulty(), SudokuDifficulty::Unimplemented);
+    }
+
+    #[test]
+    fn game_score() {
+        // a filled carpet missing one cell per sudoku is an easy game
+        let easy_game = |pattern: CarpetPattern| {
+            let mut carpet = CarpetSudoku::generate_full(3, pattern);
+            for sudoku_id in 0..carpet.get_n_sudokus() {
+                carpet.remove_value(sudoku_id, 4, 4).unwrap();
+            }
+            assert_eq!(carpet.rate_difficulty(), SudokuDifficulty::Easy);
+            carpet
+        };
+        let simple = easy_game(CarpetPattern::Simple);
+        let samurai = easy_game(CarpetPattern::Samurai);
+        let fast = Duration::from_secs(60);
+        assert_eq!(simple.get_game_score(fast, 0, 0), 100);
+        assert_eq!(samurai.get_game_score(fast, 0, 0), 500);
+
+        // mistakes and hints cost a share of the score, up to 75%
+        assert_eq!(simple.get_game_score(fast, 1, 1), 70);
+        assert_eq!(simple.get_game_score(fast, 10, 10), 25);
+
+        // slow games lose points, up to 75% too
+        assert!(simple.get_game_score(Duration::from_secs(600), 0, 0) < 100);
+        assert_eq!(simple.get_game_score(Duration::from_secs(36000), 0, 0), 25);
+    }
+
+    #[test]
+    fn per_sudoku_unreachable() {
+        let filled = CarpetSudoku::generate_full(3, CarpetPattern::Samurai);
+        assert!(filled
+            .generate_from_per_sudoku(vec![SudokuDifficulty::Easy; 4])
+            .is_none());
+
+        // no rule is unimplemented, so the generation gives up after some tries
+        let filled = CarpetSudoku::generate_full(3, CarpetPattern::Simple);
+        assert!(filled
+            .generate_from_per_sudoku(vec![SudokuDifficulty::Unimplemented])
+            .is_none());
+    }
+
+    #[test]
+    #[ignore = "test too long: run it with `cargo test --release -- tests::carpet_sudoku_test::tests::per_sudoku_difficulty --exact --nocapture --ignored`"]
+    fn per_sudoku_difficulty() {
+        let filled = CarpetSudoku::generate_full(3, CarpetPattern::Samurai);
+        check_per_sudoku_difficulty(&filled, [SudokuDifficulty::Easy; 5]);
+    }
+
+    #[test]
+    #[ignore = "test too long: run it with `cargo test --release -- tests::carpet_sudoku_test::tests::per_sudoku_medium_corner --exact --nocapture --ignored`"]
+    fn per_sudoku_medium_corner() {
+        // easy sudokus with a medium corner
+        let filled = CarpetSudoku::generate_full(3, CarpetPattern::Samurai);
+        check_per_sudoku_difficulty(
+            &filled,
+            [
+                SudokuDifficulty::Easy,
+                SudokuDifficulty::Medium,
+                SudokuDifficulty::Easy,
+                SudokuDifficulty::Easy,
+                SudokuDifficulty::Easy,
+            ],
+        );
+    }
+
+    fn check_per_sudoku_difficulty(filled: &CarpetSudoku, difficulties: [SudokuDifficulty; 5]) {
+        let game = filled
+            .generate_from_per_sudoku(difficulties.to_vec())
+            .unwrap();
+        assert_eq!(game.get_sudoku_difficulties(), difficulties);
+        assert_eq!(game.get_difficulty(), *difficulties.iter().max().unwrap());
+        assert!(game.is_unique());
+
+        let mut solved = game.clone();
+        solved.rule_solve_until_per_sudoku((false, false), &difficulties.map(Some));
+        assert!(solved.is_filled());
+        assert_eq!(solved.get_sudoku_difficulties(), difficulties);
+    }
+
+    #[test]
+    fn grid_assembly() {
+        let is_valid = |board: &[Vec<usize>]| {
+            (0..9).all(|i| {
+                let row = board[i].iter().copied().collect::<HashSet<_>>();
+                let col = board.iter().map(|row| row[i]).collect::<HashSet<_>>();
+                let square = CarpetSudoku::get_square_values(board, 3, i)
+                    .into_iter()
+                    .collect::<HashSet<_>>();
+                [row, col, square].iter().all(|values| values.len() == 9)
+            })
+        };
+        let grid = Sudoku::generate_full(3).get_board().clone();
+
+        // moving a square swaps whole bands and stacks
+        let mut moved = grid.clone();
+        CarpetSudoku::move_square(&mut moved, 3, 0, 8);
+        assert!(is_valid(&moved));
+        assert_eq!(
+            CarpetSudoku::get_square_values(&moved, 3, 8),
+            CarpetSudoku::get_square_values(&grid, 3, 0)
+        );
+        assert_eq!(
+            CarpetSudoku::get_square_values(&moved, 3, 2),
+            CarpetSudoku::get_square_values(&grid, 3, 6)
+        );
+
+        // relabelling gives the way back to the old values
+        let mut relabelled = grid.clone();
+        let old_values = CarpetSudoku::get_square_values(&grid, 3, 4);
+        let to_old =
+            CarpetSudoku::relabel_board(&mut relabelled, &old_values, &(1..=9).collect::<Vec<_>>());
+        assert!(is_valid(&relabelled));
+        assert_eq!(
+            CarpetSudoku::get_square_values(&relabelled, 3, 4),
+            (1..=9).collect::<Vec<_>>()
+        );
+        assert!((0..81).all(|i| to_old[relabelled[i / 9][i % 9]] == grid[i / 9][i % 9]));
+
+        // the grid with rows swapped inside a band, columns swapped inside a stack and its values relabelled
+        let mut permuted = grid.clone();
+        permuted.swap(0, 2);
+        for row in permuted.iter_mut() {
+            row.swap(3, 5);
+        }
+        CarpetSudoku::move_square(&mut permuted, 3, 1, 5);
+        let square_values = CarpetSudoku::get_square_values(&permuted, 3, 0);
+        let reversed_values = square_values.iter().rev().copied().collect::<Vec<_>>();
+        CarpetSudoku::relabel_board(&mut permuted, &square_values, &reversed_values);
+        let constraints = [0, 4, 5, 7]
+            .into_iter()
+            .map(|square| {
+                (
+                    square,
+                    CarpetSudoku::get_square_values(&permuted, 3, square),
+                    0,
+                )
+            })
+            .collect::<Vec<_>>();
+        let (fitted, to_old) = CarpetSudoku::fit_grid(&grid, 3, &constraints).unwrap();
+        assert!(is_valid(&fitted));
+        for (square, values, _) in constraints.iter() {
+            assert_eq!(
+                &CarpetSudoku::get_square_values(&fitted, 3, *square),
+                values
+            );
+        }
+        // once mapped back through to_old, each row of the fitted grid is a row of the grid, with the same columns order
+        let is_permuted_grid = (0..9).any(|first_row| {
+            let columns = (0..9)
+                .map(|x| {
+                    grid[first_row]
+                        .iter()
+                        .position(|&value| value == to_old[fitted[0][x]])
+                        .unwrap()
+                })
+                .collect::<Vec<_>>();
+            let rows = (0..9)
+                .map(|y| (0..9).find(|&row| grid[row][columns[0]] == to_old[fitted[y][0]]))
+                .collect::<Option<Vec<_>>>();
+            rows.is_some_and(|rows| {
+                (0..81).all(|i| to_old[fitted[i / 9][i % 9]] == grid[rows[i / 9]][columns[i % 9]])
+            })
+        });
+        assert!(is_permuted_grid);
+
+        // two squares of a band can't hold the same rows
+        let impossible = [
+            (0, (1..=9).collect::<Vec<_>>(), 0),
+            (1, (1..=9).collect::<Vec<_>>(), 0),
+        ];
+        assert!(CarpetSudoku::fit_grid(&grid, 3, &impossible).is_none());
+        assert!(CarpetSudoku::backtrack_grid(3, &impossible).is_none());
+
+        let (backtracked, _) = CarpetSudoku::backtrack_grid(3, &constraints).unwrap();
+        assert!(is_valid(&backtracked));
+        for (square, values, _) in constraints.iter() {
+            assert_eq!(
+                &CarpetSudoku::get_square_values(&backtracked, 3, *square),
+                values
+            );
+        }
+    }
+
+    #[test]
+    #[cfg(feature = "database")]
+    #[ignore = "needs a database filled with simple sudokus: run it with `cargo test --release --features database -- tests::carpet_sudoku_test::tests::filled_from_db --exact --nocapture --ignored`"]
+    fn filled_from_db() {
+        let mut database = Database::connect().unwrap();
+        for pattern in CarpetPattern::iter() {
+            let carpet = CarpetSudoku::generate_filled_from_db(&mut database, 3, pattern);
+            assert!(carpet.is_filled());
+            assert_eq!(carpet.get_pattern(), pattern);
+            for sudoku_id in 0..carpet.get_n_sudokus() {
+                for (x, y) in (0..81).map(|i| (i % 9, i / 9)) {
+                    let value = carpet.get_cell_value(sudoku_id, x, y);
+                    for (sudoku2, x2, y2) in carpet.get_twin_cells(sudoku_id, x, y) {
+                        assert_eq!(carpet.get_cell_value(sudoku2, x2, y2), value);
+                    }
+                }
+            }
+            assert_eq!(
+                carpet.get_filled_board_hash(),
+                carpet.compute_filled_board_hash()
+            );
+        }
+    }
+
+    #[test]
+    fn symmetric_hash() {
+        // copy a filled carpet cell by cell into a new one
+        let transform = |carpet: &CarpetSudoku,
+                         cell: &dyn Fn(usize, usize, usize) -> (usize, usize, usize),
+                         value: &dyn Fn(usize) -> usize| {
+            let mut transformed = CarpetSudoku::new(3, carpet.get_pattern());
+            for sudoku_id in 0..carpet.get_n_sudokus() {
+                for y in 0..9 {
+                    for x in 0..9 {
+                        let (sudoku_id2, x2, y2) = cell(sudoku_id, x, y);
+                        if transformed.get_cell_value(sudoku_id2, x2, y2) == 0 {
+                            transformed
+                                .set_value(
+                                    sudoku_id2,
+                                    x2,
+                                    y2,
+                                    value(carpet.get_cell_value(sudoku_id, x, y)),
+                                )
+                                .unwrap();
+                        }
+                    }
+                }
+            }
+            assert!(transformed.is_filled());
+            transformed
+        };
+
+        let samurai = CarpetSudoku::generate_full(3, CarpetPattern::Samurai);
+        let hash = samurai.compute_filled_board_hash();
+        assert_eq!(samurai.get_filled_board_hash(), hash);
+
+        // samurai: 0 center, 1 top left, 2 top right, 3 bottom left, 4 bottom right
+        let mirrored = transform(&samurai, &|i, x, y| ([0, 2, 1, 4, 3][i], 8 - x, y), &|v| v);
+        assert_eq!(mirrored.compute_filled_board_hash(), hash);
+        let transposed = transform(&samurai, &|i, x, y| ([0, 1, 3, 2, 4][i], y, x), &|v| v);
+        assert_eq!(transposed.compute_filled_board_hash(), hash);
+        let relabelled = transform(&samurai, &|i, x, y| (i, x, y), &|v| v % 9 + 1);
+        assert_eq!(relabelled.compute_filled_board_hash(), hash);
+
+        let other_samurai = CarpetSudoku::generate_full(3, CarpetPattern::Samurai);
+        assert_ne!(other_samurai.compute_filled_board_hash(), hash);
+
+        // shifting every sudoku of a torus one step to the right
+        let torus = CarpetSudoku::generate_full(3, CarpetPattern::Torus(3));
+        let shifted = transform(&torus, &|i, x, y| ((i + 1) % 3 + (i / 3) * 3, x, y), &|v| v);
+        assert_eq!(
+            shifted.compute_filled_board_hash(),
+            torus.compute_filled_board_hash()
+        );
+
+        // a game keeps the same cells once moved to another orientation (here, the cells holding a small value)
+        let filled_cells = |carpet: &CarpetSudoku, is_filled: &dyn Fn(usize) -> bool| {
+            (0..carpet.get_n_sudokus() * 81)
+                .map(|i| is_filled(carpet.get_cell_value(i / 81, i % 9, i % 81 / 9)) as u8)
+                .collect::<Vec<_>>()
+        };
+        let game = filled_cells(&samurai, &|v| v <= 4);
+        for other in [&mirrored, &transposed] {
+            assert_eq!(
+                samurai.move_filled_cells_to(other, &game),
+                Some(filled_cells(other, &|v| v <= 4))
+            );
+        }
+        assert_eq!(
+            samurai.move_filled_cells_to(&relabelled, &game),
+            Some(filled_cells(&relabelled, &|v| (2..=5).contains(&v)))
+        );
+        assert_eq!(samurai.move_filled_cells_to(&other_samurai, &game), None);
+        assert_eq!(
+            torus.move_filled_cells_to(&shifted, &filled_cells(&torus, &|v| v <= 4)),
+            Some(filled_cells(&shifted, &|v| v <= 4))
+        );
+    }
+
     #[test]
     #[cfg(feature = "database")]
     fn to_from_db() {
@@ -50,7 +1088,8 @@ mod tests {
                 db_carpet.clone(),
                 db_carpet_sudokus.clone(),
                 db_sudokus.clone(),
-            );
+            )
+            .unwrap();
 
             if filled1.ne(&filled2) {
                 panic!(
@@ -73,7 +1112,8 @@ mod tests {
                     db_carpet.clone(),
                     db_carpet_sudokus.clone(),
                     db_sudokus.clone(),
-                );
+                )
+                .unwrap();
                 if game1.ne(&game2) {
                     panic!(
                         "\nORIGINAL GAME {}links: {:?}\n\n!=\n\nRECONSTRUCTED GAME {}links: {:?}",
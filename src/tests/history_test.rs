@@ -0,0 +1,121 @@
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use crate::{
+        carpet_sudoku::{CarpetLayout, CarpetSudoku},
+        display::{history::History, marks::PlayerMarks, MarksBoard, PlayerBoard},
+    };
+
+    /// Two sudokus sharing a square, with empty notes and marks
+    fn linked_game() -> (CarpetSudoku, PlayerBoard, MarksBoard) {
+        let layout = CarpetLayout::new(vec![(0, 0), (6, 6)], None);
+        let carpet = CarpetSudoku::new_from_layout(3, layout);
+        let pboard = vec![vec![vec![HashMap::new(); 9]; 9]; 2];
+        let marks = vec![vec![vec![PlayerMarks::default(); 9]; 9]; 2];
+        (carpet, pboard, marks)
+    }
+
+    #[test]
+    fn undo_redo_value() {
+        let (mut carpet, mut pboard, mut marks) = linked_game();
+        let mut history = History::new();
+        let before = History::snapshot(&carpet, &pboard, &marks);
+
+        carpet.set_value(0, 7, 7, 5).unwrap();
+        let after = History::snapshot(&carpet, &pboard, &marks);
+        assert!(history.push(before.clone(), &carpet, &pboard, &marks));
+        assert_eq!(history.undo.len(), 1);
+
+        // the twin cell and the possibilities of both sudokus are part of the action
+        let diff = &history.undo[0];
+        assert!(diff.iter().any(|(cell, _, _)| *cell == (0, 7, 7)));
+        assert!(diff.iter().any(|(cell, _, _)| *cell == (1, 1, 1)));
+        assert!(diff.iter().any(|(cell, _, _)| *cell == (1, 4, 1)));
+
+        assert!(history.undo(&mut carpet, &mut pboard, &mut marks));
+        assert_eq!(History::snapshot(&carpet, &pboard, &marks), before);
+        assert_eq!(carpet.get_cell_value(1, 1, 1), 0);
+        assert!(carpet.get_cell_possibilities(1, 4, 1).contains(&5));
+        assert!(history.undo.is_empty());
+        assert_eq!(history.redo.len(), 1);
+
+        assert!(history.redo(&mut carpet, &mut pboard, &mut marks));
+        assert_eq!(History::snapshot(&carpet, &pboard, &marks), after);
+        assert_eq!(carpet.get_cell_value(1, 1, 1), 5);
+        assert_eq!(history.undo.len(), 1);
+        assert!(history.redo.is_empty());
+
+        // the carpet keeps playing after a round trip
+        carpet.remove_value(1, 1, 1).unwrap();
+        assert_eq!(carpet.get_cell_value(0, 7, 7), 0);
+    }
+
+    #[test]
+    fn undo_redo_notes() {
+        let (mut carpet, mut pboard, mut marks) = linked_game();
+        let mut history = History::new();
+        let before = History::snapshot(&carpet, &pboard, &marks);
+
+        pboard[0][2][3].insert(4, 0xff0000);
+        pboard[1][8][8].insert(9, 0x00ff00);
+        marks[0][2][3].corner.insert(1);
+        marks[1][0][0].color = Some(0x0000ff);
+        let after = History::snapshot(&carpet, &pboard, &marks);
+        assert!(history.push(before.clone(), &carpet, &pboard, &marks));
+        assert_eq!(history.undo[0].len(), 3);
+
+        assert!(history.undo(&mut carpet, &mut pboard, &mut marks));
+        assert_eq!(History::snapshot(&carpet, &pboard, &marks), before);
+        assert!(history.redo(&mut carpet, &mut pboard, &mut marks));
+        assert_eq!(History::snapshot(&carpet, &pboard, &marks), after);
+    }
+
+    #[test]
+    fn push_history() {
+        let (mut carpet, mut pboard, mut marks) = linked_game();
+        let mut history = History::new();
+
+        // nothing to undo or redo yet
+        assert!(!history.undo(&mut carpet, &mut pboard, &mut marks));
+        assert!(!history.redo(&mut carpet, &mut pboard, &mut marks));
+
+        // an action changing nothing isn't recorded
+        let snapshot = History::snapshot(&carpet, &pboard, &marks);
+        assert!(!history.push(snapshot, &carpet, &pboard, &marks));
+        assert!(history.undo.is_empty());
+
+        for (x, value) in [(0, 1), (1, 2), (2, 3)] {
+            let snapshot = History::snapshot(&carpet, &pboard, &marks);
+            carpet.set_value(0, x, 0, value).unwrap();
+            assert!(history.push(snapshot, &carpet, &pboard, &marks));
+        }
+        assert_eq!(history.undo.len(), 3);
+
+        assert!(history.undo(&mut carpet, &mut pboard, &mut marks));
+        assert!(history.undo(&mut carpet, &mut pboard, &mut marks));
+        assert_eq!(history.undo.len(), 1);
+        assert_eq!(history.redo.len(), 2);
+        assert_eq!(carpet.get_cell_value(0, 0, 0), 1);
+        assert_eq!(carpet.get_cell_value(0, 1, 0), 0);
+        assert_eq!(carpet.get_cell_value(0, 2, 0), 0);
+
+        // a new action forgets the undone ones
+        let snapshot = History::snapshot(&carpet, &pboard, &marks);
+        carpet.set_value(0, 4, 4, 7).unwrap();
+        assert!(history.push(snapshot, &carpet, &pboard, &marks));
+        assert_eq!(history.undo.len(), 2);
+        assert!(history.redo.is_empty());
+        assert!(!history.redo(&mut carpet, &mut pboard, &mut marks));
+        assert_eq!(carpet.get_cell_value(0, 1, 0), 0);
+
+        assert!(history.undo(&mut carpet, &mut pboard, &mut marks));
+        assert!(history.undo(&mut carpet, &mut pboard, &mut marks));
+        assert!(history.undo.is_empty());
+        assert_eq!(carpet.get_cell_value(0, 0, 0), 0);
+        assert_eq!(carpet.get_cell_value(0, 4, 4), 0);
+
+        history.clear();
+        assert_eq!(history, History::new());
+    }
+}
@@ -1,2 +1,5 @@
 pub mod carpet_sudoku_test;
+pub mod history_test;
+pub mod player_stats_test;
+pub mod settings_test;
 pub mod simple_sudoku_test;
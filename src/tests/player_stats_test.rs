@@ -0,0 +1,188 @@
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use crate::{
+        carpet_sudoku::CarpetPattern,
+        player_stats::{PlayerGame, PlayerStats},
+        simple_sudoku::{SudokuDifficulty, SudokuError},
+    };
+
+    fn game(
+        finished_at: u64,
+        pattern: CarpetPattern,
+        difficulty: SudokuDifficulty,
+        seconds: u64,
+        won: bool,
+    ) -> PlayerGame {
+        PlayerGame {
+            finished_at,
+            n: 3,
+            pattern,
+            difficulty,
+            time: Duration::from_secs(seconds),
+            mistakes: 1,
+            hints: 2,
+            won,
+            synced: false,
+        }
+    }
+
+    #[test]
+    fn lines() {
+        for pattern in [
+            CarpetPattern::Simple,
+            CarpetPattern::Torus(4),
+            CarpetPattern::Custom(5),
+        ] {
+            let game = game(
+                1_700_000_000_000,
+                pattern,
+                SudokuDifficulty::Hard,
+                125,
+                true,
+            );
+            assert_eq!(PlayerGame::parse_line(&game.to_line()), Ok(game));
+        }
+
+        // lines written before the sync state was saved
+        assert_eq!(
+            PlayerGame::parse_line("1 3 0 - 1 1000 0 0 true").map(|game| game.synced),
+            Ok(false)
+        );
+        assert_eq!(
+            PlayerGame::parse_line("1 3 0 - 1 1000 0 0 true true").map(|game| game.synced),
+            Ok(true)
+        );
+
+        assert!(matches!(
+            PlayerGame::parse_line("1 3 0 - 1"),
+            Err(SudokuError::ParseString(_))
+        ));
+        assert!(matches!(
+            PlayerGame::parse_line("1 3 0 - 1 1000 0 0 maybe"),
+            Err(SudokuError::ParseString(_))
+        ));
+        assert!(matches!(
+            PlayerGame::parse_line("1 3 0 - 1 1000 0 0 true true true"),
+            Err(SudokuError::ParseString(_))
+        ));
+        // unknown pattern
+        assert!(matches!(
+            PlayerGame::parse_line("1 3 42 - 1 1000 0 0 true"),
+            Err(SudokuError::ParseString(_))
+        ));
+    }
+
+    #[test]
+    fn summaries() {
+        let mut stats = PlayerStats::new("");
+        let added = stats.merge(vec![
+            game(4, CarpetPattern::Samurai, SudokuDifficulty::Easy, 300, true),
+            game(1, CarpetPattern::Simple, SudokuDifficulty::Easy, 100, true),
+            game(
+                2,
+                CarpetPattern::Simple,
+                SudokuDifficulty::Medium,
+                50,
+                false,
+            ),
+            game(3, CarpetPattern::Simple, SudokuDifficulty::Easy, 200, true),
+            game(5, CarpetPattern::Simple, SudokuDifficulty::Easy, 60, true),
+        ]);
+        assert_eq!(added, 5);
+        // the games are sorted by end time, and known games aren't added twice
+        assert_eq!(
+            stats.merge(vec![game(
+                1,
+                CarpetPattern::Simple,
+                SudokuDifficulty::Easy,
+                100,
+                true
+            )]),
+            0
+        );
+        assert!(stats
+            .get_games()
+            .windows(2)
+            .all(|games| games[0].finished_at < games[1].finished_at));
+
+        let all = stats.get_summary(|_| true);
+        assert_eq!((all.played, all.won), (5, 4));
+        assert_eq!(all.get_win_rate(), 0.8);
+        assert_eq!(all.best_time, Some(Duration::from_secs(60)));
+        assert_eq!(all.average_time, Some(Duration::from_secs(165)));
+
+        let by_difficulty = stats.get_summaries_by_difficulty();
+        assert_eq!(
+            by_difficulty
+                .iter()
+                .map(|(difficulty, summary)| (*difficulty, summary.played))
+                .collect::<Vec<_>>(),
+            vec![(SudokuDifficulty::Easy, 4), (SudokuDifficulty::Medium, 1)]
+        );
+        // a difficulty without any win has no time
+        assert_eq!(by_difficulty[1].1.best_time, None);
+
+        let by_pattern = stats.get_summaries_by_pattern();
+        assert_eq!(
+            by_pattern
+                .iter()
+                .map(|(pattern, summary)| (*pattern, summary.won))
+                .collect::<Vec<_>>(),
+            vec![(CarpetPattern::Simple, 3), (CarpetPattern::Samurai, 1)]
+        );
+
+        // won, lost, won, won, won
+        assert_eq!(stats.get_streaks(), (3, 3));
+        stats.merge(vec![game(
+            6,
+            CarpetPattern::Simple,
+            SudokuDifficulty::Easy,
+            60,
+            false,
+        )]);
+        assert_eq!(stats.get_streaks(), (0, 3));
+    }
+
+    #[test]
+    fn file() {
+        let file_path = std::env::temp_dir()
+            .join(format!("sudoku_stats_test_{}.txt", std::process::id()))
+            .to_string_lossy()
+            .to_string();
+        let _ = std::fs::remove_file(&file_path);
+
+        let mut stats = PlayerStats::load(&file_path).unwrap();
+        assert!(stats.get_games().is_empty());
+        stats
+            .record(game(
+                1,
+                CarpetPattern::Diagonal(3),
+                SudokuDifficulty::Master,
+                600,
+                true,
+            ))
+            .unwrap();
+        stats
+            .record(game(
+                2,
+                CarpetPattern::Simple,
+                SudokuDifficulty::Easy,
+                90,
+                false,
+            ))
+            .unwrap();
+
+        assert_eq!(PlayerStats::load(&file_path).unwrap(), stats);
+
+        // a file without a player line keeps its games and gets a new player id
+        let data = std::fs::read_to_string(&file_path).unwrap();
+        let (player_line, games) = data.split_once('\n').unwrap();
+        assert_eq!(player_line, format!("player {}", stats.get_player_id()));
+        std::fs::write(&file_path, games).unwrap();
+        let loaded = PlayerStats::load(&file_path).unwrap();
+        assert_eq!(loaded.get_games(), stats.get_games());
+        std::fs::remove_file(&file_path).unwrap();
+    }
+}
@@ -0,0 +1,117 @@
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+    use std::time::Duration;
+
+    use crate::{
+        display::{
+            display::{SETTING_COLORS, SETTING_GRID_SIZE, SETTING_LIFES},
+            editor::EXPORT_FILE,
+            save::{AUTOSAVE_FILE, SAVE_FILE, STATS_FILE},
+            settings::{Settings, MAX_WINDOW_SIZE, MIN_WINDOW_SIZE, PALETTES},
+        },
+        simple_sudoku::SudokuError,
+    };
+
+    #[test]
+    fn toml_string() {
+        let mut settings = Settings::new("settings.toml");
+        settings.window_width = 1280;
+        settings.window_height = 720;
+        settings.font_path = "C:\\fonts\\\"quoted\".ttf".to_string();
+        settings.n = 4;
+        settings.lifes = 5;
+        settings.colors = vec![0xffffff, 0x000000];
+        settings.key_repeat_delay = Duration::from_millis(150);
+        assert_eq!(
+            Settings::parse_toml_string("settings.toml", &settings.to_toml_string().unwrap()),
+            Ok(settings)
+        );
+
+        // the keys left out keep their default value
+        let settings =
+            Settings::parse_toml_string("", "# comment\n\nlifes = 1\ncolors = [0xffffff, 255]\n")
+                .unwrap();
+        assert_eq!(settings.lifes, 1);
+        assert_eq!(settings.colors, vec![0xffffff, 255]);
+        assert_eq!(settings.n, Settings::new("").n);
+
+        // a window size written by hand stays within the bounds
+        let settings =
+            Settings::parse_toml_string("", "window_width = 1\nwindow_height = 100000\n").unwrap();
+        assert_eq!(
+            (settings.window_width, settings.window_height),
+            (MIN_WINDOW_SIZE.0, MAX_WINDOW_SIZE.1)
+        );
+
+        for data in [
+            "size = 3",
+            "n: 3",
+            "n = three",
+            "n = 1",
+            "n = 5",
+            "lifes = 0",
+            "font_path = res/font/thin.ttf",
+            "colors = [0xffffff, 0xzz]",
+            "colors = [0xffffff]",
+        ] {
+            assert!(matches!(
+                Settings::parse_toml_string("", data),
+                Err(SudokuError::ParseString(_))
+            ));
+        }
+    }
+
+    #[test]
+    fn data_path() {
+        // the saves and the stats go next to the settings
+        let settings_path = Path::new(&Settings::get_default_path()).to_path_buf();
+        for file_name in [SAVE_FILE, AUTOSAVE_FILE, STATS_FILE, EXPORT_FILE] {
+            let file_path = Settings::get_data_path(file_name);
+            let file_path = Path::new(&file_path);
+            assert_eq!(file_path.parent(), settings_path.parent());
+            assert_eq!(file_path.file_name().unwrap(), file_name);
+        }
+    }
+
+    #[test]
+    fn read_only() {
+        let file_path = std::env::temp_dir()
+            .join(format!("sudoku_settings_test_{}.toml", std::process::id()))
+            .to_string_lossy()
+            .to_string();
+        std::fs::write(&file_path, "n = 9\n").unwrap();
+
+        // a file that can't be read is kept as it is
+        assert!(Settings::load(&file_path).is_err());
+        let mut settings = Settings::read_only(&file_path);
+        settings.cycle(SETTING_LIFES);
+        assert!(matches!(settings.save(), Err(SudokuError::WriteFile(_))));
+        assert_eq!(std::fs::read_to_string(&file_path).unwrap(), "n = 9\n");
+
+        let mut settings = Settings::new(&file_path);
+        settings.cycle(SETTING_LIFES);
+        settings.save().unwrap();
+        assert_eq!(Settings::load(&file_path), Ok(settings));
+        std::fs::remove_file(&file_path).unwrap();
+    }
+
+    #[test]
+    fn cycle() {
+        let mut settings = Settings::new("");
+        settings.cycle(SETTING_GRID_SIZE);
+        assert_eq!(settings.n, 4);
+        settings.cycle(SETTING_GRID_SIZE);
+        assert_eq!(settings.n, 2);
+
+        settings.lifes = 7;
+        settings.cycle(SETTING_LIFES);
+        assert_eq!(settings.lifes, 1);
+
+        assert_eq!(settings.get_palette_name(), PALETTES[0].0);
+        settings.cycle(SETTING_COLORS);
+        assert_eq!(settings.colors, PALETTES[1].1.to_vec());
+        settings.colors.pop();
+        assert_eq!(settings.get_palette_name(), "Custom");
+    }
+}
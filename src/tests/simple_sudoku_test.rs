@@ -1,11 +1,15 @@
 #[cfg(test)]
 mod tests {
     use std::{
+        collections::HashSet,
         io::{stdout, BufRead, Write},
         sync::{Arc, Mutex},
     };
 
-    use crate::simple_sudoku::{Sudoku, SudokuDifficulty};
+    use crate::simple_sudoku::{
+        Sudoku, SudokuDifficulty, SudokuGroups, SudokuOutsideClue, SudokuParity, SudokuRelation,
+        SudokuStep,
+    };
 
     #[test]
     fn test_parse_file() {
@@ -131,7 +135,7 @@ mod tests {
             }
         }
 
-        sudoku_solved.sort_by(|(_, d1), (_, d2)| d1.cmp(d2));
+        sudoku_solved.sort_by_key(|(_, difficulty)| *difficulty);
 
         println!(
             "solved sudokus:\n{}",
@@ -159,6 +163,151 @@ mod tests {
         );
     }
 
+    #[test]
+    fn find_step() {
+        let mut sudoku = Sudoku::parse_file("sudoku-3-64-9.txt").unwrap();
+        let mut steps = 0;
+        while let Some(step) = sudoku.find_step(None) {
+            steps += 1;
+            let before = sudoku.clone();
+            assert_eq!(sudoku.rule_solve(None, None), Ok(Some(step.rule)));
+            assert_eq!(before.find_step(None).as_ref(), Some(&step));
+
+            assert!(!step.placements.is_empty() || !step.eliminations.is_empty());
+            for &((x, y), value) in step.placements.iter() {
+                assert_eq!(before.get_cell_value(x, y), 0);
+                assert_eq!(sudoku.get_cell_value(x, y), value);
+            }
+            for &((x, y), value) in step.eliminations.iter() {
+                assert!(before.get_cell_possibilities(x, y).contains(&value));
+                assert!(!sudoku.get_cell_possibilities(x, y).contains(&value));
+            }
+            assert!(!step.pattern.is_empty());
+            assert!(step.pattern.is_subset(&step.unit));
+        }
+        assert!(steps > 0);
+    }
+
+    /// Solves the sudoku until the rule is the next step, returns the sudoku before that step
+    fn find_rule_step(file_name: &str, rule: usize) -> (Sudoku, SudokuStep) {
+        let mut sudoku = Sudoku::parse_file(file_name).unwrap();
+        while let Some(step) = sudoku.find_step(None) {
+            if step.rule == rule {
+                return (sudoku, step);
+            }
+            sudoku.rule_solve(None, None).unwrap();
+        }
+        panic!("rule {rule} isn't used to solve {file_name}");
+    }
+
+    #[test]
+    fn find_step_wing() {
+        let (sudoku, step) = find_rule_step("sudoku-rule-17-1.txt", 17);
+        assert!(!step.eliminations.is_empty());
+        assert!(step.pattern.is_subset(&step.unit));
+
+        // a pivot seeing two pincers, every cell is bivalue
+        let cells = step.pattern.iter().copied().collect::<Vec<_>>();
+        assert_eq!(cells.len(), 3);
+        for &(x, y) in cells.iter() {
+            assert_eq!(sudoku.get_cell_possibilities(x, y).len(), 2);
+        }
+        let sees = |(x1, y1): (usize, usize), (x2, y2): (usize, usize)| {
+            sudoku
+                .get_cell_group(x1, y1, SudokuGroups::All)
+                .contains(&(x2, y2))
+        };
+        let pivot = *cells
+            .iter()
+            .find(|&&pivot| cells.iter().all(|&cell| sees(pivot, cell)))
+            .unwrap();
+        let pincers = cells
+            .iter()
+            .copied()
+            .filter(|&cell| cell != pivot)
+            .collect::<Vec<_>>();
+        for &((x, y), value) in step.eliminations.iter() {
+            for &pincer in pincers.iter() {
+                assert!(sees((x, y), pincer));
+                assert!(sudoku
+                    .get_cell_possibilities(pincer.0, pincer.1)
+                    .contains(&value));
+            }
+            assert!(!sudoku
+                .get_cell_possibilities(pivot.0, pivot.1)
+                .contains(&value));
+        }
+    }
+
+    #[test]
+    fn find_step_hidden_pair() {
+        let (sudoku, step) = find_rule_step("sudoku-3-64-9.txt", 4);
+        assert!(!step.eliminations.is_empty());
+        assert!(step.pattern.is_subset(&step.unit));
+
+        // two cells of a group are the only places of two values, their other candidates go
+        assert_eq!(step.pattern.len(), 2);
+        let cells = step.pattern.iter().copied().collect::<Vec<_>>();
+        let pair_values = sudoku
+            .get_cell_possibilities(cells[0].0, cells[0].1)
+            .intersection(sudoku.get_cell_possibilities(cells[1].0, cells[1].1))
+            .copied()
+            .filter(|&value| {
+                sudoku
+                    .get_group(SudokuGroups::All)
+                    .into_iter()
+                    .any(|group| {
+                        group.is_superset(&step.pattern)
+                            && group.iter().all(|&(x, y)| {
+                                step.pattern.contains(&(x, y))
+                                    || !sudoku.get_cell_possibilities(x, y).contains(&value)
+                            })
+                    })
+            })
+            .collect::<HashSet<_>>();
+        assert_eq!(pair_values.len(), 2);
+        for &(cell, value) in step.eliminations.iter() {
+            assert!(step.pattern.contains(&cell));
+            assert!(!pair_values.contains(&value));
+        }
+    }
+
+    #[test]
+    fn find_step_chain() {
+        let (sudoku, step) = find_rule_step("sudoku-rule-16.txt", 16);
+        assert!(!step.eliminations.is_empty());
+        assert!(step.pattern.is_subset(&step.unit));
+
+        // the chain holds a single value, linked by groups where it has only two places
+        let values = step
+            .eliminations
+            .iter()
+            .map(|&(_, value)| value)
+            .collect::<HashSet<_>>();
+        assert_eq!(values.len(), 1);
+        let value = *values.iter().next().unwrap();
+        assert!(step.pattern.len() >= 3);
+        let strong_links = sudoku
+            .get_group(SudokuGroups::All)
+            .into_iter()
+            .map(|group| {
+                group
+                    .into_iter()
+                    .filter(|&(x, y)| sudoku.get_cell_possibilities(x, y).contains(&value))
+                    .collect::<HashSet<_>>()
+            })
+            .filter(|cells| cells.len() == 2 && cells.is_subset(&step.pattern))
+            .collect::<Vec<_>>();
+        let mut linked = HashSet::from([*step.pattern.iter().next().unwrap()]);
+        while let Some(link) = strong_links
+            .iter()
+            .find(|link| link.intersection(&linked).count() == 1)
+        {
+            linked.extend(link.iter().copied());
+        }
+        assert_eq!(linked, step.pattern);
+    }
+
     #[test]
     fn canonize_randomize() {
         for i in 0..100 {
@@ -187,6 +336,352 @@ mod tests {
         }
     }
 
+    #[test]
+    fn canonical_game() {
+        for _ in 0..10 {
+            let game = Sudoku::generate_new(3, SudokuDifficulty::Easy);
+            let mut randomized = game.clone();
+            randomized.randomize(None, None, true).unwrap();
+
+            let canonical = game.to_canonical_game().unwrap();
+            let randomized_canonical = randomized.to_canonical_game().unwrap();
+            assert_eq!(canonical.get_board(), randomized_canonical.get_board());
+            assert_eq!(
+                canonical.get_canonical_filled_board_hash(),
+                randomized_canonical.get_canonical_filled_board_hash()
+            );
+            assert_eq!(canonical.get_filled_cells(), game.get_filled_cells());
+            assert_eq!(canonical.get_difficulty(), game.get_difficulty());
+            // a generated game keeps its own form, unless another of its rows leads to the same first row and column
+            if canonical.get_board() == game.get_board() {
+                assert_eq!(
+                    canonical.get_canonical_filled_board_hash(),
+                    game.get_canonical_filled_board_hash()
+                );
+            }
+
+            // the first row and column are the ones of the generated grids
+            let mut solution = canonical.clone();
+            assert!(solution.backtrack_solve(0, 0));
+            let first_column = |sudoku: &Sudoku| {
+                sudoku
+                    .get_board()
+                    .iter()
+                    .map(|row| row[0])
+                    .collect::<Vec<_>>()
+            };
+            let generated = Sudoku::generate_canonical(3);
+            assert_eq!(solution.get_board()[0], generated.get_board()[0]);
+            assert_eq!(first_column(&solution), first_column(&generated));
+        }
+
+        let mut not_unique = Sudoku::new(3);
+        not_unique.set_value(0, 0, 1).unwrap();
+        assert!(not_unique.to_canonical_game().is_err());
+    }
+
+    #[test]
+    fn anti_knight_anti_king() {
+        for (anti_knight, anti_king) in [(true, false), (false, true), (true, true)] {
+            let mut sudoku = Sudoku::new(3);
+            sudoku.set_anti_knight(anti_knight).unwrap();
+            sudoku.set_anti_king(anti_king).unwrap();
+            let filled = sudoku.into_generate_full_from().unwrap();
+            println!("filled: \n{filled}");
+
+            for y1 in 0..9 {
+                for x1 in 0..9 {
+                    for (x2, y2) in filled.get_cell_peers(x1, y1) {
+                        if (x1, y1) != (x2, y2) {
+                            assert_ne!(
+                                filled.get_cell_value(x1, y1),
+                                filled.get_cell_value(x2, y2)
+                            );
+                        }
+                    }
+                }
+            }
+
+            let parsed = Sudoku::parse_string(&filled.board_to_string()).unwrap();
+            assert!(parsed.eq(&filled));
+            assert_eq!(parsed.count_solutions(Some(2)), 1);
+
+            // a constrained grid isn't canonical, but it is randomized and canonized back once
+            assert!(!filled.is_canonical());
+            let mut randomized = filled.clone();
+            randomized.randomize(None, None, true).unwrap();
+            assert!(randomized.randomize(None, None, true).is_err());
+            randomized.canonize().unwrap();
+            assert_eq!(randomized.get_board(), filled.get_board());
+        }
+
+        let mut sudoku = Sudoku::new(3);
+        sudoku.set_value(0, 0, 1).unwrap();
+        sudoku.set_anti_knight(true).unwrap();
+        assert!(!sudoku.get_cell_possibilities(1, 2).contains(&1));
+        assert!(!sudoku.get_cell_possibilities(2, 1).contains(&1));
+        assert!(sudoku.get_cell_possibilities(1, 1).len() == 8);
+        assert!(sudoku.set_value(2, 1, 1).is_err());
+    }
+
+    #[test]
+    fn relations() {
+        let mut sudoku = Sudoku::new(3);
+        sudoku
+            .add_relation((0, 0), (1, 0), SudokuRelation::GreaterThan)
+            .unwrap();
+        sudoku
+            .add_relation((0, 1), (0, 2), SudokuRelation::BlackDot)
+            .unwrap();
+        assert!(!sudoku.get_cell_possibilities(0, 0).contains(&1));
+        assert!(!sudoku.get_cell_possibilities(1, 0).contains(&9));
+        assert!(!sudoku.get_cell_possibilities(0, 1).contains(&5));
+        assert!(sudoku
+            .add_relation((0, 0), (1, 1), SudokuRelation::WhiteDot)
+            .is_err());
+
+        sudoku.set_value(0, 0, 3).unwrap();
+        assert!(sudoku.get_cell_possibilities(1, 0).iter().all(|&v| v < 3));
+        assert!(sudoku.set_value(1, 0, 4).is_err());
+        sudoku.remove_value(1, 0).unwrap();
+        let parsed = Sudoku::parse_string(&sudoku.board_to_string()).unwrap();
+        assert!(parsed.eq(&sudoku));
+
+        sudoku.remove_value(0, 0).unwrap();
+        assert!(sudoku.get_cell_possibilities(1, 0).contains(&8));
+        assert_eq!(
+            sudoku.remove_relation((1, 0), (0, 0)).unwrap(),
+            Some(SudokuRelation::GreaterThan)
+        );
+        assert!(sudoku.get_cell_possibilities(1, 0).contains(&9));
+
+        // removing a value keeps the possibilities removed away from its peers and relations
+        sudoku.remove_possibility(8, 8, 4).unwrap();
+        sudoku.remove_possibility(5, 0, 4).unwrap();
+        sudoku.set_value(0, 0, 5).unwrap();
+        sudoku.remove_value(0, 0).unwrap();
+        assert!(!sudoku.get_cell_possibilities(8, 8).contains(&4));
+        assert!(!sudoku.get_cell_possibilities(5, 0).contains(&4));
+        assert!(sudoku.get_cell_possibilities(5, 0).contains(&5));
+        assert!(sudoku.get_cell_possibilities(0, 0).contains(&5));
+
+        // a relation that leaves a cell without possibility isn't kept
+        let mut sudoku = Sudoku::new(3);
+        sudoku.set_value(4, 4, 1).unwrap();
+        let possibilities = sudoku.get_possibility_board().clone();
+        assert!(sudoku
+            .add_relation((4, 4), (5, 4), SudokuRelation::GreaterThan)
+            .is_err());
+        assert!(sudoku.get_relations().is_empty());
+        assert_eq!(sudoku.get_possibility_board(), &possibilities);
+    }
+
+    #[test]
+    #[ignore = "test too long: run it with `cargo test --release -- tests::simple_sudoku_test::tests::generate_relations --exact --nocapture --ignored`"]
+    fn generate_relations() {
+        let filled = Sudoku::generate_full(3);
+        for relations in [
+            vec![SudokuRelation::GreaterThan],
+            vec![SudokuRelation::WhiteDot, SudokuRelation::BlackDot],
+        ] {
+            let game = filled.generate_relations_from(&relations).unwrap();
+            println!("game: \n{game}");
+            assert!(!game.get_relations().is_empty());
+            assert_eq!(game.count_solutions(Some(2)), 1);
+
+            for (&((x1, y1), (x2, y2)), relation) in game.get_relations() {
+                assert!(relations.contains(relation));
+                assert!(relation
+                    .is_satisfied(filled.get_cell_value(x1, y1), filled.get_cell_value(x2, y2)));
+            }
+
+            let parsed = Sudoku::parse_string(&game.board_to_string()).unwrap();
+            assert!(parsed.eq(&game));
+        }
+    }
+
+    #[test]
+    fn thermometers_arrows() {
+        let mut sudoku = Sudoku::new(3);
+        sudoku
+            .add_thermometer(vec![(0, 0), (1, 1), (2, 2)])
+            .unwrap();
+        sudoku.add_arrow((4, 0), vec![(5, 1), (6, 1)]).unwrap();
+        assert!(sudoku.add_thermometer(vec![(0, 3), (2, 3)]).is_err());
+        assert!(sudoku.add_arrow((0, 5), vec![]).is_err());
+
+        assert_eq!(sudoku.get_cell_possibilities(0, 0).len(), 7);
+        assert!(!sudoku.get_cell_possibilities(1, 1).contains(&1));
+        assert!(!sudoku.get_cell_possibilities(1, 1).contains(&9));
+        assert!(!sudoku.get_cell_possibilities(4, 0).contains(&1));
+        assert!(!sudoku.get_cell_possibilities(5, 1).contains(&9));
+
+        sudoku.set_value(4, 0, 4).unwrap();
+        assert!(sudoku
+            .get_cell_possibilities(5, 1)
+            .iter()
+            .all(|&value| value <= 3));
+        assert!(sudoku.set_value(5, 1, 2).is_err());
+        sudoku.remove_value(5, 1).unwrap();
+        sudoku.set_value(1, 1, 5).unwrap();
+        assert!(sudoku
+            .get_cell_possibilities(0, 0)
+            .iter()
+            .all(|&value| value < 5));
+
+        let parsed = Sudoku::parse_string(&sudoku.board_to_string()).unwrap();
+        assert!(parsed.eq(&sudoku));
+    }
+
+    #[test]
+    fn generate_lines() {
+        let filled = Sudoku::generate_full(3);
+        let game = filled.generate_lines_from(3, 3).unwrap();
+        println!("game: \n{game}");
+        assert!(!game.get_thermometers().is_empty() || !game.get_arrows().is_empty());
+        assert_eq!(game.count_solutions(Some(2)), 1);
+
+        for thermometer in game.get_thermometers() {
+            for cells in thermometer.windows(2) {
+                let ((x1, y1), (x2, y2)) = (cells[0], cells[1]);
+                assert!(filled.get_cell_value(x1, y1) < filled.get_cell_value(x2, y2));
+            }
+        }
+        for ((x, y), path) in game.get_arrows() {
+            let sum = path
+                .iter()
+                .map(|&(x1, y1)| filled.get_cell_value(x1, y1))
+                .sum::<usize>();
+            assert_eq!(filled.get_cell_value(*x, *y), sum);
+        }
+
+        let parsed = Sudoku::parse_string(&game.board_to_string()).unwrap();
+        assert!(parsed.eq(&game));
+    }
+
+    #[test]
+    fn parities_non_consecutive() {
+        let mut sudoku = Sudoku::new(3);
+        sudoku.set_non_consecutive(true).unwrap();
+        let filled = sudoku.into_generate_full_from().unwrap();
+        println!("filled: \n{filled}");
+        for y in 0..9 {
+            for x in 0..9 {
+                if x < 8 {
+                    assert_ne!(
+                        filled
+                            .get_cell_value(x, y)
+                            .abs_diff(filled.get_cell_value(x + 1, y)),
+                        1
+                    );
+                }
+                if y < 8 {
+                    assert_ne!(
+                        filled
+                            .get_cell_value(x, y)
+                            .abs_diff(filled.get_cell_value(x, y + 1)),
+                        1
+                    );
+                }
+            }
+        }
+
+        let game = filled.generate_parities_from(10).unwrap();
+        println!("game: \n{game}");
+        assert_eq!(game.get_parities().len(), 10);
+        assert_eq!(game.count_solutions(Some(2)), 1);
+        let parsed = Sudoku::parse_string(&game.board_to_string()).unwrap();
+        assert!(parsed.eq(&game));
+
+        let mut sudoku = Sudoku::new(3);
+        sudoku
+            .set_cell_parity(0, 0, Some(SudokuParity::Even))
+            .unwrap();
+        assert!(sudoku
+            .get_cell_possibilities(0, 0)
+            .iter()
+            .all(|&value| value % 2 == 0));
+        assert!(sudoku.set_value(0, 0, 3).is_err());
+        sudoku.remove_value(0, 0).unwrap();
+
+        sudoku.set_non_consecutive(true).unwrap();
+        sudoku.set_value(1, 0, 5).unwrap();
+        assert!(!sudoku.get_cell_possibilities(0, 0).contains(&4));
+        assert!(!sudoku.get_cell_possibilities(0, 0).contains(&6));
+        assert!(sudoku.get_cell_possibilities(0, 0).contains(&8));
+        assert!(sudoku.get_cell_possibilities(0, 1).contains(&6));
+    }
+
+    #[test]
+    fn outside_clues() {
+        let mut sudoku = Sudoku::new(3);
+        let sandwich = SudokuOutsideClue::Sandwich(SudokuGroups::Row, 0);
+        let little_killer = SudokuOutsideClue::LittleKiller((6, 0), (1, 1));
+        sudoku.add_outside_clue(sandwich, 0).unwrap();
+        sudoku.add_outside_clue(little_killer, 6).unwrap();
+        assert!(sudoku
+            .add_outside_clue(SudokuOutsideClue::LittleKiller((4, 4), (1, 1)), 10)
+            .is_err());
+        assert_eq!(
+            sudoku.get_outside_clue_cells(little_killer),
+            vec![(6, 0), (7, 1), (8, 2)]
+        );
+
+        // each cell of the diagonal leaves at least 1 to the 2 others
+        assert!(sudoku.get_cell_possibilities(7, 1).iter().all(|&v| v <= 4));
+        sudoku.set_value(6, 0, 1).unwrap();
+        assert_eq!(sudoku.get_cell_possibilities(7, 1), &HashSet::from([2, 3]));
+
+        // a sandwich of 0: the 9 is next to the 1
+        assert!(sudoku.get_cell_possibilities(5, 0).contains(&9));
+        assert!(sudoku.get_cell_possibilities(7, 0).contains(&9));
+        assert!(!sudoku.get_cell_possibilities(0, 0).contains(&9));
+        assert!(sudoku.set_value(2, 0, 9).is_err());
+        sudoku.remove_value(2, 0).unwrap();
+
+        let parsed = Sudoku::parse_string(&sudoku.board_to_string()).unwrap();
+        assert!(parsed.eq(&sudoku));
+
+        // a malformed clue isn't read as a shorter one
+        let mut sudoku = Sudoku::new(3);
+        sudoku
+            .parse_constraint_line("sandwich column 2 10")
+            .unwrap();
+        sudoku
+            .parse_constraint_line("little_killer 0 1 1 1 12")
+            .unwrap();
+        assert_eq!(sudoku.get_outside_clues().len(), 2);
+        for line in [
+            "sandwich row 2x 10",
+            "sandwich row 2 1 10",
+            "sandwich row",
+            "sandwich 10",
+            "little_killer 0 1 a 1 1 12",
+            "little_killer 0 1 1 1 1x",
+        ] {
+            assert!(sudoku.parse_constraint_line(line).is_err(), "{line}");
+        }
+        assert_eq!(sudoku.get_outside_clues().len(), 2);
+    }
+
+    #[test]
+    #[ignore = "test too long: run it with `cargo test --release -- tests::simple_sudoku_test::tests::generate_outside_clues --exact --nocapture --ignored`"]
+    fn generate_outside_clues() {
+        let filled = Sudoku::generate_full(3);
+        for (sandwiches, little_killers) in [(true, false), (false, true)] {
+            let game = filled
+                .generate_outside_clues_from(sandwiches, little_killers)
+                .unwrap();
+            println!("game: \n{game}");
+            assert_eq!(game.count_solutions(Some(2)), 1);
+            for (&clue, &sum) in game.get_outside_clues() {
+                assert_eq!(filled.get_outside_clue_sum(clue), Some(sum));
+            }
+            let parsed = Sudoku::parse_string(&game.board_to_string()).unwrap();
+            assert!(parsed.eq(&game));
+        }
+    }
+
     #[test]
     #[cfg(feature = "database")]
     fn to_from_db() {